@@ -0,0 +1,4728 @@
+//! The [`App`] state machine and the event loop driving it.
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, TimeZone};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind},
+    style::Print,
+};
+use ratatui::{
+    layout::{Position, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    Terminal,
+};
+use serde::Serialize;
+use std::{cell::RefCell, collections::{BTreeMap, HashMap, VecDeque}, hash::{Hash, Hasher}, env, fs, io::{self, Write}, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::{Duration, Instant}};
+
+use crate::{config::*, history::*, keys::*, lang::*, net::*, transcript::*, ui::*};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Focus {
+    Input,
+    Chat,
+    Help,
+    /// Participant list pane (F5)
+    Participants,
+    /// Conversation sidebar (F2)
+    Sessions,
+}
+
+/// Connection state for the status bar; stays untranslated until shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionStatus {
+    Connected,
+    Sending,
+    RateLimited,
+    Error,
+    Disconnected,
+    /// Copy mode put the selection on the clipboard
+    Copied,
+}
+
+impl ConnectionStatus {
+    pub fn label(self) -> &'static str {
+        tr(match self {
+            ConnectionStatus::Connected => "Verbunden",
+            ConnectionStatus::Sending => "Sendet...",
+            ConnectionStatus::RateLimited => "Rate-Limit",
+            ConnectionStatus::Error => "Fehler",
+            ConnectionStatus::Disconnected => "Getrennt",
+            ConnectionStatus::Copied => "Kopiert",
+        })
+    }
+}
+
+/// A joined room. The active room's messages live in `App::messages`; the
+/// entry only holds the stream state while the room is in the background.
+pub struct Room {
+    /// Room name (None = shared default channel)
+    pub name: Option<String>,
+    pub messages: Vec<Message>,
+    pub last_timestamp: u64,
+    pub unread: usize,
+    /// Newest message seen; later ones are shown below a "new messages" divider
+    pub read_marker: Option<u64>,
+    /// Message at the top of the chat pane while the room is in the background
+    pub view_anchor: Option<String>,
+    /// Chunk files of older history not loaded yet
+    pub older_chunks: usize,
+}
+
+/// A server connection. The active connection's state lives directly in
+/// `App` (like the active room); the entry holds it while in the background.
+pub struct Connection {
+    pub backend: Arc<dyn ChatBackend>,
+    pub server_url: String,
+    pub rooms: Vec<Room>,
+    pub active_room: usize,
+    pub messages: Vec<Message>,
+    pub last_timestamp: u64,
+    pub rooms_supported: bool,
+    pub available_rooms: Vec<String>,
+    pub presence_supported: bool,
+    pub typing_supported: bool,
+    pub targets_supported: bool,
+    pub is_admin: bool,
+    pub read_marker: Option<u64>,
+    pub reported_read: u64,
+    pub history_enabled: bool,
+    pub session: Option<String>,
+    pub older_chunks: usize,
+    pub connection_status: ConnectionStatus,
+    pub server_info: Option<ServerInfo>,
+    /// Capabilities probed and messages loaded once
+    pub synced: bool,
+    /// New messages in the active room of a background connection
+    pub unread: usize,
+}
+
+impl Connection {
+    /// A connection that has not talked to the server yet (no history)
+    pub fn new(backend: Arc<dyn ChatBackend>) -> Self {
+        let server_url = backend.server_url().to_string();
+        Self {
+            backend,
+            rooms: vec![Room::new(None)],
+            active_room: 0,
+            messages: vec![Message::new("system", trf("Verbunden mit {}", &[&server_url]))],
+            server_url,
+            last_timestamp: 0,
+            rooms_supported: false,
+            available_rooms: Vec::new(),
+            presence_supported: false,
+            typing_supported: false,
+            targets_supported: false,
+            is_admin: false,
+            read_marker: None,
+            reported_read: 0,
+            history_enabled: false,
+            session: None,
+            older_chunks: 0,
+            connection_status: ConnectionStatus::Connected,
+            server_info: None,
+            synced: false,
+            unread: 0,
+        }
+    }
+
+    /// Name of the room this connection is looking at (None = default channel)
+    pub fn room_name(&self) -> Option<&str> {
+        self.rooms[self.active_room].name.as_deref()
+    }
+}
+
+/// One conversation in the tab bar: a room on one of the connections
+#[derive(Debug, PartialEq)]
+pub struct Tab {
+    pub connection: usize,
+    pub room: usize,
+    pub label: String,
+    pub unread: usize,
+}
+
+impl Room {
+    pub fn new(name: Option<String>) -> Self {
+        Self {
+            name,
+            messages: Vec::new(),
+            last_timestamp: 0,
+            unread: 0,
+            read_marker: None,
+            view_anchor: None,
+            older_chunks: 0,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(tr("Hauptkanal"))
+    }
+}
+
+/// Slash commands typed into the input field
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `/join <room>`: join (or switch to) a room
+    Join(String),
+    /// `/leave`: leave the active room
+    Leave,
+    /// `/edit`: load the last own message into the input for editing
+    Edit,
+    /// `/connect <host:port|url>`: open an additional server connection
+    Connect(String),
+    /// `/disconnect`: close the active additional connection
+    Disconnect,
+    /// `/msg <user> [text]`: send a direct message, or select `user` as target
+    Msg { to: String, text: Option<String> },
+    /// `/all`: send to the whole channel again
+    All,
+    /// `/kick`, `/mute`, `/purge`: moderation (admins only, asks for confirmation)
+    Moderate(ModAction),
+    /// `/pager`: show the whole conversation in `$PAGER`
+    Pager,
+    /// `/diff`: what changed in the last edited message
+    Diff,
+    /// `/template`: pick a prompt template and fill its variables
+    Template,
+    /// `/filter [@role] [regex]`: show only matching messages (None clears)
+    Filter(Option<String>),
+    /// `/date <tag>`: scroll to the first message of a day
+    Date(String),
+    /// `/theme [name]`: switch the color theme, or list the themes
+    Theme(Option<String>),
+    /// `/export <file>`: write the conversation as Markdown, JSON or HTML
+    Export(String),
+    /// `/save-config`: make the active server the default in config.toml
+    SaveConfig,
+    /// `/status`: check the server again (version, model, round trip)
+    Status,
+}
+
+/// Moderation request sent to the server's `/moderation/<action>` endpoints
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ModAction {
+    /// Remove a participant from the room
+    Kick { user: String },
+    /// Silence a participant, optionally for a number of minutes
+    Mute {
+        user: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        minutes: Option<u32>,
+    },
+    /// Delete all messages of a participant in the room
+    Purge { user: String },
+}
+
+impl ModAction {
+    /// Endpoint name below `/moderation/`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModAction::Kick { .. } => "kick",
+            ModAction::Mute { .. } => "mute",
+            ModAction::Purge { .. } => "purge",
+        }
+    }
+
+    pub fn user(&self) -> &str {
+        match self {
+            ModAction::Kick { user } | ModAction::Mute { user, .. } | ModAction::Purge { user } => user,
+        }
+    }
+
+    /// Question for the confirmation dialog
+    pub fn question(&self) -> String {
+        match self {
+            ModAction::Kick { user } => trf("{} aus dem Raum entfernen?", &[user]),
+            ModAction::Mute { user, minutes: Some(m) } => trf("{} für {} Minuten stummschalten?", &[user, &m.to_string()]),
+            ModAction::Mute { user, minutes: None } => trf("{} stummschalten?", &[user]),
+            ModAction::Purge { user } => trf("Alle Nachrichten von {} löschen?", &[user]),
+        }
+    }
+
+    /// Audit line for the transcript once the server confirmed the action
+    pub fn audit(&self, moderator: &str) -> String {
+        let what = match self {
+            ModAction::Kick { user } => trf("{} wurde entfernt", &[user]),
+            ModAction::Mute { user, minutes: Some(m) } => trf("{} ist für {} Minuten stumm", &[user, &m.to_string()]),
+            ModAction::Mute { user, minutes: None } => trf("{} ist stumm", &[user]),
+            ModAction::Purge { user } => trf("Nachrichten von {} gelöscht", &[user]),
+        };
+        trf("Moderation: {} (von {})", &[&what, moderator])
+    }
+}
+
+impl Command {
+    /// Parse a slash command. Returns None for normal messages (including
+    /// `//text`, which sends `/text`), Some(Err) for unknown or malformed commands.
+    pub fn parse(input: &str) -> Option<Result<Command, String>> {
+        let rest = input.strip_prefix('/')?;
+        if rest.starts_with('/') {
+            return None;
+        }
+        let mut parts = rest.splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        let arg = parts.next().map(str::trim).unwrap_or("");
+        Some(match name {
+            "join" if !arg.is_empty() => Ok(Command::Join(arg.trim_start_matches('#').to_string())),
+            "join" => Err(tr("Verwendung: /join <raum>").to_string()),
+            "leave" => Ok(Command::Leave),
+            "edit" => Ok(Command::Edit),
+            "connect" if !arg.is_empty() => Ok(Command::Connect(arg.to_string())),
+            "connect" => Err(tr("Verwendung: /connect <host:port|profil>").to_string()),
+            "disconnect" => Ok(Command::Disconnect),
+            "msg" if !arg.is_empty() => {
+                let mut msg = arg.splitn(2, char::is_whitespace);
+                let to = msg.next().unwrap_or("").trim_start_matches('@').to_string();
+                let text = msg.next().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string);
+                Ok(Command::Msg { to, text })
+            }
+            "msg" => Err(tr("Verwendung: /msg <name> [text]").to_string()),
+            "all" => Ok(Command::All),
+            "pager" => Ok(Command::Pager),
+            "diff" => Ok(Command::Diff),
+            "template" => Ok(Command::Template),
+            "filter" if arg.is_empty() => Ok(Command::Filter(None)),
+            "filter" => Ok(Command::Filter(Some(arg.to_string()))),
+            "date" if !arg.is_empty() => Ok(Command::Date(arg.to_string())),
+            "date" => Err(tr("Verwendung: /date <TT.MM.JJJJ|JJJJ-MM-TT|heute|gestern>").to_string()),
+            "theme" if arg.is_empty() => Ok(Command::Theme(None)),
+            "theme" => Ok(Command::Theme(Some(arg.to_string()))),
+            "export" if !arg.is_empty() => Ok(Command::Export(arg.to_string())),
+            "export" => Err(tr("Verwendung: /export <datei.md|.json|.html>").to_string()),
+            "save-config" => Ok(Command::SaveConfig),
+            "status" => Ok(Command::Status),
+            "kick" | "mute" | "purge" => {
+                let mut words = arg.split_whitespace();
+                let Some(user) = words.next().map(|u| u.trim_start_matches('@').to_string()) else {
+                    return Some(Err(trf("Verwendung: /{} <name>{}", &[name, if name == "mute" { tr(" [minuten]") } else { "" }])));
+                };
+                Ok(Command::Moderate(match name {
+                    "kick" => ModAction::Kick { user },
+                    "purge" => ModAction::Purge { user },
+                    _ => match words.next().map(str::parse::<u32>) {
+                        Some(Err(_)) => return Some(Err(tr("Verwendung: /mute <name> [minuten]").to_string())),
+                        minutes => ModAction::Mute { user, minutes: minutes.and_then(Result::ok) },
+                    },
+                }))
+            }
+            _ => Err(trf("Unbekannter Befehl: /{} (// am Anfang sendet einen Slash)", &[name])),
+        })
+    }
+}
+
+pub struct App {
+    pub input: String,
+    pub cursor_pos: usize,
+    pub messages: Vec<Message>,
+    pub server_url: String,
+    pub backend: Arc<dyn ChatBackend>,
+    pub loading: bool,
+    pub scroll: u16,
+    pub input_scroll: u16,  // Scroll offset for input field
+    pub command_history: Vec<String>,
+    pub history_index: Option<usize>,
+    pub connection_status: ConnectionStatus,
+    pub last_error: Option<String>,
+    pub auto_scroll: bool,
+    pub focus: Focus,
+    pub history_enabled: bool,
+    /// Conversation shown in the default channel (Ctrl+N), saved under
+    /// `hank-tui/sessions/`; None = the main conversation in `history.json`
+    pub session: Option<String>,
+    /// Open conversation picker (Ctrl+O)
+    pub session_picker: Option<SessionPicker>,
+    /// Conversation sidebar on the left (F2)
+    pub session_sidebar: Option<SessionPicker>,
+    /// Own display name (None = "Du")
+    pub display_name: Option<String>,
+    /// Name in front of the assistant's answers (`[display]`, default "Hank")
+    pub assistant_name: String,
+    /// Colors of own messages and of the answers from `[display]`
+    /// (None = the theme's)
+    pub own_color: Option<Color>,
+    pub assistant_color: Option<Color>,
+    pub last_timestamp: u64,
+    pub last_poll: Instant,
+    pub debug_overlay: bool,
+    /// HTTP log of `--debug-http`, also shown in the debug overlay
+    pub http_trace: Option<Arc<HttpTrace>>,
+    /// Shared by all HTTP connections, also those opened with /connect
+    pub http_client: Option<HttpClient>,
+    /// `[servers.<name>]` from the config, for `/connect <name>`
+    pub profiles: BTreeMap<String, ServerProfile>,
+    /// Joined rooms; index 0 is always the shared default channel
+    pub rooms: Vec<Room>,
+    pub active_room: usize,
+    /// Server answered `GET /rooms`
+    pub rooms_supported: bool,
+    /// Rooms advertised by the server
+    pub available_rooms: Vec<String>,
+    /// Server answered `GET /presence`
+    pub presence_supported: bool,
+    /// Participants of the active room
+    pub presence: Vec<Presence>,
+    /// None = refresh due
+    pub last_presence_poll: Option<Instant>,
+    /// Server answered `GET /typing`
+    pub typing_supported: bool,
+    /// Other participants currently typing in the active room
+    pub typing: Vec<String>,
+    /// When we last told the server that we are typing
+    pub last_typing_sent: Option<Instant>,
+    /// Newest message seen in the active room before this visit
+    pub read_marker: Option<u64>,
+    /// Id of the own message currently being edited in the input field
+    pub editing: Option<String>,
+    /// Root id of the thread shown in the thread overlay; sends go there
+    pub thread: Option<String>,
+    /// Last read position reported to the server
+    pub reported_read: u64,
+    pub alert_mode: AlertMode,
+    /// Status bar is highlighted until then (AlertMode::Flash)
+    pub flash_until: Option<Instant>,
+    /// Short confirmation in the status bar and when it disappears
+    pub notice: Option<(String, Instant)>,
+    /// Configured mention keywords (the display name always counts)
+    pub mention_keywords: Vec<String>,
+    /// Only show messages that mention us (toggle with F4)
+    pub mentions_only: bool,
+    /// All server connections; index 0 is the one given on the command line
+    pub connections: Vec<Connection>,
+    pub active_connection: usize,
+    /// Server answered `GET /targets`
+    pub targets_supported: bool,
+    /// Participants that can receive direct messages
+    pub targets: Vec<String>,
+    /// None = refresh due
+    pub last_targets_poll: Option<Instant>,
+    /// Selected direct message recipient (None = whole channel)
+    pub target: Option<String>,
+    /// Participant list pane on the right (toggle with F5)
+    pub show_participants: bool,
+    /// Highlighted entry of the participant list
+    pub participant_index: usize,
+    /// Profile of a participant fetched from the server (name, details)
+    pub profile: Option<(String, UserProfile)>,
+    /// Server metrics overlay (F9) with the time they were fetched
+    pub stats: Option<(Result<ServerStats, String>, NaiveDateTime)>,
+    /// Timestamp of the newest announcement dismissed with Esc
+    pub acknowledged: u64,
+    /// Per-participant color overrides from the config
+    pub user_colors: BTreeMap<String, Color>,
+    /// The server grants us moderation rights (`GET /me`)
+    pub is_admin: bool,
+    /// Moderation action waiting for a yes/no confirmation
+    pub confirm: Option<ModAction>,
+    /// Old and new text of an edited message, shown as diff overlay
+    pub edit_diff: Option<(String, String)>,
+    /// Models from the config, offered when the server lists none
+    pub models: Vec<String>,
+    /// Longest message the server accepts (config or learned from HTTP 413)
+    pub max_message_chars: Option<usize>,
+    /// Open offer to split or attach a message over that limit
+    pub oversize: Option<Oversize>,
+    /// Open model picker for regenerating an answer
+    pub model_picker: Option<ModelPicker>,
+    /// Open prompt template picker
+    pub template_picker: Option<TemplatePicker>,
+    /// Copy mode of the chat pane
+    pub copy_mode: Option<CopyMode>,
+    /// Message index highlighted in the chat pane (j/k), the target of y, R, J and >
+    pub selected: Option<usize>,
+    /// Scroll the selected message into view on the next draw
+    pub reveal_selection: bool,
+    /// Search bar below the chat pane
+    pub search: Option<Search>,
+    /// Search screen across all saved histories
+    pub global_search: Option<GlobalSearch>,
+    /// Transcript filter (`/filter`)
+    pub filter: Option<Filter>,
+    /// Message index to bring to the top of the chat pane on the next draw
+    /// (`/date`), resolved there because it needs the pane's geometry
+    pub scroll_anchor: Option<usize>,
+    /// Chunk files of older history for the open view not loaded yet
+    pub older_chunks: usize,
+    /// The chat pane was scrolled to the top: load the next older chunk
+    pub wants_older: bool,
+    /// Messages waiting for delivery after the server rate-limited us
+    pub send_queue: VecDeque<PendingSend>,
+    /// When the next delivery attempt is allowed (HTTP 429 Retry-After)
+    pub retry_at: Option<Instant>,
+    /// Polling failed since this moment; triggers a full resync on recovery
+    pub offline_since: Option<Instant>,
+    /// Polls failed in a row; each one doubles the wait before the next
+    pub poll_failures: u32,
+    /// Pause between polls (`poll_interval`, `--poll-interval`)
+    pub poll_interval: Duration,
+    /// Last key, mouse event or new message; polls slow down after a
+    /// quiet minute
+    pub last_activity: Instant,
+    /// Limit for one poll request (`poll_timeout`, `--poll-timeout`)
+    pub poll_timeout: Duration,
+    /// Last successful poll (heartbeat in the status bar)
+    pub last_sync: Option<Instant>,
+    /// Why the last poll failed (None once one succeeds again)
+    pub sync_error: Option<String>,
+    /// Show arguments and results of tool calls (toggle with `t` in the chat)
+    pub tools_expanded: bool,
+    /// Screen reader mode (`--accessible`): transcript as plain lines in the
+    /// normal scrollback, only input and status are drawn
+    pub accessible: bool,
+    /// Strict ASCII output for serial consoles (`--ascii`)
+    pub ascii: bool,
+    /// Reproducible frames (`--deterministic`): frozen timestamps, no
+    /// clock, timers, measured times or flashing
+    pub deterministic: bool,
+    /// Text of the answer streaming in right now
+    pub streaming: Option<String>,
+    /// Request being delivered right now
+    pub in_flight: Option<PendingSend>,
+    /// Cancels the task of the request in flight (Esc)
+    pub request_task: Option<tokio::task::AbortHandle>,
+    /// A polling round is out; the next one starts after it is back
+    pub polling: bool,
+    /// Search hit to show once the room being joined has loaded
+    pub reveal_after_sync: Option<String>,
+    /// What has already been read out in accessible mode
+    pub narrated: Narrated,
+    pub theme: Theme,
+    /// Mouse events go to the app (wheel scrolling) instead of the terminal's
+    /// own text selection
+    pub mouse_capture: bool,
+    /// Alternate scroll mode (DECSET 1007) is on: without capture the
+    /// terminal turns wheel turns into ↑/↓
+    pub alternate_scroll: bool,
+    /// tmux integration (None outside tmux)
+    pub tmux: Option<Tmux>,
+    /// Terminal title last set (None = the terminal's own title is untouched)
+    window_title: Option<String>,
+    /// Chat pane text width and height of the last frame, to notice resizes
+    pub(crate) chat_geometry: Option<(usize, u16)>,
+    /// Messages newer than this arrived while scrolled up ("N neue ↓")
+    pub unseen_after: Option<u64>,
+    /// New messages came in while scrolled up: the next frame keeps the
+    /// same lines in view instead of following the bottom
+    pub(crate) pin_view: bool,
+    /// Wrapped chat lines of the last frame, for `pin_view`
+    pub(crate) chat_total_lines: Option<u32>,
+    /// Where the "N neue Nachrichten" pill was drawn, for clicks
+    pub(crate) unread_pill: Option<Rect>,
+    /// Ctrl+X was pressed in the input; Ctrl+E next opens `$EDITOR`
+    pub(crate) ctrl_x: bool,
+    /// The terminal speaks the kitty keyboard protocol, so Ctrl+Enter and
+    /// Shift+Enter arrive as such
+    pub enhanced_keys: bool,
+    /// Fallback chords active on this terminal (listed in the help)
+    pub fallbacks: Vec<Fallback>,
+    /// Chords of the configurable actions (`[keys]` in the config)
+    pub keys: KeyBindings,
+    pub east_asian_width: EastAsianWidth,
+    /// Status bar template from the config (None = built-in layout)
+    pub status_format: Option<String>,
+    /// Round trip of the last successful poll
+    pub latency: Option<Duration>,
+    /// Version, model and protocol from `GET /health` (startup and /status)
+    pub server_info: Option<ServerInfo>,
+    /// Model named in the last answer
+    pub model: Option<String>,
+    /// Dense transcript: no spacer lines, HH:MM, one-character senders (`d` in the chat)
+    pub compact: bool,
+    pub timestamps: TimestampMode,
+    /// Start of this session (session timer in the status bar)
+    pub started: Instant,
+    /// strftime format of the status bar clock (None = no clock)
+    pub clock: Option<String>,
+    /// Sizing policy of the input pane
+    pub input_height: InputHeight,
+}
+
+/// Progress of the accessible transcript; state changes are announced by
+/// comparing against the last spoken values
+#[derive(Default)]
+pub struct Narrated {
+    pub messages: usize,
+    pub status: Option<ConnectionStatus>,
+    pub view: (usize, usize),
+    pub loading: bool,
+    pub focus: Option<Focus>,
+}
+
+/// A reusable prompt, one file per template in
+/// `~/.config/hank-tui/templates/` (`<name>.txt` or `<name>.md`) with
+/// `{{variable}}` placeholders
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub body: String,
+}
+
+impl PromptTemplate {
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|mut path| {
+            path.push("hank-tui");
+            path.push("templates");
+            path
+        })
+    }
+
+    /// All templates in `dir`, sorted by name
+    pub fn load_all(dir: &Path) -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut templates: Vec<Self> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt" || ext == "md"))
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_string_lossy().into_owned();
+                let body = fs::read_to_string(&path).ok()?;
+                Some(Self { name, body: body.trim_end().to_string() })
+            })
+            .collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Placeholder names in order of first appearance
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = Vec::new();
+        let mut rest = self.body.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start + 2..].find("}}") else { break };
+            let name = rest[start + 2..start + 2 + end].trim().to_string();
+            if !name.is_empty() && !variables.contains(&name) {
+                variables.push(name);
+            }
+            rest = &rest[start + 2 + end + 2..];
+        }
+        variables
+    }
+
+    /// Body with every `{{variable}}` replaced; unknown ones stay as they are
+    pub fn render(&self, values: &[(String, String)]) -> String {
+        let mut out = String::new();
+        let mut rest = self.body.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start + 2..].find("}}") else { break };
+            let name = rest[start + 2..start + 2 + end].trim();
+            out.push_str(&rest[..start]);
+            match values.iter().find(|(n, _)| n == name) {
+                Some((_, value)) => out.push_str(value),
+                None => out.push_str(&rest[start..start + 2 + end + 2]),
+            }
+            rest = &rest[start + 2 + end + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Template picker (`/template`): choose a template, then fill its
+/// variables one after the other
+pub struct TemplatePicker {
+    pub templates: Vec<PromptTemplate>,
+    pub index: usize,
+    /// Variables of the chosen template with the values typed so far
+    pub values: Vec<(String, String)>,
+    /// Variable being typed (None while choosing the template)
+    pub field: Option<usize>,
+}
+
+/// Incremental search in the chat pane (`/` or Ctrl+F)
+#[derive(Default)]
+pub struct Search {
+    pub query: String,
+    /// Still typing; Enter keeps the hits and n/N step through them
+    pub editing: bool,
+    /// Index of the current hit (clamped when drawing)
+    pub current: usize,
+    /// Scroll the current hit into view on the next draw
+    pub reveal: bool,
+}
+
+/// Search across all saved histories (Ctrl+Shift+F)
+pub struct GlobalSearch {
+    pub query: String,
+    pub hits: Vec<HistoryHit>,
+    pub index: usize,
+}
+
+/// Hits the global search lists at most
+const GLOBAL_SEARCH_LIMIT: usize = 200;
+
+/// Conversation list of the picker (Ctrl+O) and the sidebar (F2): the
+/// main conversation first, then the saved ones
+pub struct SessionPicker {
+    pub sessions: Vec<SessionInfo>,
+    pub index: usize,
+}
+
+/// Copy mode of the chat pane (`v`), like tmux's: a cursor over the
+/// transcript text with vi motions and a visual selection that `y` copies
+pub struct CopyMode {
+    text: Vec<char>,
+    /// Index of the first character of every line in `text`
+    line_starts: Vec<usize>,
+    /// Cursor as index into `text`
+    pub cursor: usize,
+    /// Where the selection started (`v`), and whether it is line-wise (`V`)
+    pub anchor: Option<(usize, bool)>,
+    /// First line on screen
+    pub top: usize,
+}
+
+impl CopyMode {
+    /// Copy mode over `transcript`, cursor on the last line with text
+    pub fn new(transcript: &str) -> Self {
+        let text: Vec<char> = transcript.trim_end_matches('\n').chars().collect();
+        let line_starts = std::iter::once(0)
+            .chain(text.iter().enumerate().filter(|(_, c)| **c == '\n').map(|(idx, _)| idx + 1))
+            .collect();
+        let mut copy = Self { text, line_starts, cursor: 0, anchor: None, top: 0 };
+        let last = (0..copy.line_starts.len()).rev().find(|&row| copy.line_len(row) > 0).unwrap_or(0);
+        copy.cursor = copy.at(last, 0);
+        copy
+    }
+
+    fn row_col(&self, pos: usize) -> (usize, usize) {
+        let row = self.line_starts.partition_point(|&start| start <= pos) - 1;
+        (row, pos - self.line_starts[row])
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        let end = self.line_starts.get(row + 1).map_or(self.text.len(), |next| next - 1);
+        end - self.line_starts[row]
+    }
+
+    /// Position at `col` in line `row`, clamped to the line
+    fn at(&self, row: usize, col: usize) -> usize {
+        let row = row.min(self.line_starts.len() - 1);
+        self.line_starts[row] + col.min(self.line_len(row).saturating_sub(1))
+    }
+
+    /// 0 = whitespace, 1 = word, 2 = punctuation (for w/b/e)
+    fn class(&self, pos: usize) -> u8 {
+        match self.text.get(pos) {
+            None => 0,
+            Some(c) if c.is_whitespace() => 0,
+            Some(c) if c.is_alphanumeric() || *c == '_' => 1,
+            Some(_) => 2,
+        }
+    }
+
+    /// Move the cursor; keys without a motion are ignored
+    pub fn motion(&mut self, code: KeyCode) {
+        let (row, col) = self.row_col(self.cursor);
+        let last = self.text.len().saturating_sub(1);
+        self.cursor = match code {
+            KeyCode::Char('h') | KeyCode::Left => self.at(row, col.saturating_sub(1)),
+            KeyCode::Char('l') | KeyCode::Right => self.at(row, col + 1),
+            KeyCode::Char('j') | KeyCode::Down => self.at(row + 1, col),
+            KeyCode::Char('k') | KeyCode::Up => self.at(row.saturating_sub(1), col),
+            KeyCode::PageDown => self.at(row + 10, col),
+            KeyCode::PageUp => self.at(row.saturating_sub(10), col),
+            KeyCode::Char('0') | KeyCode::Home => self.at(row, 0),
+            KeyCode::Char('$') | KeyCode::End => self.at(row, usize::MAX),
+            KeyCode::Char('g') => 0,
+            KeyCode::Char('G') => self.at(self.line_starts.len() - 1, 0),
+            KeyCode::Char('w') => {
+                let mut pos = self.cursor;
+                let class = self.class(pos);
+                while class != 0 && pos < last && self.class(pos) == class {
+                    pos += 1;
+                }
+                while pos < last && self.class(pos) == 0 {
+                    pos += 1;
+                }
+                pos
+            }
+            KeyCode::Char('b') => {
+                let mut pos = self.cursor.saturating_sub(1);
+                while pos > 0 && self.class(pos) == 0 {
+                    pos -= 1;
+                }
+                let class = self.class(pos);
+                while pos > 0 && self.class(pos - 1) == class {
+                    pos -= 1;
+                }
+                pos
+            }
+            KeyCode::Char('e') => {
+                let mut pos = (self.cursor + 1).min(last);
+                while pos < last && self.class(pos) == 0 {
+                    pos += 1;
+                }
+                let class = self.class(pos);
+                while pos < last && self.class(pos + 1) == class {
+                    pos += 1;
+                }
+                pos
+            }
+            _ => self.cursor,
+        };
+    }
+
+    /// Selected range of `text` (end exclusive)
+    fn selected(&self) -> Option<(usize, usize)> {
+        let (anchor, line_wise) = self.anchor?;
+        let (start, end) = (anchor.min(self.cursor), anchor.max(self.cursor));
+        if line_wise {
+            let (first, last) = (self.row_col(start).0, self.row_col(end).0);
+            Some((self.line_starts[first], self.line_starts[last] + self.line_len(last)))
+        } else {
+            Some((start, (end + 1).min(self.text.len())))
+        }
+    }
+
+    /// Text to copy: the selection, or the cursor line without one
+    pub fn selection(&self) -> String {
+        let (start, end) = self.selected().unwrap_or_else(|| {
+            let row = self.row_col(self.cursor).0;
+            (self.line_starts[row], self.line_starts[row] + self.line_len(row))
+        });
+        self.text[start..end].iter().collect()
+    }
+
+    /// Keep the cursor line within `height` rows on screen
+    pub fn follow(&mut self, height: usize) {
+        let row = self.row_col(self.cursor).0;
+        if row < self.top {
+            self.top = row;
+        } else if height > 0 && row >= self.top + height {
+            self.top = row + 1 - height;
+        }
+    }
+
+    /// Cursor column (for horizontal scrolling)
+    pub fn cursor_col(&self) -> usize {
+        self.row_col(self.cursor).1
+    }
+
+    /// The transcript with selection and cursor marked
+    pub fn lines(&self, theme: &Theme) -> Vec<Line<'static>> {
+        let selected = self.selected();
+        let selection_style = Style::default().fg(theme.on_highlight).bg(theme.highlight);
+        let cursor_style = Style::default().add_modifier(Modifier::REVERSED);
+        (0..self.line_starts.len())
+            .map(|row| {
+                let start = self.line_starts[row];
+                let mut spans: Vec<Span<'static>> = Vec::new();
+                let mut run = String::new();
+                let mut run_style = Style::default();
+                let len = self.line_len(row);
+                // An empty line still shows the cursor
+                for pos in start..start + len.max(1) {
+                    let ch = if pos < start + len { self.text[pos] } else { ' ' };
+                    let style = if pos == self.cursor {
+                        cursor_style
+                    } else if selected.is_some_and(|(a, b)| (a..b).contains(&pos)) {
+                        selection_style
+                    } else {
+                        Style::default()
+                    };
+                    if style != run_style && !run.is_empty() {
+                        spans.push(Span::styled(std::mem::take(&mut run), run_style));
+                    }
+                    run_style = style;
+                    run.push(ch);
+                }
+                spans.push(Span::styled(run, run_style));
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
+
+/// Regular expression of the transcript filter. The `regex` crate runs in
+/// linear time, so no pattern can stall the UI; `^` and `$` match at every
+/// line and a leading `(?i)` ignores case.
+#[derive(Debug)]
+pub struct Regex(regex::Regex);
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        regex::RegexBuilder::new(pattern)
+            .multi_line(true)
+            .build()
+            .map(Self)
+            .map_err(|e| match e {
+                regex::Error::Syntax(text) => text.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string(),
+                other => other.to_string(),
+            })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+/// Whose messages the transcript filter keeps
+#[derive(Debug, PartialEq)]
+pub enum RoleFilter {
+    /// `@hank`: Hank's answers
+    Assistant,
+    /// `@ich`: own messages
+    Own,
+    /// `@<name>`: messages of one participant
+    Sender(String),
+}
+
+/// Transcript filter (`/filter [@role] [regex]`)
+#[derive(Debug)]
+pub struct Filter {
+    pub role: Option<RoleFilter>,
+    pub regex: Option<Regex>,
+    /// Regex result per message text (by hash), so the transcript is matched
+    /// once per filter instead of on every frame
+    pub(crate) hits: RefCell<HashMap<u64, bool>>,
+}
+
+impl Filter {
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let arg = arg.trim();
+        let (role, pattern) = match arg.strip_prefix('@') {
+            Some(rest) => {
+                let (name, pattern) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let role = match name.to_lowercase().as_str() {
+                    "hank" | "assistant" => RoleFilter::Assistant,
+                    "ich" | "du" | "me" => RoleFilter::Own,
+                    _ => RoleFilter::Sender(name.to_string()),
+                };
+                (Some(role), pattern.trim())
+            }
+            None => (None, arg),
+        };
+        let regex = match pattern {
+            "" => None,
+            pattern => Some(Regex::new(pattern).map_err(|e| trf("Ungültiger Filter: {}", &[&e.to_string()]))?),
+        };
+        Ok(Self { role, regex, hits: RefCell::default() })
+    }
+
+    /// Whether `content` matches the regex (always without one)
+    pub fn content_matches(&self, content: &str) -> bool {
+        let Some(regex) = &self.regex else {
+            return true;
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        content.hash(&mut hasher);
+        *self.hits.borrow_mut().entry(hasher.finish()).or_insert_with(|| regex.is_match(content))
+    }
+}
+
+/// Day for `/date`: `TT.MM.JJJJ`, `TT.MM.` (this year), `JJJJ-MM-TT`,
+/// `heute`, `gestern` or `-N` (days ago)
+pub fn parse_day(arg: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let arg = arg.trim();
+    let day = match arg.to_lowercase().as_str() {
+        "heute" | "today" => Some(today),
+        "gestern" | "yesterday" => today.pred_opt(),
+        _ => arg
+            .strip_prefix('-')
+            .and_then(|n| n.parse::<u64>().ok())
+            .and_then(|n| today.checked_sub_days(chrono::Days::new(n)))
+            .or_else(|| NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok())
+            .or_else(|| NaiveDate::parse_from_str(arg, "%d.%m.%Y").ok())
+            .or_else(|| {
+                let (d, m) = arg.trim_end_matches('.').split_once('.')?;
+                NaiveDate::from_ymd_opt(today.year(), m.parse().ok()?, d.parse().ok()?)
+            }),
+    };
+    day.ok_or_else(|| trf("Unbekanntes Datum '{}' (TT.MM.JJJJ, JJJJ-MM-TT, heute, gestern)", &[arg]))
+}
+
+/// Quick picker for regenerating an answer with another model (`g`)
+pub struct ModelPicker {
+    pub models: Vec<String>,
+    pub index: usize,
+    /// `timestamp_ms` of the prompt to run again
+    pub prompt_ts: u64,
+}
+
+/// A chat request together with the local message it delivers
+#[derive(Clone)]
+pub struct PendingSend {
+    /// `timestamp_ms` of the own message in the transcript
+    pub message_ts: u64,
+    pub request: ChatRequest,
+    /// When the user sent it (elapsed time in the pending panel)
+    pub queued_at: Instant,
+}
+
+impl PendingSend {
+    /// Short description for the pending panel
+    pub fn label(&self) -> String {
+        let first_line = self.request.message.lines().next().unwrap_or("");
+        let mut label: String = first_line.chars().take(30).collect();
+        if first_line.chars().count() > 30 || self.request.message.lines().nth(1).is_some() {
+            label.push('…');
+        }
+        match &self.request.model {
+            Some(model) => format!("{} → {}", label, model),
+            None => label,
+        }
+    }
+}
+
+/// A message over the server's size limit, waiting for the user to split
+/// it, attach it as a file or go back to editing
+pub struct Oversize {
+    pub text: String,
+    pub to: Option<String>,
+    pub limit: usize,
+}
+
+/// Split `text` into numbered parts ("[1/3] …") of at most `limit`
+/// characters each, label included; cuts at line breaks, else at spaces
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut count = chars.len().div_ceil(limit.max(1)).max(1);
+    loop {
+        let label_len = format!("[{}/{}] ", count, count).chars().count();
+        let room = limit.saturating_sub(label_len).max(1);
+        let mut parts = Vec::new();
+        let mut start = 0;
+        loop {
+            while start < chars.len() && chars[start].is_whitespace() {
+                start += 1;
+            }
+            if start >= chars.len() {
+                break;
+            }
+            let mut end = (start + room).min(chars.len());
+            if end < chars.len() {
+                // The break character itself may sit just past the room
+                let window = &chars[start..=end];
+                let cut = window
+                    .iter()
+                    .rposition(|&c| c == '\n')
+                    .filter(|&cut| cut > room / 2)
+                    .or_else(|| window.iter().rposition(|c| c.is_whitespace()).filter(|&cut| cut > room / 2));
+                if let Some(cut) = cut {
+                    end = start + cut;
+                }
+            }
+            parts.push(chars[start..end].iter().collect::<String>().trim().to_string());
+            start = end;
+        }
+        // More parts than guessed need a longer label; count again
+        if parts.len() <= count {
+            let total = parts.len();
+            return parts.into_iter().enumerate().map(|(i, part)| format!("[{}/{}] {}", i + 1, total, part)).collect();
+        }
+        count = parts.len();
+    }
+}
+
+/// After this long an answer counts as slow and the pending panel shows up
+pub(crate) const BUSY_AFTER: Duration = Duration::from_secs(3);
+
+impl App {
+    pub fn new(backend: Arc<dyn ChatBackend>, history_enabled: bool) -> Self {
+        let server_url = backend.server_url().to_string();
+        let mut messages = Vec::new();
+        let mut read_marker = None;
+        let mut view_anchor = None;
+        let mut older_chunks = 0;
+        
+        // Load history if enabled
+        if history_enabled {
+            if let Some(history) = ChatHistory::load() {
+                if history.server_url == server_url {
+                    messages = history.messages;
+                    read_marker = history.last_read_ms;
+                    view_anchor = history.view_anchor;
+                    older_chunks = history.chunks;
+                    messages.push(Message::new(
+                        "system",
+                        trf("Historie geladen ({} Nachrichten) - {}", &[&messages.len().to_string(), &history.saved_at]),
+                    ));
+                } else {
+                    messages.push(Message::new("system", trf("Neue Session für {}", &[&server_url])));
+                }
+            } else {
+                messages.push(Message::new("system", trf("Verbunden mit {} (History aktiviert)", &[&server_url])));
+            }
+        } else {
+            messages.push(Message::new("system", trf("Verbunden mit {} (History deaktiviert)", &[&server_url])));
+        }
+        
+        let last_timestamp = messages
+            .iter()
+            .filter_map(|m| m.timestamp_ms)
+            .max()
+            .unwrap_or(0);
+
+        let primary = Connection::new(backend.clone());
+        let mut app = Self {
+            input: String::new(),
+            cursor_pos: 0,
+            messages,
+            server_url,
+            backend,
+            loading: false,
+            scroll: 0,
+            input_scroll: 0,
+            command_history: Vec::new(),
+            history_index: None,
+            connection_status: ConnectionStatus::Connected,
+            last_error: None,
+            auto_scroll: true,
+            focus: Focus::Input,
+            history_enabled,
+            session: None,
+            session_picker: None,
+            session_sidebar: None,
+            display_name: None,
+            assistant_name: "Hank".to_string(),
+            own_color: None,
+            assistant_color: None,
+            last_timestamp,
+            last_poll: Instant::now(),
+            debug_overlay: false,
+            http_trace: None,
+            http_client: None,
+            profiles: BTreeMap::new(),
+            stats: None,
+            rooms: vec![Room::new(None)],
+            active_room: 0,
+            rooms_supported: false,
+            available_rooms: Vec::new(),
+            presence_supported: false,
+            presence: Vec::new(),
+            last_presence_poll: None,
+            typing_supported: false,
+            typing: Vec::new(),
+            last_typing_sent: None,
+            read_marker,
+            reported_read: 0,
+            editing: None,
+            thread: None,
+            alert_mode: AlertMode::None,
+            flash_until: None,
+            notice: None,
+            mention_keywords: Vec::new(),
+            mentions_only: false,
+            connections: Vec::new(),
+            active_connection: 0,
+            targets_supported: false,
+            targets: Vec::new(),
+            last_targets_poll: None,
+            target: None,
+            show_participants: false,
+            participant_index: 0,
+            profile: None,
+            // Announcements seen in an earlier session do not pop up again
+            acknowledged: read_marker.unwrap_or(0),
+            user_colors: BTreeMap::new(),
+            is_admin: false,
+            confirm: None,
+            models: Vec::new(),
+            max_message_chars: None,
+            oversize: None,
+            model_picker: None,
+            template_picker: None,
+            copy_mode: None,
+            filter: None,
+            scroll_anchor: None,
+            older_chunks,
+            wants_older: false,
+            selected: None,
+            reveal_selection: false,
+            search: None,
+            global_search: None,
+            edit_diff: None,
+            send_queue: VecDeque::new(),
+            retry_at: None,
+            offline_since: None,
+            poll_failures: 0,
+            poll_interval: POLL_INTERVAL,
+            last_activity: Instant::now(),
+            poll_timeout: POLL_TIMEOUT,
+            last_sync: None,
+            sync_error: None,
+            tools_expanded: false,
+            accessible: false,
+            narrated: Narrated::default(),
+            theme: Theme::default(),
+            mouse_capture: false,
+            ascii: false,
+            deterministic: false,
+            streaming: None,
+            request_task: None,
+            polling: false,
+            reveal_after_sync: None,
+            in_flight: None,
+            alternate_scroll: false,
+            tmux: None,
+            enhanced_keys: false,
+            fallbacks: Vec::new(),
+            keys: KeyBindings::default(),
+            east_asian_width: EastAsianWidth::Narrow,
+            status_format: None,
+            latency: None,
+            server_info: None,
+            model: None,
+            compact: false,
+            timestamps: TimestampMode::Time,
+            input_height: InputHeight::default(),
+            started: Instant::now(),
+            clock: None,
+            window_title: None,
+            chat_geometry: None,
+            unseen_after: None,
+            pin_view: false,
+            chat_total_lines: None,
+            unread_pill: None,
+            ctrl_x: false,
+        };
+        app.connections.push(primary);
+        app.restore_view(view_anchor.as_deref());
+        app
+    }
+
+    /// Exchange the active connection state with a parked `Connection`
+    fn swap_connection_state(&mut self, conn: &mut Connection) {
+        std::mem::swap(&mut self.backend, &mut conn.backend);
+        std::mem::swap(&mut self.server_url, &mut conn.server_url);
+        std::mem::swap(&mut self.rooms, &mut conn.rooms);
+        std::mem::swap(&mut self.active_room, &mut conn.active_room);
+        std::mem::swap(&mut self.messages, &mut conn.messages);
+        std::mem::swap(&mut self.last_timestamp, &mut conn.last_timestamp);
+        std::mem::swap(&mut self.rooms_supported, &mut conn.rooms_supported);
+        std::mem::swap(&mut self.available_rooms, &mut conn.available_rooms);
+        std::mem::swap(&mut self.presence_supported, &mut conn.presence_supported);
+        std::mem::swap(&mut self.typing_supported, &mut conn.typing_supported);
+        std::mem::swap(&mut self.targets_supported, &mut conn.targets_supported);
+        std::mem::swap(&mut self.is_admin, &mut conn.is_admin);
+        std::mem::swap(&mut self.read_marker, &mut conn.read_marker);
+        std::mem::swap(&mut self.reported_read, &mut conn.reported_read);
+        std::mem::swap(&mut self.history_enabled, &mut conn.history_enabled);
+        std::mem::swap(&mut self.session, &mut conn.session);
+        std::mem::swap(&mut self.older_chunks, &mut conn.older_chunks);
+        std::mem::swap(&mut self.connection_status, &mut conn.connection_status);
+        std::mem::swap(&mut self.server_info, &mut conn.server_info);
+    }
+
+    /// Make connection `idx` the active one, parking the current connection
+    pub fn switch_connection(&mut self, idx: usize) {
+        if idx >= self.connections.len() || idx == self.active_connection {
+            return;
+        }
+        let mut current = std::mem::replace(&mut self.connections[self.active_connection], Connection::new(self.backend.clone()));
+        self.swap_connection_state(&mut current);
+        current.synced = true;
+        self.connections[self.active_connection] = current;
+
+        let mut next = std::mem::replace(&mut self.connections[idx], Connection::new(self.backend.clone()));
+        self.swap_connection_state(&mut next);
+        next.unread = 0;
+        self.connections[idx] = next;
+        self.active_connection = idx;
+
+        self.poll_failures = 0;
+        self.unseen_after = None;
+        self.presence.clear();
+        self.last_presence_poll = None;
+        self.typing.clear();
+        self.targets.clear();
+        self.last_targets_poll = None;
+        self.target = None;
+        self.confirm = None;
+        self.model_picker = None;
+        self.thread = None;
+        self.editing = None;
+        self.scroll_to_bottom();
+    }
+
+    /// Add a connection (or find the existing one for the same server)
+    pub fn add_connection(&mut self, backend: Arc<dyn ChatBackend>) -> usize {
+        let url = backend.server_url();
+        let active_url = &self.server_url;
+        if let Some(idx) = self
+            .connections
+            .iter()
+            .enumerate()
+            .position(|(idx, c)| if idx == self.active_connection { active_url == url } else { c.server_url == url })
+        {
+            return idx;
+        }
+        self.connections.push(Connection::new(backend));
+        self.connections.len() - 1
+    }
+
+    /// Every joined room on every connection, in tab bar order
+    pub fn tabs(&self) -> Vec<Tab> {
+        let multi_server = self.connections.len() > 1;
+        let mut tabs = Vec::new();
+        for (conn_idx, conn) in self.connections.iter().enumerate() {
+            let active = conn_idx == self.active_connection;
+            let (server_url, rooms, active_room) = if active {
+                (&self.server_url, &self.rooms, self.active_room)
+            } else {
+                (&conn.server_url, &conn.rooms, conn.active_room)
+            };
+            let host = server_url.split("://").nth(1).unwrap_or(server_url);
+            for (room_idx, room) in rooms.iter().enumerate() {
+                let name = match &room.name {
+                    Some(name) => format!("#{}", name),
+                    None => room.label().to_string(),
+                };
+                let unread = if !active && room_idx == active_room { conn.unread } else { room.unread };
+                tabs.push(Tab {
+                    connection: conn_idx,
+                    room: room_idx,
+                    label: if multi_server { format!("{} {}", host, name) } else { name },
+                    unread,
+                });
+            }
+        }
+        tabs
+    }
+
+    /// Unread messages over all conversations that are not on screen
+    pub fn unread_total(&self) -> usize {
+        self.tabs().iter().map(|tab| tab.unread).sum()
+    }
+
+    /// Name of the active conversation (tab label)
+    pub fn title(&self) -> String {
+        let tabs = self.tabs();
+        tabs[self.active_tab(&tabs)].label.clone()
+    }
+
+    /// Terminal window title: conversation plus unread count
+    pub fn window_title_text(&self) -> String {
+        match self.unread_total() {
+            0 => format!("hank-tui — {}", self.title()),
+            unread => trf("hank-tui — {} ({} ungelesen)", &[&self.title(), &unread.to_string()]),
+        }
+    }
+
+    /// Set the terminal title (OSC 2) when it changed; the first call saves
+    /// the terminal's own title on its title stack (XTWINOPS 22)
+    pub fn sync_window_title(&mut self) -> io::Result<()> {
+        let title = self.window_title_text();
+        if self.window_title.as_deref() == Some(title.as_str()) {
+            return Ok(());
+        }
+        let mut stdout = io::stdout();
+        if self.window_title.is_none() {
+            write!(stdout, "\x1b[22;0t")?;
+        }
+        crossterm::execute!(stdout, crossterm::terminal::SetTitle(&title))?;
+        self.window_title = Some(title);
+        Ok(())
+    }
+
+    /// Give the terminal its original title back (XTWINOPS 23)
+    pub fn restore_window_title(&mut self) -> io::Result<()> {
+        if self.window_title.take().is_some() {
+            let mut stdout = io::stdout();
+            write!(stdout, "\x1b[23;0t")?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Index of the active conversation in `tabs()`
+    pub fn active_tab(&self, tabs: &[Tab]) -> usize {
+        tabs.iter()
+            .position(|t| t.connection == self.active_connection && t.room == self.active_room)
+            .unwrap_or(0)
+    }
+
+    /// Close the active connection (never the primary one) and return to it
+    pub fn close_connection(&mut self) -> Option<Connection> {
+        if self.active_connection == 0 {
+            return None;
+        }
+        let idx = self.active_connection;
+        self.switch_connection(0);
+        Some(self.connections.remove(idx))
+    }
+
+    /// Color of a participant: configured override, else derived from the name
+    pub fn color_for(&self, name: &str) -> Color {
+        if !self.theme.sender_colors {
+            return Color::Reset;
+        }
+        self.user_colors.get(name).copied().unwrap_or_else(|| sender_color(name))
+    }
+
+    /// Whether a message was written by someone else (not our own echo)
+    pub fn is_from_others(&self, msg: &Message) -> bool {
+        match msg.role.as_str() {
+            "assistant" | "announcement" => true,
+            "user" => msg.sender.is_some() && msg.sender != self.display_name,
+            _ => false,
+        }
+    }
+
+    /// Whether `msg` passes the transcript filter (everything without one;
+    /// system lines never with one)
+    pub fn filter_matches(&self, msg: &Message) -> bool {
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+        let role_ok = match &filter.role {
+            _ if msg.role == "system" => false,
+            None => true,
+            Some(RoleFilter::Assistant) => msg.role == "assistant",
+            Some(RoleFilter::Own) => msg.role == "user" && !self.is_from_others(msg),
+            Some(RoleFilter::Sender(name)) => msg.sender.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(name)),
+        };
+        role_ok && filter.content_matches(&msg.content)
+    }
+
+    /// Whether a message from someone else contains the display name or a
+    /// configured keyword (case-insensitive)
+    pub fn is_mention(&self, msg: &Message) -> bool {
+        if !self.is_from_others(msg) {
+            return false;
+        }
+        let content = msg.content.to_lowercase();
+        self.display_name
+            .iter()
+            .chain(&self.mention_keywords)
+            .any(|word| !word.is_empty() && content.contains(&word.to_lowercase()))
+    }
+
+    /// Select the next direct message recipient; wraps back to the channel
+    pub fn cycle_target(&mut self) {
+        self.target = match &self.target {
+            None => self.targets.first().cloned(),
+            Some(current) => self
+                .targets
+                .iter()
+                .position(|t| t == current)
+                .and_then(|idx| self.targets.get(idx + 1))
+                .cloned(),
+        };
+    }
+
+    /// Whether any message newer than `since` in `messages` deserves an alert
+    pub fn any_from_others_since(&self, messages: &[Message], since: u64) -> bool {
+        messages
+            .iter()
+            .any(|m| m.timestamp_ms.is_some_and(|t| t > since) && self.is_from_others(m))
+    }
+
+    /// Ring the bell or start a status bar flash, depending on `alert_mode`
+    pub fn alert(&mut self) {
+        match self.alert_mode {
+            AlertMode::None => {}
+            AlertMode::Bell => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(b"\x07");
+                let _ = stdout.flush();
+            }
+            AlertMode::Flash if self.deterministic => {}
+            AlertMode::Flash => self.flash_until = Some(Instant::now() + Duration::from_millis(400)),
+        }
+    }
+
+    pub fn navigate_history_up(&mut self) {
+        if self.command_history.is_empty() {
+            return;
+        }
+        
+        let new_index = match self.history_index {
+            None => Some(self.command_history.len() - 1),
+            Some(0) => Some(0),
+            Some(i) => Some(i - 1),
+        };
+        
+        if let Some(idx) = new_index {
+            self.history_index = Some(idx);
+            self.input = self.command_history[idx].clone();
+            self.cursor_pos = self.input.chars().count();
+        }
+    }
+
+    pub fn navigate_history_down(&mut self) {
+        if self.command_history.is_empty() {
+            return;
+        }
+        
+        match self.history_index {
+            None => {}
+            Some(i) if i >= self.command_history.len() - 1 => {
+                self.history_index = None;
+                self.input.clear();
+                self.cursor_pos = 0;
+            }
+            Some(i) => {
+                self.history_index = Some(i + 1);
+                self.input = self.command_history[i + 1].clone();
+                self.cursor_pos = self.input.chars().count();
+            }
+        }
+    }
+    
+    /// Name of the active room (None = shared default channel)
+    pub fn room_name(&self) -> Option<&str> {
+        self.rooms[self.active_room].name.as_deref()
+    }
+
+    /// Make another joined room the active one, parking the current stream
+    pub fn switch_room(&mut self, idx: usize) {
+        if idx >= self.rooms.len() || idx == self.active_room {
+            return;
+        }
+        let current = &mut self.rooms[self.active_room];
+        std::mem::swap(&mut current.messages, &mut self.messages);
+        current.last_timestamp = self.last_timestamp;
+        // Everything up to now has been seen in the room we are leaving
+        current.read_marker = Some(self.last_timestamp);
+        current.older_chunks = self.older_chunks;
+        let view_anchor = self.view_anchor();
+        let current = &mut self.rooms[self.active_room];
+        current.view_anchor = view_anchor;
+
+        let next = &mut self.rooms[idx];
+        std::mem::swap(&mut next.messages, &mut self.messages);
+        self.last_timestamp = next.last_timestamp;
+        self.read_marker = next.read_marker;
+        self.older_chunks = next.older_chunks;
+        self.thread = None;
+        self.selected = None;
+        next.unread = 0;
+        self.active_room = idx;
+        self.unseen_after = None;
+        self.presence.clear();
+        self.last_presence_poll = None;
+        self.typing.clear();
+        self.last_targets_poll = None;
+        self.target = None;
+        let view_anchor = self.rooms[idx].view_anchor.take();
+        self.restore_view(view_anchor.as_deref());
+    }
+
+    /// Message at the top of the chat pane, as stored in the history
+    /// (None while following the bottom or before the first draw)
+    pub fn view_anchor(&self) -> Option<String> {
+        let (width, height) = self.chat_geometry?;
+        if self.auto_scroll {
+            return None;
+        }
+        let (lines, starts) = self.chat_layout();
+        let rows: Vec<u32> = lines.iter().map(|l| wrapped_rows(l, width, self.east_asian_width)).collect();
+        let total = rows.iter().sum::<u32>() + CHAT_PADDING_LINES;
+        let mut top = total.saturating_sub(height as u32).saturating_sub(self.scroll as u32);
+        let top_line = rows.iter().position(|&r| {
+            let inside = top < r;
+            top = top.saturating_sub(r);
+            inside
+        })?;
+        let &(idx, _) = starts.iter().rev().find(|&&(_, line)| line <= top_line)?;
+        message_key(&self.messages[idx])
+    }
+
+    /// Scroll back to the message stored by `view_anchor`, or to the bottom
+    /// if it is gone
+    pub fn restore_view(&mut self, anchor: Option<&str>) {
+        match anchor.and_then(|key| self.messages.iter().position(|m| message_key(m).as_deref() == Some(key))) {
+            Some(idx) => {
+                self.scroll_anchor = Some(idx);
+                self.auto_scroll = false;
+            }
+            None => self.scroll_to_bottom(),
+        }
+    }
+
+    /// Put the next older chunk of the saved history in front of the
+    /// messages (the chat pane was scrolled to the top). The view stays on
+    /// the same message since the scroll counts from the bottom.
+    pub fn load_older_chunk(&mut self) -> bool {
+        match self.history_path_for(self.room_name()) {
+            Some(path) => self.load_older_chunk_from(&path),
+            None => false,
+        }
+    }
+
+    /// `load_older_chunk` for the history file at `path`
+    pub fn load_older_chunk_from(&mut self, path: &Path) -> bool {
+        if self.older_chunks == 0 {
+            return false;
+        }
+        self.older_chunks -= 1;
+        let Some(mut older) = ChatHistory::load_chunk(path, self.older_chunks) else {
+            return false;
+        };
+        // After a sync the server may have sent some of them already
+        let first = self.messages.iter().filter_map(|m| m.timestamp_ms).min();
+        older.retain(|m| first.is_none_or(|first| m.timestamp_ms.is_some_and(|t| t < first)));
+        let count = older.len();
+        self.messages.splice(0..0, older);
+        self.selected = self.selected.map(|idx| idx + count);
+        self.scroll_anchor = self.scroll_anchor.map(|idx| idx + count);
+        count > 0
+    }
+
+    /// Empty the open conversation and delete its history file with the
+    /// chunks, so neither comes back on the next start or scroll up
+    pub fn clear_conversation(&mut self) -> Result<(), String> {
+        let path = self.history_path_for(self.room_name()).filter(|_| self.history_enabled);
+        self.clear_conversation_at(path.as_deref())
+    }
+
+    /// `clear_conversation` for the history file at `path`
+    pub fn clear_conversation_at(&mut self, path: Option<&Path>) -> Result<(), String> {
+        if let Some(path) = path {
+            ChatHistory::delete(path).map_err(|e| e.to_string())?;
+        }
+        self.messages.clear();
+        self.selected = None;
+        self.older_chunks = 0;
+        Ok(())
+    }
+
+    /// Index of the joined room `name`, adding it (with its saved history) if needed
+    pub fn join_room(&mut self, name: &str) -> usize {
+        if let Some(idx) = self.rooms.iter().position(|r| r.name.as_deref() == Some(name)) {
+            return idx;
+        }
+        let mut room = Room::new(Some(name.to_string()));
+        if self.history_enabled
+            && let Some(history) = ChatHistory::room_history_path(name).and_then(|path| ChatHistory::load_from(&path))
+            && history.server_url == self.server_url
+        {
+            room.last_timestamp = history.messages.iter().filter_map(|m| m.timestamp_ms).max().unwrap_or(0);
+            room.messages = history.messages;
+            room.read_marker = history.last_read_ms;
+            room.view_anchor = history.view_anchor;
+            room.older_chunks = history.chunks;
+        }
+        room.messages.push(Message::new("system", trf("Raum #{} betreten", &[name])));
+        self.rooms.push(room);
+        self.rooms.len() - 1
+    }
+
+    /// Leave the active room and return to the default channel.
+    /// Returns the room that was left so its history can be saved.
+    pub fn leave_room(&mut self) -> Option<Room> {
+        if self.active_room == 0 {
+            return None;
+        }
+        let idx = self.active_room;
+        self.switch_room(0);
+        Some(self.rooms.remove(idx))
+    }
+
+    /// Save the history of every joined room
+    pub fn save_history(&self) {
+        if !self.history_enabled {
+            return;
+        }
+        for (idx, room) in self.rooms.iter().enumerate() {
+            let (messages, view_anchor) = if idx == self.active_room {
+                (&self.messages, self.view_anchor())
+            } else {
+                (&room.messages, room.view_anchor.clone())
+            };
+            if let Some(path) = self.history_path_for(room.name.as_deref()) {
+                let _ = ChatHistory::save_to(&path, &self.server_url, messages, view_anchor);
+            }
+        }
+    }
+
+    /// The whole active conversation, including the older messages that
+    /// are still in chunk files
+    pub fn full_conversation(&self) -> Vec<Message> {
+        let Some(path) = self.history_path_for(self.room_name()).filter(|_| self.older_chunks > 0) else {
+            return self.messages.clone();
+        };
+        let mut messages = ChatHistory::load_chunks_before(&path, self.older_chunks, &self.messages);
+        messages.extend(self.messages.iter().cloned());
+        messages
+    }
+
+    /// `/export <file>`: the active conversation as Markdown, JSON or HTML,
+    /// chosen by the file extension
+    pub fn export_conversation(&self, file: &str) -> Result<String, String> {
+        let path = match file.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().map_or_else(|| PathBuf::from(file), |home| home.join(rest)),
+            None => PathBuf::from(file),
+        };
+        let format = ExportFormat::from_path(&path).ok_or_else(|| tr("Dateiendung .md, .json oder .html angeben").to_string())?;
+        let messages = self.full_conversation();
+        let title = match self.room_name() {
+            Some(room) => Some(format!("#{}", room)),
+            None => session_title(&messages),
+        };
+        let speakers = Speakers { own: self.display_name.as_deref().unwrap_or(tr("Du")), assistant: &self.assistant_name };
+        let document = export_transcript(&messages, title.as_deref(), format, speakers);
+        fs::write(&path, document).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let count = messages.iter().filter(|m| m.role != "system").count();
+        Ok(trf("Exportiert nach {} ({} Nachrichten)", &[&path.display().to_string(), &count.to_string()]))
+    }
+
+    /// History file of a room, for the default channel the one of the
+    /// open conversation
+    pub(crate) fn history_path_for(&self, room: Option<&str>) -> Option<PathBuf> {
+        match (room, &self.session) {
+            (None, Some(id)) => ChatHistory::session_path(id),
+            _ => ChatHistory::path_for(room),
+        }
+    }
+
+    /// Start a new, empty conversation in the default channel (Ctrl+N);
+    /// the current one stays saved
+    pub fn new_session(&mut self) {
+        self.switch_room(0);
+        self.save_history();
+        self.session = Some(new_session_id());
+        self.messages = vec![Message::new("system", tr("Neue Unterhaltung – frühere öffnet Ctrl+O"))];
+        self.older_chunks = 0;
+        self.read_marker = None;
+        self.thread = None;
+        self.selected = None;
+        self.scroll_to_bottom();
+        self.refresh_session_sidebar();
+    }
+
+    /// Reload the sidebar's list after the open conversation changed
+    fn refresh_session_sidebar(&mut self) {
+        if self.session_sidebar.is_some() {
+            self.session_sidebar = Some(self.conversation_list());
+        }
+    }
+
+    /// The main conversation and the saved ones, most recent first. The
+    /// open one is described from memory, so listing writes nothing.
+    pub fn conversations(&self) -> Vec<SessionInfo> {
+        let mut saved = ChatHistory::sessions_dir()
+            .map(|dir| ChatHistory::list_sessions_in(&dir, &self.server_url))
+            .unwrap_or_default();
+        let main = match &self.session {
+            None => self.open_conversation_info(),
+            Some(id) => {
+                saved.retain(|s| s.id.as_ref() != Some(id));
+                saved.insert(0, self.open_conversation_info());
+                ChatHistory::load()
+                    .filter(|h| h.server_url == self.server_url)
+                    .map(|h| h.session_info(None))
+                    .unwrap_or_else(|| SessionInfo {
+                        id: None,
+                        title: tr("Hauptunterhaltung").to_string(),
+                        saved_at: String::new(),
+                        messages: 0,
+                        unread: 0,
+                        preview: String::new(),
+                    })
+            }
+        };
+        std::iter::once(main).chain(saved).collect()
+    }
+
+    /// List entry of the conversation open in the default channel, as it is
+    /// now rather than when it was last saved
+    fn open_conversation_info(&self) -> SessionInfo {
+        let messages = if self.active_room == 0 { &self.messages } else { &self.rooms[0].messages };
+        let title = match &self.session {
+            None => tr("Hauptunterhaltung").to_string(),
+            // The saved title also covers messages moved into chunks
+            Some(_) => self
+                .history_path_for(None)
+                .and_then(|path| ChatHistory::load_from(&path))
+                .and_then(|history| history.title)
+                .or_else(|| session_title(messages))
+                .unwrap_or_else(|| tr("(ohne Titel)").to_string()),
+        };
+        SessionInfo {
+            id: self.session.clone(),
+            title,
+            saved_at: Local::now().to_rfc3339(),
+            messages: messages.iter().filter(|m| m.role != "system").count(),
+            unread: 0,
+            preview: session_preview(messages),
+        }
+    }
+
+    /// A list of conversations with the open one preselected
+    fn conversation_list(&self) -> SessionPicker {
+        let sessions = self.conversations();
+        let index = sessions.iter().position(|s| s.id == self.session).unwrap_or(0);
+        SessionPicker { sessions, index }
+    }
+
+    /// Open the conversation picker (Ctrl+O)
+    pub fn open_session_picker(&mut self) -> Result<(), String> {
+        if !self.history_enabled {
+            return Err(tr("History deaktiviert – Unterhaltungen werden nicht gespeichert").to_string());
+        }
+        self.session_picker = Some(self.conversation_list());
+        Ok(())
+    }
+
+    /// Show or hide the conversation sidebar (F2); shown, it takes the focus
+    pub fn toggle_session_sidebar(&mut self) -> Result<(), String> {
+        if self.session_sidebar.take().is_some() {
+            if self.focus == Focus::Sessions {
+                self.focus = Focus::Input;
+            }
+            return Ok(());
+        }
+        if !self.history_enabled {
+            return Err(tr("History deaktiviert – Unterhaltungen werden nicht gespeichert").to_string());
+        }
+        self.session_sidebar = Some(self.conversation_list());
+        self.focus = Focus::Sessions;
+        Ok(())
+    }
+
+    /// Key press while the conversation picker is open: ↑/↓ choose,
+    /// Enter opens, Esc cancels
+    pub fn session_picker_key(&mut self, key: KeyEvent) {
+        let Some(picker) = self.session_picker.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.session_picker = None,
+            KeyCode::Up => picker.index = picker.index.saturating_sub(1),
+            KeyCode::Down => picker.index = (picker.index + 1).min(picker.sessions.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                let id = picker.sessions.get(picker.index).and_then(|s| s.id.clone());
+                self.session_picker = None;
+                self.open_conversation(id);
+            }
+            _ => {}
+        }
+    }
+
+    /// Open the conversation `id` (None = main conversation) from its file
+    pub fn open_conversation(&mut self, id: Option<String>) {
+        let path = match &id {
+            Some(id) => ChatHistory::session_path(id),
+            None => ChatHistory::history_path(),
+        };
+        self.open_session(id, path.as_deref());
+    }
+
+    /// Show the conversation `id` (None = main conversation) saved at `path`
+    /// in the default channel, saving the current one first
+    pub fn open_session(&mut self, id: Option<String>, path: Option<&Path>) {
+        self.switch_room(0);
+        self.save_history();
+        let history = path
+            .and_then(ChatHistory::load_from)
+            .filter(|h| h.server_url == self.server_url);
+        let (messages, read_marker, view_anchor, title, chunks) = match history {
+            Some(h) => (h.messages, h.last_read_ms, h.view_anchor, h.title, h.chunks),
+            None => (Vec::new(), None, None, None, 0),
+        };
+        self.messages = messages;
+        self.older_chunks = chunks;
+        // Polling goes on from here instead of fetching the server's messages again
+        let newest = self.messages.iter().filter_map(|m| m.timestamp_ms).max().unwrap_or(0);
+        self.last_timestamp = self.last_timestamp.max(newest);
+        self.read_marker = read_marker;
+        self.session = id;
+        self.thread = None;
+        self.selected = None;
+        let label = match &self.session {
+            Some(_) => format!("„{}“", title.as_deref().unwrap_or(tr("ohne Titel"))),
+            None => tr("Hauptunterhaltung").to_string(),
+        };
+        self.messages.push(Message::new("system", trf("Unterhaltung {} geöffnet", &[&label])));
+        self.restore_view(view_anchor.as_deref());
+        self.refresh_session_sidebar();
+    }
+
+    /// Everyone in the active room: presence if the server reports it,
+    /// otherwise the direct message targets
+    pub fn participants(&self) -> Vec<Presence> {
+        if !self.presence.is_empty() {
+            return self.presence.clone();
+        }
+        self.targets
+            .iter()
+            .map(|name| Presence { name: name.clone(), status: PresenceStatus::Unknown, role: None })
+            .collect()
+    }
+
+    /// Newest server announcement that has not been acknowledged yet
+    pub fn pending_announcement(&self) -> Option<&Message> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "announcement")
+            .filter(|m| m.timestamp_ms.is_some_and(|t| t > self.acknowledged))
+    }
+
+    /// Dismiss the banner of the pending announcement
+    pub fn acknowledge_announcement(&mut self) {
+        if let Some(ts) = self.pending_announcement().and_then(|m| m.timestamp_ms) {
+            self.acknowledged = ts;
+        }
+    }
+
+    /// Update the delivery state of the own message sent at `message_ts`
+    pub fn set_delivery(&mut self, message_ts: u64, state: Delivery) -> Option<&mut Message> {
+        let msg = self
+            .messages
+            .iter_mut()
+            .rev()
+            .find(|m| {
+                // The server's copy may have replaced the local timestamp
+                m.role == "user"
+                    && (m.timestamp_ms == Some(message_ts) || m.local_id.as_deref() == Some(&provisional_id(message_ts)))
+            })?;
+        msg.delivery = Some(state);
+        Some(msg)
+    }
+
+    /// Add an own message to the transcript and build its request; the
+    /// caller delivers or queues it
+    pub fn push_own_message(&mut self, text: String, to: Option<String>) -> PendingSend {
+        // Replying means the new messages have been read
+        self.read_marker = None;
+        let mut own_msg = Message::new("user", text.clone());
+        own_msg.sender = self.display_name.clone();
+        own_msg.thread_id = self.thread.clone();
+        own_msg.to = to.clone();
+        own_msg.delivery = Some(Delivery::Sending);
+        // Parts sent back to back need distinct timestamps as their key
+        let mut message_ts = own_msg.timestamp_ms.unwrap_or_default();
+        while self.messages.iter().any(|m| m.timestamp_ms == Some(message_ts)) {
+            message_ts += 1;
+        }
+        own_msg.timestamp_ms = Some(message_ts);
+        let client_id = Some(provisional_id(message_ts));
+        own_msg.local_id = client_id.clone();
+        self.messages.push(own_msg);
+        PendingSend {
+            message_ts,
+            request: ChatRequest {
+                message: text,
+                display_name: self.display_name.clone(),
+                room: self.room_name().map(str::to_string),
+                thread_id: self.thread.clone(),
+                to,
+                model: None,
+                client_id,
+                session: self.session.clone(),
+            },
+            queued_at: Instant::now(),
+        }
+    }
+
+    /// Send the oversized message as numbered parts, queued back to back;
+    /// returns how many
+    pub fn accept_split(&mut self) -> usize {
+        let Some(offer) = self.oversize.take() else {
+            return 0;
+        };
+        let parts = split_message(&offer.text, offer.limit);
+        for part in &parts {
+            let pending = self.push_own_message(part.clone(), offer.to.clone());
+            self.send_queue.push_back(pending);
+        }
+        self.input.clear();
+        self.cursor_pos = 0;
+        self.input_scroll = 0;
+        self.scroll_to_bottom();
+        parts.len()
+    }
+
+    /// Close the size offer; the text goes back into the input for editing
+    pub fn reject_oversize(&mut self) {
+        if let Some(offer) = self.oversize.take()
+            && self.input.is_empty()
+        {
+            self.insert_str(&offer.text);
+            self.focus = Focus::Input;
+        }
+    }
+
+    /// Mark the newest failed message as sending again and return its request
+    pub fn retry_failed(&mut self) -> Option<PendingSend> {
+        let room = self.room_name().map(str::to_string);
+        let display_name = self.display_name.clone();
+        let session = self.session.clone();
+        let msg = self.messages.iter_mut().rev().find(|m| m.delivery == Some(Delivery::Failed))?;
+        msg.delivery = Some(Delivery::Sending);
+        Some(PendingSend {
+            message_ts: msg.timestamp_ms?,
+            request: ChatRequest {
+                message: msg.content.clone(),
+                display_name,
+                room,
+                thread_id: msg.thread_id.clone(),
+                to: msg.to.clone(),
+                model: None,
+                client_id: msg.local_id.clone(),
+                session,
+            },
+            queued_at: Instant::now(),
+        })
+    }
+
+    /// The prompt behind the newest answer from Hank (to regenerate it)
+    pub fn regenerate_prompt(&self) -> Option<&Message> {
+        let answer = self.messages.iter().rposition(|m| m.role == "assistant")?;
+        self.messages[..answer].iter().rev().find(|m| m.role == "user" && m.timestamp_ms.is_some())
+    }
+
+    /// Run the prompt sent at `prompt_ts` again with `model`; the answer is
+    /// appended as an alternative
+    pub fn regenerate_request(&self, prompt_ts: u64, model: &str) -> Option<PendingSend> {
+        let prompt = self.messages.iter().find(|m| m.role == "user" && m.timestamp_ms == Some(prompt_ts))?;
+        Some(PendingSend {
+            message_ts: prompt_ts,
+            request: ChatRequest {
+                message: prompt.content.clone(),
+                display_name: self.display_name.clone(),
+                room: self.room_name().map(str::to_string),
+                thread_id: prompt.thread_id.clone(),
+                to: prompt.to.clone(),
+                model: Some(model.to_string()),
+                client_id: None,
+                session: self.session.clone(),
+            },
+            queued_at: Instant::now(),
+        })
+    }
+
+    /// The pending panel shows while messages wait in the queue or the
+    /// server takes long to answer
+    pub fn pending_panel_visible(&self) -> bool {
+        !self.send_queue.is_empty() || self.in_flight.as_ref().is_some_and(|p| self.age(p.queued_at) >= BUSY_AFTER)
+    }
+
+    /// Drop the newest queued request (`x`); its message is marked as not
+    /// delivered so `r` can send it again. False if the queue is empty.
+    pub fn cancel_queued(&mut self) -> bool {
+        let Some(pending) = self.send_queue.pop_back() else {
+            return false;
+        };
+        self.abandon(&pending);
+        if self.send_queue.is_empty() {
+            self.retry_at = None;
+        }
+        true
+    }
+
+    /// A request given up by the user; regenerations leave their prompt alone
+    fn abandon(&mut self, pending: &PendingSend) {
+        if pending.request.model.is_none() {
+            self.set_delivery(pending.message_ts, Delivery::Failed);
+        }
+        self.last_error = Some(tr("Anfrage abgebrochen").to_string());
+    }
+
+    /// Status bar note while rate-limited messages wait for their retry
+    pub fn rate_limit_status(&self) -> Option<String> {
+        let retry_at = self.retry_at?;
+        let secs = retry_at.saturating_duration_since(Instant::now()).as_secs_f32().ceil() as u64;
+        Some(trf("⏳ Rate-Limit: {}s ({} wartend)", &[&secs.to_string(), &self.send_queue.len().to_string()]))
+    }
+
+    /// Name of the highlighted participant
+    pub fn selected_participant(&self) -> Option<String> {
+        let participants = self.participants();
+        let idx = self.participant_index.min(participants.len().checked_sub(1)?);
+        Some(participants[idx].name.clone())
+    }
+
+    /// Presence of the Hank assistant worker, if the server reports it
+    pub fn assistant_presence(&self) -> Option<PresenceStatus> {
+        self.presence
+            .iter()
+            .find(|p| p.role.as_deref() == Some("assistant"))
+            .map(|p| p.status)
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll = 0;
+        self.auto_scroll = true;
+    }
+    
+    pub fn scroll_up(&mut self) {
+        self.auto_scroll = false;
+        self.scroll = self.scroll.saturating_add(1);
+    }
+    
+    pub fn scroll_down(&mut self) {
+        if self.scroll > 0 {
+            self.scroll = self.scroll.saturating_sub(1);
+        }
+        if self.scroll == 0 {
+            self.auto_scroll = true;
+        }
+    }
+
+    pub fn scroll_page_up(&mut self, amount: u16) {
+        self.auto_scroll = false;
+        self.scroll = self.scroll.saturating_add(amount.max(1));
+    }
+
+    pub fn scroll_page_down(&mut self, amount: u16) {
+        if self.scroll > amount {
+            self.scroll = self.scroll.saturating_sub(amount);
+        } else {
+            self.scroll = 0;
+            self.auto_scroll = true;
+        }
+    }
+
+    pub fn jump_to_top(&mut self) {
+        self.auto_scroll = false;
+        self.scroll = u16::MAX;
+    }
+
+    pub fn jump_to_bottom(&mut self) {
+        self.scroll = 0;
+        self.auto_scroll = true;
+    }
+
+    /// Messages that arrived while scrolled up
+    pub fn unseen_count(&self) -> usize {
+        let Some(after) = self.unseen_after else {
+            return 0;
+        };
+        self.messages
+            .iter()
+            .filter(|m| m.role != "system" && !self.is_thread_reply(m) && m.timestamp_ms.is_some_and(|t| t > after))
+            .count()
+    }
+
+    /// Scroll the first message that arrived while scrolled up to the top
+    /// of the chat; false without one
+    pub fn jump_to_unseen(&mut self) -> bool {
+        let Some(after) = self.unseen_after.take() else {
+            return false;
+        };
+        let first = self
+            .messages
+            .iter()
+            .position(|m| m.role != "system" && !self.is_thread_reply(m) && m.timestamp_ms.is_some_and(|t| t > after));
+        match first {
+            Some(idx) => {
+                self.scroll_anchor = Some(idx);
+                self.auto_scroll = false;
+                true
+            }
+            None => false,
+        }
+    }
+    
+    pub fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Input => Focus::Chat,
+            Focus::Chat if self.show_participants => Focus::Participants,
+            Focus::Chat | Focus::Participants if self.session_sidebar.is_some() => Focus::Sessions,
+            Focus::Chat => Focus::Input,
+            Focus::Help | Focus::Participants | Focus::Sessions => Focus::Input,
+        };
+    }
+    
+    /// Capture the mouse, or hand it to the terminal and let the wheel
+    /// arrive as arrow keys (alternate scroll mode) instead
+    pub fn set_mouse_capture(&mut self, on: bool) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        if on {
+            crossterm::execute!(stdout, event::EnableMouseCapture, Print("\x1b[?1007l"))?;
+        } else {
+            crossterm::execute!(stdout, event::DisableMouseCapture, Print("\x1b[?1007h"))?;
+        }
+        self.mouse_capture = on;
+        self.alternate_scroll = !on;
+        Ok(())
+    }
+
+    /// Leave the mouse as the terminal had it (on exit)
+    pub fn reset_mouse(&mut self) -> io::Result<()> {
+        if self.mouse_capture {
+            crossterm::execute!(io::stdout(), event::DisableMouseCapture)?;
+        }
+        if self.alternate_scroll {
+            crossterm::execute!(io::stdout(), Print("\x1b[?1007l"))?;
+        }
+        self.mouse_capture = false;
+        self.alternate_scroll = false;
+        Ok(())
+    }
+
+    /// Switch to the kitty keyboard protocol where the terminal supports it
+    pub fn enable_enhanced_keys(&mut self) -> io::Result<()> {
+        use crossterm::event::{KeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+        if crossterm::terminal::supports_keyboard_enhancement()? {
+            crossterm::execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+            )?;
+            self.enhanced_keys = true;
+        }
+        Ok(())
+    }
+
+    pub fn disable_enhanced_keys(&mut self) -> io::Result<()> {
+        if self.enhanced_keys {
+            crossterm::execute!(io::stdout(), event::PopKeyboardEnhancementFlags)?;
+            self.enhanced_keys = false;
+        }
+        Ok(())
+    }
+
+    /// The key is bound to `action`. Without the kitty keyboard protocol
+    /// most terminals deliver Ctrl+Enter as Ctrl+J.
+    pub fn bound(&self, action: Action, key: KeyEvent) -> bool {
+        let legacy_ctrl_enter = !self.enhanced_keys
+            && key.code == KeyCode::Char('j')
+            && key.modifiers == KeyModifiers::CONTROL
+            && self.keys.matches(action, KeyEvent::new(KeyCode::Enter, KeyModifiers::CONTROL));
+        legacy_ctrl_enter || self.keys.matches(action, key)
+    }
+
+    pub fn toggle_help(&mut self) {
+        self.focus = match self.focus {
+            Focus::Help => Focus::Input,
+            _ => Focus::Help,
+        };
+    }
+    
+    /// Name shown in front of a user message: the relayed sender, else our own name
+    pub(crate) fn user_label<'a>(&'a self, msg: &'a Message) -> &'a str {
+        msg.sender
+            .as_deref()
+            .or(self.display_name.as_deref())
+            .unwrap_or(tr("Du"))
+    }
+
+    /// Load the last own message that has a server id into the input.
+    /// Returns false if there is nothing to edit.
+    pub fn start_edit(&mut self) -> bool {
+        let Some(msg) = self.messages.iter().rev().find(|m| {
+            m.role == "user" && m.id.is_some() && (m.sender.is_none() || m.sender == self.display_name)
+        }) else {
+            return false;
+        };
+        self.editing = msg.id.clone();
+        self.input = msg.content.clone();
+        self.cursor_pos = self.input.chars().count();
+        self.input_scroll = 0;
+        self.focus = Focus::Input;
+        true
+    }
+
+    /// Apply an edit confirmed by the server to the local transcript; the
+    /// old text is kept as a revision
+    pub fn apply_edit(&mut self, id: &str, content: String) {
+        if let Some(msg) = self.messages.iter_mut().find(|m| m.id.as_deref() == Some(id)) {
+            let previous = std::mem::replace(&mut msg.content, content);
+            if previous != msg.content {
+                msg.revisions.push(previous);
+            }
+            msg.edited = true;
+        }
+    }
+
+    /// Key press in copy mode: motions move the cursor, `v`/`V` start or
+    /// drop a selection, `y`/Enter return the text to copy and leave,
+    /// Esc/`q` leave without copying
+    pub fn copy_mode_key(&mut self, key: KeyEvent) -> Option<String> {
+        let copy = self.copy_mode.as_mut()?;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.copy_mode = None,
+            KeyCode::Char('y') | KeyCode::Enter => {
+                let text = copy.selection();
+                self.copy_mode = None;
+                return Some(text);
+            }
+            KeyCode::Char('>') => {
+                let text = copy.selection();
+                self.copy_mode = None;
+                self.quote_into_input(&text);
+            }
+            KeyCode::Char(c @ ('v' | 'V')) => {
+                let line_wise = c == 'V';
+                copy.anchor = match copy.anchor {
+                    Some((_, current)) if current == line_wise => None,
+                    Some((anchor, _)) => Some((anchor, line_wise)),
+                    None => Some((copy.cursor, line_wise)),
+                };
+            }
+            code => copy.motion(code),
+        }
+        None
+    }
+
+    /// Show `text` in the status bar for a few seconds
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.notice = Some((text.into(), Instant::now() + NOTICE_FOR));
+    }
+
+    /// Hank's newest answer for Ctrl+Y, or only its code blocks for
+    /// Ctrl+Shift+Y, together with the confirmation to show
+    pub fn last_reply_for_copy(&self, code_only: bool) -> Result<(String, String), String> {
+        let reply = self
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "assistant")
+            .ok_or_else(|| trf("Noch keine Antwort von {}", &[&self.assistant_name]))?;
+        if !code_only {
+            return Ok((reply.content.clone(), trf("Antwort kopiert ({} Zeichen)", &[&reply.content.chars().count().to_string()])));
+        }
+        let blocks = code_blocks(&reply.content);
+        match blocks.len() {
+            0 => Err(tr("Keine Codeblöcke in der letzten Antwort").to_string()),
+            1 => Ok((blocks.join(""), tr("Codeblock kopiert").to_string())),
+            n => Ok((blocks.join("\n\n"), trf("{} Codeblöcke kopiert", &[&n.to_string()]))),
+        }
+    }
+
+    /// Move the message selection by `step` shown messages; the first move
+    /// selects the newest one
+    pub fn move_selection(&mut self, step: isize) {
+        let shown: Vec<usize> = self.chat_layout().1.into_iter().map(|(idx, _)| idx).collect();
+        let Some(&newest) = shown.last() else {
+            return;
+        };
+        let next = match self.selected.and_then(|sel| shown.iter().position(|&idx| idx == sel)) {
+            Some(pos) => shown[pos.saturating_add_signed(step).min(shown.len() - 1)],
+            None => newest,
+        };
+        self.selected = Some(next);
+        self.reveal_selection = true;
+    }
+
+    /// Start a new search; the hits show in the chat pane
+    pub fn open_search(&mut self) {
+        self.search = Some(Search { editing: true, ..Search::default() });
+        self.focus = Focus::Chat;
+    }
+
+    /// Hits of the current search in the chat lines
+    pub fn search_hits(&self) -> Vec<SearchHit> {
+        match &self.search {
+            Some(search) => find_in_lines(&self.chat_layout().0, &search.query),
+            None => Vec::new(),
+        }
+    }
+
+    /// Key press while typing the search: every change jumps to the newest
+    /// hit, Enter keeps the search, Esc drops it
+    pub fn search_key(&mut self, key: KeyEvent) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.search = None,
+            KeyCode::Enter if search.query.is_empty() => self.search = None,
+            KeyCode::Enter => search.editing = false,
+            KeyCode::Backspace => {
+                search.query.pop();
+                search.current = usize::MAX;
+                search.reveal = true;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                search.query.push(c);
+                search.current = usize::MAX;
+                search.reveal = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Move to the next hit further down (n) or up (N), wrapping around
+    pub fn step_search(&mut self, forward: bool) {
+        let total = self.search_hits().len();
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        if total == 0 {
+            return;
+        }
+        let current = search.current.min(total - 1);
+        search.current = if forward { (current + 1) % total } else { (current + total - 1) % total };
+        search.reveal = true;
+    }
+
+    /// Open the search across all saved histories (Ctrl+Shift+F)
+    pub fn open_global_search(&mut self) -> Result<(), String> {
+        if !self.history_enabled {
+            return Err(tr("History deaktiviert – es gibt nichts zu durchsuchen").to_string());
+        }
+        // The open conversation is searched as saved
+        self.save_history();
+        self.global_search = Some(GlobalSearch { query: String::new(), hits: Vec::new(), index: 0 });
+        Ok(())
+    }
+
+    /// Key press on the global search screen: typing searches, ↑/↓ choose,
+    /// Enter returns the hit to open, Esc closes
+    pub fn global_search_key(&mut self, key: KeyEvent, index: &dyn HistorySearch) -> Option<HistoryHit> {
+        let search = self.global_search.as_mut()?;
+        match key.code {
+            KeyCode::Esc => self.global_search = None,
+            KeyCode::Up => search.index = search.index.saturating_sub(1),
+            KeyCode::Down => search.index = (search.index + 1).min(search.hits.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                let hit = search.hits.get(search.index).cloned();
+                if hit.is_some() {
+                    self.global_search = None;
+                }
+                return hit;
+            }
+            KeyCode::Backspace | KeyCode::Char(_) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                match key.code {
+                    KeyCode::Char(c) => search.query.push(c),
+                    _ => {
+                        search.query.pop();
+                    }
+                }
+                search.hits = index.search(&search.query, GLOBAL_SEARCH_LIMIT);
+                search.index = 0;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Select the message `key` of the open conversation and scroll to it
+    pub fn reveal_message(&mut self, key: Option<&str>) {
+        // Hits from the search can sit in a chunk that is not loaded yet
+        while let Some(key) = key
+            && self.older_chunks > 0
+            && !self.messages.iter().any(|m| message_key(m).as_deref() == Some(key))
+        {
+            self.load_older_chunk();
+        }
+        self.restore_view(key);
+        self.selected = key.and_then(|key| self.messages.iter().position(|m| message_key(m).as_deref() == Some(key)));
+    }
+
+    /// The selected message, or else the newest one matching `pred`
+    pub fn target_message(&self, pred: impl Fn(&Message) -> bool) -> Option<usize> {
+        match self.selected {
+            Some(idx) => (idx < self.messages.len()).then_some(idx),
+            None => self.messages.iter().rposition(pred),
+        }
+    }
+
+    /// Insert `text` as a `> ` quoted block at the cursor, on a line of its
+    /// own, and leave the cursor below it for the follow-up question
+    pub fn quote_into_input(&mut self, text: &str) {
+        let text = text.trim_matches('\n');
+        if text.trim().is_empty() {
+            return;
+        }
+        let mut quote: String = text
+            .lines()
+            .map(|line| if line.trim().is_empty() { ">".to_string() } else { format!("> {}", line.trim_end()) })
+            .collect::<Vec<_>>()
+            .join("\n");
+        quote.push('\n');
+        let before = self.input.chars().nth(self.cursor_pos.wrapping_sub(1));
+        if before.is_some_and(|c| c != '\n') {
+            quote.insert(0, '\n');
+        }
+        self.insert_str(&quote);
+        self.focus = Focus::Input;
+    }
+
+    /// Open the template picker (`/template`); false if there are none
+    pub fn open_template_picker(&mut self, templates: Vec<PromptTemplate>) -> bool {
+        if templates.is_empty() {
+            return false;
+        }
+        self.template_picker = Some(TemplatePicker { templates, index: 0, values: Vec::new(), field: None });
+        true
+    }
+
+    /// Key press while the template picker is open: ↑/↓ and Enter choose
+    /// the template, then each variable is typed and confirmed with Enter;
+    /// the rendered prompt goes into the input at the cursor. Esc cancels.
+    pub fn template_picker_key(&mut self, key: KeyEvent) {
+        let Some(picker) = self.template_picker.as_mut() else {
+            return;
+        };
+        match (picker.field, key.code) {
+            (_, KeyCode::Esc) => {
+                self.template_picker = None;
+                return;
+            }
+            (None, KeyCode::Up) => picker.index = picker.index.saturating_sub(1),
+            (None, KeyCode::Down) => picker.index = (picker.index + 1).min(picker.templates.len() - 1),
+            (None, KeyCode::Enter) => {
+                let variables = picker.templates[picker.index].variables();
+                picker.values = variables.into_iter().map(|name| (name, String::new())).collect();
+                picker.field = Some(0);
+            }
+            (Some(field), KeyCode::Enter) => picker.field = Some(field + 1),
+            (Some(field), KeyCode::Up) => picker.field = Some(field.saturating_sub(1)),
+            (Some(field), KeyCode::Backspace) => {
+                picker.values[field].1.pop();
+            }
+            (Some(field), KeyCode::Char(c)) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                picker.values[field].1.push(c);
+            }
+            _ => {}
+        }
+        // All variables filled: insert the prompt
+        if picker.field.is_some_and(|field| field >= picker.values.len()) {
+            let prompt = picker.templates[picker.index].render(&picker.values);
+            self.template_picker = None;
+            self.insert_str(&prompt);
+            self.focus = Focus::Input;
+        }
+    }
+
+    /// Show what changed in the newest edited message (`/diff`).
+    /// Returns false if no message has an earlier version.
+    pub fn show_last_edit_diff(&mut self) -> bool {
+        let Some(msg) = self.messages.iter().rev().find(|m| !m.revisions.is_empty()) else {
+            return false;
+        };
+        self.edit_diff = msg.revisions.last().map(|old| (old.clone(), msg.content.clone()));
+        true
+    }
+
+    /// Whether `msg` is a reply that belongs into a thread view rather than
+    /// the main transcript (replies whose root is unknown stay visible)
+    pub(crate) fn is_thread_reply(&self, msg: &Message) -> bool {
+        msg.thread_id
+            .as_deref()
+            .is_some_and(|root| self.messages.iter().any(|m| m.id.as_deref() == Some(root)))
+    }
+
+    /// Number of replies in the thread started by `msg`
+    pub(crate) fn reply_count(&self, msg: &Message) -> usize {
+        match msg.id.as_deref() {
+            Some(id) => self.messages.iter().filter(|m| m.thread_id.as_deref() == Some(id)).count(),
+            None => 0,
+        }
+    }
+
+    /// Scroll to the first message on `day` (or the next day with messages;
+    /// the history only reaches back as far as it was kept). Returns a hint
+    /// when the day itself had no messages.
+    pub fn jump_to_date(&mut self, day: NaiveDate) -> Result<Option<String>, String> {
+        let start = day.and_hms_opt(0, 0, 0).and_then(|t| Local.from_local_datetime(&t).earliest());
+        let start_ms = start.map_or(0, |t| t.timestamp_millis().max(0) as u64);
+        let Some(idx) = self.messages.iter().position(|m| {
+            m.role != "system" && !self.is_thread_reply(m) && m.timestamp_ms.is_some_and(|t| t >= start_ms)
+        }) else {
+            return Err(trf("Keine Nachrichten ab dem {}", &[&day.format("%d.%m.%Y").to_string()]));
+        };
+        self.scroll_anchor = Some(idx);
+        self.auto_scroll = false;
+        let found = self.messages[idx]
+            .timestamp_ms
+            .and_then(|ms| Local.timestamp_millis_opt(ms as i64).single())
+            .map(|t| t.date_naive());
+        Ok(found.filter(|&found| found != day).map(|found| {
+            trf("Keine Nachrichten am {}, weiter am {}", &[&day.format("%d.%m.%Y").to_string(), &found.format("%d.%m.%Y").to_string()])
+        }))
+    }
+
+    /// Stop waiting for the streaming answer: what arrived so far stays as
+    /// an interrupted assistant message
+    pub fn interrupt_stream(&mut self, thread_id: Option<String>) {
+        let mut reply = Message::new("assistant", self.streaming.take().unwrap_or_default());
+        reply.interrupted = true;
+        reply.thread_id = thread_id;
+        self.messages.push(reply);
+        self.loading = false;
+        self.connection_status = ConnectionStatus::Connected;
+        self.scroll_to_bottom();
+    }
+
+    /// Esc while a request is out: stop the answer (what came so far stays)
+    /// or, while nothing has arrived yet, cancel the request
+    pub fn stop_request(&mut self) {
+        let Some(pending) = self.in_flight.take() else {
+            return;
+        };
+        if let Some(task) = self.request_task.take() {
+            task.abort();
+        }
+        if self.streaming.is_some() {
+            self.set_delivery(pending.message_ts, Delivery::Sent);
+            self.interrupt_stream(pending.request.thread_id.clone());
+        } else {
+            self.abandon(&pending);
+            self.loading = false;
+            self.connection_status = ConnectionStatus::Connected;
+        }
+    }
+
+    /// Open the thread of the newest message that has replies, or start one
+    /// on the newest message with a server id. Returns false if neither exists.
+    pub fn open_thread(&mut self) -> bool {
+        let root = self
+            .messages
+            .iter()
+            .rev()
+            .find(|m| self.reply_count(m) > 0)
+            .or_else(|| self.messages.iter().rev().find(|m| m.id.is_some() && m.thread_id.is_none()))
+            .and_then(|m| m.id.clone());
+        self.thread = root;
+        self.thread.is_some()
+    }
+
+    /// The transcript as plain text, the way it is shown in the chat pane
+    pub fn transcript_text(&self) -> String {
+        self.chat_lines()
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect::<String>() + "\n")
+            .collect()
+    }
+
+    /// A message as one spoken-friendly line: "12:03, Mara sagt: ..."
+    pub fn spoken_message(&self, msg: &Message) -> String {
+        let content = msg.content.lines().collect::<Vec<_>>().join(" / ");
+        let who = match msg.role.as_str() {
+            "user" if self.is_from_others(msg) => trf("{} sagt", &[self.user_label(msg)]),
+            "user" => tr("Du sagst").to_string(),
+            "assistant" => trf("{} sagt", &[&self.assistant_name]),
+            "system" => return format!("System: {}", content),
+            "error" => tr("Fehler").to_string(),
+            "announcement" => tr("Ankündigung").to_string(),
+            other => other.to_string(),
+        };
+        let who = match &msg.to {
+            Some(to) => trf("{} privat zu {}", &[&who, to]),
+            None => who,
+        };
+        let mut line = format!("{}, {}: {}", msg.timestamp, who, content);
+        if msg.edited {
+            line.push_str(tr(" (bearbeitet)"));
+        }
+        if msg.delivery == Some(Delivery::Failed) {
+            line.push_str(tr(" – nicht zugestellt, r sendet erneut"));
+        }
+        for call in &msg.tool_calls {
+            line.push_str(&trf(" – Werkzeug {}", &[&call.summary()]));
+        }
+        line
+    }
+
+    /// Lines to print since the last call in accessible mode: new messages
+    /// plus announcements of room, status, focus and loading changes
+    pub fn narrate(&mut self) -> Vec<String> {
+        let mut out = Vec::new();
+        let view = (self.active_connection, self.active_room);
+        if view != self.narrated.view {
+            self.narrated.view = view;
+            self.narrated.messages = self.messages.len();
+            let room = self.rooms[self.active_room].label().to_string();
+            out.push(trf("Gewechselt zu {} auf {}", &[&room, &self.server_url]));
+        }
+        if self.narrated.messages > self.messages.len() {
+            self.narrated.messages = self.messages.len();
+        }
+        for msg in &self.messages[self.narrated.messages..] {
+            if !self.is_thread_reply(msg) || self.thread.is_some() {
+                out.push(self.spoken_message(msg));
+            }
+        }
+        self.narrated.messages = self.messages.len();
+        if self.narrated.status != Some(self.connection_status) {
+            self.narrated.status = Some(self.connection_status);
+            out.push(format!("Status: {}", self.connection_status.label()));
+        }
+        if self.loading != self.narrated.loading {
+            self.narrated.loading = self.loading;
+            if self.loading {
+                out.push(trf("{} denkt nach...", &[&self.assistant_name]));
+            }
+        }
+        if self.narrated.focus != Some(self.focus) {
+            // The initial input focus needs no announcement
+            if self.narrated.focus.is_some() {
+                let name = match self.focus {
+                    Focus::Input => tr("Eingabe"),
+                    Focus::Chat => "Chat",
+                    Focus::Help => tr("Hilfe"),
+                    Focus::Participants => tr("Teilnehmerliste"),
+                    Focus::Sessions => tr("Unterhaltungen"),
+                };
+                out.push(trf("Fokus: {}", &[name]));
+            }
+            self.narrated.focus = Some(self.focus);
+        }
+        out
+    }
+
+    /// Visual (line, col) of every cursor position `0..=chars` for the given width.
+    /// Uses the same character wrapping as `wrap_text_for_display`, so all cursor
+    /// movement derives from what is actually on screen.
+    pub(crate) fn cursor_positions(&self, width: usize) -> Vec<(usize, usize)> {
+        let mut positions = Vec::with_capacity(self.input.len() + 1);
+        let mut line = 0;
+        let mut col = 0;
+
+        for ch in self.input.chars() {
+            if ch == '\n' {
+                positions.push((line, col));
+                line += 1;
+                col = 0;
+                continue;
+            }
+            let char_width = self.east_asian_width.char_width(ch);
+            // Wrap BEFORE adding character if it would exceed width
+            if col > 0 && col + char_width > width {
+                line += 1;
+                col = 0;
+            }
+            positions.push((line, col));
+            col += char_width;
+        }
+
+        // A cursor behind a completely filled line sits at the start of the next one
+        if col >= width {
+            positions.push((line + 1, 0));
+        } else {
+            positions.push((line, col));
+        }
+        positions
+    }
+
+    /// Position on `line` closest to `target_col` without passing it
+    pub(crate) fn position_on_line(positions: &[(usize, usize)], line: usize, target_col: usize) -> Option<usize> {
+        let mut best = None;
+        for (i, &(l, c)) in positions.iter().enumerate() {
+            if l == line {
+                if best.is_none() || c <= target_col {
+                    best = Some(i);
+                }
+            } else if l > line {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Byte offset of the given character position in `input`
+    fn byte_index(&self, char_pos: usize) -> usize {
+        self.input
+            .char_indices()
+            .nth(char_pos)
+            .map(|(i, _)| i)
+            .unwrap_or(self.input.len())
+    }
+
+    /// Calculate cursor line and column for given width (accounting for wrapping and newlines)
+    pub fn cursor_line_col(&self, width: usize) -> (usize, usize) {
+        if width == 0 {
+            return (0, 0);
+        }
+        let positions = self.cursor_positions(width);
+        positions[self.cursor_pos.min(positions.len() - 1)]
+    }
+    
+    /// Time since Hank's last answer in the active conversation
+    pub fn since_last_reply(&self) -> Option<Duration> {
+        if self.deterministic {
+            return None;
+        }
+        let last = self.messages.iter().rev().find(|m| m.role == "assistant")?.timestamp_ms?;
+        Some(Duration::from_millis(now_ms().saturating_sub(last)))
+    }
+
+    /// Wall clock, frozen at `DETERMINISTIC_TIME` with `--deterministic`
+    pub fn now(&self) -> NaiveDateTime {
+        if self.deterministic { DETERMINISTIC_TIME } else { Local::now().naive_local() }
+    }
+
+    /// Time since `since`; always zero with `--deterministic`
+    pub fn age(&self, since: Instant) -> Duration {
+        if self.deterministic { Duration::ZERO } else { since.elapsed() }
+    }
+
+    /// Screen column of the cursor: like `cursor_line_col`, but in display
+    /// order when the line contains right-to-left text. Movement itself
+    /// stays logical (←/→ step through the text as typed).
+    pub fn cursor_display_col(&self, width: usize) -> (usize, usize) {
+        let (line, col) = self.cursor_line_col(width);
+        let positions = self.cursor_positions(width);
+        let on_line: Vec<(usize, char)> = self
+            .input
+            .chars()
+            .enumerate()
+            .filter(|&(idx, ch)| positions[idx].0 == line && ch != '\n')
+            .collect();
+        let text: String = on_line.iter().map(|&(_, ch)| ch).collect();
+        let (Some(levels), Some(order)) = (bidi_levels(&text), bidi_visual_order(&text)) else {
+            return (line, col);
+        };
+        let logical = on_line.iter().position(|&(idx, _)| idx == self.cursor_pos).unwrap_or(on_line.len());
+        let visual_of = |logical: usize| order.iter().position(|&idx| idx == logical).unwrap_or(0);
+        // The cursor sits on the trailing side of the character before it,
+        // which is its left side inside a right-to-left run
+        let visual = match logical.checked_sub(1) {
+            Some(prev) if levels[prev] % 2 == 1 => visual_of(prev),
+            Some(prev) => visual_of(prev) + 1,
+            None if levels[0] % 2 == 1 => visual_of(0) + 1,
+            None => visual_of(0),
+        };
+        let col = order[..visual].iter().map(|&idx| self.east_asian_width.char_width(on_line[idx].1)).sum();
+        (line, col)
+    }
+
+    /// Input as shown on screen: wrapped, each line in bidi display order
+    pub fn input_display_text(&self, width: usize) -> String {
+        self.wrap_text_for_display(width).split('\n').map(bidi_display).collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn input_total_lines(&self, width: usize) -> usize {
+        if width == 0 || self.input.is_empty() {
+            return 1;
+        }
+        self.cursor_positions(width).last().map_or(1, |&(line, _)| line + 1)
+    }
+    
+    /// Move cursor up one line in input; false if it already is on the first
+    pub fn cursor_up(&mut self, width: usize) -> bool {
+        if width == 0 {
+            return false;
+        }
+        let positions = self.cursor_positions(width);
+        let (line, col) = positions[self.cursor_pos.min(positions.len() - 1)];
+        if line == 0 {
+            return false; // Already at first line
+        }
+        if let Some(pos) = Self::position_on_line(&positions, line - 1, col) {
+            self.cursor_pos = pos;
+            return true;
+        }
+        false
+    }
+    
+    /// Move cursor down one line in input
+    pub fn cursor_down(&mut self, width: usize) -> bool {
+        if width == 0 {
+            return false;
+        }
+        let positions = self.cursor_positions(width);
+        let (line, col) = positions[self.cursor_pos.min(positions.len() - 1)];
+        if let Some(pos) = Self::position_on_line(&positions, line + 1, col) {
+            self.cursor_pos = pos;
+            return true;
+        }
+        false
+    }
+
+    /// Move cursor to the start of its visual line
+    pub fn cursor_home(&mut self, width: usize) {
+        if width == 0 {
+            return;
+        }
+        let positions = self.cursor_positions(width);
+        let (line, _) = positions[self.cursor_pos.min(positions.len() - 1)];
+        if let Some(pos) = Self::position_on_line(&positions, line, 0) {
+            self.cursor_pos = pos;
+        }
+    }
+
+    /// Move cursor to the end of its visual line
+    pub fn cursor_end(&mut self, width: usize) {
+        if width == 0 {
+            return;
+        }
+        let positions = self.cursor_positions(width);
+        let (line, _) = positions[self.cursor_pos.min(positions.len() - 1)];
+        if let Some(pos) = Self::position_on_line(&positions, line, usize::MAX) {
+            self.cursor_pos = pos;
+        }
+    }
+
+    pub fn cursor_left(&mut self) {
+        self.cursor_pos = self.cursor_pos.saturating_sub(1);
+    }
+
+    pub fn cursor_right(&mut self) {
+        if self.cursor_pos < self.input.chars().count() {
+            self.cursor_pos += 1;
+        }
+    }
+
+    /// Start of the word before the cursor (Alt+B, Ctrl+←)
+    fn word_start_before(&self, is_word: impl Fn(char) -> bool) -> usize {
+        let chars: Vec<char> = self.input.chars().collect();
+        let mut pos = self.cursor_pos.min(chars.len());
+        while pos > 0 && !is_word(chars[pos - 1]) {
+            pos -= 1;
+        }
+        while pos > 0 && is_word(chars[pos - 1]) {
+            pos -= 1;
+        }
+        pos
+    }
+
+    /// End of the word after the cursor (Alt+F, Ctrl+→)
+    fn word_end_after(&self) -> usize {
+        let chars: Vec<char> = self.input.chars().collect();
+        let mut pos = self.cursor_pos.min(chars.len());
+        while pos < chars.len() && !chars[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        while pos < chars.len() && chars[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        pos
+    }
+
+    pub fn cursor_word_left(&mut self) {
+        self.cursor_pos = self.word_start_before(char::is_alphanumeric);
+    }
+
+    pub fn cursor_word_right(&mut self) {
+        self.cursor_pos = self.word_end_after();
+    }
+
+    /// Remove the characters between two positions and put the cursor there
+    fn delete_range(&mut self, from: usize, to: usize) {
+        if from >= to {
+            return;
+        }
+        let range = self.byte_index(from)..self.byte_index(to);
+        self.input.drain(range);
+        self.cursor_pos = from;
+        self.history_index = None;
+    }
+
+    /// Delete back to the previous whitespace (Ctrl+W, as in a shell)
+    pub fn delete_word_back(&mut self) {
+        let start = self.word_start_before(|ch| !ch.is_whitespace());
+        self.delete_range(start, self.cursor_pos);
+    }
+
+    /// Delete to the start of the line (Ctrl+U); at its start the line
+    /// break before it goes, joining the two lines
+    pub fn kill_to_line_start(&mut self) {
+        let before = &self.input[..self.byte_index(self.cursor_pos)];
+        let start = match before.rfind('\n') {
+            Some(newline) if newline + 1 == before.len() => self.cursor_pos - 1,
+            Some(newline) => before[..newline + 1].chars().count(),
+            None => 0,
+        };
+        self.delete_range(start, self.cursor_pos);
+    }
+
+    /// Delete to the end of the line (Ctrl+K); at its end the line break
+    /// goes, joining the next line
+    pub fn kill_to_line_end(&mut self) {
+        let after = &self.input[self.byte_index(self.cursor_pos)..];
+        let len = match after.find('\n') {
+            Some(0) => 1,
+            Some(newline) => after[..newline].chars().count(),
+            None => after.chars().count(),
+        };
+        self.delete_range(self.cursor_pos, self.cursor_pos + len);
+    }
+
+    /// Insert a character at the cursor
+    pub fn insert_char(&mut self, c: char) {
+        let byte_pos = self.byte_index(self.cursor_pos);
+        self.input.insert(byte_pos, c);
+        self.cursor_pos += 1;
+        self.history_index = None;
+    }
+
+    /// Insert text at the cursor (paste)
+    pub fn insert_str(&mut self, text: &str) {
+        let byte_pos = self.byte_index(self.cursor_pos);
+        self.input.insert_str(byte_pos, text);
+        self.cursor_pos += text.chars().count();
+        self.history_index = None;
+    }
+
+    /// Take the text saved in the external editor as input, without the
+    /// line break editors add at the end of the file
+    pub fn take_edited_input(&mut self, text: &str) {
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        self.input = text.strip_suffix('\r').unwrap_or(text).to_string();
+        self.cursor_pos = self.input.chars().count();
+        self.input_scroll = 0;
+        self.history_index = None;
+    }
+
+    /// Delete the character before the cursor (Backspace)
+    pub fn delete_back(&mut self) {
+        if self.cursor_pos == 0 {
+            return;
+        }
+        self.cursor_pos -= 1;
+        self.delete_forward();
+    }
+
+    /// Delete the character under the cursor (Delete)
+    pub fn delete_forward(&mut self) {
+        let byte_pos = self.byte_index(self.cursor_pos);
+        if let Some(ch) = self.input[byte_pos..].chars().next() {
+            self.input.drain(byte_pos..byte_pos + ch.len_utf8());
+            self.history_index = None;
+        }
+    }
+    
+    /// Update input scroll to keep cursor visible
+    pub fn update_input_scroll(&mut self, width: usize, visible_lines: u16) {
+        if width == 0 || visible_lines == 0 {
+            return;
+        }
+        
+        let (cursor_line, _) = self.cursor_line_col(width);
+        let cursor_line = cursor_line as u16;
+        
+        // Scroll up if cursor is above visible area
+        if cursor_line < self.input_scroll {
+            self.input_scroll = cursor_line;
+        }
+        // Scroll down if cursor is below visible area
+        if cursor_line >= self.input_scroll + visible_lines {
+            self.input_scroll = cursor_line - visible_lines + 1;
+        }
+    }
+    
+    /// Wrap text manually using character-wrapping (not word-wrapping)
+    /// This ensures cursor calculation matches display exactly
+    pub fn wrap_text_for_display(&self, width: usize) -> String {
+        if width == 0 {
+            return self.input.clone();
+        }
+        
+        let mut result = String::with_capacity(self.input.len() + self.input.len() / width);
+        let mut col = 0;
+        
+        for ch in self.input.chars() {
+            if ch == '\n' {
+                result.push(ch);
+                col = 0;
+            } else {
+                let char_width = self.east_asian_width.char_width(ch);
+                // Wrap BEFORE adding character if it would exceed width
+                if col > 0 && col + char_width > width {
+                    result.push('\n');
+                    col = 0;
+                }
+                result.push(ch);
+                col += char_width;
+            }
+        }
+        
+        result
+    }
+}
+
+/// How long a status bar notice stays
+const NOTICE_FOR: Duration = Duration::from_secs(3);
+
+/// Polls are due every 2s by default; this long without one the heartbeat turns red
+pub(crate) const SYNC_STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// Pause between polls while the server answers (`poll_interval`)
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a poll may take before it counts as failed (`poll_timeout`)
+pub(crate) const POLL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest pause between polls while the server is unreachable
+pub(crate) const POLL_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Each quiet minute doubles the pause between polls, up to this
+pub(crate) const POLL_IDLE_MAX: Duration = Duration::from_secs(15);
+
+/// What the clock shows with `--deterministic`
+pub(crate) const DETERMINISTIC_TIME: NaiveDateTime = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+
+
+/// Everything the main loop reacts to, fed through one channel so neither
+/// requests nor polls hold up the keyboard
+pub(crate) enum AppEvent {
+    /// Key, mouse or resize from the terminal
+    Input(Event),
+    /// Periodic wake-up: due polls, queued sends, clocks
+    Tick,
+    /// One polling round is back
+    Polled(Box<PollBatch>),
+    /// Next piece of the answer to the request sent at this timestamp
+    Chunk(u64, String),
+    /// The request sent at `message_ts` to `server_url` is done
+    Replied {
+        message_ts: u64,
+        server_url: String,
+        elapsed: Duration,
+        result: Result<ChatResponse, ChatError>,
+    },
+    /// A request made for the user on `server_url` is back
+    Fetched { server_url: String, fetched: Box<Fetched> },
+}
+
+/// How often `AppEvent::Tick` wakes the loop
+const TICK: Duration = Duration::from_millis(100);
+
+/// Reads terminal events on a thread of its own and forwards them to the
+/// main loop; paused while an external program ($PAGER) owns the terminal
+pub(crate) struct InputReader {
+    events: tokio::sync::mpsc::UnboundedSender<AppEvent>,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl InputReader {
+    pub(crate) fn start(events: tokio::sync::mpsc::UnboundedSender<AppEvent>) -> Self {
+        let mut reader = Self { events, stop: Arc::new(AtomicBool::new(false)), thread: None };
+        reader.resume();
+        reader
+    }
+
+    pub(crate) fn resume(&mut self) {
+        if self.thread.is_some() {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        self.stop = stop.clone();
+        let events = self.events.clone();
+        self.thread = Some(std::thread::spawn(move || {
+            // Short polls, so a pause takes effect before the next event is read
+            while !stop.load(Ordering::Relaxed) {
+                match event::poll(Duration::from_millis(50)) {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        let forwarded = event::read().is_ok_and(|event| events.send(AppEvent::Input(event)).is_ok());
+                        if !forwarded {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        }));
+    }
+
+    /// Returns once the thread has stopped reading
+    pub(crate) fn pause(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for InputReader {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
+
+/// What one polling round brought back; applied by the main loop unless
+/// the user switched server or room meanwhile
+pub(crate) struct PollBatch {
+    server_url: String,
+    room: Option<String>,
+    since: u64,
+    latency: Duration,
+    messages: Result<Vec<ServerMessage>, String>,
+    typing: Option<Vec<String>>,
+    presence: Option<Vec<Presence>>,
+    targets: Option<Vec<String>>,
+    /// Background rooms: index, name, polled since, new messages
+    rooms: Vec<(usize, Option<String>, u64, Vec<ServerMessage>)>,
+    /// Other synced connections: index, server, polled since, new messages
+    connections: Vec<(usize, String, u64, Vec<ServerMessage>)>,
+}
+
+/// Start a polling round for the active conversation, background rooms and
+/// other connections; the result comes back as `AppEvent::Polled`
+pub(crate) fn spawn_poll(app: &mut App, events: tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    app.last_poll = Instant::now();
+    app.polling = true;
+    let backend = app.backend.clone();
+    let server_url = app.server_url.clone();
+    let room = app.room_name().map(str::to_string);
+    let since = app.last_timestamp;
+    let typing = app.typing_supported;
+    // Presence and targets change slowly, refresh them every 10 seconds
+    let presence = app.presence_supported && app.last_presence_poll.is_none_or(|t| t.elapsed().as_secs() >= 10);
+    if presence {
+        app.last_presence_poll = Some(Instant::now());
+    }
+    let targets = app.targets_supported && app.last_targets_poll.is_none_or(|t| t.elapsed().as_secs() >= 10);
+    if targets {
+        app.last_targets_poll = Some(Instant::now());
+    }
+    // Background rooms only count unread messages
+    let rooms: Vec<(usize, Option<String>, u64)> = app
+        .rooms
+        .iter()
+        .enumerate()
+        .filter(|&(idx, _)| idx != app.active_room)
+        .map(|(idx, room)| (idx, room.name.clone(), room.last_timestamp))
+        .collect();
+    // Other server connections keep following the room they showed last
+    let connections: Vec<_> = app
+        .connections
+        .iter()
+        .enumerate()
+        .filter(|&(idx, conn)| idx != app.active_connection && conn.synced)
+        .map(|(idx, conn)| (idx, conn.backend.clone(), conn.room_name().map(str::to_string), conn.last_timestamp))
+        .collect();
+
+    let timeout = app.poll_timeout;
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let messages = backend.fetch_messages(since, room.as_deref(), timeout).await;
+        let latency = started.elapsed();
+        let mut batch = PollBatch {
+            server_url,
+            since,
+            latency,
+            messages,
+            typing: None,
+            presence: None,
+            targets: None,
+            rooms: Vec::new(),
+            connections: Vec::new(),
+            room,
+        };
+        if typing {
+            batch.typing = backend.fetch_typing(batch.room.as_deref()).await.ok();
+        }
+        if presence {
+            batch.presence = backend.fetch_presence(batch.room.as_deref()).await.ok();
+        }
+        if targets {
+            batch.targets = backend.fetch_targets(batch.room.as_deref()).await.ok();
+        }
+        for (idx, name, since) in rooms {
+            if let Ok(messages) = backend.fetch_messages(since, name.as_deref(), timeout).await {
+                batch.rooms.push((idx, name, since, messages));
+            }
+        }
+        for (idx, backend, room, since) in connections {
+            if let Ok(messages) = backend.fetch_messages(since, room.as_deref(), timeout).await {
+                batch.connections.push((idx, backend.server_url().to_string(), since, messages));
+            }
+        }
+        let _ = events.send(AppEvent::Polled(Box::new(batch)));
+    });
+}
+
+/// Merge a polling round into the conversations it was made for
+pub(crate) fn apply_poll(app: &mut App, batch: PollBatch, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    app.polling = false;
+    // Switched server or room meanwhile: the next round polls the new one
+    if batch.server_url != app.server_url || batch.room.as_deref() != app.room_name() {
+        return;
+    }
+    match &batch.messages {
+        Ok(_) => {
+            app.latency = Some(batch.latency);
+            app.last_sync = Some(Instant::now());
+            app.sync_error = None;
+        }
+        Err(e) => app.sync_error = Some(e.clone()),
+    }
+    app.poll_failures = if batch.messages.is_ok() { 0 } else { app.poll_failures.saturating_add(1) };
+    match batch.messages {
+        // First answer after an outage: fetch the whole missed window instead
+        Ok(_) if app.offline_since.is_some() => spawn_resync(app, events),
+        Ok(messages) => {
+            let added = merge_server_messages(&mut app.messages, &mut app.last_timestamp, messages);
+            if added > 0 {
+                app.last_activity = Instant::now();
+            }
+            if added > 0 && app.any_from_others_since(&app.messages, batch.since) {
+                app.alert();
+            }
+            // Auto-scroll bei neuen Nachrichten; wer hochgescrollt hat, behält
+            // seine Stelle und bekommt den Hinweis "N neue Nachrichten ↓"
+            if added > 0 && app.auto_scroll {
+                app.scroll_to_bottom();
+                report_read(app);
+            } else if added > 0 {
+                app.unseen_after.get_or_insert(batch.since);
+                app.pin_view = true;
+            }
+        }
+        Err(_) => {
+            if app.offline_since.is_none() {
+                app.offline_since = Some(Instant::now());
+                app.connection_status = ConnectionStatus::Disconnected;
+            }
+        }
+    }
+
+    if let Some(names) = batch.typing {
+        app.typing = names
+            .into_iter()
+            .filter(|name| app.display_name.as_deref() != Some(name.as_str()))
+            .collect();
+    }
+    if let Some(presence) = batch.presence {
+        app.presence = presence;
+    }
+    if let Some(targets) = batch.targets {
+        app.targets = targets;
+    }
+
+    for (idx, name, since, messages) in batch.rooms {
+        if idx == app.active_room || app.rooms.get(idx).is_none_or(|room| room.name != name) {
+            continue;
+        }
+        let room = &mut app.rooms[idx];
+        let added = merge_server_messages(&mut room.messages, &mut room.last_timestamp, messages);
+        room.unread += added;
+        if added > 0 && app.any_from_others_since(&app.rooms[idx].messages, since) {
+            app.alert();
+        }
+    }
+
+    for (idx, server_url, since, messages) in batch.connections {
+        if idx == app.active_connection || app.connections.get(idx).is_none_or(|conn| conn.server_url != server_url) {
+            continue;
+        }
+        let conn = &mut app.connections[idx];
+        let added = merge_server_messages(&mut conn.messages, &mut conn.last_timestamp, messages);
+        conn.unread += added;
+        if added > 0 && app.any_from_others_since(&app.connections[idx].messages, since) {
+            app.alert();
+        }
+    }
+}
+
+/// Report the read position of the active room if it moved (fire-and-forget)
+fn report_read(app: &mut App) {
+    if app.last_timestamp <= app.reported_read {
+        return;
+    }
+    app.reported_read = app.last_timestamp;
+    let backend = app.backend.clone();
+    let room = app.room_name().map(str::to_string);
+    let timestamp = app.last_timestamp;
+    // Servers with ids get the exact message, not just a point in time
+    let message_id = app.messages.iter().rev().find_map(|m| m.id.clone());
+    tokio::spawn(async move {
+        let _ = backend.mark_read(room.as_deref(), timestamp, message_id.as_deref()).await;
+    });
+}
+
+/// Result of a request made outside the polling rounds; applied by
+/// `apply_fetched` unless the user switched server meanwhile
+pub(crate) enum Fetched {
+    /// Optional features of the server (see `probe_capabilities`)
+    Capabilities(Capabilities),
+    /// The full state of `room`, loaded on connect and when joining
+    Synced { room: Option<String>, dump: bool, messages: Result<Vec<ServerMessage>, String> },
+    /// The window missed during an outage
+    Resynced { room: Option<String>, messages: Result<Vec<ServerMessage>, String> },
+    ServerInfo { asked: bool, rtt: Duration, info: Result<ServerInfo, String> },
+    Moderated { room: Option<String>, action: ModAction, result: Result<(), String> },
+    /// An oversized message uploaded as a file; `reference` is its address
+    Uploaded { offer: Oversize, name: String, reference: Result<String, String> },
+    Stats(Result<ServerStats, String>),
+    Models { prompt_ts: u64, models: Result<Vec<String>, String> },
+    Profile { name: String, profile: Result<UserProfile, String> },
+    Cleared { room: Option<String>, result: Result<(), String> },
+    Edited { id: String, content: String, result: Result<(), String> },
+}
+
+/// Optional features a server offers; a probe that fails means "no"
+pub(crate) struct Capabilities {
+    rooms: Option<Vec<String>>,
+    presence: Option<Vec<Presence>>,
+    typing: bool,
+    targets: Option<Vec<String>>,
+    admin: bool,
+}
+
+/// Run `fetch` in the background; its result comes back as `AppEvent::Fetched`
+pub(crate) fn spawn_fetch(
+    app: &App,
+    events: &tokio::sync::mpsc::UnboundedSender<AppEvent>,
+    fetch: impl Future<Output = Fetched> + Send + 'static,
+) {
+    let server_url = app.server_url.clone();
+    let events = events.clone();
+    tokio::spawn(async move {
+        let fetched = fetch.await;
+        let _ = events.send(AppEvent::Fetched { server_url, fetched: Box::new(fetched) });
+    });
+}
+
+/// Apply what a background request brought back. Results for a server the
+/// user left meanwhile are dropped, as are messages for a room left.
+pub(crate) fn apply_fetched(app: &mut App, server_url: &str, fetched: Fetched) {
+    // A resync stands in for a polling round, whatever became of it
+    if matches!(fetched, Fetched::Resynced { .. }) {
+        app.polling = false;
+    }
+    if server_url != app.server_url {
+        return;
+    }
+    match fetched {
+        Fetched::Capabilities(capabilities) => apply_capabilities(app, capabilities),
+        Fetched::Synced { room, dump, messages } => {
+            if room.as_deref() == app.room_name()
+                && let Ok(messages) = messages
+            {
+                finish_initial_sync(app, messages, dump);
+            }
+        }
+        Fetched::Resynced { room, messages } => {
+            if room.as_deref() == app.room_name()
+                && let Ok(messages) = messages
+            {
+                finish_resync(app, messages);
+                report_read(app);
+            }
+        }
+        Fetched::ServerInfo { asked, rtt, info } => report_server_info(app, asked, rtt, info),
+        Fetched::Moderated { room, action, result } => {
+            if room.as_deref() == app.room_name() {
+                finish_moderation(app, action, result);
+            }
+        }
+        Fetched::Uploaded { offer, name, reference } => match reference {
+            Ok(reference) => {
+                let chars = offer.text.chars().count();
+                let text = trf("📎 {} ({} Zeichen): {}", &[&name, &chars.to_string(), &reference]);
+                // The input still holds the long text unless it was edited meanwhile
+                if app.input.trim() == offer.text {
+                    app.input.clear();
+                    app.cursor_pos = 0;
+                    app.input_scroll = 0;
+                }
+                let pending = app.push_own_message(text, offer.to);
+                app.send_queue.push_back(pending);
+                app.scroll_to_bottom();
+            }
+            Err(e) => {
+                app.last_error = Some(e);
+                app.oversize = Some(offer);
+                app.reject_oversize();
+            }
+        },
+        Fetched::Stats(stats) => app.stats = Some((stats, app.now())),
+        Fetched::Models { prompt_ts, models } => {
+            let models = match models {
+                Ok(models) if !models.is_empty() => models,
+                _ => app.models.clone(),
+            };
+            if models.is_empty() {
+                app.last_error = Some(tr("Keine Modelle bekannt (Server /models oder models in der Config)").to_string());
+            } else {
+                let index = app.model.as_ref().and_then(|m| models.iter().position(|x| x == m)).unwrap_or(0);
+                app.model_picker = Some(ModelPicker { models, index, prompt_ts });
+            }
+        }
+        Fetched::Profile { name, profile } => match profile {
+            Ok(profile) => app.profile = Some((name, profile)),
+            Err(e) => app.last_error = Some(e),
+        },
+        Fetched::Cleared { room, result } => {
+            if room.as_deref() != app.room_name() {
+                return;
+            }
+            match result.and_then(|()| app.clear_conversation()) {
+                Ok(()) => {
+                    app.messages.push(Message::new("system", trf("Chat gelöscht (Server + lokal). Verbunden mit {}", &[&app.server_url])));
+                    app.last_error = None;
+                }
+                Err(e) => {
+                    app.last_error = Some(trf("Clear fehlgeschlagen: {}", &[&e.to_string()]));
+                }
+            }
+        }
+        Fetched::Edited { id, content, result } => match result {
+            Ok(()) => {
+                app.apply_edit(&id, content);
+                app.show_last_edit_diff();
+                app.last_error = None;
+            }
+            Err(e) => app.last_error = Some(e),
+        },
+    }
+}
+
+/// Ask the active server which optional features it offers
+pub(crate) fn probe_capabilities(app: &App, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    let backend = app.backend.clone();
+    spawn_fetch(app, events, async move {
+        Fetched::Capabilities(Capabilities {
+            rooms: backend.list_rooms().await.ok().map(|rooms| rooms.into_iter().map(|r| r.name).collect()),
+            presence: backend.fetch_presence(None).await.ok(),
+            typing: backend.fetch_typing(None).await.is_ok(),
+            targets: backend.fetch_targets(None).await.ok(),
+            admin: backend.fetch_permissions().await.is_ok_and(|p| p.admin),
+        })
+    });
+}
+
+fn apply_capabilities(app: &mut App, capabilities: Capabilities) {
+    // Rooms are optional: only show them when the server offers any
+    if let Some(rooms) = capabilities.rooms {
+        app.rooms_supported = true;
+        app.available_rooms = rooms;
+    }
+
+    // Presence is optional as well: stop asking if the first request fails
+    if let Some(presence) = capabilities.presence {
+        app.presence_supported = true;
+        app.presence = presence;
+        app.last_presence_poll = Some(Instant::now());
+    }
+
+    if capabilities.typing {
+        app.typing_supported = true;
+    }
+
+    if let Some(targets) = capabilities.targets {
+        app.targets_supported = true;
+        app.targets = targets;
+    }
+
+    app.is_admin = capabilities.admin;
+}
+
+/// Ask the server who it is; `report_server_info` shows the answer
+pub(crate) fn check_server(app: &App, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>, asked: bool) {
+    let backend = app.backend.clone();
+    spawn_fetch(app, events, async move {
+        let started = Instant::now();
+        let info = backend.fetch_server_info().await;
+        Fetched::ServerInfo { asked, rtt: started.elapsed(), info }
+    });
+}
+
+/// Report version, model and round-trip time as a system line; an
+/// incompatible protocol is an error. When the check failed, `/status`
+/// (`asked`) shows the connection state instead and the startup check warns.
+fn report_server_info(app: &mut App, asked: bool, rtt: Duration, info: Result<ServerInfo, String>) {
+    match info {
+        Ok(info) => {
+            app.latency = Some(rtt);
+            if app.model.is_none() {
+                app.model = info.model.clone();
+            }
+            let mut parts = vec![app.server_url.clone()];
+            parts.extend(info.version.as_ref().map(|v| format!("v{}", v.trim_start_matches('v'))));
+            parts.extend(info.model.as_ref().map(|m| trf("Modell {}", &[m])));
+            if !app.deterministic {
+                parts.push(format!("{} ms", rtt.as_millis()));
+            }
+            app.messages.push(Message::new("system", trf("Server: {}", &[&parts.join(" · ")])));
+            if info.incompatible() {
+                let warning = trf(
+                    "Server spricht Protokoll {}, hank-tui Protokoll {} – manches funktioniert evtl. nicht",
+                    &[&info.protocol.unwrap_or_default().to_string(), &PROTOCOL_VERSION.to_string()],
+                );
+                app.messages.push(Message::new("error", warning.clone()));
+                app.last_error = Some(warning);
+            }
+            app.server_info = Some(info);
+        }
+        Err(e) if asked => {
+            let line = [app.server_url.clone(), app.connection_status.label().to_string()]
+                .into_iter()
+                .chain(app.latency.map(|l| format!("{} ms", l.as_millis())).filter(|_| !app.deterministic))
+                .collect::<Vec<_>>()
+                .join(" · ");
+            app.messages.push(Message::new("system", trf("Server: {}", &[&line])));
+            app.last_error = Some(e);
+        }
+        Err(e) => {
+            let warning = trf("Server-Check fehlgeschlagen: {}", &[&e]);
+            app.messages.push(Message::new("error", warning.clone()));
+            app.last_error = Some(warning);
+        }
+    }
+    app.scroll_to_bottom();
+}
+
+/// How far before the last known message a resync starts. Messages stored
+/// while a poll was in flight can carry older timestamps than the newest
+/// one we saw, so `since=last_timestamp` alone may skip them.
+const RESYNC_OVERLAP_MS: u64 = 60_000;
+
+/// Fetch everything around and after the outage; no polling round starts
+/// until it is back
+pub(crate) fn spawn_resync(app: &mut App, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    app.polling = true;
+    let since = app.last_timestamp.saturating_sub(RESYNC_OVERLAP_MS);
+    let room = app.room_name().map(str::to_string);
+    let backend = app.backend.clone();
+    spawn_fetch(app, events, async move {
+        let messages = backend.fetch_messages(since, room.as_deref(), Duration::from_secs(5)).await;
+        Fetched::Resynced { room, messages }
+    });
+}
+
+/// Merge the missed window; duplicates are dropped by the merge. Logs
+/// "connection restored" with the number of new messages.
+fn finish_resync(app: &mut App, messages: Vec<ServerMessage>) {
+    let added = merge_server_messages(&mut app.messages, &mut app.last_timestamp, messages);
+    app.offline_since = None;
+    app.connection_status = ConnectionStatus::Connected;
+    app.messages.push(Message::new(
+        "system",
+        trf("Verbindung wiederhergestellt, {} Nachrichten synchronisiert", &[&added.to_string()]),
+    ));
+    app.scroll_to_bottom();
+}
+
+/// Server metrics for the F9 overlay, shown once they are back
+fn fetch_stats(app: &App, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    let backend = app.backend.clone();
+    spawn_fetch(app, events, async move { Fetched::Stats(backend.fetch_stats().await) });
+}
+
+/// Execute a confirmed moderation action
+fn run_moderation(app: &App, action: ModAction, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    let room = app.room_name().map(str::to_string);
+    let backend = app.backend.clone();
+    spawn_fetch(app, events, async move {
+        let result = backend.moderate(room.as_deref(), &action).await;
+        Fetched::Moderated { room, action, result }
+    });
+}
+
+/// Log a moderation action in the transcript
+fn finish_moderation(app: &mut App, action: ModAction, result: Result<(), String>) {
+    match result {
+        Ok(()) => {
+            if let ModAction::Purge { user } = &action {
+                app.messages.retain(|m| m.sender.as_deref() != Some(user.as_str()));
+            }
+            let moderator = app.display_name.clone().unwrap_or_else(|| tr("Du").to_string());
+            app.messages.push(Message::new("system", action.audit(&moderator)));
+            app.last_error = None;
+            app.scroll_to_bottom();
+        }
+        Err(e) => app.last_error = Some(e),
+    }
+}
+
+/// Switch to connection `idx`, loading it from the server on first use
+fn activate_connection(app: &mut App, idx: usize, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    let first_visit = !app.connections.get(idx).is_some_and(|c| c.synced) && idx != app.active_connection;
+    app.switch_connection(idx);
+    if first_visit {
+        probe_capabilities(app, events);
+        initial_sync(app, events, false);
+        check_server(app, events, false);
+    }
+    report_read(app);
+}
+
+/// Move to the next (or previous) tab, switching connections if needed
+fn cycle_tab(app: &mut App, forward: bool, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    let tabs = app.tabs();
+    if tabs.len() < 2 {
+        return;
+    }
+    let current = app.active_tab(&tabs);
+    let next = if forward { (current + 1) % tabs.len() } else { (current + tabs.len() - 1) % tabs.len() };
+    let target = &tabs[next];
+    if target.connection != app.active_connection {
+        activate_connection(app, target.connection, events);
+    }
+    app.switch_room(target.room);
+    report_read(app);
+}
+
+/// Load the active room's full server state (since=0); `finish_initial_sync`
+/// replaces the messages with it
+pub(crate) fn initial_sync(app: &App, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>, dump: bool) {
+    let room = app.room_name().map(str::to_string);
+    let backend = app.backend.clone();
+    spawn_fetch(app, events, async move {
+        let messages = backend.fetch_messages(0, room.as_deref(), Duration::from_secs(5)).await;
+        Fetched::Synced { room, dump, messages }
+    });
+}
+
+/// Replace the active room's messages with the full server state
+fn finish_initial_sync(app: &mut App, messages: Vec<ServerMessage>, dump: bool) {
+    app.last_sync = Some(Instant::now());
+    // Dump initial payload next to the executable for debugging
+    if dump
+        && let Ok(exe_path) = env::current_exe()
+        && let Some(dir) = exe_path.parent()
+        && let Ok(serialized) = serde_json::to_string_pretty(&messages)
+    {
+        let _ = fs::write(dir.join("initial_messages.json"), serialized);
+    }
+
+    // Clear local history and load from server; a restored view
+    // (see `restore_view`) moves to the server's copy of its message
+    let anchor = app.scroll_anchor.and_then(|idx| app.messages.get(idx)).and_then(message_key);
+    app.messages.clear();
+    app.selected = None;
+
+    for msg in messages {
+        if msg.timestamp > app.last_timestamp {
+            app.last_timestamp = msg.timestamp;
+        }
+
+        app.messages.push(Message::from_server(msg));
+    }
+
+    let msg_count = app.messages.len();
+    app.messages.push(Message::new("system", trf("{} Nachrichten vom Server geladen", &[&msg_count.to_string()])));
+
+    app.restore_view(anchor.as_deref());
+    if let Some(key) = app.reveal_after_sync.take() {
+        app.reveal_message(Some(&key));
+    }
+    report_read(app);
+}
+
+/// Execute a slash command
+fn run_command(app: &mut App, command: Command, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    match command {
+        Command::Join(name) => {
+            if !app.rooms_supported {
+                app.last_error = Some(tr("Server unterstützt keine Räume").to_string());
+                return;
+            }
+            let is_new = !app.rooms.iter().any(|r| r.name.as_deref() == Some(name.as_str()));
+            let idx = app.join_room(&name);
+            app.switch_room(idx);
+            if is_new {
+                initial_sync(app, events, false);
+            }
+            app.reported_read = 0;
+            report_read(app);
+            app.last_error = None;
+        }
+        Command::Leave => match app.leave_room() {
+            Some(room) => {
+                if app.history_enabled
+                    && let Some(name) = room.name.as_deref()
+                    && let Some(path) = ChatHistory::room_history_path(name)
+                {
+                    let _ = ChatHistory::save_to(&path, &app.server_url, &room.messages, room.view_anchor.clone());
+                }
+                app.messages.push(Message::new("system", trf("Raum #{} verlassen", &[room.label()])));
+                app.last_error = None;
+            }
+            None => {
+                app.last_error = Some(tr("Du bist in keinem Raum (/join <raum>)").to_string());
+            }
+        },
+        Command::Edit => {
+            if !app.start_edit() {
+                app.last_error = Some(tr("Keine eigene Nachricht zum Bearbeiten").to_string());
+            }
+        }
+        Command::Connect(target) => {
+            let profile = app.profiles.get(&target).cloned().unwrap_or_else(|| ServerProfile::from_target(&target));
+            let mut backend = HttpBackend::new(profile.server_url())
+                .with_trace(app.http_trace.clone())
+                .with_auth(profile.token, profile.auth_header);
+            if let Some(client) = &app.http_client {
+                backend = backend.with_client(client.clone());
+            }
+            let idx = app.add_connection(Arc::new(backend));
+            activate_connection(app, idx, events);
+            app.last_error = None;
+            if let Some(name) = profile.theme.filter(|_| !app.ascii) {
+                match Theme::load(&name, Theme::dir().as_deref()) {
+                    Ok(theme) => app.theme = theme,
+                    Err(e) => app.last_error = Some(e),
+                }
+            }
+        }
+        Command::Msg { to, text: _ } => {
+            if !app.targets_supported {
+                app.last_error = Some(tr("Server unterstützt keine Direktnachrichten").to_string());
+                return;
+            }
+            app.target = Some(to);
+            app.last_error = None;
+        }
+        Command::All => {
+            app.target = None;
+            app.last_error = None;
+        }
+        Command::Moderate(action) => {
+            if app.is_admin {
+                app.confirm = Some(action);
+                app.last_error = None;
+            } else {
+                app.last_error = Some(trf("/{} ist nur für Admins verfügbar", &[action.name()]));
+            }
+        }
+        // Needs the terminal, handled in the event loop
+        Command::Pager => {}
+        Command::Date(arg) => {
+            let result = parse_day(&arg, Local::now().date_naive()).and_then(|day| app.jump_to_date(day));
+            match result {
+                Ok(hint) => {
+                    app.last_error = hint;
+                    app.focus = Focus::Chat;
+                }
+                Err(e) => app.last_error = Some(e),
+            }
+        }
+        Command::Theme(None) => {
+            let dir = Theme::dir();
+            let names = Theme::available(dir.as_deref()).join(", ");
+            let dir = dir.map(|d| d.display().to_string()).unwrap_or_default();
+            app.messages.push(Message::new("system", trf("Themes: {} (eigene als *.toml in {})", &[&names, &dir])));
+            app.scroll_to_bottom();
+        }
+        Command::Theme(Some(name)) => {
+            if app.ascii {
+                app.last_error = Some(tr("--ascii zeigt keine Farben").to_string());
+                return;
+            }
+            match Theme::load(&name, Theme::dir().as_deref()) {
+                Ok(theme) => {
+                    app.theme = theme;
+                    app.notify(trf("Theme „{}“", &[&name]));
+                    app.last_error = Config::update(&["theme"], |config| config.theme = Some(name.clone()))
+                        .err()
+                        .map(|e| trf("Config nicht gespeichert: {}", &[&e]));
+                }
+                Err(e) => app.last_error = Some(e),
+            }
+        }
+        Command::Export(file) => match app.export_conversation(&file) {
+            Ok(note) => {
+                app.notify(note);
+                app.last_error = None;
+            }
+            Err(e) => app.last_error = Some(e),
+        },
+        Command::Status => check_server(app, events, true),
+        Command::SaveConfig => {
+            match Config::update(&["scheme", "host", "port", "url"], |config| config.remember_server(&app.server_url)) {
+                Ok(()) => {
+                    app.notify(trf("{} als Standardserver gespeichert", &[&app.server_url]));
+                    app.last_error = None;
+                }
+                Err(e) => app.last_error = Some(trf("Config nicht gespeichert: {}", &[&e])),
+            }
+        }
+        Command::Filter(None) => app.filter = None,
+        Command::Filter(Some(arg)) => match Filter::parse(&arg) {
+            Ok(filter) => {
+                app.filter = Some(filter);
+                app.last_error = None;
+                app.scroll_to_bottom();
+            }
+            Err(e) => app.last_error = Some(e),
+        },
+        Command::Template => {
+            let dir = PromptTemplate::dir();
+            let templates = dir.as_deref().map(PromptTemplate::load_all).unwrap_or_default();
+            if !app.open_template_picker(templates) {
+                let dir = dir.map(|d| d.display().to_string()).unwrap_or_default();
+                app.last_error = Some(trf("Keine Vorlagen in {} (*.txt, *.md)", &[&dir]));
+            }
+        }
+        Command::Diff => {
+            if !app.show_last_edit_diff() {
+                app.last_error = Some(tr("Keine bearbeitete Nachricht").to_string());
+            }
+        }
+        Command::Disconnect => match app.close_connection() {
+            Some(conn) => {
+                app.messages.push(Message::new("system", trf("Verbindung zu {} getrennt", &[&conn.server_url])));
+                app.last_error = None;
+            }
+            None => {
+                app.last_error = Some(tr("Die Hauptverbindung kann nicht getrennt werden").to_string());
+            }
+        },
+    }
+}
+
+/// Send right away, or keep the order: while a request is out or
+/// rate-limited, new messages wait behind the queue
+fn send_or_queue(app: &mut App, pending: PendingSend, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    if app.loading || app.retry_at.is_some() || !app.send_queue.is_empty() {
+        app.send_queue.push_back(pending);
+    } else {
+        deliver(app, pending, events);
+    }
+}
+
+/// Send one chat request in the background; its answer streams back as
+/// `AppEvent::Chunk`s and ends with `AppEvent::Replied`
+pub(crate) fn deliver(app: &mut App, pending: PendingSend, events: &tokio::sync::mpsc::UnboundedSender<AppEvent>) {
+    app.loading = true;
+    app.connection_status = ConnectionStatus::Sending;
+    app.in_flight = Some(pending.clone());
+
+    let backend = app.backend.clone();
+    let request = pending.request;
+    let message_ts = pending.message_ts;
+    let chunks = events.clone();
+    let work = tokio::spawn(async move {
+        let (chunk_tx, mut chunk_rx) = tokio::sync::mpsc::unbounded_channel();
+        let forward = async {
+            while let Some(chunk) = chunk_rx.recv().await {
+                let _ = chunks.send(AppEvent::Chunk(message_ts, chunk));
+            }
+        };
+        // The forwarder ends when the backend drops its sender, so every
+        // chunk is queued before the reply
+        let (result, ()) = tokio::join!(backend.send_chat_stream(request, chunk_tx), forward);
+        result
+    });
+    app.request_task = Some(work.abort_handle());
+
+    let server_url = app.server_url.clone();
+    let events = events.clone();
+    let sent_at = Instant::now();
+    tokio::spawn(async move {
+        let result = match work.await {
+            Ok(result) => result,
+            // Stopped with Esc; the loop has already cleaned up
+            Err(e) if e.is_cancelled() => return,
+            Err(e) => Err(ChatError::Other(format!("Task failed: {}", e))),
+        };
+        let _ = events.send(AppEvent::Replied { message_ts, server_url, elapsed: sent_at.elapsed(), result });
+    });
+}
+
+/// Apply the outcome of the request in flight. Rate-limited requests go
+/// back to the front of `App::send_queue`.
+pub(crate) fn finish_delivery(app: &mut App, server_url: &str, elapsed: Duration, result: Result<ChatResponse, ChatError>) {
+    let Some(pending) = app.in_flight.take() else {
+        return;
+    };
+    app.request_task = None;
+    app.loading = false;
+    app.streaming = None;
+    // Switched server or room meanwhile: the answer arrives with that
+    // conversation's next sync
+    if server_url != app.server_url || pending.request.room.as_deref() != app.room_name() {
+        return;
+    }
+    match result {
+        Ok(response) => {
+            // Remember the server id of our message so it can be edited
+            let state = if response.message_id.is_some() { Delivery::Acknowledged } else { Delivery::Sent };
+            if let Some(own) = app.set_delivery(pending.message_ts, state)
+                && response.message_id.is_some()
+            {
+                own.id = response.message_id;
+            }
+            // Direct messages to people may not get an answer from Hank
+            if !response.content.is_empty() || !response.tool_calls.is_empty() {
+                let (content, tool_calls) = if response.tool_calls.is_empty() {
+                    parse_structured(response.content)
+                } else {
+                    (response.content, response.tool_calls)
+                };
+                let mut reply = Message::new("assistant", content);
+                reply.tool_calls = tool_calls;
+                // Regenerated answers carry their model as label
+                if pending.request.model.is_some() {
+                    reply.model = response.model.clone().or_else(|| pending.request.model.clone());
+                }
+                if response.model.is_some() {
+                    app.model = response.model;
+                }
+                reply.thread_id = pending.request.thread_id.clone();
+                reply.response_ms = Some(elapsed.as_millis() as u64);
+                app.messages.push(reply);
+                app.alert();
+            }
+            app.connection_status = ConnectionStatus::Connected;
+            app.scroll_to_bottom();
+        }
+        Err(ChatError::RateLimited { retry_after }) => {
+            // Not an error for the user: park the message and retry automatically
+            app.send_queue.push_front(pending);
+            app.retry_at = Some(Instant::now() + retry_after);
+            app.connection_status = ConnectionStatus::RateLimited;
+        }
+        Err(ChatError::TooLarge { limit }) => {
+            // Take the message back and offer to split or attach it
+            if let Some(pos) = app
+                .messages
+                .iter()
+                .rposition(|m| m.role == "user" && m.timestamp_ms == Some(pending.message_ts))
+            {
+                app.messages.remove(pos);
+            }
+            if limit.is_some() {
+                app.max_message_chars = limit;
+            }
+            let chars = pending.request.message.chars().count();
+            let limit = limit.or(app.max_message_chars).unwrap_or(chars.div_ceil(2)).min(chars.saturating_sub(1)).max(1);
+            app.oversize = Some(Oversize { text: pending.request.message.clone(), to: pending.request.to.clone(), limit });
+            app.connection_status = ConnectionStatus::Connected;
+        }
+        Err(err) => {
+            let err = err.to_string();
+            app.set_delivery(pending.message_ts, Delivery::Failed);
+            app.messages.push(Message::new("error", err.clone()));
+            app.last_error = Some(err);
+            app.connection_status = ConnectionStatus::Error;
+            app.scroll_to_bottom();
+        }
+    }
+}
+
+pub async fn run_app<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // One channel feeds the loop: keys from the reader thread, ticks,
+    // poll results, other requests and the streamed answer
+    let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+
+    // Capabilities, ALL messages (since=0) and the server check load in
+    // the background; the first frame does not wait for them
+    probe_capabilities(app, &events);
+    initial_sync(app, &events, true);
+    check_server(app, &events, false);
+    let mut input = InputReader::start(events.clone());
+    let ticker = events.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            if ticker.send(AppEvent::Tick).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        // Redraw once the queue is drained, not for every streamed chunk
+        if inbox.is_empty() {
+            if app.accessible {
+                speak(terminal, app)?;
+            }
+            app.sync_window_title()?;
+            if app.tmux.is_some() {
+                let (unread, title) = (app.unread_total(), app.title());
+                if let Some(tmux) = app.tmux.as_mut() {
+                    tmux.sync(unread, &title);
+                }
+            }
+            if std::mem::take(&mut app.wants_older) {
+                app.load_older_chunk();
+            }
+            terminal.draw(|f| draw(f, app))?;
+        }
+
+        let Some(event) = inbox.recv().await else {
+            break;
+        };
+        if matches!(event, AppEvent::Input(Event::Key(_) | Event::Mouse(_) | Event::Paste(_))) {
+            app.last_activity = Instant::now();
+        }
+        let key = match event {
+            AppEvent::Input(Event::Key(key)) => key,
+            AppEvent::Input(Event::Mouse(mouse)) => {
+                match mouse.kind {
+                    MouseEventKind::ScrollUp => (0..3).for_each(|_| app.scroll_up()),
+                    MouseEventKind::ScrollDown => (0..3).for_each(|_| app.scroll_down()),
+                    MouseEventKind::Down(MouseButton::Left)
+                        if app.unread_pill.is_some_and(|pill| pill.contains(Position::new(mouse.column, mouse.row))) =>
+                    {
+                        app.jump_to_unseen();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            // Resize and the like: the next frame adapts
+            AppEvent::Input(_) => continue,
+            AppEvent::Tick => {
+                // Poll server für neue Nachrichten (alle poll_interval, nicht während einer Anfrage;
+                // seltener, solange der Server nicht erreichbar oder die Sitzung ruhig ist)
+                if !app.loading && !app.polling && app.last_poll.elapsed() >= app.poll_delay() {
+                    spawn_poll(app, events.clone());
+                }
+                // Deliver queued messages once the previous one is done and
+                // Retry-After has passed
+                if !app.loading
+                    && !app.send_queue.is_empty()
+                    && app.retry_at.is_none_or(|t| Instant::now() >= t)
+                {
+                    app.retry_at = None;
+                    if let Some(pending) = app.send_queue.pop_front() {
+                        deliver(app, pending, &events);
+                    }
+                }
+                continue;
+            }
+            AppEvent::Polled(batch) => {
+                apply_poll(app, *batch, &events);
+                continue;
+            }
+            AppEvent::Fetched { server_url, fetched } => {
+                apply_fetched(app, &server_url, *fetched);
+                continue;
+            }
+            AppEvent::Chunk(message_ts, chunk) => {
+                if app.in_flight.as_ref().is_some_and(|p| p.message_ts == message_ts) {
+                    app.streaming.get_or_insert_with(String::new).push_str(&chunk);
+                }
+                continue;
+            }
+            AppEvent::Replied { message_ts, server_url, elapsed, result } => {
+                if app.in_flight.as_ref().is_some_and(|p| p.message_ts == message_ts) {
+                    finish_delivery(app, &server_url, elapsed, result);
+                }
+                continue;
+            }
+        };
+
+        // Only process key press events, not release events
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let key = translate_fallback(&app.fallbacks, key);
+        
+        // Help screen: any key closes it
+        if app.focus == Focus::Help {
+            app.toggle_help();
+            continue;
+        }
+
+        // Copy mode takes all keys while on
+        if app.copy_mode.is_some() {
+            if let Some(text) = app.copy_mode_key(key) {
+                match clipboard_set_text(&text) {
+                    Ok(()) => app.connection_status = ConnectionStatus::Copied,
+                    Err(e) => app.last_error = Some(e),
+                }
+            }
+            continue;
+        }
+
+        // Template picker takes all keys while open
+        if app.template_picker.is_some() {
+            app.template_picker_key(key);
+            continue;
+        }
+
+        // Conversation picker takes all keys while open
+        if app.session_picker.is_some() {
+            app.session_picker_key(key);
+            continue;
+        }
+
+        // The global search takes all keys while open
+        if app.global_search.is_some() {
+            let scan = JsonScan::saved(&app.server_url);
+            if let Some(hit) = app.global_search_key(key, &scan) {
+                match &hit.source {
+                    HistorySource::Main => app.open_conversation(None),
+                    HistorySource::Session(id) => app.open_conversation(Some(id.clone())),
+                    HistorySource::Room(name) => {
+                        let joined = app.rooms.iter().any(|r| r.name.as_deref() == Some(name.as_str()));
+                        run_command(app, Command::Join(name.clone()), &events);
+                        // A room joined just now shows the hit once it has loaded
+                        if !joined && app.rooms_supported {
+                            app.reveal_after_sync = hit.key.clone();
+                            continue;
+                        }
+                    }
+                }
+                app.reveal_message(hit.key.as_deref());
+            }
+            continue;
+        }
+
+        // The search bar takes all keys while typing
+        if app.search.as_ref().is_some_and(|search| search.editing) {
+            app.search_key(key);
+            continue;
+        }
+
+        // Edit diff: any key closes it
+        if app.edit_diff.take().is_some() {
+            continue;
+        }
+
+        // Size offer: t splits, a attaches, anything else goes back to editing
+        if app.oversize.is_some() {
+            match key.code {
+                KeyCode::Char('t') => {
+                    app.accept_split();
+                }
+                KeyCode::Char('a') => {
+                    if let Some(offer) = app.oversize.take() {
+                        let name = format!("nachricht-{}.txt", app.now().format("%Y%m%d-%H%M%S"));
+                        let backend = app.backend.clone();
+                        spawn_fetch(app, &events, async move {
+                            let reference = backend.upload_file(&name, offer.text.clone()).await;
+                            Fetched::Uploaded { offer, name, reference }
+                        });
+                    }
+                }
+                _ => app.reject_oversize(),
+            }
+            continue;
+        }
+
+        // Server metrics: r fetches them again, any other key closes
+        if app.stats.is_some() {
+            app.stats = None;
+            if key.code == KeyCode::Char('r') {
+                fetch_stats(app, &events);
+            }
+            continue;
+        }
+
+        // Model picker: ↑/↓ choose, Enter regenerates, anything else cancels
+        if let Some(picker) = app.model_picker.as_mut() {
+            match key.code {
+                KeyCode::Up => picker.index = picker.index.saturating_sub(1),
+                KeyCode::Down => picker.index = (picker.index + 1).min(picker.models.len().saturating_sub(1)),
+                KeyCode::Enter => {
+                    let (prompt_ts, model) = (picker.prompt_ts, picker.models[picker.index].clone());
+                    app.model_picker = None;
+                    if let Some(pending) = app.regenerate_request(prompt_ts, &model) {
+                        app.last_error = None;
+                        send_or_queue(app, pending, &events);
+                    }
+                }
+                _ => app.model_picker = None,
+            }
+            continue;
+        }
+
+        // Moderation confirmation: j/y runs the action, any other key cancels
+        if let Some(action) = app.confirm.take() {
+            if matches!(key.code, KeyCode::Char('j' | 'J' | 'y' | 'Y')) {
+                run_moderation(app, action, &events);
+            }
+            continue;
+        }
+        
+        // Esc stops the answer (what came so far stays) or, while nothing
+        // has arrived yet, cancels the request
+        if app.loading && key.code == KeyCode::Esc {
+            app.stop_request();
+            continue;
+        }
+        
+        // Ctrl+X Ctrl+E composes the input in $EDITOR, as in bash; any other
+        // key after Ctrl+X is handled as usual
+        if std::mem::take(&mut app.ctrl_x)
+            && key.code == KeyCode::Char('e')
+            && key.modifiers == KeyModifiers::CONTROL
+        {
+            input.pause();
+            if let Err(e) = edit_in_editor(app) {
+                app.last_error = Some(e);
+            }
+            input.resume();
+            terminal.clear()?;
+            continue;
+        }
+
+        // Get input width for cursor calculations (same as the input pane minus borders)
+        let term_width = terminal.size()?.width.saturating_sub(2) as usize;
+        let input_before = app.input.clone();
+        
+        match key.code {
+            _ if app.bound(Action::Help, key) => {
+                app.toggle_help();
+            }
+            _ if app.bound(Action::Conversations, key) => {
+                if let Err(e) = app.toggle_session_sidebar() {
+                    app.last_error = Some(e);
+                }
+            }
+            KeyCode::Enter if app.focus == Focus::Sessions => {
+                if let Some(sidebar) = &app.session_sidebar {
+                    let id = sidebar.sessions.get(sidebar.index).and_then(|s| s.id.clone());
+                    if id != app.session || app.active_room != 0 {
+                        app.open_conversation(id);
+                    }
+                }
+            }
+            KeyCode::F(12) => {
+                app.debug_overlay = !app.debug_overlay;
+            }
+            KeyCode::F(3) => {
+                if app.thread.is_some() {
+                    app.thread = None;
+                } else if !app.open_thread() {
+                    app.last_error = Some(tr("Keine Nachricht mit Thread-Unterstützung").to_string());
+                }
+            }
+            KeyCode::F(8) => {
+                // Release the mouse for native selection, press again to restore
+                app.set_mouse_capture(!app.mouse_capture)?;
+            }
+            KeyCode::F(6) if app.connections.len() > 1 => {
+                let next = (app.active_connection + 1) % app.connections.len();
+                activate_connection(app, next, &events);
+            }
+            KeyCode::Char('p') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                input.pause();
+                if let Err(e) = open_in_pager(app) {
+                    app.last_error = Some(e);
+                }
+                input.resume();
+                terminal.clear()?;
+            }
+            KeyCode::Char('z') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                // Cycle hidden → time → date and time, remembered in the config
+                app.timestamps = app.timestamps.next();
+                if let Err(e) = Config::update(&["timestamps"], |config| config.timestamps = app.timestamps) {
+                    app.last_error = Some(trf("Config nicht gespeichert: {}", &[&e]));
+                }
+            }
+            KeyCode::Char('D') if !key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Chat => {
+                // Go to date: prefill the command
+                app.input.clear();
+                app.cursor_pos = 0;
+                app.insert_str("/date ");
+                app.focus = Focus::Input;
+            }
+            KeyCode::Char('d') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                app.compact = !app.compact;
+            }
+            KeyCode::Char('t') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                app.tools_expanded = !app.tools_expanded;
+            }
+            KeyCode::Char('R') if !key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Chat => {
+                // Raw source of the selected or newest message
+                if let Some(idx) = app.target_message(|m| m.role != "system") {
+                    app.messages[idx].raw = !app.messages[idx].raw;
+                }
+            }
+            KeyCode::Char('J') if !key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Chat => {
+                // Fold/unfold JSON in the selected answer or Hank's newest one that has some
+                if let Some(idx) =
+                    app.target_message(|m| m.role == "assistant" && json_segments(&m.content).is_some())
+                {
+                    app.messages[idx].json_expanded = !app.messages[idx].json_expanded;
+                }
+            }
+            _ if app.bound(Action::CopyReply, key) || app.bound(Action::CopyCode, key) => {
+                // Hank's newest answer, or only its code blocks
+                let code_only = app.bound(Action::CopyCode, key);
+                match app.last_reply_for_copy(code_only).and_then(|(text, note)| clipboard_set_text(&text).map(|()| note)) {
+                    Ok(note) => app.notify(note),
+                    Err(e) => app.last_error = Some(e),
+                }
+            }
+            KeyCode::Char('y') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                // Yank the selected or newest message
+                if let Some(idx) = app.target_message(|m| m.role != "system") {
+                    let text = app.messages[idx].content.clone();
+                    match clipboard_set_text(&text) {
+                        Ok(()) => app.notify(trf("Nachricht kopiert ({} Zeichen)", &[&text.chars().count().to_string()])),
+                        Err(e) => app.last_error = Some(e),
+                    }
+                }
+            }
+            KeyCode::Char('Y') if !key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Chat => {
+                // The whole conversation as in the pager
+                let text = app.transcript_text();
+                match clipboard_set_text(&text) {
+                    Ok(()) => app.notify(trf("Verlauf kopiert ({} Zeichen)", &[&text.chars().count().to_string()])),
+                    Err(e) => app.last_error = Some(e),
+                }
+            }
+            KeyCode::Char('/') if !key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Chat => {
+                app.open_search();
+            }
+            KeyCode::Char('f') if key.modifiers == KeyModifiers::CONTROL => {
+                app.open_search();
+            }
+            _ if app.bound(Action::GlobalSearch, key) => {
+                if let Err(e) = app.open_global_search() {
+                    app.last_error = Some(e);
+                }
+            }
+            KeyCode::Char(c @ ('n' | 'N'))
+                if !key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Chat && app.search.is_some() =>
+            {
+                app.step_search(c == 'n');
+            }
+            KeyCode::Char(c @ ('j' | 'k')) if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                app.move_selection(if c == 'j' { 1 } else { -1 });
+            }
+            KeyCode::Char('>') if app.focus == Focus::Chat && app.selected.is_some() => {
+                // Quote the selected message into the input
+                if let Some(msg) = app.selected.and_then(|idx| app.messages.get(idx)) {
+                    let text = msg.content.clone();
+                    app.quote_into_input(&text);
+                }
+            }
+            KeyCode::Char('r') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                if let Some(pending) = app.retry_failed() {
+                    app.last_error = None;
+                    send_or_queue(app, pending, &events);
+                }
+            }
+            KeyCode::Char('v') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                app.copy_mode = Some(CopyMode::new(&app.transcript_text()));
+            }
+            KeyCode::Char('x') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                app.cancel_queued();
+            }
+            KeyCode::Char('g') if key.modifiers.is_empty() && app.focus == Focus::Chat => {
+                // Regenerate Hank's newest answer with a model from the picker
+                match app.regenerate_prompt().and_then(|m| m.timestamp_ms) {
+                    None => app.last_error = Some(tr("Keine Antwort zum Neu-Generieren").to_string()),
+                    Some(prompt_ts) => {
+                        let backend = app.backend.clone();
+                        spawn_fetch(app, &events, async move {
+                            Fetched::Models { prompt_ts, models: backend.fetch_models().await }
+                        });
+                    }
+                }
+            }
+            KeyCode::F(5) => {
+                app.show_participants = !app.show_participants;
+                app.profile = None;
+                app.focus = if app.show_participants { Focus::Participants } else { Focus::Input };
+            }
+            KeyCode::Enter if app.focus == Focus::Participants => {
+                // Start a direct message to the highlighted participant
+                if !app.targets_supported {
+                    app.last_error = Some(tr("Server unterstützt keine Direktnachrichten").to_string());
+                } else if let Some(name) = app.selected_participant() {
+                    app.target = Some(name);
+                    app.focus = Focus::Input;
+                }
+            }
+            KeyCode::Char('i') if app.focus == Focus::Participants => {
+                if let Some(name) = app.selected_participant() {
+                    let backend = app.backend.clone();
+                    spawn_fetch(app, &events, async move {
+                        let profile = backend.fetch_profile(&name).await;
+                        Fetched::Profile { name, profile }
+                    });
+                }
+            }
+            KeyCode::F(9) => {
+                // Fetched on demand only; no background polling
+                fetch_stats(app, &events);
+            }
+            KeyCode::F(7) if app.targets_supported => {
+                app.cycle_target();
+            }
+            KeyCode::F(4) => {
+                app.mentions_only = !app.mentions_only;
+                app.scroll_to_bottom();
+            }
+            KeyCode::Esc if app.search.is_some() => {
+                app.search = None;
+            }
+            KeyCode::Esc if app.selected.is_some() => {
+                app.selected = None;
+            }
+            KeyCode::Esc if app.thread.is_some() => {
+                app.thread = None;
+            }
+            KeyCode::Esc if app.filter.is_some() => {
+                app.filter = None;
+                app.scroll_to_bottom();
+            }
+            KeyCode::Esc if app.pending_announcement().is_some() => {
+                app.acknowledge_announcement();
+            }
+            KeyCode::Char('?') if key.modifiers.is_empty() && app.focus != Focus::Input => {
+                app.toggle_help();
+            }
+            _ if app.bound(Action::Quit, key) => break,
+            _ if app.bound(Action::NewConversation, key) => {
+                app.new_session();
+            }
+            _ if app.bound(Action::OpenConversation, key) => {
+                if let Err(e) = app.open_session_picker() {
+                    app.last_error = Some(e);
+                }
+            }
+            _ if app.bound(Action::Clear, key) => {
+                // Clear chat (server + local)
+                let room = app.room_name().map(str::to_string);
+                let backend = app.backend.clone();
+                spawn_fetch(app, &events, async move {
+                    let result = backend.clear_messages(room.as_deref()).await;
+                    Fetched::Cleared { room, result }
+                });
+            }
+            _ if app.bound(Action::ClearHistory, key) => {
+                // Clear the history file of the open conversation or room
+                if app.history_enabled {
+                    match app.clear_conversation() {
+                        Ok(_) => {
+                            app.messages.push(Message::new("system", tr("Chat Historie gelöscht.")));
+                            app.last_error = None;
+                        }
+                        Err(e) => {
+                            app.last_error = Some(trf("Fehler beim Löschen: {}", &[&e.to_string()]));
+                        }
+                    }
+                } else {
+                    app.last_error = Some(tr("History ist deaktiviert (--no-history)").to_string());
+                }
+            }
+            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Input => {
+                // Paste from clipboard (Ctrl+V) - only when input is focused
+                match clipboard_get_text() {
+                    Ok(text) => {
+                        // Insert at cursor position
+                        app.insert_str(&text);
+                    }
+                    Err(e) => {
+                        app.last_error = Some(e);
+                    }
+                }
+            }
+            KeyCode::Tab if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                cycle_tab(app, true, &events);
+            }
+            KeyCode::BackTab if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                cycle_tab(app, false, &events);
+            }
+            _ if app.bound(Action::FocusToggle, key) => {
+                // Toggle focus between input and chat
+                app.toggle_focus();
+            }
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Input => {
+                // Command history navigation with Ctrl+Up
+                app.navigate_history_up();
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Input => {
+                // Command history navigation with Ctrl+Down
+                app.navigate_history_down();
+            }
+            KeyCode::Up if key.modifiers.is_empty() => {
+                match app.focus {
+                    // With alternate scroll the wheel arrives as ↑/↓: once
+                    // the cursor cannot move further, scroll the chat
+                    Focus::Input => {
+                        if !app.cursor_up(term_width) && app.alternate_scroll {
+                            app.scroll_up();
+                        }
+                    }
+                    Focus::Chat if app.selected.is_some() => app.move_selection(-1),
+                    Focus::Chat => app.scroll_up(),
+                    Focus::Participants => {
+                        app.participant_index = app.participant_index.saturating_sub(1);
+                        app.profile = None;
+                    }
+                    Focus::Sessions => {
+                        if let Some(sidebar) = app.session_sidebar.as_mut() {
+                            sidebar.index = sidebar.index.saturating_sub(1);
+                        }
+                    }
+                    Focus::Help => {}
+                }
+            }
+            KeyCode::Down if key.modifiers.is_empty() => {
+                match app.focus {
+                    Focus::Input => {
+                        if !app.cursor_down(term_width) && app.alternate_scroll {
+                            app.scroll_down();
+                        }
+                    }
+                    Focus::Chat if app.selected.is_some() => app.move_selection(1),
+                    Focus::Chat => app.scroll_down(),
+                    Focus::Participants => {
+                        let last = app.participants().len().saturating_sub(1);
+                        app.participant_index = (app.participant_index + 1).min(last);
+                        app.profile = None;
+                    }
+                    Focus::Sessions => {
+                        if let Some(sidebar) = app.session_sidebar.as_mut() {
+                            sidebar.index = (sidebar.index + 1).min(sidebar.sessions.len().saturating_sub(1));
+                        }
+                    }
+                    Focus::Help => {}
+                }
+            }
+            KeyCode::Char('x') if key.modifiers == KeyModifiers::CONTROL && app.focus == Focus::Input => {
+                app.ctrl_x = true;
+                app.notify(tr("Ctrl+X … Ctrl+E = im $EDITOR schreiben"));
+            }
+            KeyCode::Char('a') if key.modifiers == KeyModifiers::CONTROL && app.focus == Focus::Input => {
+                app.cursor_home(term_width);
+            }
+            KeyCode::Char('e') if key.modifiers == KeyModifiers::CONTROL && app.focus == Focus::Input => {
+                app.cursor_end(term_width);
+            }
+            KeyCode::Char('w') if key.modifiers == KeyModifiers::CONTROL && app.focus == Focus::Input => {
+                app.delete_word_back();
+            }
+            KeyCode::Char('u') if key.modifiers == KeyModifiers::CONTROL && app.focus == Focus::Input => {
+                app.kill_to_line_start();
+            }
+            KeyCode::Char('k') if key.modifiers == KeyModifiers::CONTROL && app.focus == Focus::Input => {
+                app.kill_to_line_end();
+            }
+            KeyCode::Char('b') if key.modifiers == KeyModifiers::ALT && app.focus == Focus::Input => {
+                app.cursor_word_left();
+            }
+            KeyCode::Char('f') if key.modifiers == KeyModifiers::ALT && app.focus == Focus::Input => {
+                app.cursor_word_right();
+            }
+            KeyCode::Left if key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Input => {
+                app.cursor_word_left();
+            }
+            KeyCode::Right if key.modifiers.contains(KeyModifiers::CONTROL) && app.focus == Focus::Input => {
+                app.cursor_word_right();
+            }
+            KeyCode::Left if app.focus == Focus::Input => {
+                app.cursor_left();
+            }
+            KeyCode::Right if app.focus == Focus::Input => {
+                app.cursor_right();
+            }
+            KeyCode::Home if app.focus == Focus::Input => {
+                // Move to start of current line
+                app.cursor_home(term_width);
+            }
+            KeyCode::End if app.focus == Focus::Input => {
+                // Move to end of current line
+                app.cursor_end(term_width);
+            }
+            _ if app.bound(Action::ScrollUp, key) => {
+                app.scroll_up();
+            }
+            _ if app.bound(Action::ScrollDown, key) => {
+                app.scroll_down();
+            }
+            KeyCode::Home if app.focus == Focus::Chat => {
+                app.jump_to_top();
+            }
+            KeyCode::End if app.focus == Focus::Chat => {
+                // First to what came in while scrolled up, then to the bottom
+                if app.unseen_after.is_some() {
+                    app.jump_to_unseen();
+                } else {
+                    app.jump_to_bottom();
+                }
+            }
+            KeyCode::PageUp if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                // Previous joined room
+                let idx = (app.active_room + app.rooms.len() - 1) % app.rooms.len();
+                app.switch_room(idx);
+            }
+            KeyCode::PageDown if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                // Next joined room
+                let idx = (app.active_room + 1) % app.rooms.len();
+                app.switch_room(idx);
+            }
+            _ if app.focus == Focus::Chat && app.bound(Action::PageUp, key) => {
+                app.scroll_page_up(10);
+            }
+            _ if app.focus == Focus::Chat && app.bound(Action::PageDown, key) => {
+                app.scroll_page_down(10);
+            }
+            _ if app.bound(Action::Send, key) => {
+                // Send message (Ctrl+S or Ctrl+Enter unless configured otherwise)
+                if let Some(id) = app.editing.take() {
+                    let content = app.input.trim().to_string();
+                    app.input.clear();
+                    app.cursor_pos = 0;
+                    app.input_scroll = 0;
+                    // Sending an empty input cancels the edit
+                    if content.is_empty() {
+                        continue;
+                    }
+                    let backend = app.backend.clone();
+                    spawn_fetch(app, &events, async move {
+                        let result = backend.edit_message(&id, content.clone()).await;
+                        Fetched::Edited { id, content, result }
+                    });
+                    continue;
+                }
+                if app.input.trim().is_empty() {
+                    continue;
+                }
+                let user_msg = app.input.trim().to_string();
+                
+                // Add to command history
+                app.command_history.push(user_msg.clone());
+                app.history_index = None;
+
+                // Slash commands are handled locally; `/msg <user> text` sends once
+                let mut to = app.target.clone();
+                let user_msg = match Command::parse(&user_msg) {
+                    Some(Ok(Command::Msg { to: recipient, text: Some(text) })) if app.targets_supported => {
+                        to = Some(recipient);
+                        text
+                    }
+                    Some(command) => {
+                        app.input.clear();
+                        app.cursor_pos = 0;
+                        app.input_scroll = 0;
+                        match command {
+                            Ok(Command::Pager) => {
+                                input.pause();
+                                if let Err(e) = open_in_pager(app) {
+                                    app.last_error = Some(e);
+                                }
+                                input.resume();
+                                terminal.clear()?;
+                            }
+                            Ok(command) => run_command(app, command, &events),
+                            Err(e) => app.last_error = Some(e),
+                        }
+                        continue;
+                    }
+                    None => match user_msg.strip_prefix("//") {
+                        Some(rest) => format!("/{}", rest),
+                        None => user_msg,
+                    },
+                };
+                
+                // Over the server's limit: offer to split or attach instead
+                if let Some(limit) = app.max_message_chars
+                    && user_msg.chars().count() > limit
+                {
+                    app.oversize = Some(Oversize { text: user_msg, to, limit });
+                    continue;
+                }
+
+                // Add user message
+                let pending = app.push_own_message(user_msg, to);
+                app.input.clear();
+                app.cursor_pos = 0;
+                app.input_scroll = 0;
+                app.last_error = None;
+                app.scroll_to_bottom();
+                send_or_queue(app, pending, &events);
+            }
+            _ if app.focus == Focus::Input && app.bound(Action::Newline, key) => {
+                // Insert newline with Enter (and Shift+Enter)
+                app.insert_char('\n');
+            }
+            KeyCode::Char(c) if app.focus == Focus::Input => {
+                app.insert_char(c);
+            }
+            KeyCode::Backspace if app.focus == Focus::Input => {
+                app.delete_back();
+            }
+            KeyCode::Delete if app.focus == Focus::Input => {
+                app.delete_forward();
+            }
+            _ => {}
+        }
+
+        // Let the others know we are composing (throttled, fire-and-forget)
+        if app.typing_supported
+            && app.input != input_before
+            && !app.input.is_empty()
+            && app.last_typing_sent.is_none_or(|t| t.elapsed().as_secs() >= 3)
+        {
+            app.last_typing_sent = Some(Instant::now());
+            let backend = app.backend.clone();
+            let room = app.room_name().map(str::to_string);
+            let display_name = app.display_name.clone();
+            tokio::spawn(async move {
+                let _ = backend.send_typing(room.as_deref(), display_name.as_deref()).await;
+            });
+        }
+    }
+    
+    Ok(())
+}
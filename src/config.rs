@@ -0,0 +1,586 @@
+//! Persisted settings (`~/.config/hank-tui/config.toml`).
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, path::{Path, PathBuf}};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{keys::*, lang::*};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    /// Full server URL, e.g. "https://hank.example.com/api/hank" (replaces
+    /// host, port and scheme)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// "http" (default) or "https" (also `--tls`)
+    #[serde(default)]
+    pub scheme: Scheme,
+    /// Extra trusted CA certificates for https, PEM file (also `--ca-bundle`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+    /// Accept self-signed and otherwise invalid certificates (also `--insecure`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure: bool,
+    /// Proxy URL, e.g. "http://proxy:3128", or "none" (default: the
+    /// HTTP_PROXY/HTTPS_PROXY/ALL_PROXY environment variables)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Seconds to wait for a connection to the server (default 10)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+    /// Seconds an idle connection is kept open for reuse (default 90)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<u64>,
+    /// Seconds to wait for Hank's answer to a message (default 120)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<u64>,
+    /// Seconds between polls for new messages (default 2)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<u64>,
+    /// Seconds one poll may take before it counts as failed (default 2)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_timeout: Option<u64>,
+    /// Credential sent with every request (`HANK_TOKEN` and `--token` win)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Header that carries the token, e.g. "X-API-Key" (default
+    /// `Authorization: Bearer <token>`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_header: Option<String>,
+    /// Name shown for own messages and sent to the server (default "Du")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// How to signal new messages: "none", "bell" or "flash"
+    #[serde(default)]
+    pub alert: AlertMode,
+    /// Extra words that count as a mention besides the display name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<String>,
+    /// Additional servers to connect to (`["host:port", …]`) or named
+    /// profiles (`[servers.work]`)
+    #[serde(default, skip_serializing_if = "Servers::is_empty")]
+    pub servers: Servers,
+    /// Models offered for regenerating (`g`) when the server lists none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+    /// Longest message the server accepts, in characters (also learned from HTTP 413)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_message_chars: Option<usize>,
+    /// Names and colors of own messages and the assistant's answers
+    #[serde(default, skip_serializing_if = "DisplaySettings::is_empty")]
+    pub display: DisplaySettings,
+    /// Fixed colors for participants, e.g. `mara = "#ff8800"` (overrides the hash)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub user_colors: BTreeMap<String, String>,
+    /// Capture the mouse for wheel scrolling (default on; F8 releases it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mouse: Option<bool>,
+    /// Inside tmux: name the window after the active conversation
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tmux_rename_window: bool,
+    /// Width of East Asian "ambiguous" characters (±, ①, Greek, Cyrillic …):
+    /// "narrow" (default) or "wide", as the terminal renders them
+    #[serde(default)]
+    pub east_asian_width: EastAsianWidth,
+    /// Status bar template, e.g. "{server} | {unread} neu | {time}"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_format: Option<String>,
+    /// Timestamps in the transcript: "hidden", "time" or "full" (`z` cycles)
+    #[serde(default)]
+    pub timestamps: TimestampMode,
+    /// Clock in the status bar, as strftime format (e.g. "%H:%M", "%a %d.%m. %H:%M")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock: Option<String>,
+    /// Language of the interface: "de" (default) or "en"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<Lang>,
+    /// Color theme: a preset or a file in `themes/` (`--theme` and `NO_COLOR` win)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    /// Input pane size: `3` lines, `"auto:8"` (grows up to 8) or `"30%"` of the screen
+    #[serde(default)]
+    pub input_height: InputHeight,
+    /// Other chords per action, e.g. `send = "ctrl+enter"` or
+    /// `clear_history = []` to unbind (see `Action` for the names)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeySetting>,
+}
+
+/// `servers` in the config: a plain list of extra connections, or named
+/// server profiles as `[servers.<name>]` tables
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Servers {
+    List(Vec<String>),
+    Profiles(BTreeMap<String, ServerProfile>),
+}
+
+impl Default for Servers {
+    fn default() -> Self {
+        Servers::List(Vec::new())
+    }
+}
+
+impl Servers {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Servers::List(list) => list.is_empty(),
+            Servers::Profiles(profiles) => profiles.is_empty(),
+        }
+    }
+
+    /// The profile `[servers.<name>]`
+    pub fn profile(&self, name: &str) -> Option<&ServerProfile> {
+        match self {
+            Servers::List(_) => None,
+            Servers::Profiles(profiles) => profiles.get(name),
+        }
+    }
+
+    pub fn profiles(&self) -> BTreeMap<String, ServerProfile> {
+        match self {
+            Servers::List(_) => BTreeMap::new(),
+            Servers::Profiles(profiles) => profiles.clone(),
+        }
+    }
+
+    /// Servers opened next to the primary one at startup: every list entry,
+    /// and the profiles with `connect = true`
+    pub fn at_startup(&self) -> Vec<ServerProfile> {
+        match self {
+            Servers::List(list) => list.iter().map(|target| ServerProfile::from_target(target)).collect(),
+            Servers::Profiles(profiles) => profiles.values().filter(|p| p.connect).cloned().collect(),
+        }
+    }
+}
+
+/// `[servers.<name>]`: a server chosen with `--profile <name>` or
+/// `/connect <name>`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ServerProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Full server URL (replaces host, port and scheme)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<Scheme>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_header: Option<String>,
+    /// Color theme while this server is the active one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    /// Also open this server as an extra connection at every start
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub connect: bool,
+}
+
+impl ServerProfile {
+    /// A server given as "host:port" or URL, without further settings
+    pub fn from_target(target: &str) -> Self {
+        Self { url: Some(target.to_string()), ..Default::default() }
+    }
+
+    /// Whether the profile names a server of its own (url, host or port)
+    pub fn sets_server(&self) -> bool {
+        self.url.is_some() || self.host.is_some() || self.port.is_some()
+    }
+
+    pub fn server_url(&self) -> String {
+        let scheme = self.scheme.unwrap_or_default();
+        match &self.url {
+            Some(url) => server_url_with(url, scheme),
+            None => format!("{}://{}:{}", scheme, self.host.as_deref().unwrap_or("localhost"), self.port.unwrap_or(8080)),
+        }
+    }
+}
+
+/// `[display]`: how the roles appear in the transcript
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct DisplaySettings {
+    /// Own name, as `display_name` (which wins when both are set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_name: Option<String>,
+    /// Instead of "Hank"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assistant_name: Option<String>,
+    /// Color of own messages, e.g. "#8be9fd" (default: the theme's accent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assistant_color: Option<String>,
+}
+
+impl DisplaySettings {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Sizing policy of the input pane (text lines, without the border)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "InputHeightSetting", into = "InputHeightSetting")]
+pub enum InputHeight {
+    Fixed(u16),
+    /// Grows with the text up to this many lines
+    Auto(u16),
+    /// Share of the screen height in percent
+    Percent(u16),
+}
+
+impl Default for InputHeight {
+    fn default() -> Self {
+        InputHeight::Fixed(3)
+    }
+}
+
+/// `input_height` as written in the config: a number or a string
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum InputHeightSetting {
+    Lines(u16),
+    Text(String),
+}
+
+impl TryFrom<InputHeightSetting> for InputHeight {
+    type Error = String;
+
+    fn try_from(setting: InputHeightSetting) -> Result<Self, String> {
+        let text = match setting {
+            InputHeightSetting::Lines(lines) => return Ok(InputHeight::Fixed(lines.max(1))),
+            InputHeightSetting::Text(text) => text,
+        };
+        let text = text.trim();
+        let number = |s: &str| s.trim().parse::<u16>().ok().filter(|&n| n > 0);
+        let policy = if text == "auto" {
+            Some(InputHeight::Auto(10))
+        } else if let Some(max) = text.strip_prefix("auto:") {
+            number(max).map(InputHeight::Auto)
+        } else if let Some(percent) = text.strip_suffix('%') {
+            number(percent).filter(|&p| p < 100).map(InputHeight::Percent)
+        } else {
+            number(text).map(InputHeight::Fixed)
+        };
+        policy.ok_or_else(|| trf("input_height: '{}' ist weder Zeilenzahl, \"auto:N\" noch \"N%\"", &[text]))
+    }
+}
+
+/// Same forms as in the config, e.g. for `--input-height auto:8`
+impl std::str::FromStr for InputHeight {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        InputHeight::try_from(InputHeightSetting::Text(text.to_string()))
+    }
+}
+
+impl From<InputHeight> for InputHeightSetting {
+    fn from(policy: InputHeight) -> Self {
+        match policy {
+            InputHeight::Fixed(lines) => InputHeightSetting::Lines(lines),
+            InputHeight::Auto(max) => InputHeightSetting::Text(format!("auto:{}", max)),
+            InputHeight::Percent(percent) => InputHeightSetting::Text(format!("{}%", percent)),
+        }
+    }
+}
+
+impl InputHeight {
+    /// Height of the input pane including its border, for `text_lines` of
+    /// input on a screen of `screen_height` rows (the chat keeps at least
+    /// three rows and the status bar one)
+    pub fn pane_height(self, text_lines: usize, screen_height: u16) -> u16 {
+        let lines = match self {
+            InputHeight::Fixed(lines) => lines,
+            InputHeight::Auto(max) => (text_lines.max(1).min(max as usize)) as u16,
+            InputHeight::Percent(percent) => (screen_height as u32 * percent as u32 / 100).saturating_sub(2) as u16,
+        };
+        (lines.max(1) + 2).min(screen_height.saturating_sub(4)).max(3)
+    }
+}
+
+/// How message timestamps are shown
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampMode {
+    Hidden,
+    /// HH:MM:SS
+    #[default]
+    Time,
+    /// Date and time
+    Full,
+}
+
+impl TimestampMode {
+    pub fn next(self) -> Self {
+        match self {
+            TimestampMode::Hidden => TimestampMode::Time,
+            TimestampMode::Time => TimestampMode::Full,
+            TimestampMode::Full => TimestampMode::Hidden,
+        }
+    }
+}
+
+/// How the terminal renders characters of ambiguous East Asian width
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EastAsianWidth {
+    #[default]
+    Narrow,
+    /// CJK terminals/fonts: ambiguous characters take two cells
+    Wide,
+}
+
+impl EastAsianWidth {
+    /// Display width of `ch` in cells (control characters count as one)
+    pub fn char_width(self, ch: char) -> usize {
+        match self {
+            EastAsianWidth::Narrow => ch.width(),
+            EastAsianWidth::Wide => ch.width_cjk(),
+        }
+        .unwrap_or(1)
+    }
+
+    pub fn str_width(self, text: &str) -> usize {
+        match self {
+            EastAsianWidth::Narrow => text.width(),
+            EastAsianWidth::Wide => text.width_cjk(),
+        }
+    }
+}
+
+/// Protocol for `host`/`port`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    #[default]
+    Http,
+    Https,
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        })
+    }
+}
+
+/// Alert on new messages from others, e.g. to notice replies inside tmux
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertMode {
+    #[default]
+    #[serde(alias = "off")]
+    None,
+    /// Terminal bell (BEL), which tmux turns into a window alert
+    Bell,
+    /// Briefly highlight the status bar
+    Flash,
+}
+
+impl Config {
+    pub fn config_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|mut path| {
+            path.push("hank-tui");
+            path.push("config.toml");
+            path
+        })
+    }
+
+    /// The saved config; without a config file the defaults. A file that
+    /// does not parse is an error rather than silently replaced by them.
+    pub fn load() -> Result<Self, String> {
+        match Self::config_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::defaults()),
+        }
+    }
+
+    /// `load` for the config file at `path`
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        parse_config(&Self::text_at(path)?).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn defaults() -> Self {
+        Config {
+            host: "localhost".to_string(),
+            port: 8080,
+            ..Default::default()
+        }
+    }
+
+    /// Text of the config file, or the defaults when there is none yet
+    pub fn saved_text() -> Result<String, String> {
+        Self::text_at(&Self::config_path().ok_or(tr("Kein Konfigurationsverzeichnis gefunden"))?)
+    }
+
+    fn text_at(path: &Path) -> Result<String, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::to_string_pretty(&Self::defaults()).map_err(|e| e.to_string()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Use the server, credentials and theme of `[servers.<name>]` (`--profile`).
+    /// Returns the profile, so the caller knows which settings it made.
+    pub fn apply_profile(&mut self, name: &str) -> Result<ServerProfile, String> {
+        let profile = self.servers.profile(name).cloned().ok_or_else(|| trf("Unbekanntes Profil '{}' (als [servers.{}] in der Config anlegen)", &[name, name]))?;
+        if profile.url.is_some() {
+            self.url.clone_from(&profile.url);
+        } else if profile.sets_server() {
+            // host/port of the profile replace a URL of the defaults
+            self.url = None;
+            self.host = profile.host.clone().unwrap_or_else(|| "localhost".to_string());
+            self.port = profile.port.unwrap_or(8080);
+        }
+        self.scheme = profile.scheme.unwrap_or(self.scheme);
+        self.token = profile.token.clone().or(self.token.take());
+        self.auth_header = profile.auth_header.clone().or(self.auth_header.take());
+        self.theme = profile.theme.clone().or(self.theme.take());
+        Ok(profile)
+    }
+
+    /// Make `server_url` the saved default server (`/save-config`): host,
+    /// port and scheme where they describe it, else `url`
+    pub fn remember_server(&mut self, server_url: &str) {
+        let (scheme, rest) = match server_url.split_once("://") {
+            Some(("https", rest)) => (Scheme::Https, rest),
+            Some((_, rest)) => (Scheme::Http, rest),
+            None => (Scheme::Http, server_url),
+        };
+        let host_port = rest.rsplit_once(':').filter(|(_, port)| !rest.contains('/') && port.parse::<u16>().is_ok());
+        match host_port {
+            Some((host, port)) => {
+                self.scheme = scheme;
+                self.host = host.to_string();
+                self.port = port.parse().unwrap_or(self.port);
+                self.url = None;
+            }
+            None => self.url = Some(server_url.to_string()),
+        }
+    }
+
+    /// Apply `change` to the saved config and write back the top-level
+    /// `keys` it touches. The keys are edited in place, so comments and the
+    /// order of the file survive; a file that does not parse is left alone.
+    pub fn update(keys: &[&str], change: impl FnOnce(&mut Config)) -> Result<(), String> {
+        Self::update_at(&Self::config_path().ok_or(tr("Kein Konfigurationsverzeichnis gefunden"))?, keys, change)
+    }
+
+    /// `update` for the config file at `path`
+    pub fn update_at(path: &Path, keys: &[&str], change: impl FnOnce(&mut Config)) -> Result<(), String> {
+        let mut config = Self::load_from(path)?;
+        change(&mut config);
+        let values = toml::Table::try_from(&config).map_err(|e| e.to_string())?;
+        let edits: Vec<(&str, Option<toml_edit::Value>)> = keys.iter().map(|&key| (key, values.get(key).map(edit_value))).collect();
+        let text = edit_config(&Self::text_at(path)?, &edits)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// `text` as a config, with the line of a syntax error
+fn parse_config(text: &str) -> Result<Config, String> {
+    toml::from_str(text).map_err(|e| match e.span() {
+        Some(span) => trf("Zeile {}: {}", &[&text[..span.start].lines().count().max(1).to_string(), e.message()]),
+        None => e.message().to_string(),
+    })
+}
+
+/// A value of the parsed config in the form `toml_edit` writes
+fn edit_value(value: &toml::Value) -> toml_edit::Value {
+    match value {
+        toml::Value::String(text) => text.as_str().into(),
+        toml::Value::Integer(number) => (*number).into(),
+        toml::Value::Float(number) => (*number).into(),
+        toml::Value::Boolean(flag) => (*flag).into(),
+        toml::Value::Datetime(datetime) => (*datetime).into(),
+        toml::Value::Array(items) => items.iter().map(edit_value).collect::<toml_edit::Array>().into(),
+        toml::Value::Table(table) => table.iter().map(|(key, value)| (key, edit_value(value))).collect::<toml_edit::InlineTable>().into(),
+    }
+}
+
+/// Set (or with `None` remove) dotted keys in the config text without
+/// touching the rest of it. The result has to be a valid config.
+fn edit_config(text: &str, edits: &[(&str, Option<toml_edit::Value>)]) -> Result<String, String> {
+    let mut document: toml_edit::DocumentMut = text.parse().map_err(|e: toml_edit::TomlError| format!("config.toml: {}", e.message()))?;
+    for (key, value) in edits {
+        let path: Vec<&str> = key.trim().split('.').collect();
+        if path.iter().any(|part| part.is_empty()) {
+            return Err(trf("Ungültiger Schlüssel '{}'", &[key.trim()]));
+        }
+        let (last, parents) = path.split_last().unwrap_or((&"", &[]));
+        let mut target: &mut dyn toml_edit::TableLike = document.as_table_mut();
+        for part in parents {
+            target = target
+                .entry(part)
+                .or_insert_with(|| {
+                    // [servers.work] without an empty [servers] above it
+                    let mut table = toml_edit::Table::new();
+                    table.set_implicit(true);
+                    toml_edit::Item::Table(table)
+                })
+                .as_table_like_mut()
+                .ok_or_else(|| trf("'{}' ist keine Tabelle", &[part]))?;
+        }
+        match (value, target.get_mut(last)) {
+            (None, _) => {
+                target.remove(last);
+            }
+            // Keep the comment behind the old value
+            (Some(value), Some(toml_edit::Item::Value(old))) => {
+                let decor = old.decor().clone();
+                *old = value.clone();
+                *old.decor_mut() = decor;
+            }
+            (Some(value), _) => {
+                target.insert(last, toml_edit::Item::Value(value.clone()));
+            }
+        }
+    }
+    let text = document.to_string();
+    parse_config(&text).map_err(|e| trf("Ungültige Config: {}", &[&e]))?;
+    Ok(text)
+}
+
+/// `hank-tui config set key=value …` applied to the text of config.toml.
+/// Values are read as TOML (`8080`, `true`, `["a", "b"]`), anything else as
+/// a string; dotted keys reach into tables (`display.user_name=me`,
+/// `servers.work.host=gpu-box`) and an empty value removes the key. Comments
+/// and the order of the other keys stay; the result has to be a valid config.
+pub fn set_config_values(text: &str, assignments: &[String]) -> Result<String, String> {
+    let mut edits = Vec::new();
+    for assignment in assignments {
+        let (key, raw) = assignment.split_once('=').ok_or_else(|| trf("'{}': als schlüssel=wert angeben", &[assignment]))?;
+        let raw = raw.trim();
+        let value = (!raw.is_empty()).then(|| raw.parse::<toml_edit::Value>().unwrap_or_else(|_| raw.into()));
+        edits.push((key, value));
+    }
+    edit_config(text, &edits)
+}
+
+/// Normalize "host:port" to a server URL; full URLs are kept as they are
+pub fn server_url_from(target: &str) -> String {
+    server_url_with(target, Scheme::Http)
+}
+
+/// Like `server_url_from`, with `scheme` for targets without one. A path
+/// after the host (reverse proxy prefix) stays part of the URL.
+pub fn server_url_with(target: &str, scheme: Scheme) -> String {
+    let target = target.trim().trim_end_matches('/');
+    if target.contains("://") {
+        target.to_string()
+    } else {
+        format!("{}://{}", scheme, target)
+    }
+}
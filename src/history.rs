@@ -0,0 +1,673 @@
+//! Chat messages and their persisted history.
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{lang::*, net::*, ui::*};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+    pub timestamp: String,
+    #[serde(default)]
+    pub timestamp_ms: Option<u64>,
+    /// Display name of the sender (multi-user servers)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// Server-assigned unique id: identity for deduplication, edits, threads
+    /// and read markers (None for local lines and servers without ids)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The message was edited after sending
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub edited: bool,
+    /// Id of the thread root when this message is a threaded reply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    /// Recipient of a direct message (None = whole channel)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Delivery state of an own message sent in this session
+    #[serde(skip)]
+    pub delivery: Option<Delivery>,
+    /// Tool/function calls Hank made while answering
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Streaming was stopped with Esc; the content is what arrived until then
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+    /// Milliseconds from sending the prompt to this answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_ms: Option<u64>,
+    /// Model of a regenerated answer (shown as its label)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Earlier versions of an edited message, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revisions: Vec<String>,
+    /// Provisional id of an own message sent in this session (see
+    /// `provisional_id`); the server's copy is matched by it
+    #[serde(skip)]
+    pub local_id: Option<String>,
+    /// Shown as the exact text received (`R`) instead of rendered
+    #[serde(skip)]
+    pub raw: bool,
+    /// Large JSON objects in the answer are shown unfolded (`J`)
+    #[serde(skip)]
+    pub json_expanded: bool,
+}
+
+/// One step of a structured assistant answer
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ToolCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+}
+
+impl ToolCall {
+    /// One-line form for the collapsed view: `name(a=1, b="x")`
+    pub fn summary(&self) -> String {
+        let args = match &self.arguments {
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(", "),
+            serde_json::Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        let mut summary = format!("{}({})", self.name, args);
+        if summary.chars().count() > 60 {
+            summary = summary.chars().take(59).collect::<String>() + "…";
+        }
+        summary
+    }
+}
+
+/// Split a JSON-encoded assistant answer (`{"content": ..., "tool_calls": [...]}`)
+/// into text and tool calls. Plain text comes back unchanged.
+pub fn parse_structured(content: String) -> (String, Vec<ToolCall>) {
+    #[derive(Deserialize)]
+    struct Structured {
+        #[serde(default)]
+        content: String,
+        tool_calls: Vec<ToolCall>,
+    }
+    if content.trim_start().starts_with('{')
+        && let Ok(structured) = serde_json::from_str::<Structured>(&content)
+    {
+        return (structured.content, structured.tool_calls);
+    }
+    (content, Vec::new())
+}
+
+/// Delivery state of an outgoing message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delivery {
+    /// Request in flight or waiting in the rate-limit queue
+    Sending,
+    /// The server answered the request
+    Sent,
+    /// The server stored the message under an id
+    Acknowledged,
+    /// Delivery failed; can be retried with `r` in the chat
+    Failed,
+}
+
+impl Delivery {
+    pub fn glyph(self) -> &'static str {
+        match self {
+            Delivery::Sending => "◌",
+            Delivery::Sent => "✓",
+            Delivery::Acknowledged => "✓✓",
+            Delivery::Failed => "✗",
+        }
+    }
+}
+
+impl Message {
+    /// Create a local message stamped with the current time
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+            timestamp: Local::now().format("%H:%M:%S").to_string(),
+            timestamp_ms: Some(now_ms()),
+            sender: None,
+            id: None,
+            edited: false,
+            thread_id: None,
+            to: None,
+            delivery: None,
+            tool_calls: Vec::new(),
+            interrupted: false,
+            response_ms: None,
+            model: None,
+            revisions: Vec::new(),
+            local_id: None,
+            raw: false,
+            json_expanded: false,
+        }
+    }
+
+    /// Convert a message received from the server
+    pub fn from_server(msg: ServerMessage) -> Self {
+        let (content, tool_calls) = if msg.role == "assistant" && msg.tool_calls.is_empty() {
+            parse_structured(msg.content)
+        } else {
+            (msg.content, msg.tool_calls)
+        };
+        Self {
+            timestamp: format_timestamp(msg.timestamp),
+            timestamp_ms: Some(msg.timestamp),
+            role: msg.role,
+            content,
+            sender: msg.sender,
+            id: msg.id,
+            edited: msg.edited,
+            thread_id: msg.thread_id,
+            to: msg.to,
+            delivery: None,
+            tool_calls,
+            interrupted: false,
+            response_ms: None,
+            model: None,
+            revisions: Vec::new(),
+            local_id: None,
+            raw: false,
+            json_expanded: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChatHistory {
+    pub server_url: String,
+    pub messages: Vec<Message>,
+    pub saved_at: String,
+    /// Timestamp of the last message seen before the session was closed
+    #[serde(default)]
+    pub last_read_ms: Option<u64>,
+    /// Key of the message at the top of the chat pane when the session was
+    /// closed (None = scrolled to the bottom)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_anchor: Option<String>,
+    /// What the conversation is about: the start of its first own message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Chunk files with older messages (`<name>.chunks/00000.json` …)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub chunks: usize,
+    /// Timestamp of the newest message in the chunk files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived_ms: Option<u64>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Messages per chunk file. The history file itself keeps the newest
+/// `HISTORY_CHUNK` up to `2 * HISTORY_CHUNK - 1` messages; older ones move
+/// into append-only chunk files that the chat pane loads on demand.
+pub const HISTORY_CHUNK: usize = 100;
+
+/// A saved conversation in `hank-tui/sessions/` (listed by Ctrl+O and
+/// the F2 sidebar)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionInfo {
+    /// None = the main conversation in `history.json`
+    pub id: Option<String>,
+    pub title: String,
+    pub saved_at: String,
+    pub messages: usize,
+    /// Messages newer than the last time the conversation was looked at
+    pub unread: usize,
+    /// First line of the newest message
+    pub preview: String,
+}
+
+/// First line of the newest message that is not a status note
+pub fn session_preview(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role != "system")
+        .and_then(|m| m.content.lines().find(|l| !l.trim().is_empty()))
+        .map(|l| l.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Title of a conversation: the first line of its first own message, cut
+/// to 40 characters
+pub fn session_title(messages: &[Message]) -> Option<String> {
+    let first = messages.iter().find(|m| m.role == "user")?;
+    let line = first.content.lines().find(|l| !l.trim().is_empty())?.trim();
+    let mut title: String = line.chars().take(40).collect();
+    if line.chars().count() > 40 {
+        title.push('…');
+    }
+    Some(title)
+}
+
+/// Id of a new conversation, also its file name: the time it was started
+pub fn new_session_id() -> String {
+    Local::now().format("%Y%m%d-%H%M%S-%3f").to_string()
+}
+
+impl ChatHistory {
+    pub fn history_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|mut path| {
+            path.push("hank-tui");
+            path.push("history.json");
+            path
+        })
+    }
+
+    /// History file of a room: `hank-tui/rooms/<room>.json`
+    pub fn room_history_path(room: &str) -> Option<PathBuf> {
+        let file_name: String = room
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        dirs::config_dir().map(|mut path| {
+            path.push("hank-tui");
+            path.push("rooms");
+            path.push(format!("{}.json", file_name));
+            path
+        })
+    }
+
+    /// Directory of the saved conversations: `hank-tui/sessions/`
+    pub fn sessions_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|mut path| {
+            path.push("hank-tui");
+            path.push("sessions");
+            path
+        })
+    }
+
+    /// History file of the conversation `id`
+    pub fn session_path(id: &str) -> Option<PathBuf> {
+        Self::sessions_dir().map(|dir| dir.join(format!("{}.json", id)))
+    }
+
+    /// Conversations with `server_url` saved in `dir`, the most recent first
+    pub fn list_sessions_in(dir: &Path, server_url: &str) -> Vec<SessionInfo> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut sessions: Vec<SessionInfo> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                let id = path.file_stem()?.to_str()?.to_string();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                let history = Self::load_from(&path).filter(|h| h.server_url == server_url)?;
+                Some(history.session_info(Some(id)))
+            })
+            .collect();
+        sessions.sort_by(|a, b| b.saved_at.cmp(&a.saved_at));
+        sessions
+    }
+
+    /// Summary of this history as the conversation `id`
+    pub fn session_info(&self, id: Option<String>) -> SessionInfo {
+        let shown = || self.messages.iter().filter(|m| m.role != "system");
+        let title = match &id {
+            Some(_) => self.title.clone().unwrap_or_else(|| tr("(ohne Titel)").to_string()),
+            None => tr("Hauptunterhaltung").to_string(),
+        };
+        SessionInfo {
+            id,
+            title,
+            saved_at: self.saved_at.clone(),
+            messages: shown().count(),
+            unread: shown()
+                .filter(|m| m.timestamp_ms.is_some_and(|t| self.last_read_ms.is_none_or(|read| t > read)))
+                .count(),
+            preview: session_preview(&self.messages),
+        }
+    }
+
+    /// Path of the history file for a room (None = shared default channel)
+    pub fn path_for(room: Option<&str>) -> Option<PathBuf> {
+        match room {
+            Some(room) => Self::room_history_path(room),
+            None => Self::history_path(),
+        }
+    }
+
+    pub fn load() -> Option<Self> {
+        Self::history_path().and_then(|path| Self::load_from(&path))
+    }
+
+    pub fn load_from(path: &Path) -> Option<Self> {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    pub fn save(server_url: &str, messages: &[Message]) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(path) = Self::history_path() {
+            Self::save_to(&path, server_url, messages, None)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_to(
+        path: &Path,
+        server_url: &str,
+        messages: &[Message],
+        view_anchor: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Messages already in a chunk file (loaded from there or archived
+        // by an earlier save) are not written again
+        let previous = Self::load_from(path);
+        let (mut chunks, mut archived_ms) = previous.as_ref().map_or((0, None), |h| (h.chunks, h.archived_ms));
+        let first_new = messages
+            .iter()
+            .position(|m| archived_ms.is_none_or(|archived| m.timestamp_ms.is_none_or(|t| t > archived)))
+            .unwrap_or(messages.len());
+        let mut head = &messages[first_new..];
+        while head.len() >= 2 * HISTORY_CHUNK {
+            let (chunk, rest) = head.split_at(HISTORY_CHUNK);
+            let chunk_path = Self::chunk_path(path, chunks);
+            if let Some(dir) = chunk_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&chunk_path, serde_json::to_string(chunk)?)?;
+            chunks += 1;
+            archived_ms = chunk.iter().filter_map(|m| m.timestamp_ms).max().or(archived_ms);
+            head = rest;
+        }
+
+        // Everything on screen when the session is saved counts as read
+        let last_read_ms = head.iter().filter_map(|m| m.timestamp_ms).max();
+        let history = ChatHistory {
+            server_url: server_url.to_string(),
+            messages: head.to_vec(),
+            saved_at: Local::now().to_rfc3339(),
+            last_read_ms,
+            view_anchor,
+            // The first own message may sit in a chunk that is not loaded
+            title: previous.and_then(|h| h.title).or_else(|| session_title(messages)),
+            chunks,
+            archived_ms,
+        };
+
+        let content = serde_json::to_string_pretty(&history)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+    
+    /// The whole conversation of the history file at `path`: the chunk
+    /// files, oldest first, then the messages of the file itself
+    pub fn all_messages(&self, path: &Path) -> Vec<Message> {
+        let mut messages = Self::load_chunks_before(path, self.chunks, &self.messages);
+        messages.extend(self.messages.iter().cloned());
+        messages
+    }
+
+    /// Messages of the first `count` chunk files of `path` that are older
+    /// than everything in `loaded`
+    pub fn load_chunks_before(path: &Path, count: usize, loaded: &[Message]) -> Vec<Message> {
+        let first = loaded.iter().filter_map(|m| m.timestamp_ms).min();
+        (0..count)
+            .filter_map(|index| Self::load_chunk(path, index))
+            .flatten()
+            .filter(|m| first.is_none_or(|first| m.timestamp_ms.is_some_and(|t| t < first)))
+            .collect()
+    }
+
+    /// Chunk file `index` (0 = oldest) of the history file at `path`
+    pub fn chunk_path(path: &Path, index: usize) -> PathBuf {
+        path.with_extension("chunks").join(format!("{:05}.json", index))
+    }
+
+    /// Messages of chunk file `index` of the history file at `path`
+    pub fn load_chunk(path: &Path, index: usize) -> Option<Vec<Message>> {
+        fs::read_to_string(Self::chunk_path(path, index))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    /// Remove the history file at `path` together with its chunks
+    pub fn delete(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        let chunks = path.with_extension("chunks");
+        if chunks.exists() {
+            fs::remove_dir_all(chunks)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which saved history a search hit comes from
+#[derive(Clone, Debug, PartialEq)]
+pub enum HistorySource {
+    /// The main conversation (`history.json`)
+    Main,
+    /// A conversation started with Ctrl+N
+    Session(String),
+    /// A room's history
+    Room(String),
+}
+
+/// A message found in a saved history
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryHit {
+    pub source: HistorySource,
+    /// Title of the conversation or `#room`
+    pub title: String,
+    /// `message_key` of the message, to scroll to it
+    pub key: Option<String>,
+    pub role: String,
+    pub sender: Option<String>,
+    pub timestamp_ms: Option<u64>,
+    /// The matching line, cut around the hit
+    pub snippet: String,
+}
+
+/// Full-text search over the saved histories (Ctrl+Shift+F). `JsonScan`
+/// reads the files on every query; an index can take its place later.
+pub trait HistorySearch {
+    /// Messages containing `query`, newest first, at most `limit`
+    fn search(&self, query: &str, limit: usize) -> Vec<HistoryHit>;
+}
+
+/// Search by reading every history file of one server
+pub struct JsonScan {
+    pub server_url: String,
+    pub files: Vec<(HistorySource, PathBuf)>,
+}
+
+impl JsonScan {
+    /// All saved histories: the main conversation, the other conversations
+    /// and the rooms
+    pub fn saved(server_url: &str) -> Self {
+        let mut files = Vec::new();
+        if let Some(path) = ChatHistory::history_path() {
+            files.push((HistorySource::Main, path));
+        }
+        let config = dirs::config_dir().map(|dir| dir.join("hank-tui"));
+        for (dir, source) in [("sessions", HistorySource::Session as fn(String) -> HistorySource), ("rooms", HistorySource::Room)] {
+            let Some(entries) = config.as_ref().and_then(|config| fs::read_dir(config.join(dir)).ok()) else {
+                continue;
+            };
+            for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+                if path.extension().is_some_and(|ext| ext == "json")
+                    && let Some(stem) = path.file_stem().and_then(|stem| stem.to_str())
+                {
+                    files.push((source(stem.to_string()), path));
+                }
+            }
+        }
+        Self { server_url: server_url.to_string(), files }
+    }
+}
+
+impl HistorySearch for JsonScan {
+    fn search(&self, query: &str, limit: usize) -> Vec<HistoryHit> {
+        let mut hits = Vec::new();
+        for (source, path) in &self.files {
+            let Some(history) = ChatHistory::load_from(path).filter(|h| h.server_url == self.server_url) else {
+                continue;
+            };
+            let title = match source {
+                HistorySource::Main => tr("Hauptunterhaltung").to_string(),
+                HistorySource::Session(_) => history.title.clone().unwrap_or_else(|| tr("(ohne Titel)").to_string()),
+                HistorySource::Room(name) => format!("#{}", name),
+            };
+            for msg in history.all_messages(path).iter().filter(|m| m.role != "system") {
+                if let Some(snippet) = match_snippet(&msg.content, query) {
+                    hits.push(HistoryHit {
+                        source: source.clone(),
+                        title: title.clone(),
+                        key: message_key(msg),
+                        role: msg.role.clone(),
+                        sender: msg.sender.clone(),
+                        timestamp_ms: msg.timestamp_ms,
+                        snippet,
+                    });
+                }
+            }
+        }
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.timestamp_ms));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// The line of `text` containing `query` (case-insensitive unless the
+/// query has capitals), cut to about 30 characters on either side
+pub fn match_snippet(text: &str, query: &str) -> Option<String> {
+    if query.is_empty() {
+        return None;
+    }
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let fold = |c: char| if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c };
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    text.lines().find_map(|line| {
+        let chars: Vec<char> = line.chars().collect();
+        let folded: Vec<char> = chars.iter().map(|&c| fold(c)).collect();
+        let at = folded.windows(needle.len()).position(|window| window == needle.as_slice())?;
+        let from = at.saturating_sub(30);
+        let to = (at + needle.len() + 30).min(chars.len());
+        let mut snippet: String = chars[from..to].iter().collect::<String>().trim().to_string();
+        if from > 0 {
+            snippet.insert(0, '…');
+        }
+        if to < chars.len() {
+            snippet.push('…');
+        }
+        Some(snippet)
+    })
+}
+
+/// Insert `msg` after every message with a timestamp <= its own, so the
+/// transcript stays ordered by `timestamp_ms` (ties keep arrival order)
+fn insert_by_timestamp(messages: &mut Vec<Message>, msg: Message) {
+    let ts = msg.timestamp_ms.unwrap_or(u64::MAX);
+    let idx = messages
+        .iter()
+        .rposition(|m| m.timestamp_ms.is_none_or(|t| t <= ts))
+        .map_or(0, |i| i + 1);
+    messages.insert(idx, msg);
+}
+
+/// Merge polled server messages into `messages`, ordered by server timestamp,
+/// and advance `last_timestamp`:
+/// - a known id replaces the local copy (edits, acknowledged sends),
+/// - without ids, an exact role+timestamp match counts as a duplicate,
+/// - a pending own message with the same text is our local echo and takes
+///   over the server's id and timestamp.
+///
+/// Returns the number of messages added.
+pub fn merge_server_messages(messages: &mut Vec<Message>, last_timestamp: &mut u64, incoming: Vec<ServerMessage>) -> usize {
+    let mut added = 0;
+    for msg in incoming {
+        *last_timestamp = (*last_timestamp).max(msg.timestamp);
+        let known = msg.id.as_deref().and_then(|id| messages.iter().position(|m| m.id.as_deref() == Some(id)));
+        if let Some(idx) = known {
+            let mut existing = messages.remove(idx);
+            existing.content = msg.content;
+            existing.edited = msg.edited;
+            existing.timestamp = format_timestamp(msg.timestamp);
+            existing.timestamp_ms = Some(msg.timestamp);
+            if existing.delivery.is_some() {
+                existing.delivery = Some(Delivery::Acknowledged);
+            }
+            insert_by_timestamp(messages, existing);
+            continue;
+        }
+        // Legacy servers without ids: fall back to role + timestamp equality
+        let already_exists = msg.id.is_none()
+            && messages
+                .iter()
+                .any(|m| m.role == msg.role && m.timestamp_ms == Some(msg.timestamp));
+        if already_exists {
+            continue;
+        }
+        // Our own local echo: by provisional id where the server hands it
+        // back, otherwise by identical content
+        let echo = match msg.client_id.as_deref() {
+            Some(client_id) => messages.iter().position(|m| m.id.is_none() && m.local_id.as_deref() == Some(client_id)),
+            None => messages.iter().position(|m| {
+                m.role == msg.role
+                    && m.id.is_none()
+                    && matches!(m.delivery, Some(Delivery::Sending | Delivery::Sent))
+                    && m.content == msg.content
+            }),
+        };
+        match echo {
+            Some(idx) => {
+                let mut local = messages.remove(idx);
+                local.id = msg.id;
+                local.content = msg.content;
+                local.timestamp = format_timestamp(msg.timestamp);
+                local.timestamp_ms = Some(msg.timestamp);
+                local.delivery = Some(Delivery::Acknowledged);
+                insert_by_timestamp(messages, local);
+            }
+            None => {
+                insert_by_timestamp(messages, Message::from_server(msg));
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+/// Stable key of a message across sessions: its server id, or the
+/// provisional id for messages the server never numbered
+pub fn message_key(msg: &Message) -> Option<String> {
+    msg.id.clone().or_else(|| msg.timestamp_ms.map(provisional_id))
+}
+
+/// Provisional id of an own message sent at `timestamp_ms`, valid until the
+/// server's copy with its canonical id arrives
+pub fn provisional_id(timestamp_ms: u64) -> String {
+    format!("local-{}", timestamp_ms)
+}
+
+pub(crate) fn now_ms() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
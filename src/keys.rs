@@ -0,0 +1,322 @@
+//! Configurable key bindings (`[keys]` in the config).
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::lang::*;
+
+/// Action that can be bound to other chords in the `[keys]` section
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Help,
+    FocusToggle,
+    Quit,
+    Conversations,
+    CopyReply,
+    CopyCode,
+    Send,
+    Newline,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    NewConversation,
+    OpenConversation,
+    GlobalSearch,
+    Clear,
+    ClearHistory,
+}
+
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Action::Help,
+        Action::FocusToggle,
+        Action::Quit,
+        Action::Conversations,
+        Action::CopyReply,
+        Action::CopyCode,
+        Action::Send,
+        Action::Newline,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::PageUp,
+        Action::PageDown,
+        Action::NewConversation,
+        Action::OpenConversation,
+        Action::GlobalSearch,
+        Action::Clear,
+        Action::ClearHistory,
+    ];
+
+    /// Name in the `[keys]` section
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Help => "help",
+            Action::FocusToggle => "focus_toggle",
+            Action::Quit => "quit",
+            Action::Conversations => "conversations",
+            Action::CopyReply => "copy_reply",
+            Action::CopyCode => "copy_code",
+            Action::Send => "send",
+            Action::Newline => "newline",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::NewConversation => "new_conversation",
+            Action::OpenConversation => "open_conversation",
+            Action::GlobalSearch => "global_search",
+            Action::Clear => "clear",
+            Action::ClearHistory => "clear_history",
+        }
+    }
+
+    /// Built-in chords
+    fn defaults(self) -> &'static [&'static str] {
+        match self {
+            Action::Help => &["f1"],
+            Action::FocusToggle => &["tab"],
+            Action::Quit => &["esc", "ctrl+c"],
+            Action::Conversations => &["f2"],
+            Action::CopyReply => &["ctrl+y"],
+            Action::CopyCode => &["ctrl+shift+y"],
+            Action::Send => &["ctrl+s", "ctrl+enter"],
+            Action::Newline => &["enter", "shift+enter", "alt+enter"],
+            Action::ScrollUp => &["alt+up"],
+            Action::ScrollDown => &["alt+down"],
+            Action::PageUp => &["pageup"],
+            Action::PageDown => &["pagedown"],
+            Action::NewConversation => &["ctrl+n"],
+            Action::OpenConversation => &["ctrl+o"],
+            Action::GlobalSearch => &["ctrl+shift+f"],
+            Action::Clear => &["ctrl+l"],
+            Action::ClearHistory => &["ctrl+shift+d"],
+        }
+    }
+}
+
+/// One key with its modifiers, e.g. `ctrl+shift+d`. Letters are kept in
+/// lower case with Shift as modifier, so `D` and `shift+d` are the same chord
+/// however the terminal reports it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if c.is_uppercase() => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_lowercase().next().unwrap_or(c))
+            }
+            // Shift is part of the character for digits and punctuation
+            KeyCode::Char(c) if !c.is_lowercase() => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::Char(c)
+            }
+            KeyCode::BackTab => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Tab
+            }
+            code => code,
+        };
+        KeyChord { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> Self {
+        KeyChord::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let invalid = || trf("'{}' ist keine Taste", &[text]);
+        // `+` itself can be the key: "+", "ctrl++"
+        let (prefix, key) = if text == "+" {
+            ("", "+")
+        } else if let Some(prefix) = text.strip_suffix("++") {
+            (prefix, "+")
+        } else {
+            text.rsplit_once('+').unwrap_or(("", text))
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for part in prefix.split('+').filter(|part| !part.is_empty()) {
+            modifiers |= match part.trim().to_lowercase().as_str() {
+                "ctrl" | "control" | "strg" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "option" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.trim().to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "esc" | "escape" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdown" | "pgdn" => KeyCode::PageDown,
+                "up" | "↑" => KeyCode::Up,
+                "down" | "↓" => KeyCode::Down,
+                "left" | "←" => KeyCode::Left,
+                "right" | "→" => KeyCode::Right,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(invalid()),
+                },
+            },
+        };
+        Ok(KeyChord::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    /// Written as in the help: `Ctrl+Shift+D`, `Alt+↑`, `F1`, `D`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chorded = self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt+")?;
+        }
+        let shifted_letter = !chorded && matches!(self.code, KeyCode::Char(c) if c.is_alphabetic());
+        if self.modifiers.contains(KeyModifiers::SHIFT) && !shifted_letter {
+            f.write_str("Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) if chorded || self.modifiers.contains(KeyModifiers::SHIFT) => {
+                write!(f, "{}", c.to_uppercase())
+            }
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => f.write_str("Enter"),
+            KeyCode::Tab => f.write_str("Tab"),
+            KeyCode::Esc => f.write_str("Esc"),
+            KeyCode::Backspace => f.write_str("Backspace"),
+            KeyCode::Delete => f.write_str("Del"),
+            KeyCode::Insert => f.write_str("Ins"),
+            KeyCode::Home => f.write_str("Home"),
+            KeyCode::End => f.write_str("End"),
+            KeyCode::PageUp => f.write_str("PgUp"),
+            KeyCode::PageDown => f.write_str("PgDown"),
+            KeyCode::Up => f.write_str("↑"),
+            KeyCode::Down => f.write_str("↓"),
+            KeyCode::Left => f.write_str("←"),
+            KeyCode::Right => f.write_str("→"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Chords of one action as written in the config: `"ctrl+s"` or a list;
+/// an empty list unbinds the action
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum KeySetting {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Chords for every action: the built-in ones, overridden per action by
+/// the `[keys]` section
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    chords: BTreeMap<Action, Vec<KeyChord>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let chords = Action::ALL
+            .iter()
+            .map(|&action| {
+                let chords = action.defaults().iter().filter_map(|chord| chord.parse().ok()).collect();
+                (action, chords)
+            })
+            .collect();
+        KeyBindings { chords }
+    }
+}
+
+impl KeyBindings {
+    /// Apply the `[keys]` section. Entries that cannot be read keep the
+    /// built-in chords and are reported, as are chords bound twice.
+    pub fn from_config(settings: &BTreeMap<String, KeySetting>) -> (Self, Vec<String>) {
+        let mut bindings = KeyBindings::default();
+        let mut problems = Vec::new();
+        for (name, setting) in settings {
+            let Some(action) = Action::ALL.into_iter().find(|action| action.name() == name) else {
+                problems.push(trf("keys.{}: unbekannte Aktion", &[name]));
+                continue;
+            };
+            let texts = match setting {
+                KeySetting::One(text) => std::slice::from_ref(text),
+                KeySetting::Many(texts) => texts.as_slice(),
+            };
+            match texts.iter().map(|text| text.parse()).collect::<Result<Vec<KeyChord>, String>>() {
+                Ok(chords) => {
+                    bindings.chords.insert(action, chords);
+                }
+                Err(e) => problems.push(format!("keys.{}: {}", name, e)),
+            }
+        }
+        let mut seen: Vec<(KeyChord, Action)> = Vec::new();
+        for (&action, chords) in &bindings.chords {
+            for &chord in chords {
+                match seen.iter().find(|(other, _)| *other == chord) {
+                    Some((_, first)) => problems.push(trf(
+                        "keys: {} doppelt belegt ({}, {})",
+                        &[&chord.to_string(), first.name(), action.name()],
+                    )),
+                    None => seen.push((chord, action)),
+                }
+            }
+        }
+        (bindings, problems)
+    }
+
+    pub fn matches(&self, action: Action, key: KeyEvent) -> bool {
+        let chord = KeyChord::from(key);
+        self.chords.get(&action).is_some_and(|chords| chords.contains(&chord))
+    }
+
+    /// The action keeps its built-in chords
+    pub fn is_default(&self, action: Action) -> bool {
+        self.chords.get(&action) == KeyBindings::default().chords.get(&action)
+    }
+
+    /// All chords of the action for the help, e.g. "Esc, Ctrl+C"
+    pub fn describe(&self, action: Action) -> String {
+        match self.chords.get(&action) {
+            Some(chords) if !chords.is_empty() => {
+                chords.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+            }
+            _ => "–".to_string(),
+        }
+    }
+
+    /// First chord of the action for short hints in titles
+    pub fn label(&self, action: Action) -> String {
+        self.chords
+            .get(&action)
+            .and_then(|chords| chords.first())
+            .map_or_else(|| "–".to_string(), ToString::to_string)
+    }
+}
@@ -0,0 +1,407 @@
+//! UI language. The German texts double as keys: `tr` looks up their
+//! translation in the active language and falls back to German for texts
+//! without one.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    De,
+    En,
+}
+
+/// Chosen once at startup (`lang` in the config, `--lang`)
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_language(lang: Lang) {
+    LANGUAGE.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Lang {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Lang::En,
+        _ => Lang::De,
+    }
+}
+
+/// The German UI text `de` in the active language
+pub fn tr(de: &'static str) -> &'static str {
+    language().translate(de)
+}
+
+/// Like `tr`, with the `{}` placeholders filled in order
+pub fn trf(de: &'static str, args: &[&str]) -> String {
+    let mut parts = tr(de).split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    for (idx, part) in parts.enumerate() {
+        text.push_str(args.get(idx).copied().unwrap_or_default());
+        text.push_str(part);
+    }
+    text
+}
+
+impl Lang {
+    pub fn translate(self, de: &'static str) -> &'static str {
+        match self {
+            Lang::De => de,
+            Lang::En => english(de).unwrap_or(de),
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "de" | "deutsch" | "german" => Ok(Lang::De),
+            "en" | "englisch" | "english" => Ok(Lang::En),
+            other => Err(format!("Unbekannte Sprache '{}' (de, en)", other)),
+        }
+    }
+}
+
+fn english(de: &str) -> Option<&'static str> {
+    Some(match de {
+        // Help
+        "═══ Hank TUI Hilfe ═══" => "═══ Hank TUI Help ═══",
+        "Ersatztasten (dieses Terminal)" => "Stand-in keys (this terminal)",
+        "Drücke eine beliebige Taste zum Schließen" => "Press any key to close",
+        "Allgemein" => "General",
+        "Eingabe (Input fokussiert)" => "Input (input focused)",
+        "Chat Scroll" => "Chat scrolling",
+        "Chat (Chat fokussiert)" => "Chat (chat focused)",
+        "Sonstiges" => "Other",
+        "Befehle" => "Commands",
+        "  Alt+1…8       statt F1…F8" => "  Alt+1…8       instead of F1…F8",
+        "  Alt+→/←       statt Ctrl+Tab / Ctrl+Shift+Tab" => "  Alt+→/←       instead of Ctrl+Tab / Ctrl+Shift+Tab",
+        "  Ctrl+J        statt Ctrl+Enter" => "  Ctrl+J        instead of Ctrl+Enter",
+        "Hilfe anzeigen/schließen" => "Show/close help",
+        "Fokus wechseln (Input ↔ Chat)" => "Switch focus (input ↔ chat)",
+        "Beenden" => "Quit",
+        "Unterhaltungen (↑↓, Enter=öffnen)" => "Conversations (↑↓, Enter=open)",
+        "Debug-Anzeige" => "Debug view",
+        "Server-Statistik (Last, Warteschlange)" => "Server statistics (load, queue)",
+        "Letzte Antwort von Hank kopieren" => "Copy Hank's last answer",
+        "Nur Codeblöcke der letzten Antwort kopieren" => "Copy only the code blocks of the last answer",
+        "Anfrage abbrechen / Antwort stoppen" => "Cancel request / stop answer",
+        "Nachricht senden" => "Send message",
+        "Neue Zeile" => "New line",
+        "Senden (Shift+Enter = neue Zeile)" => "Send (Shift+Enter = new line)",
+        "Einfügen aus Zwischenablage" => "Paste from clipboard",
+        "Eingabe im $EDITOR schreiben" => "Compose the input in $EDITOR",
+        "Cursor zwischen Zeilen bewegen" => "Move the cursor between lines",
+        "Cursor links/rechts" => "Cursor left/right",
+        "Zeilenanfang/-ende (auch Ctrl+A/E)" => "Line start/end (also Ctrl+A/E)",
+        "Wortweise springen (auch Alt+B/F)" => "Jump by words (also Alt+B/F)",
+        "Wort vor dem Cursor löschen" => "Delete the word before the cursor",
+        "Bis Zeilenanfang/-ende löschen" => "Delete to line start/end",
+        "Command History (vorherige Nachrichten)" => "Command history (previous messages)",
+        "Chat fokussieren" => "Focus the chat",
+        "Chat scrollen (immer)" => "Scroll the chat (always)",
+        "Chat scrollen" => "Scroll the chat",
+        "Scrollen (1 Zeile)" => "Scroll (1 line)",
+        "Scrollen (10 Zeilen)" => "Scroll (10 lines)",
+        "Zum Anfang" => "To the top",
+        "Zum Ende (Auto-Scroll), erst zu neuen Nachrichten" => "To the bottom (auto-scroll), first to new messages",
+        "Nicht zugestellte Nachricht erneut senden" => "Resend an undelivered message",
+        "Tool-Aufrufe auf-/zuklappen" => "Expand/collapse tool calls",
+        "Kompakte Ansicht ein/aus" => "Compact view on/off",
+        "Zeitstempel: aus/Uhrzeit/Datum" => "Timestamps: off/time/date",
+        "Verlauf im $PAGER öffnen (/pager)" => "Open the transcript in $PAGER (/pager)",
+        "Im Chat suchen (auch Ctrl+F), n/N = nächster/voriger Treffer" => {
+            "Search the chat (also Ctrl+F), n/N = next/previous hit"
+        }
+        "Nachricht auswählen (dann auch ↑/↓, Esc=aufheben)" => "Select a message (then also ↑/↓, Esc=clear)",
+        "Ausgewählte/letzte Nachricht kopieren (auch via SSH)" => "Copy the selected/last message (also via SSH)",
+        "Ganzen Verlauf kopieren" => "Copy the whole transcript",
+        "Ausgewählte Nachricht zitieren" => "Quote the selected message",
+        "Ausgewählte/letzte Nachricht roh / formatiert" => "Selected/last message raw / formatted",
+        "JSON in Hanks Antwort auf-/zuklappen" => "Expand/collapse JSON in Hank's answer",
+        "Kopiermodus (hjkl/w/b, v/V, y, > zitieren)" => "Copy mode (hjkl/w/b, v/V, y, > quote)",
+        "Letzte Antwort mit anderem Modell" => "Last answer with another model",
+        "Letzte wartende Anfrage abbrechen" => "Cancel the last waiting request",
+        "Thread öffnen / schließen" => "Open / close thread",
+        "Nur Erwähnungen anzeigen" => "Show mentions only",
+        "Teilnehmerliste (↑↓, Enter=DM, i)" => "Participant list (↑↓, Enter=DM, i)",
+        "Zwischen Servern wechseln" => "Switch between servers",
+        "Empfänger durchschalten" => "Cycle recipients",
+        "Maus freigeben/fangen (Textauswahl)" => "Release/capture the mouse (text selection)",
+        "Nächster Tab (Ctrl+Shift+Tab zurück)" => "Next tab (Ctrl+Shift+Tab back)",
+        "Zwischen Räumen wechseln" => "Switch between rooms",
+        "Neue Unterhaltung" => "New conversation",
+        "Gespeicherte Unterhaltung öffnen" => "Open a saved conversation",
+        "Alle gespeicherten Unterhaltungen durchsuchen" => "Search all saved conversations",
+        "Chat löschen (Server + lokal)" => "Clear the chat (server + local)",
+        "History-Datei löschen" => "Delete the history file",
+        "Raum betreten / wechseln" => "Join / switch room",
+        "Aktiven Raum verlassen" => "Leave the active room",
+        "Letzte eigene Nachricht bearbeiten" => "Edit your last message",
+        "Weitere Server-Verbindung oder Profil öffnen" => "Open another server connection or profile",
+        "Aktive Zusatzverbindung trennen" => "Close the active extra connection",
+        "Direktnachricht an n" => "Direct message to n",
+        "Empfänger wählen (/all = alle)" => "Choose the recipient (/all = everyone)",
+        "Moderation (Admins)" => "Moderation (admins)",
+        "Verlauf im $PAGER öffnen" => "Open the transcript in $PAGER",
+        "Änderung der letzten Bearbeitung" => "Changes of the last edit",
+        "Prompt-Vorlage einfügen" => "Insert a prompt template",
+        "Nur passende Nachrichten (Esc)" => "Only matching messages (Esc)",
+        "Zum ersten Beitrag des Tages (D)" => "To the first message of the day (D)",
+        "Farbschema wechseln / auflisten" => "Switch / list color themes",
+        "Unterhaltung als .md, .json oder .html speichern" => "Save the conversation as .md, .json or .html",
+        "Nachricht mit / am Anfang" => "Message starting with /",
+        // Panes
+        " Unterhaltungen [Enter] " => " Conversations [Enter] ",
+        "Teilnehmer" => "Participants",
+        " Räume " => " Rooms ",
+        " Teilnehmer " => " Participants ",
+        " Teilnehmer [Enter=DM, i=Profil] " => " Participants [Enter=DM, i=profile] ",
+        " Thread [F3/Esc=Schließen] " => " Thread [F3/Esc=close] ",
+        " [Erwähnungen, F4=Alle]" => " [mentions, F4=all]",
+        " Chat{} [KOPIERMODUS - hjkl/w/b/e, v/V=Auswahl, y=Kopieren, >=Zitieren, Esc] " => {
+            " Chat{} [COPY MODE - hjkl/w/b/e, v/V=select, y=copy, >=quote, Esc] "
+        }
+        " Chat{} [AUSWAHL - j/k=Nachricht, y=Kopieren, >=Zitieren, Esc] " => {
+            " Chat{} [SELECTION - j/k=message, y=copy, >=quote, Esc] "
+        }
+        " Chat{} [FOKUSSIERT - ↑↓=Scroll, j/k=Auswahl, Tab=Wechsel] " => {
+            " Chat{} [FOCUSED - ↑↓=scroll, j/k=select, Tab=switch] "
+        }
+        " Chat{} [Tab=Fokussieren] " => " Chat{} [Tab=focus] ",
+        "keine Treffer" => "no hits",
+        "Enter=fertig, Esc" => "Enter=done, Esc",
+        "n/N=weiter/zurück, /=neu, Esc" => "n/N=next/back, /=new, Esc",
+        " {} antwortet [Esc=Stopp, {}=danach senden] " => " {} is answering [Esc=stop, {}=send afterwards] ",
+        " Bearbeiten [{}=Speichern, leer senden=Abbrechen] " => " Editing [{}=save, send empty=cancel] ",
+        " An {} [{}=Senden, F7=Empfänger, /all=Alle] " => " To {} [{}=send, F7=recipient, /all=everyone] ",
+        " Nachricht [{}=Senden, {}=Hilfe] " => " Message [{}=send, {}=help] ",
+        " Nachricht [{}=Fokussieren] " => " Message [{}=focus] ",
+        // Status
+        "{} denkt nach" => "{} is thinking",
+        "{} denkt nach..." => "{} is thinking...",
+        " | Maus frei [F8]" => " | mouse released [F8]",
+        " | {} vor {}" => " | {} {} ago",
+        " | F6: {} neu" => " | F6: {} new",
+        // Transcript
+        "Historie geladen ({} Nachrichten) - {}" => "History loaded ({} messages) - {}",
+        "Neue Session für {}" => "New session for {}",
+        "Verbunden mit {} (History aktiviert)" => "Connected to {} (history enabled)",
+        "Verbunden mit {} (History deaktiviert)" => "Connected to {} (history disabled)",
+        "Chat gelöscht (Server + lokal). Verbunden mit {}" => "Chat cleared (server + local). Connected to {}",
+        "Chat Historie gelöscht." => "Chat history deleted.",
+        "{} sagt" => "{} says",
+        "Du sagst" => "You say",
+        "Fehler" => "Error",
+        " ⚠ Keine Verbindung zu {} seit {} – verbinde neu…" => " ⚠ No connection to {} for {} – reconnecting…",
+        " ⚠ Keine Verbindung zu {} seit {} – neuer Versuch in {}s" => " ⚠ No connection to {} for {} – retrying in {}s",
+        " {} neue Nachrichten ↓ [End] " => " {} new messages ↓ [End] ",
+        "Exportiert nach {} ({} Nachrichten)" => "Exported to {} ({} messages)",
+        "Exportiert aus hank-tui am {}" => "Exported from hank-tui on {}",
+        "Keine Nachrichten erkannt" => "No messages found",
+        "Importiert: {} ({} Nachrichten) → {}" => "Imported: {} ({} messages) → {}",
+        "Eingabe zu groß ({} Zeichen, max. {}); --max-stdin erhöht die Grenze" => "Input too large ({} characters, max. {}); --max-stdin raises the limit",
+        "Unterhaltung" => "Conversation",
+        "Aktiven Server als Standard in die Config schreiben" => "Write the active server to the config as default",
+        "{} als Standardserver gespeichert" => "{} saved as default server",
+        "Gespeichert in {}" => "Saved to {}",
+        "Server-Check fehlgeschlagen: {}" => "Server check failed: {}",
+        "Verbunden" => "Connected",
+        "Sendet..." => "Sending...",
+        "Getrennt" => "Disconnected",
+        "Kopiert" => "Copied",
+        "Server prüfen: Version, Modell, Antwortzeit" => "Check the server: version, model, round trip",
+        "Modell {}" => "model {}",
+        "Server spricht Protokoll {}, hank-tui Protokoll {} – manches funktioniert evtl. nicht" => "Server speaks protocol {}, hank-tui protocol {} – some things may not work",
+        "Nachricht kopiert ({} Zeichen)" => "Message copied ({} characters)",
+        "Verlauf kopiert ({} Zeichen)" => "Transcript copied ({} characters)",
+        "Antwort kopiert ({} Zeichen)" => "Answer copied ({} characters)",
+        // App
+        "Verbunden mit {}" => "Connected to {}",
+        "Hauptkanal" => "Main channel",
+        "{} aus dem Raum entfernen?" => "Remove {} from the room?",
+        "{} für {} Minuten stummschalten?" => "Mute {} for {} minutes?",
+        "{} stummschalten?" => "Mute {}?",
+        "Alle Nachrichten von {} löschen?" => "Delete all messages by {}?",
+        "{} wurde entfernt" => "{} was removed",
+        "{} ist für {} Minuten stumm" => "{} is muted for {} minutes",
+        "{} ist stumm" => "{} is muted",
+        "Nachrichten von {} gelöscht" => "Messages by {} deleted",
+        "Moderation: {} (von {})" => "Moderation: {} (by {})",
+        "Verwendung: /{} <name>{}" => "Usage: /{} <name>{}",
+        " [minuten]" => " [minutes]",
+        "Verwendung: /mute <name> [minuten]" => "Usage: /mute <name> [minutes]",
+        "Unbekannter Befehl: /{} (// am Anfang sendet einen Slash)" => "Unknown command: /{} (// at the start sends a slash)",
+        "Ungültiger Filter: {}" => "Invalid filter: {}",
+        "Unbekanntes Datum '{}' (TT.MM.JJJJ, JJJJ-MM-TT, heute, gestern)" => "Unknown date '{}' (DD.MM.YYYY, YYYY-MM-DD, heute, gestern)",
+        "hank-tui — {} ({} ungelesen)" => "hank-tui — {} ({} unread)",
+        "Raum #{} betreten" => "Joined room #{}",
+        "Dateiendung .md, .json oder .html angeben" => "Give a .md, .json or .html file extension",
+        "Du" => "You",
+        "Neue Unterhaltung – frühere öffnet Ctrl+O" => "New conversation – Ctrl+O opens earlier ones",
+        "Hauptunterhaltung" => "Main conversation",
+        "(ohne Titel)" => "(untitled)",
+        "ohne Titel" => "untitled",
+        "Unterhaltung {} geöffnet" => "Conversation {} opened",
+        "History deaktiviert – Unterhaltungen werden nicht gespeichert" => "History disabled – conversations are not saved",
+        "Anfrage abgebrochen" => "Request cancelled",
+        "⏳ Rate-Limit: {}s ({} wartend)" => "⏳ Rate limit: {}s ({} waiting)",
+        "Noch keine Antwort von {}" => "No answer from {} yet",
+        "Keine Codeblöcke in der letzten Antwort" => "No code blocks in the last answer",
+        "Codeblock kopiert" => "Code block copied",
+        "{} Codeblöcke kopiert" => "{} code blocks copied",
+        "History deaktiviert – es gibt nichts zu durchsuchen" => "History disabled – there is nothing to search",
+        "Keine Nachrichten ab dem {}" => "No messages from {} on",
+        "Keine Nachrichten am {}, weiter am {}" => "No messages on {}, continuing on {}",
+        "{} privat zu {}" => "{} privately to {}",
+        " (bearbeitet)" => " (edited)",
+        " – nicht zugestellt, r sendet erneut" => " – not delivered, r resends",
+        " – Werkzeug {}" => " – tool {}",
+        "Gewechselt zu {} auf {}" => "Switched to {} on {}",
+        "Eingabe" => "Input",
+        "Hilfe" => "Help",
+        "Teilnehmerliste" => "Participant list",
+        "Unterhaltungen" => "Conversations",
+        "Fokus: {}" => "Focus: {}",
+        "Verbindung wiederhergestellt, {} Nachrichten synchronisiert" => "Connection restored, {} messages synced",
+        "{} Nachrichten vom Server geladen" => "{} messages loaded from the server",
+        "Server unterstützt keine Räume" => "The server does not support rooms",
+        "Raum #{} verlassen" => "Left room #{}",
+        "Du bist in keinem Raum (/join <raum>)" => "You are not in a room (/join <room>)",
+        "Keine eigene Nachricht zum Bearbeiten" => "No message of yours to edit",
+        "Server unterstützt keine Direktnachrichten" => "The server does not support direct messages",
+        "/{} ist nur für Admins verfügbar" => "/{} is for admins only",
+        "Themes: {} (eigene als *.toml in {})" => "Themes: {} (your own as *.toml in {})",
+        "--ascii zeigt keine Farben" => "--ascii shows no colors",
+        "Config nicht gespeichert: {}" => "Config not saved: {}",
+        "Keine Vorlagen in {} (*.txt, *.md)" => "No templates in {} (*.txt, *.md)",
+        "Keine bearbeitete Nachricht" => "No edited message",
+        "Verbindung zu {} getrennt" => "Disconnected from {}",
+        "Die Hauptverbindung kann nicht getrennt werden" => "The main connection cannot be closed",
+        "📎 {} ({} Zeichen): {}" => "📎 {} ({} characters): {}",
+        "Keine Nachricht mit Thread-Unterstützung" => "No message with thread support",
+        "Keine Antwort zum Neu-Generieren" => "No answer to regenerate",
+        "Keine Modelle bekannt (Server /models oder models in der Config)" => "No models known (server /models or models in the config)",
+        "Clear fehlgeschlagen: {}" => "Clear failed: {}",
+        "Fehler beim Löschen: {}" => "Deleting failed: {}",
+        "History ist deaktiviert (--no-history)" => "History is disabled (--no-history)",
+        "Ctrl+X … Ctrl+E = im $EDITOR schreiben" => "Ctrl+X … Ctrl+E = write in $EDITOR",
+        "Verwendung: /join <raum>" => "Usage: /join <room>",
+        "Verwendung: /connect <host:port|profil>" => "Usage: /connect <host:port|profile>",
+        "Verwendung: /msg <name> [text]" => "Usage: /msg <name> [text]",
+        "Verwendung: /date <TT.MM.JJJJ|JJJJ-MM-TT|heute|gestern>" => "Usage: /date <DD.MM.YYYY|YYYY-MM-DD|heute|gestern>",
+        "Verwendung: /export <datei.md|.json|.html>" => "Usage: /export <file.md|.json|.html>",
+        "Ankündigung" => "Announcement",
+        "Theme „{}“" => "Theme “{}”",
+        // Screens
+        "Unbekanntes Theme '{}' (verfügbar: {})" => "Unknown theme '{}' (available: {})",
+        "base: unbekanntes Theme '{}'" => "base: unknown theme '{}'",
+        "{}: '{}' ist keine Farbe" => "{}: '{}' is not a color",
+        "{} … {} Einträge [J] {}" => "{} … {} entries [J] {}",
+        "{} tippt…" => "{} is typing…",
+        "{} und {} tippen…" => "{} and {} are typing…",
+        "{}, {} und {} weitere tippen…" => "{}, {} and {} more are typing…",
+        "Transkript nicht geschrieben: {}" => "Transcript not written: {}",
+        "Eingabe nicht geschrieben: {}" => "Input not written: {}",
+        "{} mit Fehler beendet, Eingabe unverändert" => "{} exited with an error, input unchanged",
+        "Eingabe nicht gelesen: {}" => "Input not read: {}",
+        "{} konnte nicht gestartet werden: {}" => "{} could not be started: {}",
+        " Ausstehend " => " Pending ",
+        " Ausstehend [x=letzte abbrechen] " => " Pending [x=cancel the last] ",
+        "(niemand)" => "(nobody)",
+        " ({} vor dir)" => " ({} ahead of you)",
+        "Warteschlange" => "Queue",
+        "Modell" => "Model",
+        "Last" => "Load",
+        "Keine Angaben" => "No details",
+        "Stand {}  [r=neu laden, sonst schließen]" => "As of {}  [r=reload, else close]",
+        "Die Nachricht hat {} Zeichen, der Server nimmt höchstens {}." => "The message has {} characters, the server takes at most {}.",
+        "In {} Teile aufteilen (\"[1/{}] …\"), direkt nacheinander" => "Split into {} parts (\"[1/{}] …\"), sent one after another",
+        "Als Datei anhängen" => "Attach as a file",
+        "Zurück zum Bearbeiten" => "Back to editing",
+        "{}  ·  {}  ·  {} Nachr." => "{}  ·  {}  ·  {} msgs",
+        "Suche: " => "Search: ",
+        "Suchbegriff eingeben" => "Enter a search term",
+        "Keine Treffer" => "No hits",
+        "── gefiltert: {} von {} Nachrichten [Esc=aufheben] ──" => "── filtered: {} of {} messages [Esc=clear] ──",
+        "── neue Nachrichten ──" => "── new messages ──",
+        "Argumente" => "Arguments",
+        "Ergebnis" => "Result",
+        "Ankündigung: " => "Announcement: ",
+        " [roh, R]" => " [raw, R]",
+        " {} nicht zugestellt [r=erneut]" => " {} not delivered [r=retry]",
+        "(unterbrochen)" => "(interrupted)",
+        "Antwort" => "reply",
+        "Antworten" => "replies",
+        " Ankündigung ({}): {}  [Esc=OK]" => " Announcement ({}): {}  [Esc=OK]",
+        " Vorlage wählen [Enter/Esc] " => " Choose a template [Enter/Esc] ",
+        " {} [Enter=weiter, Esc] " => " {} [Enter=next, Esc] ",
+        " Alle Unterhaltungen durchsuchen ({} Treffer) [↑↓, Enter=öffnen, Esc] " => " Search all conversations ({} hits) [↑↓, Enter=open, Esc] ",
+        " Unterhaltung öffnen [↑↓, Enter/Esc] " => " Open a conversation [↑↓, Enter/Esc] ",
+        " Bearbeitet: [-alt-] {+neu+} " => " Edited: [-old-] {+new+} ",
+        " Nachricht zu lang " => " Message too long ",
+        " Server-Statistik " => " Server statistics ",
+        " Neu generieren mit [Enter/Esc] " => " Regenerate with [Enter/Esc] ",
+        "Clipboard-Fehler: {}" => "Clipboard error: {}",
+        "Clipboard ist leer oder nicht verfügbar" => "The clipboard is empty or unavailable",
+        "Clipboard-Unterstützung nicht einkompiliert (Feature \"clipboard\")" => "Clipboard support not compiled in (feature \"clipboard\")",
+        // Config, keys, export
+        "Unbekanntes Format '{}' (md, json, html)" => "Unknown format '{}' (md, json, html)",
+        "'{}' ist keine Taste" => "'{}' is not a key",
+        "keys.{}: unbekannte Aktion" => "keys.{}: unknown action",
+        "keys: {} doppelt belegt ({}, {})" => "keys: {} bound twice ({}, {})",
+        "input_height: '{}' ist weder Zeilenzahl, \"auto:N\" noch \"N%\"" => "input_height: '{}' is neither a line count, \"auto:N\" nor \"N%\"",
+        "Kein Konfigurationsverzeichnis gefunden" => "No config directory found",
+        "Unbekanntes Profil '{}' (als [servers.{}] in der Config anlegen)" => "Unknown profile '{}' (add it as [servers.{}] to the config)",
+        "Zeile {}: {}" => "Line {}: {}",
+        "Ungültiger Schlüssel '{}'" => "Invalid key '{}'",
+        "'{}' ist keine Tabelle" => "'{}' is not a table",
+        "Ungültige Config: {}" => "Invalid config: {}",
+        "'{}': als schlüssel=wert angeben" => "'{}': give it as key=value",
+        // Server
+        "Rate-Limit erreicht, neuer Versuch in {}s" => "Rate limit reached, retrying in {}s",
+        "Nachricht zu groß (max. {} Zeichen)" => "Message too long (max. {} characters)",
+        "Nachricht zu groß für den Server" => "Message too long for the server",
+        "Server-Fehler: {}" => "Server error: {}",
+        "Zugriff verweigert (HTTP {}): Token prüfen (--token, HANK_TOKEN oder token in der Config)" => "Access denied (HTTP {}): check the token (--token, HANK_TOKEN or token in the config)",
+        "abwesend" => "away",
+        "unbekannt" => "unknown",
+        "Server unterstützt keine Presence" => "The server does not support presence",
+        "Server unterstützt keine Presence ({})" => "The server does not support presence ({})",
+        "Server unterstützt keine Tipp-Anzeige" => "The server does not support typing indicators",
+        "Server unterstützt keine Tipp-Anzeige ({})" => "The server does not support typing indicators ({})",
+        "Server unterstützt keine Direktnachrichten ({})" => "The server does not support direct messages ({})",
+        "Server liefert keine Berechtigungen" => "The server reports no permissions",
+        "Server liefert keine Berechtigungen ({})" => "The server reports no permissions ({})",
+        "Server unterstützt keine Moderation" => "The server does not support moderation",
+        "Server liefert keine Profile" => "The server provides no profiles",
+        "Server liefert keine Profile ({})" => "The server provides no profiles ({})",
+        "Server bietet keine Modellauswahl" => "The server offers no model choice",
+        "Server bietet keine Modellauswahl ({})" => "The server offers no model choice ({})",
+        "Server unterstützt keine Dateianhänge" => "The server does not support attachments",
+        "Server unterstützt keine Dateianhänge ({})" => "The server does not support attachments ({})",
+        "Server liefert keine Statistiken" => "The server provides no statistics",
+        "Server liefert keine Statistiken ({})" => "The server provides no statistics ({})",
+        "Server liefert keine Versionsinfo" => "The server provides no version info",
+        "Server unterstützt kein Bearbeiten" => "The server does not support editing",
+        "Server unterstützt keine Räume ({})" => "The server does not support rooms ({})",
+        "Keine Berechtigung für Moderation" => "No permission to moderate",
+        "Antwort-Stream vorzeitig beendet" => "Answer stream ended early",
+        "Server nannte keine Adresse für den Anhang" => "The server named no address for the attachment",
+        "Server liefert keine Versionsinfo ({})" => "The server provides no version info ({})",
+        "Ungültige Server-URL: {}" => "Invalid server URL: {}",
+        "Keine Antwort nach {}" => "No answer after {}",
+        "Moderation fehlgeschlagen: {}" => "Moderation failed: {}",
+        "Bearbeiten fehlgeschlagen: {}" => "Editing failed: {}",
+        // Command line
+        "{}: keine gespeicherte Unterhaltung" => "{}: no saved conversation",
+        "Keine Frage: als Argument angeben oder über stdin hineinleiten" => "No question: give it as an argument or pipe it in via stdin",
+        "Ungültiges JSON: {}" => "Invalid JSON: {}",
+        _ => return None,
+    })
+}
@@ -0,0 +1,3122 @@
+//! Hank chat client logic shared by the `hank-tui` binary.
+//!
+//! The crate exposes the application state ([`App`]), persisted config and
+//! history ([`Config`], [`ChatHistory`]) and the [`ChatBackend`] trait that
+//! abstracts the Hank server, so bots, tests and alternative frontends can
+//! reuse the client without the terminal UI.
+//!
+//! [`draw`] renders one frame from the [`App`] state, so screens can be
+//! checked against ratatui's `TestBackend` without a terminal.
+
+mod app;
+mod config;
+mod history;
+mod keys;
+mod lang;
+mod net;
+mod transcript;
+mod ui;
+
+pub use app::*;
+pub use config::*;
+pub use history::*;
+pub use keys::*;
+pub use lang::*;
+pub use net::*;
+pub use transcript::*;
+pub use ui::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, NaiveDate, TimeZone};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use futures::future::BoxFuture;
+    use ratatui::{
+        style::{Color, Style},
+        text::{Line, Span},
+        widgets::{Block, Borders, Paragraph},
+    };
+    use std::{env, fs, sync::Arc, time::{Duration, Instant}};
+    use unicode_width::UnicodeWidthChar;
+
+    fn scroll_values(lines: &[Line], width: usize, visible_lines: u16, auto_scroll: bool, scroll: u16) -> (u16, u16, u32) {
+        let total_lines: u32 = wrapped_line_count(lines, width, EastAsianWidth::Narrow).saturating_add(CHAT_PADDING_LINES);
+        let visible_lines_u32 = visible_lines as u32;
+        let max_scroll_u32 = total_lines.saturating_sub(visible_lines_u32);
+        let max_scroll: u16 = max_scroll_u32.min(u32::from(u16::MAX)) as u16;
+
+        let scroll_offset = if total_lines <= visible_lines_u32 {
+            0
+        } else if auto_scroll {
+            max_scroll
+        } else {
+            max_scroll.saturating_sub(scroll)
+        };
+
+        (max_scroll, scroll_offset, total_lines)
+    }
+
+    #[test]
+    fn counts_wrapped_lines_basic() {
+        let lines = vec![Line::from("12345"), Line::from("1234567890")]; // second wraps once at width 8
+        let total = wrapped_line_count(&lines, 8, EastAsianWidth::Narrow);
+        assert_eq!(total, 3); // two logical + one wrapped
+    }
+
+    #[test]
+    fn counts_wrapped_lines_unicode_width() {
+        let lines = vec![Line::from("😀abc")]; // emoji width 2
+        let total = wrapped_line_count(&lines, 3, EastAsianWidth::Narrow); // 2+1 exceeds 3, so wrap after emoji
+        assert_eq!(total, 2);
+    }
+
+    #[test]
+    fn scroll_auto_goes_to_max_with_padding() {
+        let lines = vec![Line::from("one"), Line::from("two"), Line::from("three")];
+        let (max_scroll, scroll_offset, total) = scroll_values(&lines, 10, 2, true, 0);
+        assert!(total > wrapped_line_count(&lines, 10, EastAsianWidth::Narrow)); // padding applied
+        assert_eq!(scroll_offset, max_scroll);
+    }
+
+    #[test]
+    fn manual_scroll_clamps() {
+        let lines = vec![Line::from("short"), Line::from("another short line"), Line::from("last")];
+        let (max_scroll, scroll_offset, _) = scroll_values(&lines, 10, 2, false, 5);
+        assert!(max_scroll >= scroll_offset);
+    }
+
+    struct NullBackend;
+
+    impl ChatBackend for NullBackend {
+        fn server_url(&self) -> &str {
+            "http://test"
+        }
+
+        fn fetch_messages<'a>(&'a self, _since: u64, _room: Option<&'a str>, _timeout: Duration) -> BoxFuture<'a, Result<Vec<ServerMessage>, String>> {
+            Box::pin(async { Err("offline".to_string()) })
+        }
+
+        fn send_chat(&self, _request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+            Box::pin(async { Err("offline".into()) })
+        }
+
+        fn clear_messages<'a>(&'a self, _room: Option<&'a str>) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async { Err("offline".to_string()) })
+        }
+    }
+
+    fn input_app(input: &str, cursor_pos: usize) -> App {
+        let mut app = App::new(Arc::new(NullBackend), false);
+        app.input = input.to_string();
+        app.cursor_pos = cursor_pos.min(input.chars().count());
+        app
+    }
+
+    #[test]
+    fn user_prefix_uses_display_and_sender_names() {
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        app.messages.push(Message::new("user", "hallo"));
+        let mut relayed = Message::new("user", "hi");
+        relayed.sender = Some("alice".to_string());
+        app.messages.push(relayed);
+
+        let prefixes: Vec<String> = app
+            .chat_lines()
+            .iter()
+            .filter(|l| l.spans.len() > 2)
+            .map(|l| l.spans[2].content.to_string())
+            .collect();
+        assert_eq!(prefixes, vec!["Du: ", "alice: "]);
+
+        app.display_name = Some("glan".to_string());
+        let first = app.chat_lines()[0].spans[2].content.to_string();
+        assert_eq!(first, "glan: ");
+    }
+
+    #[test]
+    fn parses_slash_commands() {
+        assert_eq!(Command::parse("hallo"), None);
+        assert_eq!(Command::parse("//etc/hosts"), None);
+        assert_eq!(Command::parse("/join #dev"), Some(Ok(Command::Join("dev".to_string()))));
+        assert_eq!(Command::parse("/leave"), Some(Ok(Command::Leave)));
+        assert_eq!(Command::parse("/pager"), Some(Ok(Command::Pager)));
+        assert_eq!(Command::parse("/diff"), Some(Ok(Command::Diff)));
+        assert_eq!(Command::parse("/template"), Some(Ok(Command::Template)));
+        assert_eq!(Command::parse("/filter"), Some(Ok(Command::Filter(None))));
+        assert_eq!(Command::parse("/date gestern"), Some(Ok(Command::Date("gestern".to_string()))));
+        assert!(matches!(Command::parse("/date"), Some(Err(_))));
+        assert_eq!(Command::parse("/filter @hank fehler"), Some(Ok(Command::Filter(Some("@hank fehler".to_string())))));
+        assert_eq!(
+            Command::parse("/msg @mara hallo du"),
+            Some(Ok(Command::Msg { to: "mara".to_string(), text: Some("hallo du".to_string()) }))
+        );
+        assert_eq!(Command::parse("/msg mara"), Some(Ok(Command::Msg { to: "mara".to_string(), text: None })));
+        assert_eq!(
+            Command::parse("/mute @troll 10"),
+            Some(Ok(Command::Moderate(ModAction::Mute { user: "troll".to_string(), minutes: Some(10) })))
+        );
+        assert_eq!(
+            Command::parse("/purge troll"),
+            Some(Ok(Command::Moderate(ModAction::Purge { user: "troll".to_string() })))
+        );
+        assert!(matches!(Command::parse("/kick"), Some(Err(_))));
+        assert!(matches!(Command::parse("/mute troll bald"), Some(Err(_))));
+        assert!(matches!(Command::parse("/join"), Some(Err(_))));
+        assert!(matches!(Command::parse("/frobnicate"), Some(Err(_))));
+    }
+
+    #[test]
+    fn switching_rooms_parks_streams_and_tracks_unread() {
+        let mut app = input_app("", 0);
+        app.messages = vec![Message::new("user", "lobby")];
+        app.last_timestamp = 10;
+
+        let dev = app.join_room("dev");
+        app.switch_room(dev);
+        assert_eq!(app.room_name(), Some("dev"));
+        assert!(app.messages.iter().all(|m| m.content != "lobby"));
+
+        // Background messages for the default channel count as unread
+        let incoming = vec![ServerMessage { role: "assistant".to_string(), content: "neu".to_string(), timestamp: 20, ..Default::default() }];
+        let lobby = &mut app.rooms[0];
+        lobby.unread += merge_server_messages(&mut lobby.messages, &mut lobby.last_timestamp, incoming);
+        assert_eq!(app.rooms[0].unread, 1);
+
+        let left = app.leave_room().expect("left dev");
+        assert_eq!(left.name.as_deref(), Some("dev"));
+        assert_eq!(app.room_name(), None);
+        assert_eq!(app.last_timestamp, 20);
+        assert_eq!(app.rooms[0].unread, 0);
+        assert_eq!(app.messages.len(), 2);
+        assert!(app.leave_room().is_none());
+    }
+
+    #[test]
+    fn presence_parses_unknown_status_and_finds_assistant() {
+        let presence: Vec<Presence> = serde_json::from_str(
+            r#"[{"name":"Hank","status":"online","role":"assistant"},{"name":"alice","status":"busy"}]"#,
+        )
+        .unwrap();
+        assert_eq!(presence[1].status, PresenceStatus::Unknown);
+
+        let mut app = input_app("", 0);
+        app.presence = presence;
+        assert_eq!(app.assistant_presence(), Some(PresenceStatus::Online));
+    }
+
+    #[test]
+    fn typing_text_lists_names() {
+        let names = |list: &[&str]| list.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(typing_text(&[]), None);
+        assert_eq!(typing_text(&names(&["Hank"])).as_deref(), Some("Hank tippt…"));
+        assert_eq!(typing_text(&names(&["alice", "bob"])).as_deref(), Some("alice und bob tippen…"));
+        assert_eq!(
+            typing_text(&names(&["alice", "bob", "carol", "dave"])).as_deref(),
+            Some("alice, bob und 2 weitere tippen…")
+        );
+    }
+
+    #[test]
+    fn divider_marks_first_unread_message() {
+        let mut app = input_app("", 0);
+        let mut old = Message::new("assistant", "alt");
+        old.timestamp_ms = Some(100);
+        let mut info = Message::new("system", "Historie geladen");
+        info.timestamp_ms = Some(300);
+        let mut new = Message::new("assistant", "neu");
+        new.timestamp_ms = Some(200);
+        app.messages = vec![old, info, new];
+
+        let divider = |app: &App| {
+            app.chat_lines()
+                .iter()
+                .position(|l| l.spans.first().is_some_and(|s| s.content.contains("neue Nachrichten")))
+        };
+        assert_eq!(divider(&app), None);
+
+        app.read_marker = Some(100);
+        // old (2 lines) + system (2 lines), then the divider before "neu"
+        assert_eq!(divider(&app), Some(4));
+    }
+
+    #[test]
+    fn edits_replace_own_message_content() {
+        let mut app = input_app("", 0);
+        let mut own = Message::new("user", "Hallo Wlet");
+        own.id = Some("m1".to_string());
+        app.messages = vec![own, Message::new("assistant", "Hi")];
+
+        assert!(app.start_edit());
+        assert_eq!(app.editing.as_deref(), Some("m1"));
+        assert_eq!(app.input, "Hallo Wlet");
+        assert_eq!(app.cursor_pos, 10);
+
+        app.apply_edit("m1", "Hallo Welt".to_string());
+        assert!(app.messages[0].edited);
+        assert!(app.chat_lines()[0].spans.iter().any(|s| s.content.contains("(bearbeitet)")));
+
+        // Edits made elsewhere arrive through polling and update in place
+        let mut last = 0;
+        let incoming = vec![ServerMessage {
+            role: "user".to_string(),
+            content: "Hallo Welt!".to_string(),
+            timestamp: 5,
+            id: Some("m1".to_string()),
+            edited: true,
+            ..Default::default()
+        }];
+        assert_eq!(merge_server_messages(&mut app.messages, &mut last, incoming), 0);
+        assert_eq!(app.messages[0].content, "Hallo Welt!");
+    }
+
+    #[test]
+    fn replies_move_into_thread_view() {
+        let mut app = input_app("", 0);
+        let mut root = Message::new("user", "Frage");
+        root.id = Some("r".to_string());
+        let mut reply = Message::new("assistant", "Antwort im Thread");
+        reply.thread_id = Some("r".to_string());
+        let mut orphan = Message::new("assistant", "Waise");
+        orphan.thread_id = Some("unbekannt".to_string());
+        app.messages = vec![root, reply, orphan];
+
+        let text = |lines: Vec<Line>| {
+            lines.iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect::<String>()).collect::<Vec<_>>().join("\n")
+        };
+        let main = text(app.chat_lines());
+        assert!(main.contains("↳ 1 Antwort"));
+        assert!(!main.contains("Antwort im Thread"));
+        assert!(main.contains("Waise"));
+
+        assert!(app.open_thread());
+        assert_eq!(app.thread.as_deref(), Some("r"));
+        let thread = text(app.thread_lines());
+        assert!(thread.contains("Frage") && thread.contains("Antwort im Thread"));
+        assert!(!thread.contains("Waise"));
+    }
+
+    #[test]
+    fn alerts_only_for_messages_from_others() {
+        let mut app = input_app("", 0);
+        app.display_name = Some("Glan".to_string());
+        let mut own = Message::new("user", "hi");
+        own.sender = Some("Glan".to_string());
+        let mut other = Message::new("user", "hallo");
+        other.sender = Some("Mara".to_string());
+        assert!(!app.is_from_others(&own));
+        assert!(!app.is_from_others(&Message::new("system", "info")));
+        assert!(app.is_from_others(&other));
+        assert!(app.is_from_others(&Message::new("assistant", "Antwort")));
+
+        app.alert();
+        assert!(app.flash_until.is_none());
+        app.alert_mode = AlertMode::Flash;
+        app.alert();
+        assert!(app.flash_until.is_some());
+
+        let config: Config = toml::from_str("host = \"h\"\nport = 1\nalert = \"bell\"").unwrap();
+        assert_eq!(config.alert, AlertMode::Bell);
+    }
+
+    #[test]
+    fn mentions_match_name_and_keywords() {
+        let mut app = input_app("", 0);
+        app.display_name = Some("Glan".to_string());
+        app.mention_keywords = vec!["Deploy".to_string()];
+        let mut own = Message::new("user", "glan hier");
+        own.sender = Some("Glan".to_string());
+        let mut named = Message::new("user", "Hey GLAN, schau mal");
+        named.sender = Some("Mara".to_string());
+        let keyword = Message::new("assistant", "Das deploy ist fertig");
+        let plain = Message::new("assistant", "Nichts besonderes");
+        app.messages = vec![own, named, keyword, plain];
+
+        let hits: Vec<bool> = app.messages.iter().map(|m| app.is_mention(m)).collect();
+        assert_eq!(hits, vec![false, true, true, false]);
+
+        app.mentions_only = true;
+        let shown: String = app.chat_lines().iter().flat_map(|l| l.spans.iter().map(|s| s.content.to_string())).collect();
+        assert!(shown.contains("schau mal") && shown.contains("deploy"));
+        assert!(!shown.contains("Nichts"));
+    }
+
+    struct NamedBackend(&'static str);
+
+    impl ChatBackend for NamedBackend {
+        fn server_url(&self) -> &str {
+            self.0
+        }
+
+        fn fetch_messages<'a>(
+            &'a self,
+            _since: u64,
+            _room: Option<&'a str>,
+            _timeout: Duration,
+        ) -> BoxFuture<'a, Result<Vec<ServerMessage>, String>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn send_chat(&self, _request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+            Box::pin(async { Err("offline".into()) })
+        }
+
+        fn clear_messages<'a>(&'a self, _room: Option<&'a str>) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[test]
+    fn connections_keep_separate_message_stores() {
+        let mut app = App::new(Arc::new(NamedBackend("http://local:8080")), false);
+        app.messages = vec![Message::new("user", "lokal")];
+        app.rooms_supported = true;
+
+        let remote = app.add_connection(Arc::new(NamedBackend("http://remote:8080")));
+        assert_eq!(remote, 1);
+        assert_eq!(app.add_connection(Arc::new(NamedBackend("http://local:8080"))), 0);
+
+        app.switch_connection(remote);
+        assert_eq!(app.server_url, "http://remote:8080");
+        assert_eq!(app.backend.server_url(), "http://remote:8080");
+        assert!(!app.rooms_supported);
+        assert!(app.messages.iter().all(|m| m.content != "lokal"));
+        app.messages.push(Message::new("user", "remote"));
+
+        app.switch_connection(0);
+        assert_eq!(app.server_url, "http://local:8080");
+        assert!(app.rooms_supported);
+        assert_eq!(app.messages[0].content, "lokal");
+        assert!(app.connections[remote].synced);
+
+        app.switch_connection(remote);
+        let closed = app.close_connection().unwrap();
+        assert_eq!(closed.server_url, "http://remote:8080");
+        assert!(closed.messages.iter().any(|m| m.content == "remote"));
+        assert_eq!(app.connections.len(), 1);
+        assert_eq!(app.server_url, "http://local:8080");
+        assert!(app.close_connection().is_none());
+
+        let remote = app.add_connection(Arc::new(NamedBackend("http://remote:8080")));
+        app.join_room("dev");
+        app.connections[remote].unread = 2;
+        let tabs = app.tabs();
+        let labels: Vec<&str> = tabs.iter().map(|t| t.label.as_str()).collect();
+        assert_eq!(labels, vec!["local:8080 Hauptkanal", "local:8080 #dev", "remote:8080 Hauptkanal"]);
+        assert_eq!(tabs[2].unread, 2);
+        assert_eq!(app.active_tab(&tabs), 0);
+
+        assert_eq!(server_url_from("remote:9000/"), "http://remote:9000");
+        assert_eq!(server_url_from("https://hank.example"), "https://hank.example");
+        assert_eq!(server_url_with("https://hank.example.com/api/hank/", Scheme::Http), "https://hank.example.com/api/hank");
+        assert_eq!(server_url_with("hank.example.com/api/hank", Scheme::Https), "https://hank.example.com/api/hank");
+        let backend = HttpBackend::new(server_url_with("https://hank.example.com/api/hank", Scheme::Http));
+        let request = backend.request(reqwest::Method::GET, "/messages").build().unwrap();
+        assert_eq!(request.url().as_str(), "https://hank.example.com/api/hank/messages");
+    }
+
+    #[test]
+    fn direct_messages_have_target_and_style() {
+        let mut app = input_app("", 0);
+        app.targets = vec!["mara".to_string(), "olaf".to_string()];
+        app.cycle_target();
+        assert_eq!(app.target.as_deref(), Some("mara"));
+        app.cycle_target();
+        assert_eq!(app.target.as_deref(), Some("olaf"));
+        app.cycle_target();
+        assert_eq!(app.target, None);
+
+        let mut dm = Message::new("user", "psst");
+        dm.to = Some("mara".to_string());
+        app.messages = vec![dm];
+        let first = &app.chat_lines()[0];
+        assert_eq!(first.spans[2].content, "Du → mara: ");
+        assert_eq!(first.spans[3].style.fg, Some(Color::LightMagenta));
+    }
+
+    #[test]
+    fn participant_list_selects_and_falls_back_to_targets() {
+        let mut app = input_app("", 0);
+        app.targets = vec!["mara".to_string(), "olaf".to_string()];
+        assert_eq!(app.participants().len(), 2);
+        assert_eq!(app.participants()[0].status, PresenceStatus::Unknown);
+
+        app.participant_index = 5;
+        assert_eq!(app.selected_participant().as_deref(), Some("olaf"));
+
+        app.presence = vec![Presence { name: "hank".to_string(), status: PresenceStatus::Online, role: Some("assistant".to_string()) }];
+        app.participant_index = 0;
+        app.focus = Focus::Participants;
+        let lines = app.participant_lines();
+        let text: String = lines[0].spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, "▶ ● hank [assistant]");
+
+        app.profile = Some(("hank".to_string(), UserProfile::from([("about".to_string(), serde_json::json!("Bot"))])));
+        assert!(app.participant_lines().iter().any(|l| l.spans.iter().any(|s| s.content == "about: Bot")));
+
+        app.focus = Focus::Chat;
+        app.show_participants = true;
+        app.toggle_focus();
+        assert!(app.focus == Focus::Participants);
+        app.toggle_focus();
+        assert!(app.focus == Focus::Input);
+    }
+
+    #[test]
+    fn announcements_stay_pending_until_acknowledged() {
+        let mut app = input_app("", 0);
+        let mut first = Message::new("announcement", "Wartung um 22 Uhr");
+        first.timestamp_ms = Some(10);
+        app.messages = vec![first, Message::new("assistant", "ok")];
+        assert_eq!(app.pending_announcement().map(|m| m.content.as_str()), Some("Wartung um 22 Uhr"));
+        assert!(app.is_from_others(&app.messages[0]));
+        assert_eq!(app.chat_lines()[0].spans[2].style.bg, Some(Color::Yellow));
+
+        app.acknowledge_announcement();
+        assert!(app.pending_announcement().is_none());
+
+        let mut second = Message::new("announcement", "Verschoben auf 23 Uhr");
+        second.timestamp_ms = Some(20);
+        app.messages.push(second);
+        assert!(app.pending_announcement().is_some());
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        assert_eq!(parse_retry_after(Some("12")), Duration::from_secs(12));
+        assert_eq!(parse_retry_after(None), Duration::from_secs(5));
+        assert_eq!(parse_retry_after(Some("bald")), Duration::from_secs(5));
+        assert_eq!(parse_retry_after(Some("Wed, 21 Oct 2015 07:28:00 GMT")), Duration::ZERO);
+        assert_eq!(
+            ChatError::RateLimited { retry_after: Duration::from_secs(3) }.to_string(),
+            "Rate-Limit erreicht, neuer Versuch in 3s"
+        );
+
+        let mut app = input_app("", 0);
+        assert!(app.rate_limit_status().is_none());
+        app.retry_at = Some(Instant::now() + Duration::from_secs(10));
+        app.send_queue.push_back(PendingSend {
+            message_ts: 0,
+            request: ChatRequest { message: "hi".to_string(), display_name: None, room: None, thread_id: None, to: None, model: None, client_id: None, session: None },
+            queued_at: Instant::now(),
+        });
+        assert_eq!(app.rate_limit_status().as_deref(), Some("⏳ Rate-Limit: 10s (1 wartend)"));
+    }
+
+    #[test]
+    fn regex_supports_classes_groups_and_repeats() {
+        let is_match = |pattern: &str, text: &str| Regex::new(pattern).unwrap().is_match(text);
+        assert!(is_match("fehler", "ein Fehler? nein, ein fehler"));
+        assert!(!is_match("^fehler", "ein fehler"));
+        assert!(is_match("(?i)^FEHLER$", "Zeile\nfehler\nmehr"));
+        assert!(is_match("v\\d+\\.\\d{1,2}", "Version v1.20 ist da"));
+        assert!(!is_match("v\\d+\\.\\d{3}", "v1.20"));
+        assert!(is_match("(rust|go)lang", "golang"));
+        assert!(is_match("[^a-z ]", "abc D"));
+        assert!(!is_match("[^a-z ]", "abc d"));
+        assert!(is_match("a.*b?c", "a--c"));
+        assert!(is_match("(a*)*b", "aaab"));
+        assert!(is_match("", "egal"));
+        assert!(Regex::new("(offen").is_err());
+        assert!(Regex::new("*").is_err());
+        assert!(Regex::new("[abc").is_err());
+    }
+
+    #[test]
+    fn filter_regex_runs_in_linear_time_and_is_cached() {
+        let started = Instant::now();
+        assert!(!Regex::new("(a+)+b").unwrap().is_match(&"a".repeat(10_000)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(Regex::new("(offen").unwrap_err(), "unclosed group");
+
+        let mut app = input_app("", 0);
+        app.messages = vec![Message::new("user", "aaaa"), Message::new("assistant", "aab"), Message::new("assistant", "aab")];
+        app.filter = Some(Filter::parse("(a+)+b").unwrap());
+        app.transcript_text();
+        app.transcript_text();
+        // Two distinct texts, each matched once
+        assert_eq!(app.filter.as_ref().unwrap().hits.borrow().len(), 2);
+    }
+
+    #[test]
+    fn filter_hides_non_matching_messages() {
+        let mut app = input_app("", 0);
+        app.messages = vec![
+            Message::new("system", "verbunden"),
+            Message::new("user", "Wie behebe ich Fehler 42?"),
+            Message::new("assistant", "Fehler 42 heißt: Kabel prüfen"),
+            Message::new("assistant", "Sonst noch was?"),
+        ];
+        app.filter = Some(Filter::parse("fehler \\d+").unwrap());
+        assert!(!app.filter_matches(&app.messages[1])); // case-sensitive
+        app.filter = Some(Filter::parse("(?i)fehler \\d+").unwrap());
+        let text = app.transcript_text();
+        assert!(text.starts_with("── gefiltert: 2 von 3 Nachrichten [Esc=aufheben] ──"));
+        assert!(!text.contains("Sonst") && !text.contains("verbunden"));
+
+        app.filter = Some(Filter::parse("@hank").unwrap());
+        assert!(app.transcript_text().contains("gefiltert: 2 von 3"));
+        app.filter = Some(Filter::parse("@hank Kabel|nie").unwrap());
+        assert!(app.transcript_text().contains("gefiltert: 1 von 3"));
+        assert_eq!(Filter::parse("@mara").unwrap().role, Some(RoleFilter::Sender("mara".to_string())));
+        assert!(Filter::parse("(").is_err());
+    }
+
+    #[test]
+    fn date_jump_scrolls_to_first_message_of_the_day() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        assert_eq!(parse_day("gestern", today), Ok(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap()));
+        assert_eq!(parse_day("-3", today), Ok(NaiveDate::from_ymd_opt(2024, 3, 7).unwrap()));
+        assert_eq!(parse_day("1.2.", today), Ok(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()));
+        assert_eq!(parse_day("24.12.2023", today), Ok(NaiveDate::from_ymd_opt(2023, 12, 24).unwrap()));
+        assert_eq!(parse_day("2023-12-24", today), parse_day("24.12.2023", today));
+        assert!(parse_day("32.1.", today).is_err());
+
+        let at = |d: u32, h: u32| Some(Local.with_ymd_and_hms(2024, 3, d, h, 0, 0).unwrap().timestamp_millis() as u64);
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        for (i, (day, hour)) in [(8, 9), (8, 18), (10, 7), (10, 8)].into_iter().enumerate() {
+            let mut msg = Message::new("user", format!("Nachricht {}", i));
+            msg.timestamp_ms = at(day, hour);
+            app.messages.push(msg);
+        }
+        assert_eq!(app.jump_to_date(NaiveDate::from_ymd_opt(2024, 3, 8).unwrap()), Ok(None));
+        assert_eq!(app.scroll_anchor, Some(0));
+        let hint = app.jump_to_date(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap()).unwrap();
+        assert_eq!(hint.as_deref(), Some("Keine Nachrichten am 09.03.2024, weiter am 10.03.2024"));
+        assert_eq!(app.scroll_anchor, Some(2));
+        assert!(app.jump_to_date(NaiveDate::from_ymd_opt(2024, 3, 11).unwrap()).is_err());
+
+        // The anchored message ends up in the first visible row
+        let (lines, starts) = app.chat_layout();
+        assert_eq!(starts.iter().map(|&(idx, _)| idx).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        let size = (40, 5);
+        let scroll = scroll_to_line(&lines, EastAsianWidth::Narrow, size, starts[2].1);
+        let total = wrapped_line_count(&lines, 40, EastAsianWidth::Narrow) + CHAT_PADDING_LINES;
+        let top = total - size.1 as u32 - scroll as u32;
+        assert_eq!(top, wrapped_line_count(&lines[..starts[2].1], 40, EastAsianWidth::Narrow));
+    }
+
+    #[test]
+    fn deterministic_mode_freezes_time_dependent_output() {
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        let mut reply = Message::new("assistant", "Hallo");
+        reply.response_ms = Some(4_200);
+        app.messages.push(reply);
+        app.clock = Some("%H:%M:%S".to_string());
+        app.alert_mode = AlertMode::Flash;
+        app.deterministic = true;
+
+        let first: String = app.chat_lines()[0].spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(first, "12:00:00 Hank: Hallo");
+        app.timestamps = TimestampMode::Full;
+        assert!(app.chat_lines()[0].spans[0].content.starts_with("01.01.2000 12:00:00"));
+        assert_eq!(app.clock_text(), None);
+        assert_eq!(app.since_last_reply(), None);
+        assert_eq!(app.status_line("{time} {elapsed} {latency}"), "12:00 0:00 -");
+        app.alert();
+        assert!(app.flash_until.is_none());
+    }
+
+    #[test]
+    fn json_answers_are_pretty_printed_and_folded() {
+        let segments = json_segments("Hier:\n```json\n{\"b\": 1, \"a\": [true, null]}\n```\n```rust\nfn x() {}\n```").unwrap();
+        assert_eq!(segments[0], JsonSegment::Text("Hier:".to_string()));
+        assert_eq!(segments[1], JsonSegment::Json(serde_json::json!({"b": 1, "a": [true, null]})));
+        assert_eq!(segments[2..], [
+            JsonSegment::Text("```rust".to_string()),
+            JsonSegment::Text("fn x() {}".to_string()),
+            JsonSegment::Text("```".to_string()),
+        ]);
+        assert_eq!(json_segments("Kein JSON {hier}"), None);
+        assert_eq!(json_segments(" [1, 2] ").unwrap(), vec![JsonSegment::Json(serde_json::json!([1, 2]))]);
+
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        let big: Vec<u32> = (0..20).collect();
+        let content = serde_json::json!({"name": "hank", "ok": true, "werte": big}).to_string();
+        app.messages.push(Message::new("assistant", content));
+        let text = |app: &App| -> Vec<String> {
+            app.chat_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect()
+        };
+        let folded = text(&app);
+        assert!(folded[0].ends_with("Hank: {"));
+        assert!(folded[1].ends_with("  \"name\": \"hank\","));
+        assert!(folded[3].ends_with("  \"werte\": [ … 20 Einträge [J] ]"));
+        assert!(folded[4].ends_with('}'));
+        let key = &app.chat_lines()[1].spans[2];
+        assert_eq!((key.content.as_ref(), key.style.fg), ("\"name\"", Some(app.theme.accent)));
+
+        app.messages[0].json_expanded = true;
+        let expanded = text(&app);
+        assert_eq!(expanded.len(), folded.len() + 21);
+        assert!(expanded[4].ends_with("    0,"));
+        // User messages stay as typed
+        app.messages[0].role = "user".to_string();
+        assert_eq!(text(&app).len(), 2);
+    }
+
+    #[test]
+    fn raw_view_shows_content_verbatim() {
+        assert_eq!(raw_text_lines("a\tb\r\n\u{200b}x  \n"), vec!["a\\tb\\r".to_string(), "\\u{200b}x··".to_string(), String::new()]);
+
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        app.messages.push(Message::new("assistant", "שלום abc\nzeile\t2\n"));
+        let text = |app: &App| -> Vec<String> {
+            app.chat_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect()
+        };
+        let rendered = text(&app);
+        assert!(!rendered[0].contains("[roh"));
+        app.messages[0].raw = true;
+        let raw = text(&app);
+        assert!(raw[0].ends_with("Hank: שלום abc [roh, R]"));
+        assert!(raw[1].ends_with("zeile\\t2"));
+        assert_eq!(raw[2].trim(), ""); // the final newline
+        app.messages[0].raw = false;
+        assert_eq!(text(&app), rendered);
+    }
+
+    #[test]
+    fn conversations_are_saved_listed_and_reopened() {
+        let dir = env::temp_dir().join(format!("hank-tui-sessions-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut first = vec![Message::new("user", "\nWie plane ich einen Umzug nach Hamburg mit zwei Katzen und einem Klavier?")];
+        first[0].timestamp_ms = Some(5_000);
+        first.push(Message::new("assistant", "Schritt für Schritt"));
+        first[1].timestamp_ms = Some(6_000);
+        ChatHistory::save_to(&dir.join("20260101-090000-000.json"), "http://test", &first, None).unwrap();
+        ChatHistory::save_to(&dir.join("20260102-090000-000.json"), "http://test", &[Message::new("system", "leer")], None).unwrap();
+        ChatHistory::save_to(&dir.join("fremd.json"), "http://other", &first, None).unwrap();
+
+        let sessions = ChatHistory::list_sessions_in(&dir, "http://test");
+        assert_eq!(sessions.len(), 2); // only this server's
+        let umzug = sessions.iter().find(|s| s.id.as_deref() == Some("20260101-090000-000")).unwrap();
+        assert_eq!(umzug.title, "Wie plane ich einen Umzug nach Hamburg m…");
+        assert_eq!(umzug.messages, 2);
+
+        // A new conversation starts empty, switching back brings the old one
+        let mut app = input_app("", 0);
+        app.last_timestamp = 9_000;
+        app.messages.push(Message::new("user", "alt"));
+        app.new_session();
+        assert!(app.session.is_some());
+        assert!(app.messages.iter().all(|m| m.role == "system"));
+        app.open_session(umzug.id.clone(), Some(&dir.join("20260101-090000-000.json")));
+        assert_eq!(app.session.as_deref(), Some("20260101-090000-000"));
+        assert_eq!(app.messages[1].content, "Schritt für Schritt");
+        assert!(app.messages.last().unwrap().content.contains("Umzug"));
+        assert_eq!(app.last_timestamp, 9_000); // no refetch of the server's messages
+        let pending = app.push_own_message("weiter".to_string(), None);
+        assert_eq!(pending.request.session.as_deref(), Some("20260101-090000-000"));
+
+        let main = ChatHistory::load_from(&dir.join("fremd.json")).unwrap().session_info(None);
+        assert_eq!(main.title, "Hauptunterhaltung");
+        app.session_picker = Some(SessionPicker { sessions: std::iter::once(main).chain(sessions).collect(), index: 0 });
+        app.session_picker_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        app.session_picker_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        app.session_picker_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(app.session_picker.as_ref().unwrap().index, 2);
+        // The main conversation, then the most recently saved one
+        let lines = app.session_picker_lines();
+        assert!(lines[0].to_string().starts_with("  Hauptunterhaltung"));
+        assert!(lines[1].to_string().starts_with("  (ohne Titel)"));
+        assert!(lines[2].to_string().starts_with("▶ Wie plane"));
+        app.session_picker_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(app.session_picker.is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn global_search_finds_messages_in_saved_histories() {
+        let dir = env::temp_dir().join(format!("hank-tui-global-{}", std::process::id()));
+        let message = |role: &str, content: &str, ts: u64| {
+            let mut msg = Message::new(role, content);
+            msg.timestamp_ms = Some(ts);
+            msg
+        };
+        let main = vec![message("user", "Meine Katze frisst kein Trockenfutter", 1_000), message("assistant", "Das ist normal.", 2_000)];
+        let session = vec![
+            message("user", "Urlaub planen", 3_000),
+            message("assistant", &format!("{}Katzen dürfen in die Ferienwohnung{}", "x".repeat(40), " y".repeat(30)), 4_000),
+        ];
+        ChatHistory::save_to(&dir.join("history.json"), "http://test", &main, None).unwrap();
+        ChatHistory::save_to(&dir.join("urlaub.json"), "http://test", &session, None).unwrap();
+        ChatHistory::save_to(&dir.join("fremd.json"), "http://other", &main, None).unwrap();
+        let scan = JsonScan {
+            server_url: "http://test".to_string(),
+            files: vec![
+                (HistorySource::Main, dir.join("history.json")),
+                (HistorySource::Session("urlaub".to_string()), dir.join("urlaub.json")),
+                (HistorySource::Room("fremd".to_string()), dir.join("fremd.json")),
+            ],
+        };
+
+        let mut app = input_app("", 0);
+        app.global_search = Some(GlobalSearch { query: String::new(), hits: Vec::new(), index: 0 });
+        for c in "katze".chars() {
+            assert_eq!(app.global_search_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE), &scan), None);
+        }
+        let hits = app.global_search.as_ref().unwrap().hits.clone();
+        // Newest first, only this server's histories
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].title, "Urlaub planen");
+        assert_eq!(hits[0].snippet, format!("…{}Katzen dürfen in die Ferienwohnung…", "x".repeat(30)));
+        assert_eq!((hits[1].source.clone(), hits[1].snippet.as_str()), (HistorySource::Main, "Meine Katze frisst kein Trockenfutter"));
+        assert!(scan.search("KATZE", 10).is_empty()); // capitals match exactly
+
+        app.global_search_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE), &scan);
+        let hit = app.global_search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &scan).unwrap();
+        assert!(app.global_search.is_none());
+        app.messages = ChatHistory::load_from(&dir.join("history.json")).unwrap().messages;
+        app.reveal_message(hit.key.as_deref());
+        assert_eq!((app.selected, app.scroll_anchor), (Some(0), Some(0)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn session_sidebar_lists_unread_counts_and_previews() {
+        let mut messages = vec![Message::new("user", "Rezept für Apfelstrudel?"), Message::new("assistant", "Erst den Teig\nziehen …")];
+        messages[0].timestamp_ms = Some(1_000);
+        messages[1].timestamp_ms = Some(2_000);
+        let history = ChatHistory {
+            server_url: "http://test".to_string(),
+            messages,
+            saved_at: "2026-01-01T09:00:00+01:00".to_string(),
+            last_read_ms: Some(1_000),
+            view_anchor: None,
+            title: Some("Rezept für Apfelstrudel?".to_string()),
+            chunks: 0,
+            archived_ms: None,
+        };
+        let strudel = history.session_info(Some("strudel".to_string()));
+        assert_eq!((strudel.unread, strudel.preview.as_str()), (1, "Erst den Teig"));
+
+        let mut app = input_app("", 0);
+        app.messages.push(Message::new("user", "Und was ist mit Zimt?"));
+        let main = SessionInfo { id: None, title: "Hauptunterhaltung".to_string(), saved_at: String::new(), messages: 0, unread: 4, preview: "alt".to_string() };
+        app.session_sidebar = Some(SessionPicker { sessions: vec![main, strudel], index: 1 });
+        app.focus = Focus::Sessions;
+        let lines: Vec<String> = app.session_sidebar_lines(20).iter().map(|l| l.to_string()).collect();
+        // The open conversation has no unread count and a live preview
+        assert_eq!(lines[0], "  Hauptunterhaltung");
+        assert_eq!(lines[1], "  Und was ist mit Z…");
+        assert_eq!(lines[2], "▶ Rezept für Ap… (1)");
+        assert_eq!(lines[3], "  Erst den Teig");
+
+        // The sidebar takes part in the Tab cycle
+        app.focus = Focus::Chat;
+        app.toggle_focus();
+        assert!(app.focus == Focus::Sessions);
+        app.toggle_focus();
+        assert!(app.focus == Focus::Input);
+    }
+
+    #[test]
+    fn long_histories_move_into_chunks_loaded_when_scrolling_up() {
+        let dir = env::temp_dir().join(format!("hank-tui-chunks-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("history.json");
+        let messages: Vec<Message> = (0..510u64)
+            .map(|i| {
+                let mut msg = Message::new(if i % 2 == 0 { "user" } else { "assistant" }, format!("Nachricht {}", i));
+                msg.timestamp_ms = Some(1_000 + i);
+                msg
+            })
+            .collect();
+        // Nothing is cut off: three full chunks, the rest in the history file
+        ChatHistory::save_to(&path, "http://test", &messages[..450], None).unwrap();
+        let history = ChatHistory::load_from(&path).unwrap();
+        assert_eq!((history.chunks, history.messages.len()), (3, 150));
+        assert_eq!(history.title.as_deref(), Some("Nachricht 0"));
+        // Chunks are only appended; the loaded head plus new messages go on from there
+        let mut head = history.messages.clone();
+        head.extend_from_slice(&messages[450..]);
+        ChatHistory::save_to(&path, "http://test", &head, None).unwrap();
+        let history = ChatHistory::load_from(&path).unwrap();
+        assert_eq!((history.chunks, history.messages.len()), (4, 110));
+        assert_eq!(history.title.as_deref(), Some("Nachricht 0"));
+        assert_eq!(ChatHistory::load_chunk(&path, 3).unwrap()[0].content, "Nachricht 300");
+
+        let mut app = input_app("", 0);
+        app.messages = history.messages;
+        app.older_chunks = history.chunks;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(50, 14)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        assert!(!app.wants_older);
+        app.auto_scroll = false;
+        app.scroll = u16::MAX;
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        assert!(app.wants_older);
+        let top = rendered_rows(&terminal).into_iter().find(|row| row.contains("Nachricht")).unwrap();
+        assert!(top.contains("Nachricht 400"), "{}", top);
+
+        // The older chunk goes in front; the view stays on the same message
+        assert!(app.load_older_chunk_from(&path));
+        assert_eq!((app.older_chunks, app.messages.len(), app.messages[0].content.as_str()), (3, 210, "Nachricht 300"));
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let top = rendered_rows(&terminal).into_iter().find(|row| row.contains("Nachricht")).unwrap();
+        assert!(top.contains("Nachricht 400"), "{}", top);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn listing_conversations_writes_nothing() {
+        let id = format!("19990101-000000-{}", std::process::id());
+        let mut app = input_app("", 0);
+        app.history_enabled = true;
+        app.server_url = format!("http://listing-{}", std::process::id());
+        app.session = Some(id.clone());
+        app.messages = vec![Message::new("user", "Wie backe ich Brot?"), Message::new("assistant", "Mit Sauerteig.")];
+
+        let list = app.conversations();
+        let open = list.iter().find(|s| s.id.as_deref() == Some(id.as_str())).unwrap();
+        assert_eq!((open.title.as_str(), open.preview.as_str(), open.messages, open.unread), ("Wie backe ich Brot?", "Mit Sauerteig.", 2, 0));
+        assert_eq!(list[0].id, None);
+        assert!(!ChatHistory::session_path(&id).unwrap().exists());
+    }
+
+    #[test]
+    fn search_covers_chunks_and_clearing_drops_them() {
+        let dir = env::temp_dir().join(format!("hank-tui-chunk-search-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("history.json");
+        let messages: Vec<Message> = (0..250u64)
+            .map(|i| {
+                let text = if i == 10 { "Nadel im Heuhaufen".to_string() } else { format!("Nachricht {}", i) };
+                Message { timestamp_ms: Some(1_000 + i), ..Message::new("user", text) }
+            })
+            .collect();
+        ChatHistory::save_to(&path, "http://test", &messages, None).unwrap();
+        let history = ChatHistory::load_from(&path).unwrap();
+        assert!(history.chunks > 0 && !history.messages.iter().any(|m| m.content.contains("Nadel")));
+
+        let scan = JsonScan { server_url: "http://test".to_string(), files: vec![(HistorySource::Main, path.clone())] };
+        let hits = scan.search("nadel", 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].timestamp_ms, Some(1_010));
+
+        // Ctrl+L: the chunks go together with the loaded messages
+        let mut app = input_app("", 0);
+        app.messages = history.messages;
+        app.older_chunks = history.chunks;
+        app.clear_conversation_at(Some(&path)).unwrap();
+        assert_eq!((app.messages.len(), app.older_chunks), (0, 0));
+        assert!(!path.exists() && !path.with_extension("chunks").exists());
+        assert!(scan.search("nadel", 10).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn clearing_a_session_leaves_the_main_history_untouched() {
+        let dir = env::temp_dir().join(format!("hank-tui-delete-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let main = dir.join("history.json");
+        let session = dir.join("sessions").join("20240501-120000-000.json");
+        let messages: Vec<Message> = (0..250u64).map(|i| Message::new("user", format!("Nachricht {}", i))).collect();
+        ChatHistory::save_to(&main, "http://test", &messages, None).unwrap();
+        ChatHistory::save_to(&session, "http://test", &messages, None).unwrap();
+        assert!(ChatHistory::chunk_path(&session, 0).exists());
+
+        ChatHistory::delete(&session).unwrap();
+        assert!(!session.exists() && !session.with_extension("chunks").exists());
+        assert_eq!(ChatHistory::load_from(&main).unwrap().chunks, 1);
+        assert!(ChatHistory::chunk_path(&main, 0).exists());
+
+        // Ctrl+Shift+D deletes the file of the open conversation or room
+        let mut app = input_app("", 0);
+        app.session = Some("20240501-120000-000".to_string());
+        assert_eq!(app.history_path_for(None), ChatHistory::session_path("20240501-120000-000"));
+        assert_eq!(app.history_path_for(Some("ops")), ChatHistory::room_history_path("ops"));
+        app.session = None;
+        assert_eq!(app.history_path_for(None), ChatHistory::history_path());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn scroll_position_survives_reopening_the_session() {
+        let dir = std::env::temp_dir().join(format!("hank-tui-view-{}", std::process::id()));
+        let path = dir.join("history.json");
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        for i in 0..30u64 {
+            let mut msg = Message::new("user", format!("Nachricht {}", i));
+            msg.timestamp_ms = Some(1_000 + i);
+            if i == 12 {
+                msg.id = Some("m12".to_string());
+            }
+            app.messages.push(msg);
+        }
+        app.chat_geometry = Some((40, 10));
+        assert_eq!(app.view_anchor(), None); // following the bottom
+
+        let (lines, starts) = app.chat_layout();
+        app.scroll = scroll_to_line(&lines, app.east_asian_width, (40, 10), starts[12].1);
+        app.auto_scroll = false;
+        assert_eq!(app.view_anchor().as_deref(), Some("m12"));
+        ChatHistory::save_to(&path, &app.server_url, &app.messages, app.view_anchor()).unwrap();
+
+        let history = ChatHistory::load_from(&path).unwrap();
+        let mut reopened = input_app("", 0);
+        reopened.messages = history.messages;
+        reopened.restore_view(history.view_anchor.as_deref());
+        assert_eq!(reopened.scroll_anchor, Some(12));
+        assert!(!reopened.auto_scroll);
+
+        // Messages without a server id are keyed by their timestamp
+        reopened.scroll_anchor = None;
+        reopened.restore_view(Some(&provisional_id(1_020)));
+        assert_eq!(reopened.scroll_anchor, Some(20));
+        reopened.restore_view(Some("weg"));
+        assert!(reopened.auto_scroll);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn input_height_policy_from_config() {
+        let parse = |toml: &str| toml::from_str::<Config>(&format!("host = \"h\"\nport = 1\n{}", toml)).map(|c| c.input_height);
+        assert_eq!(parse("").unwrap(), InputHeight::Fixed(3));
+        assert_eq!(parse("input_height = 6").unwrap(), InputHeight::Fixed(6));
+        assert_eq!(parse("input_height = \"auto:8\"").unwrap(), InputHeight::Auto(8));
+        assert_eq!(parse("input_height = \"25%\"").unwrap(), InputHeight::Percent(25));
+        assert!(parse("input_height = \"viel\"").is_err());
+        // --input-height takes the same forms
+        assert_eq!("auto".parse::<InputHeight>(), Ok(InputHeight::Auto(10)));
+        assert_eq!("7".parse::<InputHeight>(), Ok(InputHeight::Fixed(7)));
+        assert!("0".parse::<InputHeight>().is_err());
+
+        assert_eq!(InputHeight::Fixed(3).pane_height(12, 40), 5);
+        assert_eq!(InputHeight::Auto(8).pane_height(1, 40), 3);
+        assert_eq!(InputHeight::Auto(8).pane_height(5, 40), 7);
+        assert_eq!(InputHeight::Auto(8).pane_height(30, 40), 10);
+        assert_eq!(InputHeight::Percent(25).pane_height(1, 40), 10);
+        // The chat and status bar keep their rows on tiny screens
+        assert_eq!(InputHeight::Fixed(20).pane_height(1, 12), 8);
+
+        let mut config: Config = toml::from_str("host = \"h\"\nport = 1\ninput_height = \"auto:4\"").unwrap();
+        config.input_height = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap().input_height;
+        assert_eq!(config.input_height, InputHeight::Auto(4));
+    }
+
+    #[test]
+    fn copy_mode_quotes_selection_into_input() {
+        let mut app = input_app("Zu", 2);
+        app.copy_mode = Some(CopyMode::new("Hank: Erste Zeile\n\nZweite Zeile"));
+        let press = |app: &mut App, c| app.copy_mode_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        press(&mut app, 'k');
+        press(&mut app, 'k');
+        press(&mut app, 'V');
+        press(&mut app, 'j');
+        press(&mut app, 'j');
+        assert_eq!(press(&mut app, '>'), None);
+        assert!(app.copy_mode.is_none());
+        assert!(app.focus == Focus::Input);
+        assert_eq!(app.input, "Zu\n> Hank: Erste Zeile\n>\n> Zweite Zeile\n");
+        assert_eq!(app.cursor_pos, app.input.chars().count());
+    }
+
+    #[test]
+    fn copy_mode_moves_selects_and_yanks() {
+        let press = |app: &mut App, c: char| app.copy_mode_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        let mut app = input_app("", 0);
+        app.copy_mode = Some(CopyMode::new("Du: hallo welt\n\nHank: foo_bar, baz\n"));
+        // Starts on the last line with text
+        assert_eq!(app.copy_mode.as_ref().unwrap().cursor_col(), 0);
+        press(&mut app, 'w');
+        press(&mut app, 'w');
+        assert_eq!(app.copy_mode.as_ref().unwrap().cursor_col(), 6);
+        press(&mut app, 'v');
+        press(&mut app, 'e');
+        assert_eq!(press(&mut app, 'y').as_deref(), Some("foo_bar"));
+        assert!(app.copy_mode.is_none());
+
+        // Across lines, and line-wise
+        app.copy_mode = Some(CopyMode::new("Du: hallo welt\n\nHank: foo_bar, baz"));
+        for c in ['k', 'k', 'l', 'l', 'l', 'l', 'v', 'j', 'j', 'e'] {
+            press(&mut app, c);
+        }
+        assert_eq!(press(&mut app, 'y').as_deref(), Some("hallo welt\n\nHank"));
+        app.copy_mode = Some(CopyMode::new("a\nb\nc"));
+        for c in ['k', 'V', 'k'] {
+            press(&mut app, c);
+        }
+        let rendered = app.copy_mode.as_ref().unwrap().lines(&Theme::default());
+        assert_eq!(rendered.len(), 3);
+        assert_eq!(press(&mut app, 'y').as_deref(), Some("a\nb"));
+        // Without selection the cursor line is copied; Esc copies nothing
+        app.copy_mode = Some(CopyMode::new("eins\nzwei"));
+        assert_eq!(press(&mut app, 'y').as_deref(), Some("zwei"));
+        app.copy_mode = Some(CopyMode::new("eins"));
+        assert_eq!(app.copy_mode_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)), None);
+        assert!(app.copy_mode.is_none());
+    }
+
+    #[test]
+    fn pending_panel_lists_and_cancels_queued_requests() {
+        let mut app = input_app("", 0);
+        assert!(!app.pending_panel_visible());
+        let mut own = Message::new("user", "eine sehr lange Frage, die nicht in das Panel passt");
+        own.timestamp_ms = Some(7);
+        own.delivery = Some(Delivery::Sending);
+        app.messages = vec![own];
+        let pending = PendingSend {
+            message_ts: 7,
+            request: ChatRequest {
+                message: app.messages[0].content.clone(),
+                display_name: None,
+                room: None,
+                thread_id: None,
+                to: None,
+                model: None,
+                client_id: None,
+                session: None,
+            },
+            queued_at: Instant::now(),
+        };
+        assert_eq!(pending.label(), "eine sehr lange Frage, die nic…");
+        app.retry_at = Some(Instant::now() + Duration::from_secs(5));
+        app.send_queue.push_back(pending.clone());
+        assert!(app.pending_panel_visible());
+        let text: String = app.pending_lines()[0].spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, "⏸ eine sehr lange Frage, die nic… 0:00");
+
+        assert!(app.cancel_queued());
+        assert!(!app.cancel_queued());
+        assert!(app.retry_at.is_none() && !app.pending_panel_visible());
+        assert_eq!(app.messages[0].delivery, Some(Delivery::Failed));
+
+        // Only a slow answer shows the panel
+        app.in_flight = Some(PendingSend { queued_at: Instant::now() - BUSY_AFTER, ..pending });
+        assert!(app.pending_panel_visible());
+    }
+
+    #[test]
+    fn local_echo_reconciles_by_provisional_id() {
+        let mut messages = Vec::new();
+        for (ts, text) in [(100, "ok"), (200, "ok")] {
+            let mut own = Message::new("user", text);
+            own.timestamp_ms = Some(ts);
+            own.local_id = Some(provisional_id(ts));
+            own.delivery = Some(Delivery::Sent);
+            messages.push(own);
+        }
+        // The server echoes the second message first, with normalized text
+        let mut last = 0;
+        let echo = ServerMessage {
+            role: "user".to_string(),
+            content: "ok ".to_string(),
+            timestamp: 250,
+            id: Some("s2".to_string()),
+            client_id: Some(provisional_id(200)),
+            ..Default::default()
+        };
+        assert_eq!(merge_server_messages(&mut messages, &mut last, vec![echo]), 0);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].id, None);
+        assert_eq!(
+            (messages[1].id.as_deref(), messages[1].timestamp_ms, messages[1].content.as_str()),
+            (Some("s2"), Some(250), "ok ")
+        );
+        assert_eq!(messages[1].delivery, Some(Delivery::Acknowledged));
+
+        // Delivery updates still find the message after its timestamp changed
+        let mut app = input_app("", 0);
+        app.messages = messages;
+        assert!(app.set_delivery(200, Delivery::Failed).is_some());
+        assert_eq!(app.messages[1].delivery, Some(Delivery::Failed));
+    }
+
+    #[test]
+    fn failed_messages_show_state_and_can_be_retried() {
+        let mut app = input_app("", 0);
+        let mut own = Message::new("user", "hallo");
+        own.timestamp_ms = Some(42);
+        own.delivery = Some(Delivery::Sending);
+        own.to = Some("mara".to_string());
+        app.messages = vec![own];
+        assert!(app.retry_failed().is_none());
+
+        assert!(app.set_delivery(42, Delivery::Failed).is_some());
+        let glyphs: String = app.chat_lines()[0].spans.iter().map(|s| s.content.as_ref()).collect();
+        assert!(glyphs.ends_with("✗ nicht zugestellt [r=erneut]"));
+
+        let pending = app.retry_failed().unwrap();
+        assert_eq!(pending.message_ts, 42);
+        assert_eq!(pending.request.message, "hallo");
+        assert_eq!(pending.request.to.as_deref(), Some("mara"));
+        assert_eq!(app.messages[0].delivery, Some(Delivery::Sending));
+
+        // The server echo with our id acknowledges the message
+        app.set_delivery(42, Delivery::Sent);
+        app.messages[0].id = Some("m7".to_string());
+        let mut last = 0;
+        let echo = ServerMessage { role: "user".to_string(), content: "hallo".to_string(), timestamp: 43, id: Some("m7".to_string()), ..Default::default() };
+        merge_server_messages(&mut app.messages, &mut last, vec![echo]);
+        assert_eq!(app.messages[0].delivery, Some(Delivery::Acknowledged));
+    }
+
+    #[test]
+    fn merge_orders_by_timestamp_and_absorbs_local_echo() {
+        let server = |role: &str, content: &str, ts: u64| ServerMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: ts,
+            ..Default::default()
+        };
+        let mut messages = Vec::new();
+        let mut last = 0;
+        merge_server_messages(&mut messages, &mut last, vec![server("assistant", "c", 30), server("user", "a", 10)]);
+        merge_server_messages(&mut messages, &mut last, vec![server("assistant", "b", 20), server("user", "b2", 20)]);
+        let order: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(order, vec!["a", "b", "b2", "c"]);
+        assert_eq!(last, 30);
+
+        // Our optimistic copy is replaced by the server version, not duplicated
+        let mut local = Message::new("user", "frage");
+        local.timestamp_ms = Some(99);
+        local.delivery = Some(Delivery::Sent);
+        messages.push(local);
+        let added = merge_server_messages(&mut messages, &mut last, vec![server("user", "frage", 25)]);
+        assert_eq!(added, 0);
+        let order: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(order, vec!["a", "b", "b2", "frage", "c"]);
+        assert_eq!(messages[3].timestamp_ms, Some(25));
+        assert_eq!(messages[3].delivery, Some(Delivery::Acknowledged));
+    }
+
+    #[test]
+    fn ids_identify_messages_with_equal_timestamps() {
+        let server = |id: Option<&str>, content: &str| ServerMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: 100,
+            id: id.map(str::to_string),
+            ..Default::default()
+        };
+        let mut messages = Vec::new();
+        let mut last = 0;
+        // Two people writing in the same millisecond are two messages
+        let added = merge_server_messages(&mut messages, &mut last, vec![server(Some("a"), "eins"), server(Some("b"), "zwei")]);
+        assert_eq!(added, 2);
+        // Polling the same window again adds nothing
+        assert_eq!(merge_server_messages(&mut messages, &mut last, vec![server(Some("b"), "zwei")]), 0);
+        assert_eq!(messages.len(), 2);
+
+        // Without ids the old role+timestamp rule still deduplicates
+        let mut legacy = Vec::new();
+        merge_server_messages(&mut legacy, &mut last, vec![server(None, "eins"), server(None, "zwei")]);
+        assert_eq!(legacy.len(), 1);
+    }
+
+    #[test]
+    fn moderation_actions_describe_themselves() {
+        let action = ModAction::Mute { user: "troll".to_string(), minutes: Some(5) };
+        assert_eq!(action.name(), "mute");
+        assert_eq!(action.user(), "troll");
+        assert_eq!(action.audit("Glan"), "Moderation: troll ist für 5 Minuten stumm (von Glan)");
+        assert_eq!(
+            serde_json::to_value(&action).unwrap(),
+            serde_json::json!({ "action": "mute", "user": "troll", "minutes": 5 })
+        );
+        let permissions: Permissions = serde_json::from_str(r#"{"name":"glan","admin":true}"#).unwrap();
+        assert!(permissions.admin);
+    }
+
+    #[test]
+    fn sender_colors_are_stable_and_overridable() {
+        assert_eq!(sender_color("mara"), sender_color("mara"));
+        let names = ["mara", "olaf", "jens", "ute", "kim", "ali", "bea", "tom"];
+        let distinct: std::collections::HashSet<String> = names.iter().map(|n| format!("{:?}", sender_color(n))).collect();
+        assert!(distinct.len() > 3);
+        assert!(names.iter().all(|n| ![Color::Cyan, Color::Green, Color::Red, Color::Yellow].contains(&sender_color(n))));
+
+        let mut app = input_app("", 0);
+        app.user_colors.insert("mara".to_string(), Color::Rgb(255, 136, 0));
+        let mut msg = Message::new("user", "hi");
+        msg.sender = Some("mara".to_string());
+        app.messages = vec![msg];
+        assert_eq!(app.chat_lines()[0].spans[2].style.fg, Some(Color::Rgb(255, 136, 0)));
+    }
+
+    #[test]
+    fn role_names_and_colors_come_from_display_settings() {
+        let config: Config = toml::from_str(
+            "host = \"h\"\nport = 1\n[display]\nuser_name = \"me\"\nassistant_name = \"hank\"\nuser_color = \"#8be9fd\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.display.user_name.as_deref(), Some("me"));
+        assert!(config.display.assistant_color.is_none());
+        assert!(!toml::to_string(&Config::default()).unwrap().contains("[display]"));
+
+        let mut app = input_app("", 0);
+        app.display_name = config.display.user_name.clone();
+        app.assistant_name = "hank".to_string();
+        app.own_color = Some(Color::Rgb(0x8b, 0xe9, 0xfd));
+        app.assistant_color = Some(Color::Magenta);
+        app.messages = vec![Message::new("user", "hallo"), Message::new("assistant", "servus")];
+        let lines = app.chat_lines();
+        assert_eq!((lines[0].spans[2].content.as_ref(), lines[0].spans[2].style.fg), ("me: ", Some(Color::Rgb(0x8b, 0xe9, 0xfd))));
+        assert_eq!((lines[2].spans[2].content.as_ref(), lines[2].spans[2].style.fg), ("hank: ", Some(Color::Magenta)));
+        assert_eq!(app.last_reply_for_copy(false).unwrap().0, "servus");
+        app.messages.truncate(1);
+        assert_eq!(app.last_reply_for_copy(false).unwrap_err(), "Noch keine Antwort von hank");
+    }
+
+    /// Apply the next background result, as the main loop would
+    async fn apply_next(app: &mut App, inbox: &mut tokio::sync::mpsc::UnboundedReceiver<AppEvent>) {
+        let Some(AppEvent::Fetched { server_url, fetched }) = inbox.recv().await else {
+            panic!("expected a fetched result");
+        };
+        apply_fetched(app, &server_url, *fetched);
+    }
+
+    /// Serves a fixed set of assistant messages, filtered by `since`
+    struct FixedBackend(Vec<u64>);
+
+    impl ChatBackend for FixedBackend {
+        fn server_url(&self) -> &str {
+            "http://fixed"
+        }
+
+        fn fetch_messages<'a>(
+            &'a self,
+            since: u64,
+            _room: Option<&'a str>,
+            _timeout: Duration,
+        ) -> BoxFuture<'a, Result<Vec<ServerMessage>, String>> {
+            let messages = self
+                .0
+                .iter()
+                .filter(|&&ts| ts > since)
+                .map(|&ts| ServerMessage { role: "assistant".to_string(), content: ts.to_string(), timestamp: ts, ..Default::default() })
+                .collect();
+            Box::pin(async move { Ok(messages) })
+        }
+
+        fn send_chat(&self, _request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+            Box::pin(async { Err("offline".into()) })
+        }
+
+        fn clear_messages<'a>(&'a self, _room: Option<&'a str>) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[test]
+    fn oversized_messages_split_into_numbered_parts() {
+        let text = "Erster Absatz mit einigen Wörtern.\nZweiter Absatz, ebenfalls nicht ganz kurz.\nDritter.";
+        let parts = split_message(text, 40);
+        assert!(parts.iter().all(|p| p.chars().count() <= 40), "{:?}", parts);
+        assert_eq!(parts[0], "[1/3] Erster Absatz mit einigen Wörtern.");
+        assert!(parts[2].starts_with("[3/3] "));
+        let joined: String = parts.iter().map(|p| p[6..].to_string()).collect::<Vec<_>>().join(" ");
+        assert_eq!(joined.split_whitespace().collect::<Vec<_>>(), text.split_whitespace().collect::<Vec<_>>());
+        // No spaces: hard cuts, and ten or more parts widen the label
+        let parts = split_message(&"x".repeat(100), 12);
+        assert_eq!(parts.len(), 25);
+        assert!(parts.iter().all(|p| p.chars().count() <= 12));
+        assert_eq!(parts[24], "[25/25] xxxx");
+
+        let mut app = input_app("lang", 4);
+        app.messages.clear();
+        app.oversize = Some(Oversize { text: text.to_string(), to: None, limit: 40 });
+        let first: String = app.oversize_lines()[0].spans.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(first, "Die Nachricht hat 86 Zeichen, der Server nimmt höchstens 40.");
+        assert_eq!(app.accept_split(), 3);
+        assert_eq!(app.send_queue.len(), 3);
+        assert!(app.input.is_empty() && app.oversize.is_none());
+        let stamps: Vec<u64> = app.send_queue.iter().map(|p| p.message_ts).collect();
+        assert!(stamps.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(app.messages.len(), 3);
+
+        // Going back to editing restores a cleared input
+        app.input.clear();
+        app.oversize = Some(Oversize { text: "zurück".to_string(), to: None, limit: 3 });
+        app.reject_oversize();
+        assert_eq!(app.input, "zurück");
+    }
+
+    #[test]
+    fn copies_last_reply_or_its_code_blocks() {
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        assert!(app.last_reply_for_copy(false).is_err());
+        app.messages.push(Message::new("assistant", "Alt"));
+        app.messages.push(Message::new("assistant", "So:\n```rust\nfn main() {}\n```\nund\n```\nls -l\n```"));
+        app.messages.push(Message::new("user", "danke"));
+        let (text, note) = app.last_reply_for_copy(false).unwrap();
+        assert!(text.starts_with("So:") && note.starts_with("Antwort kopiert"));
+        let (code, note) = app.last_reply_for_copy(true).unwrap();
+        assert_eq!(code, "fn main() {}\n\nls -l");
+        assert_eq!(note, "2 Codeblöcke kopiert");
+        app.messages.push(Message::new("assistant", "ohne Code"));
+        assert!(app.last_reply_for_copy(true).is_err());
+
+        app.notify("Antwort kopiert");
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 10)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        assert!(rendered_rows(&terminal).last().unwrap().starts_with(" ✓ Antwort kopiert"));
+    }
+
+    #[test]
+    fn heartbeat_turns_stale_without_successful_polls() {
+        let mut app = input_app("", 0);
+        app.last_sync = Some(Instant::now() - Duration::from_secs(3));
+        assert_eq!(app.heartbeat(), ("♥ 0:03".to_string(), false));
+
+        app.last_sync = Some(Instant::now() - Duration::from_secs(42));
+        app.sync_error = Some("Connection error: error sending request for url (http://localhost:8080/messages)".to_string());
+        let (text, stale) = app.heartbeat();
+        assert!(stale);
+        assert_eq!(text, "♥ 0:42 (Connection error: error sending request…)");
+        assert_eq!(app.status_line("{sync}"), text);
+
+        // Never reached the server: stale once the session is old enough
+        app.last_sync = None;
+        app.started = Instant::now() - SYNC_STALE_AFTER;
+        assert!(app.heartbeat().1);
+        assert!(app.heartbeat().0.starts_with("♥ - ("));
+    }
+
+    #[test]
+    fn server_stats_panel_lists_known_and_extra_figures() {
+        let stats: ServerStats =
+            serde_json::from_str(r#"{"queue_depth": 2, "model": "llama3", "load": 0.75, "gpu": "A100"}"#).unwrap();
+        assert_eq!(stats.other.get("gpu"), Some(&serde_json::json!("A100")));
+        let mut app = input_app("", 0);
+        app.deterministic = true;
+        app.stats = Some((Ok(stats), app.now()));
+        let text: Vec<String> =
+            app.stats_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect();
+        assert_eq!(text[0], "Warteschlange 2 (2 vor dir)");
+        assert_eq!(text[1], "Modell        llama3");
+        assert_eq!(text[2], format!("Last          {}{} 75%", "█".repeat(15), "░".repeat(5)));
+        assert_eq!(text[3], "gpu           A100");
+        assert_eq!(text[5], "Stand 12:00:00  [r=neu laden, sonst schließen]");
+
+        app.stats = Some((Err("Server liefert keine Statistiken".to_string()), app.now()));
+        assert_eq!(app.stats_lines()[0].spans[0].style.fg, Some(app.theme.error));
+    }
+
+    #[tokio::test]
+    async fn debug_http_logs_exchanges_with_secrets_redacted() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let _ = socket.read(&mut request).await;
+            let body = r#"["gpt", "hank"]"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let dir = std::env::temp_dir().join(format!("hank-tui-http-{}", std::process::id()));
+        let path = dir.join("http.log");
+        let trace = Arc::new(HttpTrace::open(Some(&path)));
+        let backend = HttpBackend::new(server_url.clone()).with_trace(Some(trace.clone()));
+        // The body read for the log still reaches the caller
+        assert_eq!(backend.fetch_models().await, Ok(vec!["gpt".to_string(), "hank".to_string()]));
+
+        let recent = trace.recent(8);
+        assert_eq!(recent.len(), 1);
+        assert!(recent[0].starts_with(&format!("GET {}/models → 200 OK in ", server_url)), "{}", recent[0]);
+        assert!(recent[0].ends_with("ms, 15 Bytes"));
+        let log = fs::read_to_string(&path).unwrap();
+        assert!(log.contains("    < [\"gpt\",\"hank\"]"));
+
+        assert_eq!(trace_body(br#"{"user":"mara","auth":{"api_key":"k","token":"t"}}"#), r#"{"user":"mara","auth":{"api_key":"***","token":"***"}}"#);
+        assert!(trace_body("x".repeat(3000).as_bytes()).ends_with("… (3000 Bytes)"));
+        let url = reqwest::Url::parse("http://h/messages?since=1&access_token=geheim").unwrap();
+        assert_eq!(trace_url(&url), "http://h/messages?since=1&access_token=***");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn token_goes_into_every_request() {
+        let header = |token: Option<&str>, header: Option<&str>| {
+            let backend = HttpBackend::new("http://h".to_string())
+                .with_auth(token.map(String::from), header.map(String::from));
+            let request = backend.request(reqwest::Method::GET, "/messages").build().unwrap();
+            assert_eq!(request.url().as_str(), "http://h/messages");
+            let headers = request.headers();
+            headers.iter().next().map(|(name, value)| (name.to_string(), value.to_str().unwrap().to_string()))
+        };
+        assert_eq!(header(None, None), None);
+        assert_eq!(header(Some(" "), None), None);
+        assert_eq!(header(Some("geheim"), None), Some(("authorization".to_string(), "Bearer geheim".to_string())));
+        assert_eq!(header(Some("Basic bWFyYQ=="), None), Some(("authorization".to_string(), "Basic bWFyYQ==".to_string())));
+        assert_eq!(header(Some("geheim"), Some("X-API-Key")), Some(("x-api-key".to_string(), "geheim".to_string())));
+        assert!(is_secret("X-API-Key"));
+
+        let config: Config = toml::from_str("host = \"h\"\nport = 1\ntoken = \"t\"\nauth_header = \"X-API-Key\"").unwrap();
+        assert_eq!((config.token.as_deref(), config.auth_header.as_deref()), (Some("t"), Some("X-API-Key")));
+    }
+
+    #[tokio::test]
+    async fn refused_credentials_are_reported_as_such() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend = HttpBackend::new(format!("http://{}", listener.local_addr().unwrap()));
+        // A proxy in front of Hank answers with HTML, not JSON
+        tokio::spawn(async move {
+            for status in ["401 Unauthorized", "403 Forbidden", "502 Bad Gateway"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let _ = socket.read(&mut request).await;
+                let body = "<html>nope</html>";
+                let response = format!("HTTP/1.1 {}\r\ncontent-type: text/html\r\ncontent-length: {}\r\n\r\n{}", status, body.len(), body);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let err = backend.fetch_messages(0, None, Duration::from_secs(2)).await.err().unwrap();
+        assert!(err.starts_with("Zugriff verweigert (HTTP 401): Token prüfen"), "{}", err);
+        let (chunks, _) = tokio::sync::mpsc::unbounded_channel();
+        let request = ChatRequest { message: "hi".to_string(), display_name: None, room: None, thread_id: None, to: None, model: None, client_id: None, session: None };
+        assert_eq!(backend.send_chat_stream(request, chunks).await.err(), Some(ChatError::Unauthorized { status: 403 }));
+        let err = backend.fetch_messages(0, None, Duration::from_secs(2)).await.err();
+        assert_eq!(err.as_deref(), Some("Server-Fehler: 502 Bad Gateway"));
+    }
+
+    #[test]
+    fn profile_urls_escape_the_user_name() {
+        let backend = HttpBackend::new("http://h/api/hank/".to_string());
+        let url = backend.url_with_segments(&["users", "mara k/?#x"]).unwrap();
+        assert_eq!(url.as_str(), "http://h/api/hank/users/mara%20k%2F%3F%23x");
+        let url = HttpBackend::new("http://h:8080".to_string()).url_with_segments(&["users", "mara"]).unwrap();
+        assert_eq!(url.as_str(), "http://h:8080/users/mara");
+    }
+
+    #[tokio::test]
+    async fn edited_message_ids_are_escaped() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend = HttpBackend::new(format!("http://{}", listener.local_addr().unwrap()));
+        let seen = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let len = socket.read(&mut request).await.unwrap();
+            socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8_lossy(&request[..len]).lines().next().unwrap_or_default().to_string()
+        });
+        assert_eq!(backend.edit_message("42/../x?y", "neu".to_string()).await, Ok(()));
+        assert_eq!(seen.await.unwrap(), "PUT /messages/42%2F..%2Fx%3Fy HTTP/1.1");
+    }
+
+    #[test]
+    fn tls_options_from_config() {
+        let config: Config = toml::from_str("host = \"h\"\nport = 1\nscheme = \"https\"\ninsecure = true").unwrap();
+        assert_eq!((config.scheme, config.insecure), (Scheme::Https, true));
+        assert_eq!(format!("{}://{}:{}", config.scheme, config.host, config.port), "https://h:1");
+        assert_eq!(toml::from_str::<Config>("host = \"h\"\nport = 1").unwrap().scheme, Scheme::Http);
+        assert!(toml::from_str::<Config>("host = \"h\"\nport = 1\nscheme = \"ftp\"").is_err());
+
+        assert!(ClientOptions { insecure: true, ..Default::default() }.client().is_ok());
+        let dir = std::env::temp_dir().join(format!("hank-tui-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let missing = ClientOptions { ca_bundle: Some(dir.join("fehlt.pem")), ..Default::default() };
+        assert!(missing.client().unwrap_err().starts_with("CA-Bundle "));
+        let empty = dir.join("leer.pem");
+        fs::write(&empty, "kein Zertifikat").unwrap();
+        let empty = ClientOptions { ca_bundle: Some(empty), ..Default::default() };
+        assert!(empty.client().unwrap_err().ends_with("keine Zertifikate gefunden"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn backends_share_pooled_connections() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    while socket.read(&mut request).await.is_ok_and(|len| len > 0) {
+                        let body = r#"["gpt"]"#;
+                        let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+                        socket.write_all(response.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        let client = ClientOptions::default().client().unwrap();
+        let first = HttpBackend::new(server_url.clone()).with_client(client.clone());
+        let second = HttpBackend::new(server_url).with_client(client);
+        for backend in [&first, &second, &first] {
+            assert_eq!(backend.fetch_models().await, Ok(vec!["gpt".to_string()]));
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        let config: Config = toml::from_str("host = \"h\"\nport = 1\nconnect_timeout = 3\nkeep_alive = 30").unwrap();
+        assert_eq!((config.connect_timeout, config.keep_alive), (Some(3), Some(30)));
+    }
+
+    #[tokio::test]
+    async fn timeouts_and_poll_interval_come_from_the_config() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        // Accepts, reads nothing and never answers
+        tokio::spawn(async move {
+            let _socket = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+        let options = ClientOptions { request_timeout: Some(Duration::from_millis(300)), ..Default::default() };
+        let backend = HttpBackend::new(server_url).with_client(options.client().unwrap());
+        let request = ChatRequest { message: "hi".to_string(), display_name: None, room: None, thread_id: None, to: None, model: None, client_id: None, session: None };
+        let (chunks, _) = tokio::sync::mpsc::unbounded_channel();
+        let started = Instant::now();
+        assert_eq!(backend.send_chat_stream(request, chunks).await.err(), Some(ChatError::Other("Keine Antwort nach 300ms".to_string())));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // A slow model streaming for longer than the timeout is not cut off
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let _ = socket.read(&mut request).await;
+            socket.write_all(b"HTTP/1.1 200 OK\r\ncontent-type: application/x-ndjson\r\n\r\n{\"delta\":\"Lang\"}\n").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            socket.write_all(b"{\"content\":\"Langsam\",\"complete\":true}\n").await.unwrap();
+        });
+        let backend = HttpBackend::new(server_url).with_client(options.client().unwrap());
+        let request = ChatRequest { message: "hi".to_string(), display_name: None, room: None, thread_id: None, to: None, model: None, client_id: None, session: None };
+        let (chunks, _) = tokio::sync::mpsc::unbounded_channel();
+        assert_eq!(backend.send_chat_stream(request, chunks).await.map(|r| r.content).ok().as_deref(), Some("Langsam"));
+
+        let config: Config = toml::from_str("host = \"h\"\nport = 1\nrequest_timeout = 600\npoll_interval = 10\npoll_timeout = 5").unwrap();
+        assert_eq!((config.request_timeout, config.poll_interval, config.poll_timeout), (Some(600), Some(10), Some(5)));
+        let mut app = input_app("", 0);
+        app.poll_interval = Duration::from_secs(10);
+        assert_eq!(app.poll_delay(), Duration::from_secs(10));
+        app.poll_failures = 10;
+        assert_eq!(app.poll_delay(), Duration::from_secs(60));
+        app.poll_interval = Duration::from_secs(90);
+        assert_eq!(app.poll_delay(), Duration::from_secs(90));
+    }
+
+    #[tokio::test]
+    async fn unchanged_polls_cost_a_304_and_quiet_sessions_poll_less() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        let (seen_tx, mut seen) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let seen_tx = seen_tx.clone();
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    while let Ok(len) = socket.read(&mut request).await
+                        && len > 0
+                    {
+                        let text = String::from_utf8_lossy(&request[..len]).to_ascii_lowercase();
+                        let revalidated = text.contains("if-none-match: \"v1\"");
+                        seen_tx.send(revalidated).unwrap();
+                        let response = if revalidated {
+                            "HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\n\r\n".to_string()
+                        } else {
+                            "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: 2\r\n\r\n[]".to_string()
+                        };
+                        socket.write_all(response.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        let backend = HttpBackend::new(server_url);
+        let timeout = Duration::from_secs(2);
+        assert!(backend.fetch_messages(5, None, timeout).await.is_ok_and(|messages| messages.is_empty()));
+        assert!(backend.fetch_messages(5, None, timeout).await.is_ok_and(|messages| messages.is_empty()));
+        // Another `since` or room is a different question
+        assert!(backend.fetch_messages(6, None, timeout).await.is_ok_and(|messages| messages.is_empty()));
+        assert!(backend.fetch_messages(6, Some("dev"), timeout).await.is_ok_and(|messages| messages.is_empty()));
+        let mut revalidated = Vec::new();
+        while let Ok(flag) = seen.try_recv() {
+            revalidated.push(flag);
+        }
+        assert_eq!(revalidated, vec![false, true, false, false]);
+
+        let mut app = input_app("", 0);
+        assert_eq!(app.poll_delay(), Duration::from_secs(2));
+        app.last_activity = Instant::now() - Duration::from_secs(61);
+        assert_eq!(app.poll_delay(), Duration::from_secs(4));
+        app.last_activity = Instant::now() - Duration::from_secs(600);
+        assert_eq!(app.poll_delay(), Duration::from_secs(15));
+    }
+
+    #[tokio::test]
+    async fn requests_go_through_the_configured_proxy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let seen = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let len = socket.read(&mut request).await.unwrap();
+            let body = r#"["gpt"]"#;
+            let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request[..len]).lines().next().unwrap_or_default().to_string()
+        });
+
+        let options = ClientOptions { proxy: Some(proxy), ..Default::default() };
+        let backend = HttpBackend::new("http://hank.invalid".to_string()).with_client(options.client().unwrap());
+        assert_eq!(backend.fetch_models().await, Ok(vec!["gpt".to_string()]));
+        assert_eq!(seen.await.unwrap(), "GET http://hank.invalid/models HTTP/1.1");
+
+        assert!(ClientOptions { proxy: Some("none".to_string()), ..Default::default() }.client().is_ok());
+        let socks = ClientOptions { proxy: Some("socks5://host:1080".to_string()), ..Default::default() };
+        assert!(socks.client().is_ok());
+    }
+
+    #[tokio::test]
+    async fn restored_view_survives_the_initial_sync() {
+        let mut app = App::new(Arc::new(FixedBackend(vec![10, 20, 30])), false);
+        app.messages = [10, 20, 30].iter().map(|&ts| {
+            Message::from_server(ServerMessage { role: "assistant".to_string(), content: ts.to_string(), timestamp: ts, ..Default::default() })
+        }).collect();
+        app.restore_view(Some(&provisional_id(20)));
+        app.messages.insert(0, Message::new("system", "Historie geladen"));
+        app.scroll_anchor = Some(2);
+
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        initial_sync(&app, &events, false);
+        apply_next(&mut app, &mut inbox).await;
+        assert_eq!(app.scroll_anchor, Some(1));
+        assert_eq!(app.messages[1].content, "20");
+        assert!(!app.auto_scroll);
+    }
+
+    #[tokio::test]
+    async fn resync_fetches_the_window_missed_during_an_outage() {
+        // 100_000 was seen; 90_000 was stored late (older timestamp), 120_000 during the outage
+        let mut app = App::new(Arc::new(FixedBackend(vec![10_000, 90_000, 100_000, 120_000])), false);
+        app.messages = vec![Message::from_server(ServerMessage {
+            role: "assistant".to_string(),
+            content: "100000".to_string(),
+            timestamp: 100_000,
+            ..Default::default()
+        })];
+        app.last_timestamp = 100_000;
+        app.offline_since = Some(Instant::now());
+
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        spawn_resync(&mut app, &events);
+        assert!(app.polling);
+        apply_next(&mut app, &mut inbox).await;
+        assert!(!app.polling);
+        let contents: Vec<&str> = app.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["90000", "100000", "120000", "Verbindung wiederhergestellt, 2 Nachrichten synchronisiert"]);
+        assert!(app.offline_since.is_none());
+        assert_eq!(app.last_timestamp, 120_000);
+    }
+
+    #[test]
+    fn tool_calls_render_collapsed_and_expanded() {
+        let raw = r#"{"content":"Erledigt.","tool_calls":[{"name":"search","arguments":{"q":"rust"},"result":{"hits":3}}]}"#;
+        let msg = Message::from_server(ServerMessage {
+            role: "assistant".to_string(),
+            content: raw.to_string(),
+            timestamp: 1,
+            ..Default::default()
+        });
+        assert_eq!(msg.content, "Erledigt.");
+        assert_eq!(msg.tool_calls.len(), 1);
+        assert_eq!(msg.tool_calls[0].summary(), r#"search(q="rust")"#);
+        assert_eq!(parse_structured("{kein json".to_string()).0, "{kein json");
+
+        let mut app = input_app("", 0);
+        app.messages = vec![msg];
+        let text = |app: &App| -> Vec<String> {
+            app.chat_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect()
+        };
+        let collapsed = text(&app);
+        assert!(collapsed[1].trim_start().starts_with("▸ ⚙ search(q=\"rust\") ✓"));
+        assert!(!collapsed.iter().any(|l| l.contains("hits")));
+
+        app.tools_expanded = true;
+        let expanded = text(&app);
+        assert!(expanded.iter().any(|l| l.trim() == "Argumente:"));
+        assert!(expanded.iter().any(|l| l.trim() == "\"hits\": 3"));
+    }
+
+    #[test]
+    fn accessible_mode_narrates_new_messages_and_state_changes() {
+        let mut app = input_app("", 0);
+        app.display_name = Some("Glan".to_string());
+        let mut other = Message::new("user", "Hallo\nzusammen");
+        other.sender = Some("Mara".to_string());
+        app.messages = vec![other];
+        let first = app.narrate();
+        assert!(first[0].ends_with("Mara sagt: Hallo / zusammen"), "{:?}", first);
+        assert_eq!(first[1], "Status: Verbunden");
+
+        assert!(app.narrate().is_empty());
+        app.messages.push(Message::new("assistant", "Servus"));
+        app.connection_status = ConnectionStatus::Disconnected;
+        app.focus = Focus::Chat;
+        let next = app.narrate();
+        assert_eq!(next.len(), 3);
+        assert!(next[0].ends_with("Hank sagt: Servus"));
+        assert_eq!(next[1], "Status: Getrennt");
+        assert_eq!(next[2], "Fokus: Chat");
+    }
+
+    #[test]
+    fn themes_select_presets_and_no_color() {
+        assert!(Theme::PRESETS.iter().all(|name| Theme::preset(name).is_some()));
+        assert!(Theme::select(Some("sepia"), None).is_err());
+        let mut cvd = input_app("", 0);
+        cvd.theme = Theme::preset("deuteranopia").unwrap();
+        cvd.messages = vec![Message::new("assistant", "servus"), Message::new("error", "kaputt")];
+        let lines = cvd.chat_lines();
+        assert_eq!(lines[0].spans[2].content, "◆ Hank: ");
+        assert_eq!(lines[2].spans[2].content, "✗ Error: ");
+        assert_eq!(Theme::select(Some("light-terminal"), Some("dark")).unwrap(), Theme::preset("light").unwrap());
+
+        let mut app = input_app("", 0);
+        app.theme = Theme::no_color();
+        let mut msg = Message::new("user", "hi");
+        msg.sender = Some("mara".to_string());
+        app.messages = vec![msg, Message::new("error", "kaputt")];
+        for line in app.chat_lines() {
+            for span in line.spans {
+                assert!(span.style.fg.is_none_or(|c| c == Color::Reset), "{:?}", span);
+                assert!(span.style.bg.is_none_or(|c| c == Color::Reset), "{:?}", span);
+            }
+        }
+    }
+
+    #[test]
+    fn theme_files_override_a_base_preset() {
+        let dir = env::temp_dir().join(format!("hank-tui-themes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("nacht.toml"), "base = \"solarized\"\naccent = \"#ff8800\"\nmuted = \"245\"\nrole_glyphs = true\n").unwrap();
+        fs::write(dir.join("kaputt.toml"), "error = \"rötlich\"\n").unwrap();
+        fs::write(dir.join("tippfehler.toml"), "acent = \"red\"\n").unwrap();
+
+        let theme = Theme::load("nacht", Some(&dir)).unwrap();
+        let solarized = Theme::preset("solarized").unwrap();
+        assert_eq!((theme.accent, theme.muted), (Color::Rgb(0xff, 0x88, 0x00), Color::Indexed(245)));
+        assert_eq!((theme.assistant, theme.status_bg), (solarized.assistant, solarized.status_bg));
+        assert!(theme.role_glyphs);
+        assert!(Theme::load("kaputt", Some(&dir)).unwrap_err().ends_with("error: 'rötlich' ist keine Farbe"));
+        assert!(Theme::load("tippfehler", Some(&dir)).unwrap_err().contains("acent"));
+        assert_eq!(Theme::load("dark", Some(&dir)).unwrap(), Theme::default());
+        let names = Theme::available(Some(&dir));
+        assert_eq!(names[Theme::PRESETS.len()..], ["kaputt", "nacht", "tippfehler"]);
+        assert!(Theme::load("sepia", Some(&dir)).unwrap_err().contains("solarized, no-color"));
+
+        assert_eq!(Command::parse("/theme"), Some(Ok(Command::Theme(None))));
+        assert_eq!(Command::parse("/theme nacht"), Some(Ok(Command::Theme(Some("nacht".to_string())))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn osc52_encodes_base64_and_wraps_for_tmux() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(osc52_sequence("hi", false), "\x1b]52;c;aGk=\x07");
+        assert_eq!(osc52_sequence("hi", true), "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\");
+    }
+
+    #[test]
+    fn templates_fill_variables_into_the_input() {
+        let dir = env::temp_dir().join(format!("hank-tui-templates-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("review.md"), "Review {{lang}} code:\n{{ code }} ({{lang}})\n").unwrap();
+        fs::write(dir.join("hallo.txt"), "Hallo!").unwrap();
+        fs::write(dir.join("notes.json"), "{}").unwrap();
+        let templates = PromptTemplate::load_all(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(templates.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["hallo", "review"]);
+        assert_eq!(templates[1].variables(), ["lang", "code"]);
+
+        let mut app = input_app(">> ", 3);
+        assert!(!app.open_template_picker(Vec::new()));
+        assert!(app.open_template_picker(templates));
+        let press = |app: &mut App, code| app.template_picker_key(KeyEvent::new(code, KeyModifiers::NONE));
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        for c in "Rust".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        assert!(app.template_picker_lines().iter().any(|l| l.spans.iter().any(|s| s.content == "code: ")));
+        press(&mut app, KeyCode::Char('x'));
+        press(&mut app, KeyCode::Enter);
+        assert!(app.template_picker.is_none());
+        assert_eq!(app.input, ">> Review Rust code:\nx (Rust)");
+    }
+
+    #[test]
+    fn edits_keep_revisions_and_show_word_diff() {
+        assert_eq!(
+            word_diff("bitte kurz erklären", "bitte ausführlich erklären"),
+            vec![
+                (DiffOp::Same, "bitte ".to_string()),
+                (DiffOp::Removed, "kurz".to_string()),
+                (DiffOp::Added, "ausführlich".to_string()),
+                (DiffOp::Same, " erklären".to_string()),
+            ]
+        );
+
+        let mut app = input_app("", 0);
+        assert!(!app.show_last_edit_diff());
+        let mut own = Message::new("user", "Wie spät\nist es?");
+        own.id = Some("m1".to_string());
+        app.messages = vec![own];
+        app.apply_edit("m1", "Wie spät\nwar es?".to_string());
+        assert_eq!(app.messages[0].revisions, ["Wie spät\nist es?"]);
+        assert!(app.show_last_edit_diff());
+        let text: Vec<String> =
+            app.edit_diff_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect();
+        assert_eq!(text, ["Wie spät", "[-ist-]{+war+} es?"]);
+    }
+
+    #[test]
+    fn regenerate_reruns_prompt_with_chosen_model() {
+        let mut app = input_app("", 0);
+        assert!(app.regenerate_prompt().is_none());
+        let mut prompt = Message::new("user", "Erklär Lifetimes");
+        prompt.timestamp_ms = Some(1_000);
+        app.messages = vec![prompt, Message::new("assistant", "Gern!"), Message::new("system", "info")];
+        let prompt_ts = app.regenerate_prompt().and_then(|m| m.timestamp_ms).unwrap();
+        let pending = app.regenerate_request(prompt_ts, "gpt-x").unwrap();
+        assert_eq!(pending.message_ts, 1_000);
+        assert_eq!(pending.request.message, "Erklär Lifetimes");
+        assert_eq!(pending.request.model.as_deref(), Some("gpt-x"));
+        assert!(serde_json::to_string(&pending.request).unwrap().contains("\"model\":\"gpt-x\""));
+
+        let mut alternative = Message::new("assistant", "Anders");
+        alternative.model = Some("gpt-x".to_string());
+        app.messages.push(alternative);
+        let last = app.chat_lines().into_iter().rev().find(|l| !l.spans.is_empty()).unwrap();
+        assert!(last.spans.iter().any(|s| s.content == " [gpt-x]"));
+    }
+
+    #[test]
+    fn response_time_is_shown_next_to_the_timestamp() {
+        assert_eq!(format_response_time(4_230), "4.2s");
+        assert_eq!(format_response_time(65_000), "1m05s");
+
+        let mut app = input_app("", 0);
+        let mut msg = Message::new("assistant", "Antwort");
+        msg.timestamp = "10:00:00".to_string();
+        msg.response_ms = Some(4_200);
+        app.messages = vec![msg];
+        assert_eq!(app.chat_lines()[0].spans[0].content, "10:00:00 4.2s");
+        app.timestamps = TimestampMode::Hidden;
+        assert_eq!(app.chat_lines()[0].spans[0].content, "");
+        let saved = serde_json::to_string(&app.messages[0]).unwrap();
+        assert!(saved.contains("\"response_ms\":4200"));
+    }
+
+    #[test]
+    fn interrupted_stream_keeps_partial_answer() {
+        let mut app = App::new(Arc::new(NullBackend), false);
+        app.loading = true;
+        app.streaming = Some("Die Antwort ist".to_string());
+        app.interrupt_stream(None);
+        assert!(app.streaming.is_none() && !app.loading);
+        let reply = app.messages.last().unwrap();
+        assert_eq!((reply.role.as_str(), reply.content.as_str(), reply.interrupted), ("assistant", "Die Antwort ist", true));
+        let text: Vec<String> = app.chat_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect();
+        assert!(text.iter().any(|l| l.trim() == "(unterbrochen)"));
+    }
+
+    #[test]
+    fn stream_lines_parse_deltas_and_final_response() {
+        assert!(matches!(parse_stream_line("{\"delta\":\"Hal\"}"), Ok(Some(StreamEvent::Delta(t))) if t == "Hal"));
+        assert!(matches!(parse_stream_line("  \n"), Ok(None)));
+        match parse_stream_line("{\"content\":\"Hallo\",\"complete\":true}") {
+            Ok(Some(StreamEvent::Done(response))) => assert_eq!(response.content, "Hallo"),
+            _ => panic!("final response expected"),
+        }
+        assert!(parse_stream_line("{\"delta\":").is_err());
+    }
+
+    #[test]
+    fn streaming_answer_renders_with_caret() {
+        let mut app = App::new(Arc::new(NullBackend), false);
+        assert_eq!(app.streaming_lines().len(), 1); // "Hank denkt nach..."
+        app.streaming = Some("Teil".to_string());
+        let text: String = app.streaming_lines().iter().flat_map(|l| l.spans.iter().map(|s| s.content.to_string())).collect();
+        assert!(text.contains("Teil▌"));
+    }
+
+    #[test]
+    fn ascii_mode_folds_frame_and_drops_color() {
+        let mut buf = ratatui::buffer::Buffer::empty(ratatui::layout::Rect::new(0, 0, 12, 3));
+        let block = Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::Red));
+        ratatui::widgets::Widget::render(Paragraph::new("⚠ Größe").block(block), buf.area, &mut buf);
+        ascii_buffer(&mut buf);
+        let rows: Vec<String> = (0..3)
+            .map(|y| (0..12).map(|x| buf[(x, y)].symbol().to_string()).collect())
+            .collect();
+        assert_eq!(rows, ["+----------+", "|! Grose   |", "+----------+"]);
+        assert!(buf.content.iter().all(|c| c.fg == Color::Reset && c.bg == Color::Reset));
+    }
+
+    #[test]
+    fn cursor_reports_when_it_hits_the_input_edge() {
+        // Up/Down past the edge is what scrolls the chat with alternate scroll
+        let mut app = input_app("ab\ncd", 4);
+        assert!(!app.cursor_down(10));
+        assert!(app.cursor_up(10));
+        assert!(!app.cursor_up(10));
+    }
+
+    #[test]
+    fn resize_keeps_top_message_anchored() {
+        let lines: Vec<Line> = (0..20).map(|i| Line::from(format!("{:02} abcdefghij", i))).collect();
+        // 20 columns: one row per line; top row is line 5
+        let total = 20 + CHAT_PADDING_LINES;
+        let scroll = (total - 10 - 5) as u16;
+        let narrow = reanchor_scroll(&lines, EastAsianWidth::Narrow, (20, 10), (7, 10), scroll);
+        // 7 columns: two rows per line, line 5 starts at row 10
+        let narrow_total = 40 + CHAT_PADDING_LINES;
+        assert_eq!(narrow, (narrow_total - 10 - 10) as u16);
+        // and back
+        assert_eq!(reanchor_scroll(&lines, EastAsianWidth::Narrow, (7, 10), (20, 10), narrow), scroll);
+        // taller pane: same top row, offset from the bottom shrinks
+        assert_eq!(reanchor_scroll(&lines, EastAsianWidth::Narrow, (20, 10), (20, 12), scroll), scroll - 2);
+    }
+
+    #[test]
+    fn window_title_shows_unread_count() {
+        let mut app = App::new(Arc::new(NamedBackend("http://a:1")), false);
+        assert_eq!(app.window_title_text(), "hank-tui — Hauptkanal");
+        app.join_room("ops");
+        app.switch_room(1);
+        app.rooms[0].unread = 3;
+        assert_eq!(app.window_title_text(), "hank-tui — #ops (3 ungelesen)");
+    }
+
+    #[test]
+    fn unread_total_and_title_follow_tabs() {
+        let mut app = App::new(Arc::new(NamedBackend("http://a:1")), false);
+        app.join_room("ops");
+        assert_eq!(app.title(), "Hauptkanal");
+        app.rooms[1].unread = 4;
+        assert_eq!(app.unread_total(), 4);
+        app.switch_room(1);
+        assert_eq!(app.title(), "#ops");
+        assert_eq!(app.unread_total(), 0);
+    }
+
+    #[test]
+    fn fallback_keymaps_follow_terminal_capabilities() {
+        assert!(Fallback::detect("xterm-kitty", "", false, true, true).is_empty());
+        assert!(Fallback::detect("linux", "", true, false, false).is_empty());
+        assert_eq!(
+            Fallback::detect("linux", "", false, false, true),
+            vec![Fallback::FunctionKeys, Fallback::CtrlTab, Fallback::CtrlEnter]
+        );
+        assert_eq!(Fallback::detect("xterm-256color", "Apple_Terminal", false, true, true), vec![Fallback::FunctionKeys]);
+
+        let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+        let all = [Fallback::FunctionKeys, Fallback::CtrlTab];
+        assert_eq!(translate_fallback(&all, alt(KeyCode::Char('5'))).code, KeyCode::F(5));
+        let tab = translate_fallback(&all, alt(KeyCode::Right));
+        assert_eq!((tab.code, tab.modifiers), (KeyCode::Tab, KeyModifiers::CONTROL));
+        // Inactive fallbacks leave the chord alone
+        assert_eq!(translate_fallback(&[], alt(KeyCode::Char('5'))).code, KeyCode::Char('5'));
+    }
+
+    #[test]
+    fn wide_ambiguous_characters_wrap_earlier() {
+        // "①" is ambiguous: one cell on western terminals, two on CJK ones
+        let mut app = input_app("①①", 2);
+        assert_eq!(app.cursor_line_col(3), (0, 2));
+        assert_eq!(app.wrap_text_for_display(3), "①①");
+        app.east_asian_width = EastAsianWidth::Wide;
+        assert_eq!(app.cursor_line_col(3), (1, 2));
+        assert_eq!(app.wrap_text_for_display(3), "①\n①");
+        let lines = vec![Line::from("①①")];
+        assert_eq!(wrapped_line_count(&lines, 3, EastAsianWidth::Narrow), 1);
+        assert_eq!(wrapped_line_count(&lines, 3, EastAsianWidth::Wide), 2);
+    }
+
+    #[test]
+    fn bidi_reorders_rtl_runs_and_keeps_cursor_logical() {
+        assert_eq!(bidi_visual_order("hello"), None);
+        // Hebrew word inside an English sentence: only the word is reversed
+        assert_eq!(bidi_display("say שלום now"), "say םולש now");
+        // RTL paragraph: words run right to left, numbers stay readable
+        assert_eq!(bidi_display("שלום 42"), "42 םולש");
+        assert_eq!(bidi_display("(אב)"), "(בא)");
+
+        let mut app = input_app("ab אבג", 7);
+        assert_eq!(app.input_display_text(20), "ab גבא");
+        // End of the logical text is the left end of the Hebrew run
+        assert_eq!(app.cursor_display_col(20), (0, 3));
+        app.cursor_left();
+        assert_eq!(app.cursor_pos, 5);
+        assert_eq!(app.cursor_display_col(20), (0, 4));
+    }
+
+    #[test]
+    fn status_line_fills_placeholders() {
+        let mut app = App::new(Arc::new(NamedBackend("http://hank:8080")), false);
+        app.latency = Some(Duration::from_millis(42));
+        app.rooms[0].unread = 0;
+        assert_eq!(
+            app.status_line("{server} {latency} {session} {unread} {model} {foo}"),
+            "hank:8080 42ms Hauptkanal 0 - {foo}"
+        );
+        app.model = Some("hank-large".to_string());
+        assert_eq!(app.status_line("[{model}]"), "[hank-large]");
+        assert_eq!(app.status_line("{time}").len(), 5);
+        assert_eq!(app.status_line("{elapsed} {idle}"), "0:00 -");
+        let mut reply = Message::new("assistant", "ok");
+        reply.timestamp_ms = Some(now_ms() - 125_000);
+        app.messages.push(reply);
+        assert_eq!(app.status_line("{idle}"), "2:05");
+        assert_eq!(format_elapsed(Duration::from_secs(3 * 3600 + 62)), "3:01:02");
+        assert_eq!(app.clock_text(), None);
+        app.clock = Some("%Y".to_string());
+        assert_eq!(app.status_line("{time}"), Local::now().format("%Y").to_string());
+        app.clock = Some("%Q kaputt".to_string());
+        assert_eq!(app.clock_text().map(|t| t.len()), Some(5));
+    }
+
+    #[test]
+    fn compact_mode_drops_spacers_and_shortens_prefixes() {
+        let mut app = input_app("", 0);
+        let mut other = Message::new("user", "hi");
+        other.sender = Some("mara".to_string());
+        other.timestamp = "12:34:56".to_string();
+        app.messages = vec![other, Message::new("assistant", "servus")];
+        assert_eq!(app.chat_lines().len(), 4);
+
+        app.compact = true;
+        let lines = app.chat_lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].spans[0].content, "12:34");
+        assert_eq!(lines[0].spans[2].content, "M ");
+        assert_eq!(lines[1].spans[2].content, "◆ ");
+    }
+
+    #[test]
+    fn timestamp_modes_cycle_and_render() {
+        assert_eq!(TimestampMode::Full.next(), TimestampMode::Hidden);
+        let mut app = input_app("", 0);
+        let mut msg = Message::new("assistant", "servus");
+        msg.timestamp_ms = Some(Local.with_ymd_and_hms(2024, 3, 9, 14, 5, 7).unwrap().timestamp_millis() as u64);
+        msg.timestamp = "14:05:07".to_string();
+        app.messages = vec![msg];
+        let first = |app: &App| app.chat_lines()[0].spans.iter().map(|s| s.content.to_string()).collect::<String>();
+        assert_eq!(first(&app), "14:05:07 Hank: servus");
+        app.timestamps = TimestampMode::Full;
+        assert_eq!(first(&app), "09.03.2024 14:05:07 Hank: servus");
+        app.timestamps = TimestampMode::Hidden;
+        assert_eq!(first(&app), "Hank: servus");
+    }
+
+    #[test]
+    fn transcript_text_matches_chat_pane() {
+        let mut app = input_app("", 0);
+        let mut msg = Message::new("assistant", "zeile 1\nzeile 2");
+        msg.timestamp = "10:00:00".to_string();
+        app.messages = vec![msg];
+        assert_eq!(app.transcript_text(), "10:00:00 Hank: zeile 1\n               zeile 2\n\n");
+    }
+
+    #[test]
+    fn key_bindings_come_from_the_config() {
+        let config: Config = toml::from_str(
+            "host = \"h\"\nport = 1\n[keys]\nsend = \"alt+s\"\nclear_history = []\nquit = [\"ctrl+q\", \"Esc\"]\nhelp = \"strg+hyper+h\"\nwhatever = \"f3\"\nclear = \"F1\"\n",
+        )
+        .unwrap();
+        let (keys, problems) = KeyBindings::from_config(&config.keys);
+        assert_eq!(
+            problems,
+            vec![
+                "keys.help: 'strg+hyper+h' ist keine Taste".to_string(),
+                "keys.whatever: unbekannte Aktion".to_string(),
+                "keys: F1 doppelt belegt (help, clear)".to_string(),
+            ]
+        );
+
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        assert!(keys.matches(Action::Send, key(KeyCode::Char('s'), KeyModifiers::ALT)));
+        assert!(!keys.matches(Action::Send, key(KeyCode::Char('s'), KeyModifiers::CONTROL)));
+        assert!(!keys.matches(Action::ClearHistory, key(KeyCode::Char('D'), KeyModifiers::CONTROL)));
+        assert!(keys.matches(Action::Quit, key(KeyCode::Char('q'), KeyModifiers::CONTROL)));
+        // Terminals report Ctrl+Shift+Y with either case
+        for event in [key(KeyCode::Char('Y'), KeyModifiers::CONTROL), key(KeyCode::Char('y'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)] {
+            assert!(keys.matches(Action::CopyCode, event));
+            assert!(!keys.matches(Action::CopyReply, event));
+        }
+        assert_eq!("ctrl++".parse::<KeyChord>().unwrap().to_string(), "Ctrl++");
+        assert_eq!("shift+d".parse::<KeyChord>(), "D".parse::<KeyChord>());
+        assert_eq!(keys.describe(Action::ScrollUp), "Alt+↑");
+
+        let mut app = input_app("", 0);
+        app.keys = keys;
+        assert!(app.bound(Action::Send, key(KeyCode::Char('s'), KeyModifiers::ALT)));
+        let text: Vec<String> = app.help_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect();
+        assert!(text.contains(&"  Alt+S         Nachricht senden".to_string()));
+        assert!(text.contains(&"  Ctrl+Q, Esc   Beenden".to_string()));
+        assert!(text.contains(&"  –             History-Datei löschen".to_string()));
+        assert!(text.contains(&"  F1, ?         Hilfe anzeigen/schließen".to_string()));
+
+        // Without the kitty protocol Ctrl+Enter arrives as Ctrl+J
+        app.keys = KeyBindings::default();
+        assert!(app.bound(Action::Send, key(KeyCode::Char('j'), KeyModifiers::CONTROL)));
+        app.enhanced_keys = true;
+        assert!(!app.bound(Action::Send, key(KeyCode::Char('j'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn readline_shortcuts_move_by_words_and_kill_lines() {
+        let mut app = input_app("echo foo-bar  baz\nzweite zeile", 17);
+        app.cursor_word_left();
+        assert_eq!(app.cursor_pos, 14);
+        app.cursor_word_left();
+        assert_eq!(app.cursor_pos, 9);
+        app.cursor_word_right();
+        assert_eq!(app.cursor_pos, 12);
+        app.cursor_word_right();
+        assert_eq!(app.cursor_pos, 17);
+
+        app.delete_word_back();
+        assert_eq!((app.input.as_str(), app.cursor_pos), ("echo foo-bar  \nzweite zeile", 14));
+        app.delete_word_back();
+        assert_eq!((app.input.as_str(), app.cursor_pos), ("echo \nzweite zeile", 5));
+
+        // Ctrl+K at the end of a line joins the next one, Ctrl+U in the
+        // middle only clears back to the line start
+        app.kill_to_line_end();
+        assert_eq!(app.input, "echo zweite zeile");
+        app.cursor_pos = 12;
+        app.kill_to_line_end();
+        assert_eq!((app.input.as_str(), app.cursor_pos), ("echo zweite ", 12));
+        app.input = "eins\nzwei drei".to_string();
+        app.cursor_pos = 9;
+        app.kill_to_line_start();
+        assert_eq!((app.input.as_str(), app.cursor_pos), ("eins\n drei", 5));
+        app.kill_to_line_start();
+        assert_eq!((app.input.as_str(), app.cursor_pos), ("eins drei", 4));
+        app.kill_to_line_start();
+        assert_eq!((app.input.as_str(), app.cursor_pos), (" drei", 0));
+    }
+
+    #[test]
+    fn text_from_the_editor_replaces_the_input() {
+        let mut app = input_app("entwurf", 3);
+        app.input_scroll = 2;
+        app.take_edited_input("erster absatz\n\nzweiter absatz\r\n");
+        assert_eq!(app.input, "erster absatz\n\nzweiter absatz");
+        assert_eq!((app.cursor_pos, app.input_scroll), (app.input.chars().count(), 0));
+        // Only the final line break goes, intended blank lines stay
+        app.take_edited_input("text\n\n");
+        assert_eq!(app.input, "text\n");
+    }
+
+    #[test]
+    fn english_bundle_covers_the_help() {
+        for binding in KEYMAP {
+            assert_ne!(Lang::En.translate(binding.description), binding.description);
+        }
+        for section in HelpSection::ALL {
+            assert_ne!(Lang::En.translate(section.title()), section.title());
+        }
+        for fallback in [Fallback::FunctionKeys, Fallback::CtrlTab, Fallback::CtrlEnter] {
+            // Same key column as in German
+            let (de, en) = (fallback.describe(), Lang::En.translate(fallback.describe()));
+            assert_eq!(en.split("instead").next(), de.split("statt").next());
+        }
+        assert_eq!(Lang::De.translate("Nachricht senden"), "Nachricht senden");
+        assert_eq!(Lang::En.translate("Nicht übersetzt"), "Nicht übersetzt");
+        assert_eq!(trf("{} sagt", &["mara"]), "mara sagt");
+        assert_eq!("EN".parse::<Lang>(), Ok(Lang::En));
+        assert!("fr".parse::<Lang>().is_err());
+        assert_eq!(toml::from_str::<Config>("host = \"h\"\nport = 1\nlang = \"en\"").unwrap().lang, Some(Lang::En));
+    }
+
+    #[test]
+    fn english_bundle_covers_status_and_errors() {
+        for status in [ConnectionStatus::Connected, ConnectionStatus::Sending, ConnectionStatus::Disconnected, ConnectionStatus::Copied] {
+            assert_ne!(Lang::En.translate(status.label()), status.label());
+        }
+        for presence in [PresenceStatus::Away, PresenceStatus::Unknown] {
+            assert_ne!(Lang::En.translate(presence.label()), presence.label());
+        }
+        for text in [
+            "{} tippt…",
+            "{}, {} und {} weitere tippen…",
+            "{} … {} Einträge [J] {}",
+            "hank-tui — {} ({} ungelesen)",
+            "Verbindung wiederhergestellt, {} Nachrichten synchronisiert",
+            "(ohne Titel)",
+            "Hauptunterhaltung",
+            "Kein Konfigurationsverzeichnis gefunden",
+            "'{}' ist keine Taste",
+            "Nachricht zu groß für den Server",
+            "Server liefert keine Versionsinfo",
+        ] {
+            assert_ne!(Lang::En.translate(text), text);
+        }
+        assert_eq!(ChatError::TooLarge { limit: Some(500) }.to_string(), "Nachricht zu groß (max. 500 Zeichen)");
+    }
+
+    #[test]
+    fn help_is_generated_from_keymap() {
+        let mut app = input_app("", 0);
+        let text: Vec<String> = app.help_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect();
+        assert!(text.contains(&"  Ctrl+S        Nachricht senden".to_string()));
+        assert_eq!(text.iter().filter(|l| l.starts_with("── ")).count(), HelpSection::ALL.len());
+        assert_eq!(text.len(), 2 + KEYMAP.len() + 2 * HelpSection::ALL.len() + 1);
+
+        app.fallbacks = vec![Fallback::CtrlEnter];
+        let text: Vec<String> = app.help_lines().iter().map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect()).collect();
+        assert!(text.contains(&"── Ersatztasten (dieses Terminal) ──".to_string()));
+        assert!(text.contains(&Fallback::CtrlEnter.describe().to_string()));
+    }
+
+    mod cursor_props {
+        use super::*;
+        use proptest::prelude::*;
+
+        #[derive(Debug, Clone)]
+        enum Op {
+            Left,
+            Right,
+            Up,
+            Down,
+            Home,
+            End,
+            Insert(char),
+            Backspace,
+            Delete,
+        }
+
+        fn input_char() -> impl Strategy<Value = char> {
+            prop_oneof![
+                4 => any::<char>(),
+                3 => prop::char::range('a', 'z'),
+                1 => Just(' '),
+                1 => Just('\n'),
+                1 => Just('😀'),
+                1 => Just('日'),
+                1 => Just('\u{301}'), // combining accent (zero width)
+            ]
+        }
+
+        fn input_text() -> impl Strategy<Value = String> {
+            prop::collection::vec(input_char(), 0..120).prop_map(|chars| chars.into_iter().collect())
+        }
+
+        fn op() -> impl Strategy<Value = Op> {
+            prop_oneof![
+                Just(Op::Left),
+                Just(Op::Right),
+                Just(Op::Up),
+                Just(Op::Down),
+                Just(Op::Home),
+                Just(Op::End),
+                input_char().prop_map(Op::Insert),
+                Just(Op::Backspace),
+                Just(Op::Delete),
+            ]
+        }
+
+        fn apply(app: &mut App, op: &Op, width: usize) {
+            match op {
+                Op::Left => app.cursor_left(),
+                Op::Right => app.cursor_right(),
+                Op::Up => {
+                    app.cursor_up(width);
+                }
+                Op::Down => {
+                    app.cursor_down(width);
+                }
+                Op::Home => app.cursor_home(width),
+                Op::End => app.cursor_end(width),
+                Op::Insert(c) => app.insert_char(*c),
+                Op::Backspace => app.delete_back(),
+                Op::Delete => app.delete_forward(),
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn cursor_always_within_bounds(text in input_text(), cursor in 0usize..130, width in 2usize..40, ops in prop::collection::vec(op(), 0..60)) {
+                let mut app = input_app(&text, cursor);
+                for op in &ops {
+                    apply(&mut app, op, width);
+                    prop_assert!(app.cursor_pos <= app.input.chars().count());
+                    let (line, col) = app.cursor_line_col(width);
+                    prop_assert!(col <= width);
+                    prop_assert!(line < app.input_total_lines(width));
+                }
+            }
+
+            #[test]
+            fn line_col_round_trips(text in input_text(), width in 2usize..40) {
+                let app = input_app(&text, 0);
+                let positions = app.cursor_positions(width);
+                prop_assert_eq!(positions.len(), text.chars().count() + 1);
+                for &(line, col) in &positions {
+                    let pos = App::position_on_line(&positions, line, col);
+                    prop_assert!(pos.is_some());
+                    prop_assert_eq!(positions[pos.unwrap()], (line, col));
+                }
+                // Positions are in reading order
+                prop_assert!(positions.windows(2).all(|w| w[0] <= w[1]));
+            }
+
+            #[test]
+            fn wrapped_width_never_exceeded(text in input_text(), width in 2usize..40) {
+                let app = input_app(&text, 0);
+                let wrapped = app.wrap_text_for_display(width);
+                for line in wrapped.split('\n') {
+                    let line_width: usize = line.chars().map(|c| c.width().unwrap_or(1)).sum();
+                    prop_assert!(line_width <= width, "line {:?} wider than {}", line, width);
+                }
+                // Wrapping only inserts line breaks
+                prop_assert_eq!(wrapped.replace('\n', ""), text.replace('\n', ""));
+                prop_assert!(wrapped.split('\n').count() <= app.input_total_lines(width));
+            }
+
+            #[test]
+            fn home_end_idempotent(text in input_text(), cursor in 0usize..130, width in 2usize..40) {
+                let mut app = input_app(&text, cursor);
+                let (line, _) = app.cursor_line_col(width);
+
+                app.cursor_home(width);
+                let home = app.cursor_pos;
+                prop_assert_eq!(app.cursor_line_col(width), (line, 0));
+                app.cursor_home(width);
+                prop_assert_eq!(app.cursor_pos, home);
+
+                app.cursor_end(width);
+                let end = app.cursor_pos;
+                prop_assert_eq!(app.cursor_line_col(width).0, line);
+                app.cursor_end(width);
+                prop_assert_eq!(app.cursor_pos, end);
+                prop_assert!(home <= end);
+            }
+
+            #[test]
+            fn up_down_keep_line_adjacency(text in input_text(), cursor in 0usize..130, width in 2usize..40) {
+                let mut app = input_app(&text, cursor);
+                let (line, _) = app.cursor_line_col(width);
+                let total = app.input_total_lines(width);
+
+                let moved = line + 1 < total;
+
+                app.cursor_down(width);
+                prop_assert_eq!(app.cursor_line_col(width).0, if moved { line + 1 } else { line });
+
+                app.cursor_up(width);
+                prop_assert_eq!(app.cursor_line_col(width).0, if moved { line } else { line.saturating_sub(1) });
+            }
+        }
+    }
+
+    fn rendered_rows(terminal: &ratatui::Terminal<ratatui::backend::TestBackend>) -> Vec<String> {
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn draws_main_screen_on_test_backend() {
+        let mut app = input_app("Entwurf", 7);
+        app.deterministic = true;
+        app.messages.clear();
+        app.messages.push(Message::new("user", "Hallo Hank"));
+        app.messages.push(Message::new("assistant", "Servus"));
+        // Scrolled all the way up, past the padding below the transcript
+        app.auto_scroll = false;
+        app.scroll = u16::MAX;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(50, 14)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let rows = rendered_rows(&terminal);
+        let find = |text: &str| rows.iter().position(|row| row.contains(text));
+        let (own, answer, draft) = (find("Hallo Hank"), find("Servus"), find("Entwurf"));
+        assert!(own.is_some() && answer > own && draft > answer, "{:#?}", rows);
+        assert_eq!(app.chat_geometry, Some((48, 6)));
+
+        // Overlays draw on top of the same frame
+        app.oversize = Some(Oversize { text: "x".repeat(30), to: None, limit: 20 });
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        assert!(rendered_rows(&terminal).iter().any(|row| row.contains("Nachricht zu lang")));
+    }
+
+    #[tokio::test]
+    async fn new_messages_while_scrolled_up_keep_the_view_and_show_a_pill() {
+        let mut app = App::new(Arc::new(FixedBackend((1001..=1023).collect())), false);
+        app.deterministic = true;
+        app.messages = (1001..=1020)
+            .map(|ts| Message::from_server(ServerMessage { role: "assistant".to_string(), content: ts.to_string(), timestamp: ts, ..Default::default() }))
+            .collect();
+        app.last_timestamp = 1020;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(50, 14)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        (0..3).for_each(|_| app.scroll_up());
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let before: Vec<String> = rendered_rows(&terminal)[1..7].to_vec();
+
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        spawn_poll(&mut app, events.clone());
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        apply_poll(&mut app, *batch, &events);
+        assert_eq!(app.unseen_count(), 3);
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let rows = rendered_rows(&terminal);
+        assert_eq!(rows[1..7], before[..], "{:#?}", rows);
+        assert!(rows.iter().any(|row| row.contains("3 neue Nachrichten ↓ [End]")), "{:#?}", rows);
+        let pill = app.unread_pill.unwrap();
+        assert!(rows[pill.y as usize].contains("3 neue"));
+
+        // End (or a click on the pill) shows the first of them
+        assert!(app.jump_to_unseen());
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let rows = rendered_rows(&terminal);
+        assert!(rows[1].contains("1021"), "{:#?}", rows);
+        assert!(app.unread_pill.is_none() && !app.jump_to_unseen());
+    }
+
+    #[test]
+    fn draws_streaming_answer_below_transcript_on_test_backend() {
+        let mut app = input_app("nächste", 7);
+        app.messages.clear();
+        app.messages.push(Message::new("user", "Frage"));
+        app.loading = true;
+        app.streaming = Some("Teilantwort".to_string());
+        app.auto_scroll = false;
+        app.scroll = u16::MAX;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 12)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let rows = rendered_rows(&terminal);
+        let find = |text: &str| rows.iter().position(|row| row.contains(text));
+        assert!(find("Frage").is_some() && find("Teilantwort▌") > find("Frage"), "{:#?}", rows);
+        // The input stays usable while the answer streams in
+        assert!(find("Esc=Stopp").is_some() && find("nächste") > find("Esc=Stopp"));
+    }
+
+    #[test]
+    fn incremental_search_highlights_and_scrolls_to_hits() {
+        let lines = vec![Line::from(vec![Span::raw("Hallo "), Span::styled("Welt", Style::default().fg(Color::Green))]), Line::from("welt, WELT")];
+        let hits = find_in_lines(&lines, "welt");
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0], SearchHit { line: 0, start: 6, len: 4 });
+        assert_eq!(find_in_lines(&lines, "WELT"), vec![SearchHit { line: 1, start: 6, len: 4 }]);
+        let mut line = lines[0].clone();
+        highlight_chars(&mut line, 4, 4, Style::default().bg(Color::Yellow));
+        let parts: Vec<(&str, Option<Color>)> = line.spans.iter().map(|s| (s.content.as_ref(), s.style.bg)).collect();
+        assert_eq!(parts, vec![("Hall", None), ("o ", Some(Color::Yellow)), ("We", Some(Color::Yellow)), ("lt", None)]);
+        assert_eq!(line.spans[2].style.fg, Some(Color::Green));
+
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        app.messages.push(Message::new("user", format!("{} Nadel", "Heu ".repeat(30))));
+        for i in 0..20 {
+            app.messages.push(Message::new("assistant", format!("Antwort {}", i)));
+        }
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(50, 16)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        assert!(!rendered_rows(&terminal).iter().any(|row| row.contains("Nadel")));
+
+        app.open_search();
+        for c in "nadel".chars() {
+            app.search_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        app.search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let rows = rendered_rows(&terminal);
+        // The hit sits on a wrapped row further down the long message
+        let row = rows.iter().position(|row| row.contains("Nadel")).expect("hit scrolled into view");
+        assert!(rows.iter().any(|row| row.contains("/nadel  1/1")));
+        let x = rows[row][..rows[row].find("Nadel").unwrap()].chars().count() as u16;
+        assert!(terminal.backend().buffer()[(x, row as u16)].modifier.contains(ratatui::style::Modifier::UNDERLINED));
+
+        let search = app.search.as_mut().unwrap();
+        search.query = "antwort 1".to_string();
+        search.current = 1;
+        assert_eq!(app.search_hits().len(), 11); // 1 and 10…19
+        app.step_search(false);
+        assert_eq!(app.search.as_ref().unwrap().current, 0);
+        app.step_search(false);
+        assert_eq!(app.search.as_ref().unwrap().current, 10);
+    }
+
+    #[test]
+    fn selected_message_is_highlighted_and_scrolled_into_view() {
+        let mut app = input_app("", 0);
+        app.messages.clear();
+        for i in 0..10 {
+            app.messages.push(Message::new(if i % 2 == 0 { "user" } else { "assistant" }, format!("Nachricht {}", i)));
+        }
+        app.messages.push(Message::new("system", "Hinweis"));
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(50, 14)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+
+        // The first move selects the newest message, later ones walk up and stop at the top
+        app.move_selection(-1);
+        assert_eq!(app.selected, Some(10));
+        assert_eq!(app.target_message(|m| m.role != "system"), Some(10));
+        for _ in 0..20 {
+            app.move_selection(-1);
+        }
+        assert_eq!(app.selected, Some(0));
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let rows = rendered_rows(&terminal);
+        let row = rows.iter().position(|row| row.contains("Nachricht 0")).expect("selection scrolled into view");
+        let col = rows[row].find("Nachricht 0").unwrap();
+        let x = rows[row][..col].chars().count() as u16;
+        let cell = &terminal.backend().buffer()[(x, row as u16)];
+        assert!(cell.modifier.contains(ratatui::style::Modifier::REVERSED));
+        assert!(!app.auto_scroll);
+
+        app.move_selection(1);
+        assert_eq!(app.selected, Some(1));
+        assert_eq!(app.target_message(|_| false), Some(1));
+        app.selected = None;
+        assert_eq!(app.target_message(|m| m.role != "system"), Some(9));
+    }
+
+    /// Streams the request back in two pieces
+    struct EchoBackend;
+
+    impl ChatBackend for EchoBackend {
+        fn server_url(&self) -> &str {
+            "http://echo"
+        }
+
+        fn fetch_messages<'a>(&'a self, _since: u64, _room: Option<&'a str>, _timeout: Duration) -> BoxFuture<'a, Result<Vec<ServerMessage>, String>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn send_chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+            Box::pin(async move {
+                Ok(ChatResponse {
+                    content: format!("echo: {}", request.message),
+                    complete: true,
+                    message_id: None,
+                    tool_calls: Vec::new(),
+                    model: None,
+                })
+            })
+        }
+
+        fn send_chat_stream(
+            &self,
+            request: ChatRequest,
+            chunks: tokio::sync::mpsc::UnboundedSender<String>,
+        ) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+            let _ = chunks.send("echo: ".to_string());
+            let _ = chunks.send(request.message.clone());
+            self.send_chat(request)
+        }
+
+        fn clear_messages<'a>(&'a self, _room: Option<&'a str>) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_run_in_background_and_report_through_events() {
+        let mut app = App::new(Arc::new(EchoBackend), false);
+        app.messages.clear();
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        let pending = app.push_own_message("hallo".to_string(), None);
+        deliver(&mut app, pending, &events);
+        assert!(app.loading && app.in_flight.is_some());
+
+        // Chunks arrive before the reply, then the reply replaces them
+        let mut streamed = String::new();
+        loop {
+            match inbox.recv().await.unwrap() {
+                AppEvent::Chunk(_, chunk) => streamed.push_str(&chunk),
+                AppEvent::Replied { server_url, elapsed, result, .. } => {
+                    finish_delivery(&mut app, &server_url, elapsed, result);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(streamed, "echo: hallo");
+        assert!(!app.loading && app.in_flight.is_none() && app.streaming.is_none());
+        assert_eq!(app.messages.last().unwrap().content, "echo: hallo");
+        assert!(matches!(app.messages[0].delivery, Some(Delivery::Sent)));
+
+        // Esc while waiting: the request is abandoned, nothing arrives later
+        let pending = app.push_own_message("noch eins".to_string(), None);
+        deliver(&mut app, pending, &events);
+        app.stop_request();
+        assert!(!app.loading && app.in_flight.is_none());
+        assert_eq!(app.last_error.as_deref(), Some("Anfrage abgebrochen"));
+        let count = app.messages.len();
+        while let Ok(Some(event)) = tokio::time::timeout(Duration::from_millis(50), inbox.recv()).await {
+            if let AppEvent::Replied { server_url, elapsed, result, .. } = event {
+                finish_delivery(&mut app, &server_url, elapsed, result);
+            }
+        }
+        assert_eq!(app.messages.len(), count);
+    }
+
+    #[tokio::test]
+    async fn polls_run_in_background_and_skip_switched_rooms() {
+        let mut app = App::new(Arc::new(FixedBackend(vec![10, 20])), false);
+        app.messages.clear();
+        app.last_timestamp = 0;
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        spawn_poll(&mut app, events.clone());
+        assert!(app.polling);
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        apply_poll(&mut app, *batch, &events);
+        assert!(!app.polling && app.last_sync.is_some());
+        assert_eq!(app.messages.len(), 2);
+
+        // A round made for another room is dropped
+        app.last_timestamp = 0;
+        spawn_poll(&mut app, events.clone());
+        let idx = app.join_room("anderswo");
+        app.switch_room(idx);
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        let before = app.messages.len();
+        apply_poll(&mut app, *batch, &events);
+        assert_eq!(app.messages.len(), before);
+        assert!(!app.polling);
+    }
+
+    #[tokio::test]
+    async fn startup_requests_do_not_hold_up_the_loop() {
+        // A server that accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        let app = App::new(Arc::new(HttpBackend::new(server_url)), false);
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        let started = Instant::now();
+        probe_capabilities(&app, &events);
+        initial_sync(&app, &events, false);
+        check_server(&app, &events, false);
+        assert!(started.elapsed() < Duration::from_millis(100));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(inbox.try_recv().is_err());
+
+        // Answers for a server left meanwhile are dropped
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        let mut app = App::new(Arc::new(FixedBackend(vec![10, 20])), false);
+        let before = app.messages.len();
+        initial_sync(&app, &events, false);
+        app.server_url = "http://anders".to_string();
+        apply_next(&mut app, &mut inbox).await;
+        assert_eq!(app.messages.len(), before);
+        assert!(app.last_sync.is_none());
+
+        // Otherwise they land as if awaited in place
+        app.server_url = "http://fixed".to_string();
+        initial_sync(&app, &events, false);
+        apply_next(&mut app, &mut inbox).await;
+        let contents: Vec<&str> = app.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["10", "20", "2 Nachrichten vom Server geladen"]);
+    }
+
+    #[tokio::test]
+    async fn polls_for_left_rooms_and_other_servers_are_discarded() {
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+
+        // Polled inside a room that is left before the answer arrives
+        let mut app = App::new(Arc::new(FixedBackend(vec![10, 20])), false);
+        app.messages.clear();
+        let idx = app.join_room("weg");
+        app.switch_room(idx);
+        spawn_poll(&mut app, events.clone());
+        app.leave_room();
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        apply_poll(&mut app, *batch, &events);
+        assert!(app.messages.is_empty());
+        assert!(app.last_sync.is_none() && !app.polling);
+
+        // A background room left meanwhile: its slot now holds another room
+        let mut app = App::new(Arc::new(FixedBackend(vec![10, 20])), false);
+        let left = app.join_room("alt");
+        app.join_room("neu");
+        spawn_poll(&mut app, events.clone());
+        app.switch_room(left);
+        app.leave_room();
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        apply_poll(&mut app, *batch, &events);
+        assert_eq!(app.rooms[1].name.as_deref(), Some("neu"));
+        assert_eq!((app.rooms[1].messages.len(), app.rooms[1].unread), (1, 0));
+
+        // Switched to another server before the answer arrived
+        let mut app = App::new(Arc::new(FixedBackend(vec![10, 20])), false);
+        app.messages.clear();
+        spawn_poll(&mut app, events.clone());
+        app.server_url = "http://anders".to_string();
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        apply_poll(&mut app, *batch, &events);
+        assert!(app.messages.is_empty() && app.last_sync.is_none());
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_backs_off_and_recovers() {
+        let mut app = App::new(Arc::new(HttpBackend::new("http://127.0.0.1:9".to_string())), false);
+        assert_eq!(app.poll_delay(), Duration::from_secs(2));
+        assert!(app.reconnect_banner().is_none());
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        spawn_poll(&mut app, events.clone());
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        apply_poll(&mut app, *batch, &events);
+        assert_eq!((app.poll_failures, app.connection_status), (1, ConnectionStatus::Disconnected));
+        assert_eq!(app.poll_delay(), Duration::from_secs(4));
+        let banner = app.reconnect_banner().unwrap();
+        assert!(banner.contains("Keine Verbindung zu http://127.0.0.1:9") && banner.ends_with("neuer Versuch in 4s"), "{}", banner);
+        app.poll_failures = 3;
+        assert_eq!(app.poll_delay(), Duration::from_secs(16));
+        app.poll_failures = 40;
+        assert_eq!(app.poll_delay(), Duration::from_secs(60));
+
+        // The server is back: polls return to their pace and the banner goes
+        app.backend = Arc::new(FixedBackend(vec![10]));
+        spawn_poll(&mut app, events.clone());
+        let Some(AppEvent::Polled(batch)) = inbox.recv().await else {
+            panic!("expected a poll result");
+        };
+        apply_poll(&mut app, *batch, &events);
+        // The missed window is fetched in the background before polling goes on
+        assert_eq!((app.poll_failures, app.polling), (0, true));
+        apply_next(&mut app, &mut inbox).await;
+        assert_eq!((app.polling, app.connection_status), (false, ConnectionStatus::Connected));
+        assert!(app.reconnect_banner().is_none());
+        assert!(app.messages.iter().any(|m| m.content.starts_with("Verbindung wiederhergestellt")));
+    }
+
+    #[test]
+    fn exports_keep_roles_timestamps_and_code_blocks() {
+        let question = Message { timestamp_ms: Some(1_714_560_000_000), ..Message::new("user", "Wie sortiere ich?") };
+        let answer = Message {
+            model: Some("qwen".to_string()),
+            ..Message::new("assistant", "So:\n\n```rust\nlet v: Vec<u8> = sort(v);\n```\nFertig.")
+        };
+        let messages = vec![question, Message::new("system", "Verbunden"), answer];
+        let speakers = Speakers { own: "Ada", assistant: "Hank" };
+
+        let md = export_transcript(&messages, Some("Sortieren"), ExportFormat::Markdown, speakers);
+        assert!(md.starts_with("# Sortieren\n"));
+        assert!(md.contains("### Ada · 2024-05-01") && md.contains("### Hank (qwen)"), "{}", md);
+        assert!(md.contains("```rust\nlet v: Vec<u8> = sort(v);\n```"));
+        assert!(!md.contains("Verbunden"));
+
+        let json: serde_json::Value = serde_json::from_str(&export_transcript(&messages, None, ExportFormat::Json, speakers)).unwrap();
+        assert_eq!(json["messages"].as_array().unwrap().len(), 2);
+        assert_eq!((json["messages"][0]["role"].as_str(), json["messages"][0]["name"].as_str()), (Some("user"), Some("Ada")));
+        assert_eq!(json["messages"][1]["model"].as_str(), Some("qwen"));
+
+        let html = export_transcript(&messages, None, ExportFormat::Html, speakers);
+        assert!(html.contains("<pre><code class=\"language-rust\">let v: Vec&lt;u8&gt; = sort(v);</code></pre>"), "{}", html);
+        assert!(html.contains("<p>Fertig.</p>"));
+
+        assert_eq!(ExportFormat::from_path(std::path::Path::new("chat.HTML")), Some(ExportFormat::Html));
+        assert_eq!(ExportFormat::from_path(std::path::Path::new("chat.txt")), None);
+        assert!(matches!(Command::parse("/export chat.md"), Some(Ok(Command::Export(file))) if file == "chat.md"));
+
+        let dir = std::env::temp_dir().join(format!("hank-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut app = input_app("", 0);
+        app.messages = messages;
+        let file = dir.join("chat.md");
+        assert!(app.export_conversation(file.to_str().unwrap()).is_ok());
+        assert!(std::fs::read_to_string(&file).unwrap().contains("```rust"));
+        assert!(app.export_conversation(dir.join("chat.txt").to_str().unwrap()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn imports_openai_chatgpt_and_markdown_transcripts() {
+        let speakers = Speakers { own: "Ada", assistant: "Hank" };
+        let openai = r#"[{"role": "system", "content": "Sei kurz."},
+            {"role": "user", "content": "Was ist 2+2?"},
+            {"role": "assistant", "content": [{"type": "text", "text": "4"}]}]"#;
+        let imported = import_transcript(openai, speakers).unwrap();
+        assert_eq!(imported.len(), 1);
+        let roles: Vec<(&str, &str)> = imported[0].messages.iter().map(|m| (m.role.as_str(), m.content.as_str())).collect();
+        assert_eq!(roles, vec![("user", "Was ist 2+2?"), ("assistant", "4")]);
+
+        // ChatGPT: only the branch leading to current_node counts
+        let chatgpt = r#"[{"title": "Rezepte", "current_node": "c", "mapping": {
+            "r": {"message": null, "parent": null},
+            "a": {"message": {"author": {"role": "user"}, "content": {"content_type": "text", "parts": ["Pfannkuchen?"]}, "create_time": 1714560000.5}, "parent": "r"},
+            "x": {"message": {"author": {"role": "assistant"}, "content": {"parts": ["verworfen"]}}, "parent": "a"},
+            "c": {"message": {"author": {"role": "assistant"}, "content": {"parts": ["Mehl, Eier, Milch"]}, "metadata": {"model_slug": "gpt-4o"}}, "parent": "a"}}}]"#;
+        let imported = import_transcript(chatgpt, speakers).unwrap();
+        assert_eq!(imported[0].title.as_deref(), Some("Rezepte"));
+        let contents: Vec<&str> = imported[0].messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["Pfannkuchen?", "Mehl, Eier, Milch"]);
+        assert_eq!(imported[0].messages[0].timestamp_ms, Some(1_714_560_000_500));
+        assert_eq!(imported[0].messages[1].model.as_deref(), Some("gpt-4o"));
+
+        // Our own Markdown export reads back with roles, times and code blocks
+        let messages = vec![
+            Message { timestamp_ms: Some(1_714_560_000_000), ..Message::new("user", "Code?") },
+            Message::new("assistant", "```md\n## User\n```\nso"),
+        ];
+        let md = export_transcript(&messages, Some("Runde"), ExportFormat::Markdown, speakers);
+        let imported = import_transcript(&md, speakers).unwrap();
+        assert_eq!(imported[0].title.as_deref(), Some("Runde"));
+        assert_eq!(imported[0].messages.len(), 2);
+        assert_eq!((imported[0].messages[0].role.as_str(), imported[0].messages[0].timestamp_ms), ("user", Some(1_714_560_000_000)));
+        assert_eq!(imported[0].messages[1].content, "```md\n## User\n```\nso");
+
+        let plain = "**User:** Hallo\n\n**ChatGPT:** Hi!\nWie geht's?";
+        let imported = import_transcript(plain, speakers).unwrap();
+        assert_eq!(imported[0].messages[1].content, "Hi!\nWie geht's?");
+        assert!(import_transcript("nur Text", speakers).is_err());
+
+        // Saved as a conversation of the server, found by the global search
+        let dir = env::temp_dir().join(format!("hank-import-{}", std::process::id()));
+        let path = save_imported(&dir, "http://hank", &ImportedConversation { title: Some("Alt".to_string()), messages: imported[0].messages.clone() }).unwrap();
+        let id = path.file_stem().unwrap().to_str().unwrap().to_string();
+        assert_eq!(ChatHistory::load_from(&path).unwrap().title.as_deref(), Some("Alt"));
+        let scan = JsonScan { server_url: "http://hank".to_string(), files: vec![(HistorySource::Session(id), path)] };
+        let hits = scan.search("geht", 10);
+        assert_eq!((hits.len(), hits[0].title.as_str()), (1, "Alt"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn one_shot_ask_passes_chunks_and_returns_the_answer() {
+        let request = ChatRequest {
+            message: "ping".to_string(),
+            display_name: None,
+            room: None,
+            thread_id: None,
+            to: None,
+            model: None,
+            client_id: None,
+            session: None,
+        };
+        let mut printed = String::new();
+        let response = ask(&EchoBackend, request, |chunk| printed.push_str(chunk)).await.unwrap();
+        assert_eq!(printed, "echo: ping");
+        assert_eq!(response.content, "echo: ping");
+    }
+
+    #[test]
+    fn piped_text_is_appended_fenced_and_size_checked() {
+        assert_eq!(piped_prompt("review this", "+ neu\n- alt\n", None, 100).unwrap(), "review this\n\n+ neu\n- alt");
+        assert_eq!(piped_prompt("review this", "+ neu", Some("diff"), 100).unwrap(), "review this\n\n```diff\n+ neu\n```");
+        // Backticks inside need a longer fence
+        assert_eq!(piped_prompt("", "a ```x``` b", Some(""), 100).unwrap(), "````\na ```x``` b\n````");
+        assert_eq!(piped_prompt("nur Frage", "", Some("rs"), 100).unwrap(), "nur Frage");
+        let err = piped_prompt("q", &"x".repeat(200), None, 100).unwrap_err();
+        assert!(err.contains("203 Zeichen, max. 100"), "{}", err);
+    }
+
+    #[test]
+    fn history_show_prints_greppable_lines() {
+        let messages = vec![
+            Message { timestamp_ms: Some(1_714_560_000_000), ..Message::new("user", "Erste Frage") },
+            Message::new("system", "Verbunden"),
+            Message { timestamp_ms: Some(1_714_560_001_000), ..Message::new("user", "Zweite") },
+            Message { timestamp_ms: Some(1_714_560_002_000), ..Message::new("assistant", "Zeile eins\nZeile zwei") },
+        ];
+        let speakers = Speakers { own: "Ada", assistant: "Hank" };
+        let plain = print_transcript(&messages, speakers, Some(2), false);
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", plain);
+        assert!(lines[0].starts_with("[2024-05-01 ") && lines[0].ends_with("] Ada: Zweite"), "{}", lines[0]);
+        assert!(lines[1].ends_with("] Hank: Zeile eins"));
+        assert_eq!(lines[2], "  Zeile zwei");
+        assert!(!plain.contains('\x1b'));
+
+        let colored = print_transcript(&messages, speakers, None, true);
+        assert!(colored.contains("\x1b[1;35mHank\x1b[0m:") && colored.contains("Erste Frage"));
+    }
+
+    #[test]
+    fn server_profiles_pick_server_token_and_theme() {
+        let mut config: Config = toml::from_str(
+            "host = \"localhost\"\nport = 8080\ntheme = \"dark\"\n\
+             [servers.work]\nurl = \"https://hank.firma.de\"\ntoken = \"geheim\"\ntheme = \"light\"\n\
+             [servers.home]\nhost = \"gpu-box\"\nconnect = true\n\
+             [servers.dunkel]\ntheme = \"dark\"\n",
+        )
+        .unwrap();
+        let home = config.servers.profile("home").unwrap();
+        assert_eq!(home.server_url(), "http://gpu-box:8080");
+        assert_eq!(config.servers.at_startup(), vec![home.clone()]);
+
+        config.apply_profile("work").unwrap();
+        assert_eq!((config.url.as_deref(), config.token.as_deref(), config.theme.as_deref()), (Some("https://hank.firma.de"), Some("geheim"), Some("light")));
+        config.apply_profile("home").unwrap();
+        assert_eq!((config.url.as_deref(), config.host.as_str(), config.port), (None, "gpu-box", 8080));
+        assert!(config.apply_profile("urlaub").unwrap_err().contains("[servers.urlaub]"));
+        // What a profile leaves out still comes from the environment (main)
+        let dark = config.apply_profile("dunkel").unwrap();
+        assert!(!dark.sets_server() && dark.token.is_none());
+        assert!(config.servers.profile("home").unwrap().sets_server());
+
+        // The plain list of extra servers still works and round-trips
+        let config: Config = toml::from_str("host = \"h\"\nport = 1\nservers = [\"gpu-box:8080\"]").unwrap();
+        assert_eq!(config.servers.at_startup()[0].server_url(), "http://gpu-box:8080");
+        assert!(config.servers.profile("gpu-box").is_none());
+        assert!(toml::to_string(&config).unwrap().contains("servers = [\"gpu-box:8080\"]"));
+    }
+
+    #[test]
+    fn config_is_only_written_on_request() {
+        let text = set_config_values("host = \"localhost\"\nport = 8080\ntheme = \"dark\"\n", &["host=gpu-box".to_string(), "port=9000".to_string(), "display.user_name=Ada".to_string(), "theme=".to_string()]).unwrap();
+        let config: Config = toml::from_str(&text).unwrap();
+        assert_eq!((config.host.as_str(), config.port, config.theme), ("gpu-box", 9000, None));
+        assert_eq!(config.display.user_name.as_deref(), Some("Ada"));
+        assert!(set_config_values(&text, &["port=hoch".to_string()]).unwrap_err().starts_with("Ungültige Config"));
+        assert!(set_config_values(&text, &["port".to_string()]).is_err());
+
+        let mut config = Config::default();
+        config.remember_server("https://hank.lan:8443");
+        assert_eq!((config.scheme, config.host.as_str(), config.port, config.url.as_deref()), (Scheme::Https, "hank.lan", 8443, None));
+        config.remember_server("https://hank.example.com/api/hank");
+        assert_eq!(config.url.as_deref(), Some("https://hank.example.com/api/hank"));
+        assert_eq!(Command::parse("/save-config"), Some(Ok(Command::SaveConfig)));
+    }
+
+    #[test]
+    fn config_edits_keep_comments_and_refuse_broken_files() {
+        let text = "# Mein Hank\nhost = \"localhost\" # LAN\nport = 8080\n\n# Farben\ntheme = \"dark\"\n";
+        let edited = set_config_values(text, &["host=gpu-box".to_string(), "servers.work.port=9000".to_string()]).unwrap();
+        assert_eq!(edited, "# Mein Hank\nhost = \"gpu-box\" # LAN\nport = 8080\n\n# Farben\ntheme = \"dark\"\n\n[servers.work]\nport = 9000\n");
+        assert!(set_config_values(&edited, &["host.name=x".to_string()]).unwrap_err().contains("keine Tabelle"));
+
+        let dir = env::temp_dir().join(format!("hank-tui-config-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        // No file yet: the defaults plus the change
+        Config::update_at(&path, &["theme"], |config| config.theme = Some("light".to_string())).unwrap();
+        assert_eq!(Config::load_from(&path).unwrap().theme.as_deref(), Some("light"));
+
+        fs::write(&path, text).unwrap();
+        Config::update_at(&path, &["timestamps", "theme"], |config| {
+            config.timestamps = TimestampMode::Full;
+            config.theme = None;
+        })
+        .unwrap();
+        // A removed key takes the comment above it along
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Mein Hank\nhost = \"localhost\" # LAN\nport = 8080\ntimestamps = \"full\"\n");
+        Config::update_at(&path, &["scheme", "host", "port", "url"], |config| config.remember_server("https://hank.example.com/api/hank")).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# Mein Hank\nhost = \"localhost\" # LAN\n") && saved.contains("url = \"https://hank.example.com/api/hank\""), "{}", saved);
+
+        // A file that does not parse is reported and never overwritten
+        let broken = "host = \"localhost\"\nport = 80 80\n";
+        fs::write(&path, broken).unwrap();
+        assert!(Config::load_from(&path).unwrap_err().contains("Zeile 2"));
+        assert!(Config::update_at(&path, &["theme"], |config| config.theme = Some("light".to_string())).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), broken);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn server_check_reports_version_model_and_protocol_mismatch() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        // No /health, but /version
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let n = socket.read(&mut request).await.unwrap_or(0);
+                let response = if request[..n].starts_with(b"GET /version ") {
+                    let body = r#"{"version": "2.0.1", "model": "qwen2.5", "protocol": 2}"#;
+                    format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{}", body.len(), body)
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nconnection: close\r\ncontent-length: 0\r\n\r\n".to_string()
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let mut app = App::new(Arc::new(HttpBackend::new(server_url.clone())), false);
+        app.deterministic = true;
+        let (events, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+        check_server(&app, &events, false);
+        apply_next(&mut app, &mut inbox).await;
+        let info = app.server_info.clone().unwrap();
+        assert_eq!((info.version.as_deref(), info.protocol), (Some("2.0.1"), Some(2)));
+        assert_eq!(app.model.as_deref(), Some("qwen2.5"));
+        let lines: Vec<&str> = app.messages.iter().rev().take(2).map(|m| m.content.as_str()).collect();
+        assert_eq!(lines[1], format!("Server: {} · v2.0.1 · Modell qwen2.5", server_url));
+        assert!(lines[0].starts_with("Server spricht Protokoll 2, hank-tui Protokoll 1"));
+        assert!(app.status_line("{version}").starts_with("2.0.1"));
+        assert_eq!(Command::parse("/status"), Some(Ok(Command::Status)));
+
+        // Without the endpoints the startup check warns
+        let mut app = App::new(Arc::new(FixedBackend(vec![])), false);
+        check_server(&app, &events, false);
+        apply_next(&mut app, &mut inbox).await;
+        let last = app.messages.last().unwrap();
+        assert_eq!((last.role.as_str(), last.content.as_str()), ("error", "Server-Check fehlgeschlagen: Server liefert keine Versionsinfo"));
+        check_server(&app, &events, true);
+        apply_next(&mut app, &mut inbox).await;
+        assert!(app.last_error.as_deref().is_some_and(|e| e.contains("Versionsinfo")));
+    }
+
+    #[tokio::test]
+    async fn plain_health_answers_go_on_to_the_version() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let n = socket.read(&mut request).await.unwrap_or(0);
+                let body = if request[..n].starts_with(b"GET /health ") { "ok" } else { r#"{"version": "1.4.2", "model": "llama3", "protocol": 2}"# };
+                let response = format!("HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let info = HttpBackend::new(server_url).fetch_server_info().await.unwrap();
+        assert_eq!((info.version.as_deref(), info.model.as_deref(), info.protocol), (Some("1.4.2"), Some("llama3"), Some(2)));
+        assert!(info.incompatible());
+    }
+}
@@ -1,27 +1,27 @@
-use arboard::Clipboard;
-use chrono::{Local, TimeZone};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{DisableMouseCapture, PopKeyboardEnhancementFlags},
     execute,
+    style::Print,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ratatui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
-    text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Wrap},
-    Terminal,
-};
-use serde::{Deserialize, Serialize};
-use std::{env, fs, io, panic, path::PathBuf, time::Instant};
-use unicode_width::UnicodeWidthChar;
+use hank_tui::{export_transcript, parse_structured, piped_prompt, print_transcript, ChatRequest, PIPE_LIMIT, import_transcript, run_app, save_imported, session_title, tr, trf, server_url_with, set_config_values, set_language, App, ChatHistory, ClientOptions, Config, ExportFormat, Fallback, HttpBackend, HttpTrace, InputHeight, KeyBindings, Lang, Scheme, ServerProfile, Speakers, Theme, Tmux};
+use ratatui::{backend::CrosstermBackend, Terminal, TerminalOptions, Viewport};
+use std::{fs, io::{self, IsTerminal, Read, Write}, panic, path::{Path, PathBuf}, sync::Arc, time::Duration};
 
 #[derive(Parser, Debug)]
 #[command(name = "hank-tui")]
 #[command(about = "Terminal UI for Hank chat server", long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    task: Option<Task>,
+
+    /// Full server URL, also behind a reverse proxy with a path prefix, e.g.
+    /// https://hank.example.com/api/hank (can also be set via HANK_SERVER
+    /// environment variable)
+    #[arg(long, value_name = "URL", conflicts_with_all = ["host", "port"])]
+    url: Option<String>,
+
     /// Host to connect to (can also be set via HANK_HOST environment variable)
     #[arg(short = 'H', long)]
     host: Option<String>,
@@ -29,1683 +29,502 @@ struct Args {
     /// Port to connect to (can also be set via HANK_PORT environment variable)
     #[arg(short, long)]
     port: Option<u16>,
-    
-    /// Disable chat history (do not load or save)
+
+    /// Connect via https (same as scheme = "https" in the config)
     #[arg(long)]
-    no_history: bool,
-}
+    tls: bool,
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-struct Config {
-    host: String,
-    port: u16,
-}
+    /// PEM file with extra trusted CA certificates for https
+    #[arg(long, value_name = "FILE")]
+    ca_bundle: Option<std::path::PathBuf>,
 
-impl Config {
-    fn config_path() -> Option<PathBuf> {
-        dirs::config_dir().map(|mut path| {
-            path.push("hank-tui");
-            path.push("config.toml");
-            path
-        })
-    }
+    /// Accept self-signed or otherwise invalid certificates
+    #[arg(long)]
+    insecure: bool,
 
-    fn load() -> Self {
-        Self::config_path()
-            .and_then(|path| fs::read_to_string(path).ok())
-            .and_then(|content| toml::from_str(&content).ok())
-            .unwrap_or_else(|| Config {
-                host: "localhost".to_string(),
-                port: 8080,
-            })
-    }
+    /// Proxy for all requests, e.g. http://proxy:3128 or socks5://proxy:1080, or "none" to ignore
+    /// HTTP_PROXY/HTTPS_PROXY/ALL_PROXY (overrides proxy in the config)
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
 
-    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(path) = Self::config_path() {
-            if let Some(parent) = path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            let content = toml::to_string_pretty(self)?;
-            fs::write(path, content)?;
-        }
-        Ok(())
-    }
-}
+    /// Seconds to wait for an answer to start (default 120; a streamed answer
+    /// may take longer; overrides request_timeout in the config)
+    #[arg(long, value_name = "SECS")]
+    request_timeout: Option<u64>,
 
-#[derive(Clone, Serialize, Deserialize)]
-struct Message {
-    role: String,
-    content: String,
-    timestamp: String,
-    #[serde(default)]
-    timestamp_ms: Option<u64>,
-}
+    /// Seconds between polls for new messages (default 2; overrides
+    /// poll_interval in the config)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    poll_interval: Option<u64>,
 
-#[derive(Serialize, Deserialize)]
-struct ChatHistory {
-    server_url: String,
-    messages: Vec<Message>,
-    saved_at: String,
-}
+    /// Seconds one poll may take (default 2; overrides poll_timeout in the
+    /// config)
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    poll_timeout: Option<u64>,
 
-impl ChatHistory {
-    fn history_path() -> Option<PathBuf> {
-        dirs::config_dir().map(|mut path| {
-            path.push("hank-tui");
-            path.push("history.json");
-            path
-        })
-    }
+    /// Token sent with every request (can also be set via HANK_TOKEN
+    /// environment variable; overrides token in the config)
+    #[arg(long, value_name = "TOKEN")]
+    token: Option<String>,
 
-    fn load() -> Option<Self> {
-        Self::history_path()
-            .and_then(|path| fs::read_to_string(path).ok())
-            .and_then(|content| serde_json::from_str(&content).ok())
-    }
+    /// Disable chat history (do not load or save)
+    #[arg(long)]
+    no_history: bool,
 
-    fn save(server_url: &str, messages: &[Message]) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(path) = Self::history_path() {
-            if let Some(parent) = path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            
-            // Only save last 100 messages
-            let messages_to_save: Vec<Message> = messages
-                .iter()
-                .rev()
-                .take(100)
-                .rev()
-                .cloned()
-                .collect();
-            
-            let history = ChatHistory {
-                server_url: server_url.to_string(),
-                messages: messages_to_save,
-                saved_at: Local::now().to_rfc3339(),
-            };
-            
-            let content = serde_json::to_string_pretty(&history)?;
-            fs::write(path, content)?;
-        }
-        Ok(())
-    }
-    
-    fn delete() -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(path) = Self::history_path() {
-            if path.exists() {
-                fs::remove_file(path)?;
-            }
-        }
-        Ok(())
-    }
-}
+    /// Use the server of `[servers.<name>]` from the config (host, port, token, theme)
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
 
-#[derive(PartialEq)]
-enum Focus {
-    Input,
-    Chat,
-    Help,
-}
+    /// Additional server to connect to (host:port, URL or profile name, repeatable; switch with F6)
+    #[arg(long = "connect", value_name = "SERVER")]
+    connect: Vec<String>,
 
-struct App {
-    input: String,
-    cursor_pos: usize,
-    messages: Vec<Message>,
-    server_url: String,
-    loading: bool,
-    scroll: u16,
-    input_scroll: u16,  // Scroll offset for input field
-    command_history: Vec<String>,
-    history_index: Option<usize>,
-    connection_status: String,
-    last_error: Option<String>,
-    auto_scroll: bool,
-    focus: Focus,
-    history_enabled: bool,
-    last_timestamp: u64,
-    last_poll: Instant,
-    debug_overlay: bool,
-}
+    /// Screen reader mode: no alternate screen, transcript as plain lines
+    #[arg(long)]
+    accessible: bool,
 
-#[derive(Serialize)]
-struct ChatRequest {
-    message: String,
-}
+    /// Send this message, print the answer and exit (like `hank-tui ask`)
+    #[arg(long, value_name = "QUESTION")]
+    oneshot: Option<String>,
 
-#[derive(Deserialize)]
-struct ChatResponse {
-    content: String,
-    #[allow(dead_code)]
-    complete: bool,
-}
+    /// With --oneshot: print the answer as JSON
+    #[arg(long, requires = "oneshot")]
+    json: bool,
 
-#[derive(Deserialize, Serialize)]
-struct ServerMessage {
-    role: String,
-    content: String,
-    timestamp: u64,
-}
+    /// Color preset: dark, high-contrast, light-terminal, solarized,
+    /// no-color, deuteranopia, protanopia, tritanopia or a theme file in
+    /// ~/.config/hank-tui/themes/ (default: theme in the config, dark, or
+    /// no-color if NO_COLOR is set)
+    #[arg(long, value_name = "PRESET")]
+    theme: Option<String>,
 
-enum PollEvent {
-    Messages(Vec<Message>),
-    Error(String),
-}
+    /// Input pane size for this run: lines, "auto:N" (grows up to N lines)
+    /// or "N%" of the screen (overrides input_height in the config)
+    #[arg(long, value_name = "HEIGHT")]
+    input_height: Option<InputHeight>,
 
-impl App {
-    fn new(server_url: String, history_enabled: bool) -> Self {
-        let mut messages = Vec::new();
-        
-        // Load history if enabled
-        if history_enabled {
-            if let Some(history) = ChatHistory::load() {
-                if history.server_url == server_url {
-                    messages = history.messages;
-                    messages.push(Message {
-                        role: "system".to_string(),
-                        content: format!("Historie geladen ({} Nachrichten) - {}", 
-                            messages.len(), history.saved_at),
-                        timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                    });
-                } else {
-                    messages.push(Message {
-                        role: "system".to_string(),
-                        content: format!("Neue Session für {}", server_url),
-                        timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                    });
-                }
-            } else {
-                messages.push(Message {
-                    role: "system".to_string(),
-                    content: format!("Verbunden mit {} (History aktiviert)", server_url),
-                    timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                });
-            }
-        } else {
-            messages.push(Message {
-                role: "system".to_string(),
-                content: format!("Verbunden mit {} (History deaktiviert)", server_url),
-                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-            });
-        }
-        
-        let last_timestamp = messages
-            .iter()
-            .filter_map(|m| m.timestamp_ms)
-            .max()
-            .unwrap_or(0);
-
-        Self {
-            input: String::new(),
-            cursor_pos: 0,
-            messages,
-            server_url,
-            loading: false,
-            scroll: 0,
-            input_scroll: 0,
-            command_history: Vec::new(),
-            history_index: None,
-            connection_status: "Connected".to_string(),
-            last_error: None,
-            auto_scroll: true,
-            focus: Focus::Input,
-            history_enabled,
-            last_timestamp,
-            last_poll: Instant::now(),
-            debug_overlay: false,
-        }
-    }
+    /// Language of the interface: de or en (overrides lang in the config)
+    #[arg(long, value_name = "LANG")]
+    lang: Option<Lang>,
 
-    fn navigate_history_up(&mut self) {
-        if self.command_history.is_empty() {
-            return;
-        }
-        
-        let new_index = match self.history_index {
-            None => Some(self.command_history.len() - 1),
-            Some(0) => Some(0),
-            Some(i) => Some(i - 1),
-        };
-        
-        if let Some(idx) = new_index {
-            self.history_index = Some(idx);
-            self.input = self.command_history[idx].clone();
-            self.cursor_pos = self.input.len();
-        }
-    }
-
-    fn navigate_history_down(&mut self) {
-        if self.command_history.is_empty() {
-            return;
-        }
-        
-        match self.history_index {
-            None => {}
-            Some(i) if i >= self.command_history.len() - 1 => {
-                self.history_index = None;
-                self.input.clear();
-                self.cursor_pos = 0;
-            }
-            Some(i) => {
-                self.history_index = Some(i + 1);
-                self.input = self.command_history[i + 1].clone();
-                self.cursor_pos = self.input.len();
-            }
-        }
-    }
-    
-    fn scroll_to_bottom(&mut self) {
-        self.scroll = 0;
-        self.auto_scroll = true;
-    }
-    
-    fn scroll_up(&mut self) {
-        self.auto_scroll = false;
-        self.scroll = self.scroll.saturating_add(1);
-    }
-    
-    fn scroll_down(&mut self) {
-        if self.scroll > 0 {
-            self.scroll = self.scroll.saturating_sub(1);
-        }
-        if self.scroll == 0 {
-            self.auto_scroll = true;
-        }
-    }
+    /// Plain ASCII without color (serial consoles, minimal terminals)
+    #[arg(long)]
+    ascii: bool,
 
-    fn scroll_page_up(&mut self, amount: u16) {
-        self.auto_scroll = false;
-        self.scroll = self.scroll.saturating_add(amount.max(1));
-    }
+    /// Reproducible frames for recordings and snapshot tests: frozen
+    /// timestamps, no clock, timers or flashing
+    #[arg(long)]
+    deterministic: bool,
 
-    fn scroll_page_down(&mut self, amount: u16) {
-        if self.scroll > amount {
-            self.scroll = self.scroll.saturating_sub(amount);
-        } else {
-            self.scroll = 0;
-            self.auto_scroll = true;
-        }
-    }
+    /// Log every HTTP request and response (secrets redacted) to
+    /// ~/.config/hank-tui/http.log and the F12 debug pane
+    #[arg(long)]
+    debug_http: bool,
+}
 
-    fn jump_to_top(&mut self) {
-        self.auto_scroll = false;
-        self.scroll = u16::MAX;
-    }
+#[derive(Subcommand, Debug)]
+enum Task {
+    /// Write a saved conversation as Markdown, JSON or HTML
+    Export {
+        /// Conversation id from ~/.config/hank-tui/sessions/ or a history
+        /// file (default: the main conversation)
+        #[arg(long)]
+        session: Option<String>,
+
+        /// Export the history of this room instead
+        #[arg(long, conflicts_with = "session")]
+        room: Option<String>,
+
+        /// md, json or html (default: from the output file, else md)
+        #[arg(long, short)]
+        format: Option<ExportFormat>,
+
+        /// File to write (default: standard output)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Turn an OpenAI-style JSON chat export or a Markdown transcript into
+    /// saved conversations for the current server
+    Import {
+        file: PathBuf,
+    },
+
+    /// Saved conversations without the UI
+    History {
+        #[command(subcommand)]
+        action: HistoryTask,
+    },
+
+    /// Change the saved settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigTask,
+    },
+
+    /// Send one message, print Hank's answer and exit (for scripts)
+    /// Text piped into stdin is appended to the question
+    Ask {
+        question: Vec<String>,
+
+        /// Wrap the piped text in a code fence, optionally with a language
+        #[arg(long, value_name = "LANG", num_args = 0..=1, default_missing_value = "")]
+        fence: Option<String>,
+
+        /// Longest prompt in characters (default: max_message_chars from the
+        /// config, else 100000)
+        #[arg(long, value_name = "CHARS")]
+        max_stdin: Option<usize>,
+
+        /// Ask in this room instead of the default channel
+        #[arg(long)]
+        room: Option<String>,
+
+        /// Answer with this model instead of the server's default
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Print the answer as one JSON object (content, model, tool_calls)
+        #[arg(long)]
+        json: bool,
+    },
+}
 
-    fn jump_to_bottom(&mut self) {
-        self.scroll = 0;
-        self.auto_scroll = true;
-    }
-    
-    fn toggle_focus(&mut self) {
-        self.focus = match self.focus {
-            Focus::Input => Focus::Chat,
-            Focus::Chat => Focus::Input,
-            Focus::Help => Focus::Input,
-        };
-    }
-    
-    fn toggle_help(&mut self) {
-        self.focus = match self.focus {
-            Focus::Help => Focus::Input,
-            _ => Focus::Help,
-        };
-    }
-    
-    /// Calculate cursor line and column for given width (accounting for wrapping and newlines)
-    fn cursor_line_col(&self, width: usize) -> (usize, usize) {
-        if width == 0 {
-            return (0, 0);
-        }
-        
-        let mut line = 0;
-        let mut col = 0;
-        
-        for (i, ch) in self.input.chars().enumerate() {
-            // Return position BEFORE processing this character
-            if i == self.cursor_pos {
-                return (line, col);
-            }
-            
-            if ch == '\n' {
-                line += 1;
-                col = 0;
-            } else {
-                let char_width = ch.width().unwrap_or(1);
-                // Wrap BEFORE adding character if it would exceed width
-                if col + char_width > width {
-                    line += 1;
-                    col = 0;
-                }
-                col += char_width;
-            }
-        }
-        
-        // Cursor is at the end of input
-        (line, col)
-    }
-    
-    /// Calculate total lines for input (accounting for wrapping and newlines)
-    fn input_total_lines(&self, width: usize) -> usize {
-        if width == 0 || self.input.is_empty() {
-            return 1;
-        }
-        
-        let mut lines = 1;
-        let mut col = 0;
-        
-        for ch in self.input.chars() {
-            if ch == '\n' {
-                lines += 1;
-                col = 0;
-            } else {
-                let char_width = ch.width().unwrap_or(1);
-                // Wrap BEFORE adding character if it would exceed width
-                if col + char_width > width {
-                    lines += 1;
-                    col = 0;
-                }
-                col += char_width;
-            }
-        }
-        
-        lines
-    }
-    
-    /// Move cursor up one line in input
-    fn cursor_up(&mut self, width: usize) {
-        if width == 0 {
-            return;
-        }
-        
-        let (line, target_col) = self.cursor_line_col(width);
-        
-        if line == 0 {
-            return; // Already at first line
-        }
-        
-        // Find position at same column in previous line
-        let target_line = line - 1;
-        let mut current_line = 0;
-        let mut current_col = 0;
-        let mut last_pos_on_target_line = 0;
-        
-        for (i, ch) in self.input.chars().enumerate() {
-            if current_line == target_line {
-                last_pos_on_target_line = i;
-                if current_col >= target_col {
-                    self.cursor_pos = i;
-                    return;
-                }
-            }
-            if current_line > target_line {
-                // Went past target line
-                self.cursor_pos = last_pos_on_target_line;
-                return;
-            }
-            
-            if ch == '\n' {
-                if current_line == target_line {
-                    // End of target line before reaching column
-                    self.cursor_pos = i;
-                    return;
-                }
-                current_line += 1;
-                current_col = 0;
-            } else {
-                let char_width = ch.width().unwrap_or(1);
-                // Wrap BEFORE if would exceed
-                if current_col + char_width > width {
-                    if current_line == target_line {
-                        // End of target line (wrapped)
-                        self.cursor_pos = i;
-                        return;
-                    }
-                    current_line += 1;
-                    current_col = 0;
-                }
-                current_col += char_width;
-            }
-        }
-        
-        self.cursor_pos = last_pos_on_target_line.min(self.input.len());
-    }
-    
-    /// Move cursor down one line in input
-    fn cursor_down(&mut self, width: usize) {
-        if width == 0 {
-            return;
-        }
-        
-        let (line, target_col) = self.cursor_line_col(width);
-        let total_lines = self.input_total_lines(width);
-        
-        if line >= total_lines - 1 {
-            return; // Already at last line
-        }
-        
-        // Find position at same column in next line
-        let target_line = line + 1;
-        let mut current_line = 0;
-        let mut current_col = 0;
-        let mut last_pos_on_target_line = self.input.len();
-        
-        for (i, ch) in self.input.chars().enumerate() {
-            if current_line == target_line {
-                last_pos_on_target_line = i;
-                if current_col >= target_col {
-                    self.cursor_pos = i;
-                    return;
-                }
-            }
-            
-            if ch == '\n' {
-                if current_line == target_line {
-                    // End of target line before reaching column
-                    self.cursor_pos = i;
-                    return;
-                }
-                current_line += 1;
-                current_col = 0;
-            } else {
-                let char_width = ch.width().unwrap_or(1);
-                // Wrap BEFORE if would exceed
-                if current_col + char_width > width {
-                    if current_line == target_line {
-                        // End of target line (wrapped)
-                        self.cursor_pos = i;
-                        return;
-                    }
-                    current_line += 1;
-                    current_col = 0;
-                }
-                current_col += char_width;
-            }
-        }
-        
-        // Cursor ends up at end of input if target line is last
-        self.cursor_pos = self.input.len();
-    }
-    
-    /// Update input scroll to keep cursor visible
-    fn update_input_scroll(&mut self, width: usize, visible_lines: u16) {
-        if width == 0 || visible_lines == 0 {
-            return;
-        }
-        
-        let (cursor_line, _) = self.cursor_line_col(width);
-        let cursor_line = cursor_line as u16;
-        
-        // Scroll up if cursor is above visible area
-        if cursor_line < self.input_scroll {
-            self.input_scroll = cursor_line;
-        }
-        // Scroll down if cursor is below visible area
-        if cursor_line >= self.input_scroll + visible_lines {
-            self.input_scroll = cursor_line - visible_lines + 1;
-        }
-    }
-    
-    /// Wrap text manually using character-wrapping (not word-wrapping)
-    /// This ensures cursor calculation matches display exactly
-    fn wrap_text_for_display(&self, width: usize) -> String {
-        if width == 0 {
-            return self.input.clone();
-        }
-        
-        let mut result = String::with_capacity(self.input.len() + self.input.len() / width);
-        let mut col = 0;
-        
-        for ch in self.input.chars() {
-            if ch == '\n' {
-                result.push(ch);
-                col = 0;
-            } else {
-                let char_width = ch.width().unwrap_or(1);
-                // Wrap BEFORE adding character if it would exceed width
-                if col + char_width > width {
-                    result.push('\n');
-                    col = 0;
-                }
-                result.push(ch);
-                col += char_width;
-            }
-        }
-        
-        result
-    }
+#[derive(Subcommand, Debug)]
+enum HistoryTask {
+    /// Print a saved conversation to stdout
+    Show {
+        /// Conversation id from ~/.config/hank-tui/sessions/ or a history
+        /// file (default: the main conversation)
+        #[arg(long)]
+        session: Option<String>,
+
+        /// Show the history of this room instead
+        #[arg(long, conflicts_with = "session")]
+        room: Option<String>,
+
+        /// Only the newest N messages
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+
+        /// Color names and times with ANSI codes
+        #[arg(long)]
+        color: bool,
+    },
 }
 
-fn now_ms() -> u64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+#[derive(Subcommand, Debug)]
+enum ConfigTask {
+    /// Set values in config.toml, e.g. `host=gpu-box port=8080`
+    /// (`key=` removes a value)
+    Set {
+        #[arg(required = true, value_name = "KEY=VALUE")]
+        assignments: Vec<String>,
+    },
 }
 
-fn format_timestamp(ms: u64) -> String {
-    let ts = chrono::Local.timestamp_millis_opt(ms as i64).single();
-    match ts {
-        Some(t) => t.format("%H:%M:%S").to_string(),
-        None => Local::now().format("%H:%M:%S").to_string(),
+/// `hank-tui config set`
+fn set_config(assignments: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Config::config_path().ok_or(tr("Kein Konfigurationsverzeichnis gefunden"))?;
+    let text = set_config_values(&Config::saved_text()?, assignments)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
     }
+    fs::write(&path, text)?;
+    println!("{}", trf("Gespeichert in {}", &[&path.display().to_string()]));
+    Ok(())
 }
 
-fn wrapped_line_count(lines: &[Line], width: usize) -> u32 {
-    if width == 0 {
-        return lines.len() as u32;
+fn speakers(config: &Config) -> Speakers<'_> {
+    Speakers {
+        own: config.display_name.as_deref().or(config.display.user_name.as_deref()).unwrap_or(tr("Du")),
+        assistant: config.display.assistant_name.as_deref().unwrap_or("Hank"),
     }
+}
 
-    let mut total: u32 = 0;
-    for line in lines {
-        if line.spans.is_empty() {
-            total = total.saturating_add(1);
-            continue;
-        }
-
-        let mut col = 0usize;
-        let mut line_count: u32 = 1;
-        for span in &line.spans {
-            for ch in span.content.chars() {
-                let char_width = ch.width().unwrap_or(1);
-                if char_width == 0 {
-                    continue;
-                }
-                if col + char_width > width {
-                    line_count = line_count.saturating_add(1);
-                    col = char_width;
-                } else {
-                    col += char_width;
-                }
-            }
-        }
+/// The saved conversation named by `--session`/`--room` and its file
+fn saved_history(session: Option<&str>, room: Option<&str>) -> Result<(PathBuf, ChatHistory), Box<dyn std::error::Error>> {
+    let path = match (room, session) {
+        (Some(room), _) => ChatHistory::room_history_path(room.trim_start_matches('#')),
+        (None, None | Some("main")) => ChatHistory::history_path(),
+        (None, Some(file)) if Path::new(file).is_file() => Some(PathBuf::from(file)),
+        (None, Some(id)) => ChatHistory::session_path(id),
+    }
+    .ok_or(tr("Kein Konfigurationsverzeichnis gefunden"))?;
+    let history = ChatHistory::load_from(&path).ok_or_else(|| trf("{}: keine gespeicherte Unterhaltung", &[&path.display().to_string()]))?;
+    Ok((path, history))
+}
 
-        total = total.saturating_add(line_count);
+/// `hank-tui export`
+fn export(config: &Config, session: Option<&str>, room: Option<&str>, format: Option<ExportFormat>, output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let (path, history) = saved_history(session, room)?;
+    let messages = history.all_messages(&path);
+    let title = room.map(|room| format!("#{}", room.trim_start_matches('#'))).or(history.title.clone());
+    let format = format.or_else(|| output.and_then(ExportFormat::from_path)).unwrap_or_default();
+    let document = export_transcript(&messages, title.as_deref(), format, speakers(config));
+    match output {
+        Some(file) => fs::write(file, document)?,
+        None => print!("{}", document),
     }
-
-    total
+    Ok(())
 }
 
-const CHAT_PADDING_LINES: u32 = 20;
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn scroll_values(lines: &[Line], width: usize, visible_lines: u16, auto_scroll: bool, scroll: u16) -> (u16, u16, u32) {
-        let total_lines: u32 = wrapped_line_count(lines, width).saturating_add(CHAT_PADDING_LINES);
-        let visible_lines_u32 = visible_lines as u32;
-        let max_scroll_u32 = total_lines.saturating_sub(visible_lines_u32);
-        let max_scroll: u16 = max_scroll_u32.min(u32::from(u16::MAX)) as u16;
-
-        let scroll_offset = if total_lines <= visible_lines_u32 {
-            0
-        } else if auto_scroll {
-            max_scroll
-        } else {
-            max_scroll.saturating_sub(scroll)
-        };
-
-        (max_scroll, scroll_offset, total_lines)
-    }
+/// `hank-tui history show`
+fn show_history(config: &Config, session: Option<&str>, room: Option<&str>, last: Option<usize>, color: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (path, history) = saved_history(session, room)?;
+    let transcript = print_transcript(&history.all_messages(&path), speakers(config), last, color);
+    // A closed pipe (`| head`) is not an error
+    let _ = io::stdout().write_all(transcript.as_bytes());
+    Ok(())
+}
 
-    #[test]
-    fn counts_wrapped_lines_basic() {
-        let lines = vec![Line::from("12345"), Line::from("1234567890")]; // second wraps once at width 8
-        let total = wrapped_line_count(&lines, 8);
-        assert_eq!(total, 3); // two logical + one wrapped
+/// `hank-tui import`
+fn import(config: &Config, server_url: &str, file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(file)?;
+    let dir = ChatHistory::sessions_dir().ok_or(tr("Kein Konfigurationsverzeichnis gefunden"))?;
+    for conversation in import_transcript(&text, speakers(config))? {
+        let path = save_imported(&dir, server_url, &conversation)?;
+        let title = conversation.title.clone().or_else(|| session_title(&conversation.messages)).unwrap_or_default();
+        println!("{}", trf("Importiert: {} ({} Nachrichten) → {}", &[&title, &conversation.messages.len().to_string(), &path.display().to_string()]));
     }
+    Ok(())
+}
 
-    #[test]
-    fn counts_wrapped_lines_unicode_width() {
-        let lines = vec![Line::from("😀abc")]; // emoji width 2
-        let total = wrapped_line_count(&lines, 3); // 2+1 exceeds 3, so wrap after emoji
-        assert_eq!(total, 2);
+/// `question` plus whatever is piped into stdin (a terminal is not read)
+fn with_stdin(question: &str, fence: Option<&str>, limit: Option<usize>) -> Result<String, Box<dyn std::error::Error>> {
+    let mut piped = String::new();
+    if !io::stdin().is_terminal() {
+        io::stdin().read_to_string(&mut piped)?;
     }
-
-    #[test]
-    fn scroll_auto_goes_to_max_with_padding() {
-        let lines = vec![Line::from("one"), Line::from("two"), Line::from("three")];
-        let (max_scroll, scroll_offset, total) = scroll_values(&lines, 10, 2, true, 0);
-        assert!(total > wrapped_line_count(&lines, 10)); // padding applied
-        assert_eq!(scroll_offset, max_scroll);
+    let prompt = piped_prompt(question, &piped, fence, limit.unwrap_or(PIPE_LIMIT))?;
+    if prompt.trim().is_empty() {
+        return Err(tr("Keine Frage: als Argument angeben oder über stdin hineinleiten").into());
     }
+    Ok(prompt)
+}
 
-    #[test]
-    fn manual_scroll_clamps() {
-        let lines = vec![Line::from("short"), Line::from("another short line"), Line::from("last")];
-        let (max_scroll, scroll_offset, _) = scroll_values(&lines, 10, 2, false, 5);
-        assert!(max_scroll >= scroll_offset);
+/// `hank-tui ask` and `--oneshot`: the answer goes to stdout as it arrives,
+/// no alternate screen
+async fn ask(backend: &HttpBackend, config: &Config, question: &str, room: Option<String>, model: Option<String>, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let request = ChatRequest {
+        message: question.to_string(),
+        display_name: config.display_name.clone().or_else(|| config.display.user_name.clone()),
+        room: room.map(|room| room.trim_start_matches('#').to_string()),
+        thread_id: None,
+        to: None,
+        model,
+        client_id: None,
+        session: None,
+    };
+    let mut stdout = io::stdout();
+    let mut streamed = false;
+    let response = hank_tui::ask(backend, request, |chunk| {
+        if !json {
+            streamed = true;
+            let _ = write!(stdout, "{}", chunk);
+            let _ = stdout.flush();
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    let (content, tool_calls) = if response.tool_calls.is_empty() { parse_structured(response.content) } else { (response.content, response.tool_calls) };
+    if json {
+        let reply = serde_json::json!({ "content": content, "model": response.model, "message_id": response.message_id, "tool_calls": tool_calls });
+        println!("{}", reply);
+    } else if streamed {
+        println!();
+    } else {
+        println!("{}", content.trim_end());
     }
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let mut config = Config::load();
-    
-    // Priority: CLI args > environment variables > config file > defaults
+    let config = Config::load();
+    set_language(args.lang.or(config.as_ref().ok().and_then(|config| config.lang)).unwrap_or_default());
+    // Before anything that reads the config can fail, so a broken value can
+    // be fixed this way
+    if let Some(Task::Config { action: ConfigTask::Set { assignments } }) = &args.task {
+        return set_config(assignments);
+    }
+    let mut config = config?;
+    // Command line and environment only apply to this run; the config is
+    // written by `hank-tui config set` and /save-config
+    let profile = match &args.profile {
+        Some(name) => config.apply_profile(name)?,
+        None => ServerProfile::default(),
+    };
+    let theme = if args.ascii { Theme::no_color() } else { Theme::select(args.theme.as_deref(), config.theme.as_deref())? };
+
+    // Priority: CLI args > environment variables > config file > defaults.
+    // A full URL replaces host and port from the same or a lower level.
+    // --profile is given on the command line, so what it sets (server,
+    // token) beats the environment; the rest still comes from there.
+    let env = |name: &str| std::env::var(name).ok().filter(|_| !profile.sets_server());
+    let host_given = args.host.is_some() || args.port.is_some();
+    let host_in_env = env("HANK_HOST").is_some() || env("HANK_PORT").is_some();
+    let url = args.url
+        .or_else(|| env("HANK_SERVER").filter(|_| !host_given))
+        .or_else(|| config.url.clone().filter(|_| !host_given && !host_in_env));
+
     let host = args.host
-        .or_else(|| std::env::var("HANK_HOST").ok())
+        .or_else(|| env("HANK_HOST"))
         .unwrap_or(config.host.clone());
-    
+
     let port = args.port
-        .or_else(|| std::env::var("HANK_PORT").ok().and_then(|p| p.parse().ok()))
+        .or_else(|| env("HANK_PORT").and_then(|p| p.parse().ok()))
         .unwrap_or(config.port);
-    
-    // Update config with the values being used
-    config.host = host.clone();
-    config.port = port;
-    
-    // Save config for next time (ignore errors)
-    let _ = config.save();
-    
-    let server_url = format!("http://{}:{}", host, port);
+
+    // Not written back: a token from the command line or the environment
+    // stays out of the config file
+    let token = args.token
+        .or_else(|| std::env::var("HANK_TOKEN").ok().filter(|_| profile.token.is_none()))
+        .or(config.token.clone());
+
+    let scheme = if args.tls { Scheme::Https } else { config.scheme };
+    let server_url = match &url {
+        Some(url) => server_url_with(url, scheme),
+        None => format!("{}://{}:{}", scheme, host, port),
+    };
+
+    // Applies to the extra servers as well
+    let options = ClientOptions {
+        ca_bundle: args.ca_bundle.or(config.ca_bundle.clone()),
+        insecure: args.insecure || config.insecure,
+        proxy: args.proxy.or(config.proxy.clone()),
+        connect_timeout: config.connect_timeout.map(Duration::from_secs),
+        keep_alive: config.keep_alive.map(Duration::from_secs),
+        request_timeout: args.request_timeout.or(config.request_timeout).map(Duration::from_secs),
+    };
+    let client = options.client()?;
+    let http_trace = args.debug_http.then(|| Arc::new(HttpTrace::open(HttpTrace::path().as_deref())));
+    let backend = HttpBackend::new(server_url.clone())
+        .with_client(client.clone())
+        .with_trace(http_trace.clone())
+        .with_auth(token, config.auth_header.clone());
+
+    if let Some(question) = &args.oneshot {
+        let prompt = with_stdin(question, None, config.max_message_chars)?;
+        return ask(&backend, &config, &prompt, None, None, args.json).await;
+    }
+    if let Some(task) = &args.task {
+        return match task {
+            Task::Export { session, room, format, output } => export(&config, session.as_deref(), room.as_deref(), *format, output.as_deref()),
+            Task::Import { file } => import(&config, &server_url, file),
+            Task::Config { .. } => Ok(()),
+            Task::History { action: HistoryTask::Show { session, room, last, color } } => show_history(&config, session.as_deref(), room.as_deref(), *last, *color),
+            Task::Ask { question, fence, max_stdin, room, model, json } => {
+                let prompt = with_stdin(&question.join(" "), fence.as_deref(), max_stdin.or(config.max_message_chars))?;
+                ask(&backend, &config, &prompt, room.clone(), model.clone(), *json).await
+            }
+        };
+    }
 
     // Setup panic handler to restore terminal
     let original_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
         let _ = disable_raw_mode();
-        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        let _ = execute!(
+            io::stdout(),
+            PopKeyboardEnhancementFlags,
+            DisableMouseCapture,
+            Print("\x1b[?1007l"),
+            LeaveAlternateScreen
+        );
         original_hook(panic_info);
     }));
 
-    // Setup terminal
+    // Setup terminal; accessible mode stays in the normal screen so the
+    // transcript ends up in the scrollback where screen readers find it
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    
+    let mut terminal = if args.accessible {
+        let viewport = Viewport::Inline(3);
+        Terminal::with_options(CrosstermBackend::new(stdout), TerminalOptions { viewport })?
+    } else {
+        execute!(stdout, EnterAlternateScreen)?;
+        Terminal::new(CrosstermBackend::new(stdout))?
+    };
+
     // Clear the terminal to prevent any echo issues
     terminal.clear()?;
 
-    let mut app = App::new(server_url.clone(), !args.no_history);
+    let mut app = App::new(Arc::new(backend), !args.no_history);
+    app.http_trace = http_trace;
+    app.http_client = Some(client.clone());
+    if let Some(secs) = args.poll_interval.or(config.poll_interval).filter(|&secs| secs > 0) {
+        app.poll_interval = Duration::from_secs(secs);
+    }
+    if let Some(secs) = args.poll_timeout.or(config.poll_timeout).filter(|&secs| secs > 0) {
+        app.poll_timeout = Duration::from_secs(secs);
+    }
+    app.display_name = config.display_name.clone().or_else(|| config.display.user_name.clone());
+    if let Some(name) = config.display.assistant_name.clone().filter(|name| !name.trim().is_empty()) {
+        app.assistant_name = name;
+    }
+    app.own_color = config.display.user_color.as_deref().and_then(|color| color.parse().ok());
+    app.assistant_color = config.display.assistant_color.as_deref().and_then(|color| color.parse().ok());
+    app.accessible = args.accessible;
+    app.ascii = args.ascii;
+    app.deterministic = args.deterministic;
+    app.theme = theme;
+    app.east_asian_width = config.east_asian_width;
+    app.status_format = config.status_format.clone();
+    app.timestamps = config.timestamps;
+    app.clock = config.clock.clone();
+    app.input_height = args.input_height.unwrap_or(config.input_height);
+    let (keys, key_problems) = KeyBindings::from_config(&config.keys);
+    app.keys = keys;
+    if !key_problems.is_empty() {
+        app.last_error = Some(key_problems.join("; "));
+    }
+    app.tmux = Tmux::detect(config.tmux_rename_window);
+    app.enable_enhanced_keys()?;
+    app.fallbacks = Fallback::probe(app.enhanced_keys);
+    // Screen readers work with the terminal's own selection
+    if !args.accessible {
+        app.set_mouse_capture(config.mouse.unwrap_or(true))?;
+    }
+    app.alert_mode = config.alert;
+    app.mention_keywords = config.mentions.clone();
+    app.models = config.models.clone();
+    app.max_message_chars = config.max_message_chars;
+    app.user_colors = config
+        .user_colors
+        .iter()
+        .filter_map(|(name, color)| color.parse().ok().map(|color| (name.clone(), color)))
+        .collect();
+    app.profiles = config.servers.profiles();
+    let extra = config.servers.at_startup().into_iter().chain(args.connect.iter().map(|server| app.profiles.get(server).cloned().unwrap_or_else(|| ServerProfile::from_target(server))));
+    for server in extra.collect::<Vec<_>>() {
+        let backend = HttpBackend::new(server.server_url())
+            .with_client(client.clone())
+            .with_trace(app.http_trace.clone())
+            .with_auth(server.token, server.auth_header);
+        app.add_connection(Arc::new(backend));
+    }
 
     let result = run_app(&mut terminal, &mut app).await;
 
-    // Save history on exit if enabled
-    if app.history_enabled {
-        let _ = ChatHistory::save(&server_url, &app.messages);
-    }
+    // Save history of all joined rooms on exit (no-op with --no-history);
+    // only the primary connection keeps a history
+    app.switch_connection(0);
+    app.save_history();
 
     // Restore terminal
+    app.reset_mouse()?;
+    app.disable_enhanced_keys()?;
+    app.restore_window_title()?;
+    if let Some(tmux) = &app.tmux {
+        tmux.restore();
+    }
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    if !args.accessible {
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    }
     terminal.show_cursor()?;
 
     result
 }
-
-async fn run_app<B: ratatui::backend::Backend>(
-    terminal: &mut Terminal<B>,
-    app: &mut App,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Initial load: fetch ALL messages from server (since=0)
-    {
-        let server_url = app.server_url.clone();
-        if let Ok(response) = reqwest::Client::new()
-            .get(format!("{}/messages?since=0", server_url))
-            .timeout(std::time::Duration::from_secs(5))
-            .send()
-            .await
-        {
-            if let Ok(messages) = response.json::<Vec<ServerMessage>>().await {
-                // Dump initial payload next to the executable for debugging
-                if let Ok(exe_path) = env::current_exe() {
-                    if let Some(dir) = exe_path.parent() {
-                        if let Ok(serialized) = serde_json::to_string_pretty(&messages) {
-                            let _ = fs::write(dir.join("initial_messages.json"), serialized);
-                        }
-                    }
-                }
-
-                // Clear local history and load from server
-                let had_local = !app.messages.is_empty();
-                app.messages.clear();
-                
-                for msg in messages {
-                    let timestamp_str = chrono::Local
-                        .timestamp_millis_opt(msg.timestamp as i64)
-                        .single()
-                        .map(|dt| dt.format("%H:%M:%S").to_string())
-                        .unwrap_or_else(|| "??:??:??".to_string());
-                    
-                    app.messages.push(Message {
-                        role: msg.role,
-                        content: msg.content,
-                        timestamp: timestamp_str,
-                        timestamp_ms: Some(msg.timestamp),
-                    });
-                    
-                    if msg.timestamp > app.last_timestamp {
-                        app.last_timestamp = msg.timestamp;
-                    }
-                }
-                
-                let msg_count = app.messages.len();
-                let source = "Server";
-                app.messages.push(Message {
-                    role: "system".to_string(),
-                    content: format!("{} Nachrichten vom {} geladen", msg_count, source),
-                    timestamp: Local::now().format("%H:%M:%S").to_string(),
-                    timestamp_ms: Some(now_ms()),
-                });
-                
-                app.scroll_to_bottom();
-            }
-        }
-    }
-    
-    loop {
-        // Poll server für neue Nachrichten (alle 2 Sekunden, wenn nicht loading)
-        if !app.loading && app.last_poll.elapsed().as_secs() >= 2 {
-            app.last_poll = Instant::now();
-            let server_url = app.server_url.clone();
-            let since = app.last_timestamp;
-            
-            // Non-blocking poll
-            if let Ok(response) = reqwest::Client::new()
-                .get(format!("{}/messages?since={}", server_url, since))
-                .timeout(std::time::Duration::from_secs(2))
-                .send()
-                .await
-            {
-                if let Ok(messages) = response.json::<Vec<ServerMessage>>().await {
-                    for msg in messages {
-                        // Skip only if we already have this exact message (avoid echo duplicates)
-                        if msg.role == "user" {
-                            if msg.timestamp > app.last_timestamp {
-                                app.last_timestamp = msg.timestamp;
-                            }
-                            let already_exists = app
-                                .messages
-                                .iter()
-                                .any(|m| m.role == msg.role && m.timestamp_ms == Some(msg.timestamp));
-                            if already_exists {
-                                continue;
-                            }
-                        }
-
-                        // Nur hinzufügen wenn noch nicht vorhanden (exact role+timestamp)
-                        let already_exists = app
-                            .messages
-                            .iter()
-                            .any(|m| m.role == msg.role && m.timestamp_ms == Some(msg.timestamp));
-                        
-                        if !already_exists {
-                            let timestamp_str = chrono::Local
-                                .timestamp_millis_opt(msg.timestamp as i64)
-                                .single()
-                                .map(|dt| dt.format("%H:%M:%S").to_string())
-                                .unwrap_or_else(|| "??:??:??".to_string());
-                            
-                            app.messages.push(Message {
-                                role: msg.role,
-                                content: msg.content,
-                                timestamp: timestamp_str,
-                                timestamp_ms: Some(msg.timestamp),
-                            });
-                            
-                            if msg.timestamp > app.last_timestamp {
-                                app.last_timestamp = msg.timestamp;
-                            }
-                            
-                            // Auto-scroll bei neuen Nachrichten
-                            if app.auto_scroll {
-                                app.scroll_to_bottom();
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        terminal.draw(|f| {
-            // Fixed input height of 5 lines
-            let input_height = 5u16;
-
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Min(3),
-                    Constraint::Length(input_height),
-                    Constraint::Length(1),
-                ])
-                .split(f.area());
-
-            // Chat-Verlauf mit Timestamps
-            let mut lines: Vec<Line> = Vec::new();
-            for msg in &app.messages {
-                let (prefix, style) = match msg.role.as_str() {
-                    "user" => ("Du: ", Style::default().fg(Color::Cyan)),
-                    "assistant" => ("Hank: ", Style::default().fg(Color::Green)),
-                    "system" => ("", Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC)),
-                    "error" => ("Error: ", Style::default().fg(Color::Red)),
-                    _ => ("", Style::default()),
-                };
-                
-                // Timestamp für non-system messages
-                if !msg.role.is_empty() && msg.role != "system" {
-                    lines.push(Line::from(vec![
-                        Span::styled(&msg.timestamp, Style::default().fg(Color::DarkGray)),
-                        Span::raw(" "),
-                        Span::styled(prefix, style.add_modifier(Modifier::BOLD)),
-                        Span::styled(msg.content.lines().next().unwrap_or(""), style),
-                    ]));
-                    
-                    // Weitere Zeilen
-                    for line in msg.content.lines().skip(1) {
-                        lines.push(Line::from(Span::styled(
-                            format!("{:width$}{}", "", line, width = msg.timestamp.len() + 1 + prefix.len()),
-                            style,
-                        )));
-                    }
-                } else {
-                    lines.push(Line::from(Span::styled(&msg.content, style)));
-                }
-                lines.push(Line::from(""));
-            }
-
-            if app.loading {
-                lines.push(Line::from(Span::styled(
-                    "Hank denkt nach...",
-                    Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC),
-                )));
-            }
-
-            // Show last error if any
-            if let Some(ref err) = app.last_error {
-                lines.push(Line::from(Span::styled(
-                    format!("⚠ {}", err),
-                    Style::default().fg(Color::Red),
-                )));
-            }
-
-            // Calculate scroll offset for chat using the same wrapping logic as rendering
-            let chat_width = chunks[0].width.saturating_sub(2) as usize;
-            let visible_lines = chunks[0].height.saturating_sub(2);
-            let total_lines: u32 = wrapped_line_count(&lines, chat_width)
-                .saturating_add(CHAT_PADDING_LINES);
-            let visible_lines_u32 = visible_lines as u32;
-            let max_scroll_u32 = total_lines.saturating_sub(visible_lines_u32);
-            let max_scroll: u16 = max_scroll_u32.min(u32::from(u16::MAX)) as u16;
-
-            // Clamp stored scroll to max
-            if app.scroll > max_scroll {
-                app.scroll = max_scroll;
-            }
-
-            let scroll_offset = if total_lines <= visible_lines_u32 {
-                0
-            } else if app.auto_scroll {
-                max_scroll
-            } else {
-                max_scroll.saturating_sub(app.scroll)
-            };
-
-            // Chat widget with focus indicator
-            let chat_title = if app.focus == Focus::Chat {
-                " Chat [FOKUSSIERT - ↑↓=Scroll, Tab=Wechsel] "
-            } else {
-                " Chat [Tab=Fokussieren] "
-            };
-            
-            let chat_block = Block::default()
-                .borders(Borders::ALL)
-                .title(chat_title)
-                .border_style(if app.focus == Focus::Chat {
-                    Style::default().fg(Color::Yellow)
-                } else {
-                    Style::default()
-                });
-
-            let messages_widget = Paragraph::new(lines)
-                .block(chat_block)
-                .wrap(Wrap { trim: false })
-                .scroll((scroll_offset, 0));
-            f.render_widget(messages_widget, chunks[0]);
-
-            // Input with wrapping and focus indicator
-            let input_title = if app.loading {
-                " Warte... "
-            } else if app.focus == Focus::Input {
-                " Nachricht [Ctrl+S=Senden, F1=Hilfe] "
-            } else {
-                " Nachricht [Tab=Fokussieren] "
-            };
-            
-            let input_block = Block::default()
-                .borders(Borders::ALL)
-                .title(input_title)
-                .border_style(if app.focus == Focus::Input && !app.loading {
-                    Style::default().fg(Color::Cyan)
-                } else {
-                    Style::default()
-                });
-            
-            // Calculate input dimensions
-            let input_area_width = chunks[1].width.saturating_sub(2) as usize;
-            let visible_input_lines = input_height.saturating_sub(2);
-            
-            // Update scroll to keep cursor visible
-            app.update_input_scroll(input_area_width, visible_input_lines);
-            
-            // Use manually wrapped text to ensure cursor matches display
-            let wrapped_input = app.wrap_text_for_display(input_area_width);
-            let input_widget = Paragraph::new(wrapped_input)
-                .block(input_block)
-                .scroll((app.input_scroll, 0))
-                .style(if app.loading {
-                    Style::default().fg(Color::DarkGray)
-                } else {
-                    Style::default()
-                });
-            f.render_widget(input_widget, chunks[1]);
-
-            // Status bar
-            let status_text = format!(
-                " {} | Msgs: {} | Lines: {}/{} | Scroll: {} | {}",
-                app.server_url,
-                app.messages.len(),
-                total_lines,
-                visible_lines,
-                if app.auto_scroll { "bottom".to_string() } else { app.scroll.to_string() },
-                app.connection_status
-            );
-            let status_widget = Paragraph::new(status_text)
-                .style(Style::default().bg(Color::DarkGray).fg(Color::White));
-            f.render_widget(status_widget, chunks[2]);
-
-            // Cursor positioning (only when input is focused)
-            if !app.loading && app.focus == Focus::Input {
-                let input_width = chunks[1].width.saturating_sub(2) as usize;
-                if input_width > 0 {
-                    let (cursor_line, cursor_col) = app.cursor_line_col(input_width);
-                    let visible_line = (cursor_line as u16).saturating_sub(app.input_scroll);
-                    
-                    if visible_line < visible_input_lines {
-                        f.set_cursor_position((
-                            chunks[1].x + cursor_col as u16 + 1,
-                            chunks[1].y + visible_line + 1,
-                        ));
-                    }
-                }
-            }
-            
-            // Help overlay
-            if app.focus == Focus::Help {
-                let help_text = vec![
-                    Line::from(Span::styled("═══ Hank TUI Hilfe ═══", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))),
-                    Line::from(""),
-                    Line::from(Span::styled("── Allgemein ──", Style::default().fg(Color::Cyan))),
-                    Line::from("  F1, ?         Hilfe anzeigen/schließen"),
-                    Line::from("  Tab           Fokus wechseln (Input ↔ Chat)"),
-                    Line::from("  Esc, Ctrl+C   Beenden"),
-                    Line::from(""),
-                    Line::from(Span::styled("── Eingabe (Input fokussiert) ──", Style::default().fg(Color::Cyan))),
-                    Line::from("  Ctrl+S        Nachricht senden"),
-                    Line::from("  Enter         Neue Zeile"),
-                    Line::from(""),
-                    Line::from(Span::styled("── Chat Scroll ──", Style::default().fg(Color::Cyan))),
-                    Line::from("  Tab           Chat fokussieren"),
-                    Line::from("  ↑/↓           Zeilenweise scrollen"),
-                    Line::from("  PageUp/Down   Seitenweise scrollen"),
-                    Line::from("  Home/End      Anfang/Ende"),
-                    Line::from("  Ctrl+V        Einfügen aus Zwischenablage"),
-                    Line::from("  ↑/↓           Cursor zwischen Zeilen bewegen"),
-                    Line::from("  ←/→           Cursor links/rechts"),
-                    Line::from("  Home/End      Zeilenanfang/-ende"),
-                    Line::from("  Ctrl+↑/↓      Command History (vorherige Nachrichten)"),
-                    Line::from(""),
-                    Line::from(Span::styled("── Chat (Chat fokussiert) ──", Style::default().fg(Color::Cyan))),
-                    Line::from("  ↑/↓           Scrollen (1 Zeile)"),
-                    Line::from("  PgUp/PgDown   Scrollen (10 Zeilen)"),
-                    Line::from("  Home          Zum Anfang"),
-                    Line::from("  End           Zum Ende (Auto-Scroll)"),
-                    Line::from(""),
-                    Line::from(Span::styled("── Sonstiges ──", Style::default().fg(Color::Cyan))),
-                    Line::from("  Alt+↑/↓       Chat scrollen (immer)"),
-                    Line::from("  Ctrl+L        Chat löschen (Server + lokal)"),
-                    Line::from("  Ctrl+Shift+D  History-Datei löschen"),
-                    Line::from(""),
-                    Line::from(Span::styled("Drücke eine beliebige Taste zum Schließen", Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC))),
-                ];
-                
-                // Clamp help dimensions to terminal size
-                let term_width = f.area().width;
-                let term_height = f.area().height;
-                let help_height = (help_text.len() as u16 + 2).min(term_height.saturating_sub(2));
-                let help_width = 55u16.min(term_width.saturating_sub(2));
-                let help_x = term_width.saturating_sub(help_width) / 2;
-                let help_y = term_height.saturating_sub(help_height) / 2;
-                
-                // Ensure we don't overflow
-                let help_width = help_width.min(term_width.saturating_sub(help_x));
-                let help_height = help_height.min(term_height.saturating_sub(help_y));
-                
-                if help_width > 2 && help_height > 2 {
-                    let help_area = ratatui::layout::Rect::new(help_x, help_y, help_width, help_height);
-                    
-                    // Clear area behind help
-                    f.render_widget(ratatui::widgets::Clear, help_area);
-                    
-                    let help_block = Block::default()
-                        .borders(Borders::ALL)
-                        .border_style(Style::default().fg(Color::Yellow))
-                        .style(Style::default().bg(Color::Black));
-                    
-                    let help_widget = Paragraph::new(help_text)
-                        .block(help_block)
-                        .wrap(Wrap { trim: false });
-                    f.render_widget(help_widget, help_area);
-                }
-            }
-
-            // Debug overlay (toggle with F2)
-            if app.debug_overlay {
-                let dbg_lines = vec![
-                    Line::from(format!(
-                        "tl={} vis={} max={} off={}",
-                        total_lines, visible_lines, max_scroll, scroll_offset
-                    )),
-                    Line::from(format!(
-                        "auto={} scroll={} pad={}",
-                        app.auto_scroll, app.scroll, CHAT_PADDING_LINES
-                    )),
-                    Line::from(format!("msgs={} loading={}", app.messages.len(), app.loading)),
-                ];
-
-                let term_width = f.area().width;
-                let term_height = f.area().height;
-                let dbg_width = 48u16.min(term_width.saturating_sub(2));
-                let dbg_height = (dbg_lines.len() as u16 + 2).min(term_height.saturating_sub(2));
-                let dbg_x = term_width.saturating_sub(dbg_width + 1);
-                let dbg_y = term_height.saturating_sub(dbg_height + 1);
-
-                if dbg_width > 2 && dbg_height > 2 {
-                    let dbg_area = ratatui::layout::Rect::new(dbg_x, dbg_y, dbg_width, dbg_height);
-                    f.render_widget(ratatui::widgets::Clear, dbg_area);
-
-                    let dbg_block = Block::default()
-                        .borders(Borders::ALL)
-                        .title(" debug ")
-                        .border_style(Style::default().fg(Color::Magenta))
-                        .style(Style::default().bg(Color::Black));
-
-                    let dbg_widget = Paragraph::new(dbg_lines)
-                        .block(dbg_block)
-                        .wrap(Wrap { trim: false });
-                    f.render_widget(dbg_widget, dbg_area);
-                }
-            }
-        })?;
-
-        // Kürzeres Poll-Timeout für schnelleres UI-Update (100ms statt 500ms)
-        // Das stellt sicher dass neue Nachrichten vom Server schnell angezeigt werden
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                // Only process key press events, not release events
-                if key.kind != KeyEventKind::Press {
-                    continue;
-                }
-                
-                // Help screen: any key closes it
-                if app.focus == Focus::Help {
-                    app.toggle_help();
-                    continue;
-                }
-                
-                if app.loading {
-                    continue;
-                }
-                
-                // Get terminal width for cursor calculations
-                let term_width = terminal.size()?.width.saturating_sub(4) as usize;
-                
-                match key.code {
-                    KeyCode::F(1) => {
-                        app.toggle_help();
-                    }
-                    KeyCode::F(2) => {
-                        app.debug_overlay = !app.debug_overlay;
-                    }
-                    KeyCode::Char('?') if key.modifiers.is_empty() && app.focus != Focus::Input => {
-                        app.toggle_help();
-                    }
-                    KeyCode::Esc => break,
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
-                    KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Clear chat (server + local)
-                        let url = format!("{}/messages/clear", app.server_url);
-                        match reqwest::Client::new().post(url).send().await {
-                            Ok(resp) if resp.status().is_success() => {
-                                app.messages.clear();
-                                app.messages.push(Message {
-                                    role: "system".to_string(),
-                                    content: format!("Chat gelöscht (Server + lokal). Verbunden mit {}", app.server_url),
-                                    timestamp: Local::now().format("%H:%M:%S").to_string(),
-                                    timestamp_ms: Some(now_ms()),
-                                });
-                                app.last_error = None;
-                            }
-                            Ok(resp) => {
-                                app.last_error = Some(format!("Clear fehlgeschlagen: {}", resp.status()));
-                            }
-                            Err(e) => {
-                                app.last_error = Some(format!("Clear fehlgeschlagen: {}", e));
-                            }
-                        }
-                    }
-                    KeyCode::Char('d') | KeyCode::Char('D') 
-                        if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) => {
-                        // Clear history file (Ctrl+Shift+D)
-                        if app.history_enabled {
-                            match ChatHistory::delete() {
-                                Ok(_) => {
-                                    app.messages.clear();
-                                    app.messages.push(Message {
-                                        role: "system".to_string(),
-                                        content: "Chat Historie gelöscht.".to_string(),
-                                        timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                                    });
-                                    app.last_error = None;
-                                }
-                                Err(e) => {
-                                    app.last_error = Some(format!("Fehler beim Löschen: {}", e));
-                                }
-                            }
-                        } else {
-                            app.last_error = Some("History ist deaktiviert (--no-history)".to_string());
-                        }
-                    }
-                    KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Paste from clipboard (Ctrl+V) - only when input is focused
-                        if app.focus == Focus::Input {
-                            match Clipboard::new() {
-                                Ok(mut clipboard) => {
-                                    match clipboard.get_text() {
-                                        Ok(text) => {
-                                            // Insert at cursor position (convert char pos to byte pos)
-                                            let byte_pos: usize = app.input.chars().take(app.cursor_pos).map(|c| c.len_utf8()).sum();
-                                            app.input.insert_str(byte_pos, &text);
-                                            app.cursor_pos += text.chars().count();
-                                        }
-                                        Err(_) => {
-                                            app.last_error = Some("Clipboard ist leer oder nicht verfügbar".to_string());
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    app.last_error = Some(format!("Clipboard-Fehler: {}", e));
-                                }
-                            }
-                        }
-                    }
-                    KeyCode::Tab => {
-                        // Toggle focus between input and chat
-                        app.toggle_focus();
-                    }
-                    KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Command history navigation with Ctrl+Up
-                        if app.focus == Focus::Input {
-                            app.navigate_history_up();
-                        }
-                    }
-                    KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Command history navigation with Ctrl+Down
-                        if app.focus == Focus::Input {
-                            app.navigate_history_down();
-                        }
-                    }
-                    KeyCode::Up if key.modifiers.is_empty() => {
-                        match app.focus {
-                            Focus::Input => app.cursor_up(term_width),
-                            Focus::Chat => app.scroll_up(),
-                            Focus::Help => {}
-                        }
-                    }
-                    KeyCode::Down if key.modifiers.is_empty() => {
-                        match app.focus {
-                            Focus::Input => app.cursor_down(term_width),
-                            Focus::Chat => app.scroll_down(),
-                            Focus::Help => {}
-                        }
-                    }
-                    KeyCode::Left if app.focus == Focus::Input => {
-                        if app.cursor_pos > 0 {
-                            app.cursor_pos -= 1;
-                        }
-                    }
-                    KeyCode::Right if app.focus == Focus::Input => {
-                        if app.cursor_pos < app.input.len() {
-                            app.cursor_pos += 1;
-                        }
-                    }
-                    KeyCode::Home if app.focus == Focus::Input => {
-                        // Move to start of current line
-                        let (line, _) = app.cursor_line_col(term_width);
-                        if line == 0 {
-                            app.cursor_pos = 0;
-                        } else {
-                            // Find start of current line
-                            let mut current_line = 0;
-                            let mut line_start = 0;
-                            let mut col = 0;
-                            
-                            for (i, ch) in app.input.chars().enumerate() {
-                                if current_line == line {
-                                    line_start = i;
-                                    break;
-                                }
-                                if ch == '\n' {
-                                    current_line += 1;
-                                    col = 0;
-                                } else {
-                                    col += 1;
-                                    if col >= term_width {
-                                        current_line += 1;
-                                        col = 0;
-                                    }
-                                }
-                            }
-                            app.cursor_pos = line_start;
-                        }
-                    }
-                    KeyCode::End if app.focus == Focus::Input => {
-                        // Move to end of current line
-                        let (line, _) = app.cursor_line_col(term_width);
-                        let total_lines = app.input_total_lines(term_width);
-                        
-                        if line >= total_lines - 1 {
-                            app.cursor_pos = app.input.len();
-                        } else {
-                            // Find end of current line
-                            let mut current_line = 0;
-                            let mut col = 0;
-                            
-                            for (i, ch) in app.input.chars().enumerate() {
-                                if current_line > line {
-                                    app.cursor_pos = i.saturating_sub(1);
-                                    break;
-                                }
-                                if ch == '\n' {
-                                    if current_line == line {
-                                        app.cursor_pos = i;
-                                        break;
-                                    }
-                                    current_line += 1;
-                                    col = 0;
-                                } else {
-                                    col += 1;
-                                    if col >= term_width {
-                                        if current_line == line {
-                                            app.cursor_pos = i + 1;
-                                            break;
-                                        }
-                                        current_line += 1;
-                                        col = 0;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) => {
-                        app.scroll_up();
-                    }
-                    KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
-                        app.scroll_down();
-                    }
-                    KeyCode::Home if app.focus == Focus::Chat => {
-                        app.jump_to_top();
-                    }
-                    KeyCode::End if app.focus == Focus::Chat => {
-                        app.jump_to_bottom();
-                    }
-                    KeyCode::PageUp if app.focus == Focus::Chat => {
-                        app.scroll_page_up(10);
-                    }
-                    KeyCode::PageDown if app.focus == Focus::Chat => {
-                        app.scroll_page_down(10);
-                    }
-                    KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Send message with Ctrl+S (alternative to Ctrl+Enter)
-                        if !app.input.trim().is_empty() {
-                            let user_msg = app.input.trim().to_string();
-                            
-                            // Add to command history
-                            app.command_history.push(user_msg.clone());
-                            app.history_index = None;
-                            
-                            // Add user message
-                            app.messages.push(Message {
-                                role: "user".to_string(),
-                                content: user_msg.clone(),
-                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                            });
-                            app.input.clear();
-                            app.cursor_pos = 0;
-                            app.input_scroll = 0;
-                            app.loading = true;
-                            app.connection_status = "Sending...".to_string();
-                            app.last_error = None;
-                            app.scroll_to_bottom();
-                            
-                            // Send request in background
-                            let server_url = app.server_url.clone();
-                            let handle = tokio::spawn(async move {
-                                let client = reqwest::Client::new();
-                                let result = client
-                                    .post(format!("{}/chat", server_url))
-                                    .json(&ChatRequest { message: user_msg })
-                                    .timeout(std::time::Duration::from_secs(120))
-                                    .send()
-                                    .await;
-                                
-                                match result {
-                                    Ok(response) => {
-                                        match response.json::<ChatResponse>().await {
-                                            Ok(data) => Ok(data.content),
-                                            Err(e) => Err(format!("Failed to parse response: {}", e)),
-                                        }
-                                    }
-                                    Err(e) => Err(format!("Connection error: {}", e)),
-                                }
-                            });
-                            
-                            // Wait for response with UI updates
-                            loop {
-                                terminal.draw(|f| {
-                                    let chunks = Layout::default()
-                                        .direction(Direction::Vertical)
-                                        .constraints([Constraint::Min(3), Constraint::Length(3), Constraint::Length(1)])
-                                        .split(f.area());
-
-                                    let mut lines: Vec<Line> = Vec::new();
-                                    for msg in &app.messages {
-                                        let (prefix, style) = match msg.role.as_str() {
-                                            "user" => ("Du: ", Style::default().fg(Color::Cyan)),
-                                            "assistant" => ("Hank: ", Style::default().fg(Color::Green)),
-                                            "system" => ("", Style::default().fg(Color::DarkGray)),
-                                            _ => ("", Style::default()),
-                                        };
-                                        
-                                        if !msg.role.is_empty() && msg.role != "system" {
-                                            lines.push(Line::from(vec![
-                                                Span::styled(&msg.timestamp, Style::default().fg(Color::DarkGray)),
-                                                Span::raw(" "),
-                                                Span::styled(prefix, style.add_modifier(Modifier::BOLD)),
-                                                Span::styled(msg.content.lines().next().unwrap_or(""), style),
-                                            ]));
-                                            for line in msg.content.lines().skip(1) {
-                                                lines.push(Line::from(Span::styled(line, style)));
-                                            }
-                                        } else {
-                                            lines.push(Line::from(Span::styled(&msg.content, style)));
-                                        }
-                                        lines.push(Line::from(""));
-                                    }
-                                    lines.push(Line::from(Span::styled(
-                                        "Hank denkt nach...",
-                                        Style::default().fg(Color::Yellow),
-                                    )));
-
-                                    // Auto-scroll to bottom
-                                    let total_lines = lines.len() as u16;
-                                    let visible_lines = chunks[0].height.saturating_sub(2);
-                                    let scroll_offset = total_lines.saturating_sub(visible_lines);
-
-                                    let messages = Paragraph::new(lines)
-                                        .block(Block::default().borders(Borders::ALL).title(" Chat "))
-                                        .wrap(Wrap { trim: false })
-                                        .scroll((scroll_offset, 0));
-                                    f.render_widget(messages, chunks[0]);
-
-                                    let input = Paragraph::new("")
-                                        .block(Block::default().borders(Borders::ALL).title(" Warte... "))
-                                        .style(Style::default().fg(Color::DarkGray));
-                                    f.render_widget(input, chunks[1]);
-                                    
-                                    let status_text = format!(" {} | Sending request...", app.server_url);
-                                    let status = Paragraph::new(status_text)
-                                        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
-                                    f.render_widget(status, chunks[2]);
-                                })?;
-
-                                if handle.is_finished() {
-                                    match handle.await {
-                                        Ok(Ok(content)) => {
-                                            app.messages.push(Message {
-                                                role: "assistant".to_string(),
-                                                content,
-                                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                                            });
-                                            app.connection_status = "Connected".to_string();
-                                            app.scroll_to_bottom();
-                                        }
-                                        Ok(Err(err)) => {
-                                            app.messages.push(Message {
-                                                role: "error".to_string(),
-                                                content: err.clone(),
-                                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                                            });
-                                            app.last_error = Some(err);
-                                            app.connection_status = "Error".to_string();
-                                            app.scroll_to_bottom();
-                                        }
-                                        Err(e) => {
-                                            let err_msg = format!("Task failed: {}", e);
-                                            app.messages.push(Message {
-                                                role: "error".to_string(),
-                                                content: err_msg.clone(),
-                                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                                            });
-                                            app.last_error = Some(err_msg);
-                                            app.connection_status = "Error".to_string();
-                                            app.scroll_to_bottom();
-                                        }
-                                    }
-                                    app.loading = false;
-                                    break;
-                                }
-
-                                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                            }
-                        }
-                    }
-                    KeyCode::Enter if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        // Send message with Ctrl+Enter (may not work in all terminals)
-                        if !app.input.trim().is_empty() {
-                            let user_msg = app.input.trim().to_string();
-                            
-                            // Add to command history
-                            app.command_history.push(user_msg.clone());
-                            app.history_index = None;
-                            
-                            // Add user message
-                            app.messages.push(Message {
-                                role: "user".to_string(),
-                                content: user_msg.clone(),
-                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                            });
-                            app.input.clear();
-                            app.cursor_pos = 0;
-                            app.input_scroll = 0;
-                            app.loading = true;
-                            app.connection_status = "Sending...".to_string();
-                            app.last_error = None;
-                            app.scroll_to_bottom();
-                            
-                            // Send request in background
-                            let server_url = app.server_url.clone();
-                            let handle = tokio::spawn(async move {
-                                let client = reqwest::Client::new();
-                                let result = client
-                                    .post(format!("{}/chat", server_url))
-                                    .json(&ChatRequest { message: user_msg })
-                                    .timeout(std::time::Duration::from_secs(120))
-                                    .send()
-                                    .await;
-                                
-                                match result {
-                                    Ok(response) => {
-                                        match response.json::<ChatResponse>().await {
-                                            Ok(data) => Ok(data.content),
-                                            Err(e) => Err(format!("Failed to parse response: {}", e)),
-                                        }
-                                    }
-                                    Err(e) => Err(format!("Connection error: {}", e)),
-                                }
-                            });
-                            
-                            // Wait for response with UI updates
-                            loop {
-                                terminal.draw(|f| {
-                                    let chunks = Layout::default()
-                                        .direction(Direction::Vertical)
-                                        .constraints([Constraint::Min(3), Constraint::Length(3), Constraint::Length(1)])
-                                        .split(f.area());
-
-                                    let mut lines: Vec<Line> = Vec::new();
-                                    for msg in &app.messages {
-                                        let (prefix, style) = match msg.role.as_str() {
-                                            "user" => ("Du: ", Style::default().fg(Color::Cyan)),
-                                            "assistant" => ("Hank: ", Style::default().fg(Color::Green)),
-                                            "system" => ("", Style::default().fg(Color::DarkGray)),
-                                            _ => ("", Style::default()),
-                                        };
-                                        
-                                        if !msg.role.is_empty() && msg.role != "system" {
-                                            lines.push(Line::from(vec![
-                                                Span::styled(&msg.timestamp, Style::default().fg(Color::DarkGray)),
-                                                Span::raw(" "),
-                                                Span::styled(prefix, style.add_modifier(Modifier::BOLD)),
-                                                Span::styled(msg.content.lines().next().unwrap_or(""), style),
-                                            ]));
-                                            for line in msg.content.lines().skip(1) {
-                                                lines.push(Line::from(Span::styled(line, style)));
-                                            }
-                                        } else {
-                                            lines.push(Line::from(Span::styled(&msg.content, style)));
-                                        }
-                                        lines.push(Line::from(""));
-                                    }
-                                    lines.push(Line::from(Span::styled(
-                                        "Hank denkt nach...",
-                                        Style::default().fg(Color::Yellow),
-                                    )));
-
-                                    // Auto-scroll to bottom
-                                    let total_lines = lines.len() as u16;
-                                    let visible_lines = chunks[0].height.saturating_sub(2);
-                                    let scroll_offset = total_lines.saturating_sub(visible_lines);
-
-                                    let messages = Paragraph::new(lines)
-                                        .block(Block::default().borders(Borders::ALL).title(" Chat "))
-                                        .wrap(Wrap { trim: false })
-                                        .scroll((scroll_offset, 0));
-                                    f.render_widget(messages, chunks[0]);
-
-                                    let input = Paragraph::new("")
-                                        .block(Block::default().borders(Borders::ALL).title(" Warte... "))
-                                        .style(Style::default().fg(Color::DarkGray));
-                                    f.render_widget(input, chunks[1]);
-                                    
-                                    let status_text = format!(" {} | Sending request...", app.server_url);
-                                    let status = Paragraph::new(status_text)
-                                        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
-                                    f.render_widget(status, chunks[2]);
-                                })?;
-
-                                if handle.is_finished() {
-                                    match handle.await {
-                                        Ok(Ok(content)) => {
-                                            app.messages.push(Message {
-                                                role: "assistant".to_string(),
-                                                content,
-                                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                                            });
-                                            app.connection_status = "Connected".to_string();
-                                            app.scroll_to_bottom();
-                                        }
-                                        Ok(Err(err)) => {
-                                            app.messages.push(Message {
-                                                role: "error".to_string(),
-                                                content: err.clone(),
-                                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                                            });
-                                            app.last_error = Some(err);
-                                            app.connection_status = "Error".to_string();
-                                            app.scroll_to_bottom();
-                                        }
-                                        Err(e) => {
-                                            let err_msg = format!("Task failed: {}", e);
-                                            app.messages.push(Message {
-                                                role: "error".to_string(),
-                                                content: err_msg.clone(),
-                                                timestamp: Local::now().format("%H:%M:%S").to_string(),
-                        timestamp_ms: Some(now_ms()),
-                                            });
-                                            app.last_error = Some(err_msg);
-                                            app.connection_status = "Error".to_string();
-                                            app.scroll_to_bottom();
-                                        }
-                                    }
-                                    app.loading = false;
-                                    break;
-                                }
-
-                                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                            }
-                        }
-                    }
-                    KeyCode::Enter if app.focus == Focus::Input => {
-                        // Insert newline with Enter
-                        let byte_pos: usize = app.input.chars().take(app.cursor_pos).map(|c| c.len_utf8()).sum();
-                        app.input.insert(byte_pos, '\n');
-                        app.cursor_pos += 1;
-                        app.history_index = None;
-                    }
-                    KeyCode::Char(c) if app.focus == Focus::Input => {
-                        let byte_pos: usize = app.input.chars().take(app.cursor_pos).map(|c| c.len_utf8()).sum();
-                        app.input.insert(byte_pos, c);
-                        app.cursor_pos += 1;
-                        app.history_index = None;
-                    }
-                    KeyCode::Backspace if app.focus == Focus::Input => {
-                        if app.cursor_pos > 0 {
-                            app.cursor_pos -= 1;
-                            let byte_pos: usize = app.input.chars().take(app.cursor_pos).map(|c| c.len_utf8()).sum();
-                            let char_len = app.input.chars().nth(app.cursor_pos).map(|c| c.len_utf8()).unwrap_or(1);
-                            app.input.drain(byte_pos..byte_pos + char_len);
-                            app.history_index = None;
-                        }
-                    }
-                    KeyCode::Delete if app.focus == Focus::Input => {
-                        if app.cursor_pos < app.input.chars().count() {
-                            let byte_pos: usize = app.input.chars().take(app.cursor_pos).map(|c| c.len_utf8()).sum();
-                            let char_len = app.input.chars().nth(app.cursor_pos).map(|c| c.len_utf8()).unwrap_or(1);
-                            app.input.drain(byte_pos..byte_pos + char_len);
-                            app.history_index = None;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
-    }
-    
-    Ok(())
-}
@@ -0,0 +1,1157 @@
+//! Talking to the Hank server: the [`ChatBackend`] trait, its HTTP
+//! implementation and the request/response types.
+
+use chrono::Local;
+use futures::future::BoxFuture;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::{collections::{BTreeMap, VecDeque}, fmt, fs, io::Write, path::{Path, PathBuf}, sync::{Arc, Mutex, OnceLock}, time::{Duration, Instant}};
+
+use crate::{app::*, history::*, lang::*, ui::*};
+
+#[derive(Serialize, Clone)]
+pub struct ChatRequest {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    /// Reply into the thread started by this message id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    /// Deliver only to this participant (servers that support targets)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Answer with this model instead of the server's default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Provisional id of the local echo, for servers that hand it back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Conversation started with Ctrl+N, for servers that keep Hank's
+    /// context per conversation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChatResponse {
+    pub content: String,
+    pub complete: bool,
+    /// Server id of the message we just sent (servers that support editing)
+    #[serde(default)]
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Model that produced the answer, if the server tells
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// One line of a streamed answer
+pub enum StreamEvent {
+    /// Next piece of text (`{"delta": "..."}`)
+    Delta(String),
+    /// The finished answer, a complete `ChatResponse`
+    Done(ChatResponse),
+}
+
+/// Parse one NDJSON line of a streamed answer; blank lines are skipped
+pub fn parse_stream_line(line: &str) -> Result<Option<StreamEvent>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|e| format!("Failed to parse stream: {}", e))?;
+    if let Some(delta) = value.get("delta").and_then(serde_json::Value::as_str) {
+        return Ok(Some(StreamEvent::Delta(delta.to_string())));
+    }
+    serde_json::from_value(value)
+        .map(|response| Some(StreamEvent::Done(response)))
+        .map_err(|e| format!("Failed to parse response: {}", e))
+}
+
+/// Why a chat message could not be delivered
+#[derive(Debug, PartialEq)]
+pub enum ChatError {
+    /// HTTP 429: try again after the given delay (from `Retry-After`)
+    RateLimited { retry_after: Duration },
+    /// HTTP 413: the message is over the server's limit (if it names one)
+    TooLarge { limit: Option<usize> },
+    /// HTTP 401/403: the token is missing or wrong (or a proxy in front of
+    /// Hank refuses it)
+    Unauthorized { status: u16 },
+    Other(String),
+}
+
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatError::RateLimited { retry_after } => {
+                f.write_str(&trf("Rate-Limit erreicht, neuer Versuch in {}s", &[&retry_after.as_secs().to_string()]))
+            }
+            ChatError::TooLarge { limit: Some(limit) } => f.write_str(&trf("Nachricht zu groß (max. {} Zeichen)", &[&limit.to_string()])),
+            ChatError::TooLarge { limit: None } => f.write_str(tr("Nachricht zu groß für den Server")),
+            ChatError::Unauthorized { status } => f.write_str(&auth_error(*status)),
+            ChatError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for ChatError {
+    fn from(message: String) -> Self {
+        ChatError::Other(message)
+    }
+}
+
+impl From<&str> for ChatError {
+    fn from(message: &str) -> Self {
+        ChatError::Other(message.to_string())
+    }
+}
+
+/// Error for a response that is no success. 401 and 403 name the token
+/// settings, as they usually come from a reverse proxy rather than Hank.
+pub fn status_error(status: reqwest::StatusCode) -> String {
+    match status {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => auth_error(status.as_u16()),
+        _ => trf("Server-Fehler: {}", &[&status.to_string()]),
+    }
+}
+
+fn auth_error(status: u16) -> String {
+    trf("Zugriff verweigert (HTTP {}): Token prüfen (--token, HANK_TOKEN oder token in der Config)", &[&status.to_string()])
+}
+
+/// Delay requested by a `Retry-After` header: delta seconds or an HTTP date.
+/// Falls back to 5 seconds if the header is missing or unreadable.
+pub fn parse_retry_after(value: Option<&str>) -> Duration {
+    let fallback = Duration::from_secs(5);
+    let Some(value) = value.map(str::trim) else {
+        return fallback;
+    };
+    if let Ok(secs) = value.parse::<u64>() {
+        return Duration::from_secs(secs);
+    }
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| (date.timestamp_millis() - Local::now().timestamp_millis()).max(0) as u64)
+        .map(Duration::from_millis)
+        .unwrap_or(fallback)
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct ServerMessage {
+    pub role: String,
+    pub content: String,
+    pub timestamp: u64,
+    /// Sender name when the server relays a multi-user chat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// Unique message id; older servers omit it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub edited: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Provisional id the client sent along (servers that echo it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RoomInfo {
+    pub name: String,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceStatus {
+    Online,
+    Away,
+    Offline,
+    #[serde(other)]
+    Unknown,
+}
+
+impl PresenceStatus {
+    pub fn glyph(self) -> &'static str {
+        match self {
+            PresenceStatus::Online => "●",
+            PresenceStatus::Away => "◐",
+            PresenceStatus::Offline | PresenceStatus::Unknown => "○",
+        }
+    }
+
+    pub fn color(self, theme: &Theme) -> Color {
+        match self {
+            PresenceStatus::Online => theme.assistant,
+            PresenceStatus::Away => theme.highlight,
+            PresenceStatus::Offline | PresenceStatus::Unknown => theme.muted,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PresenceStatus::Online => tr("online"),
+            PresenceStatus::Away => tr("abwesend"),
+            PresenceStatus::Offline => tr("offline"),
+            PresenceStatus::Unknown => tr("unbekannt"),
+        }
+    }
+}
+
+/// Presence of a participant (or of the Hank assistant worker, role "assistant")
+#[derive(Deserialize, Clone, Debug)]
+pub struct Presence {
+    pub name: String,
+    pub status: PresenceStatus,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+/// Profile details of a participant as returned by `GET /users/{name}`
+pub type UserProfile = std::collections::BTreeMap<String, serde_json::Value>;
+
+/// Load figures from `GET /stats`; fields the TUI does not know are listed as they come
+#[derive(Deserialize, Debug, Default, PartialEq)]
+pub struct ServerStats {
+    /// Requests waiting for the model
+    #[serde(default)]
+    pub queue_depth: Option<u64>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Utilization, 0.0–1.0
+    #[serde(default)]
+    pub load: Option<f64>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+/// Protocol version this client speaks; servers report theirs in
+/// `GET /health` as `protocol`
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Who is answering, from `GET /health` or `GET /version`
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ServerInfo {
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub protocol: Option<u32>,
+}
+
+impl ServerInfo {
+    /// A protocol other than `PROTOCOL_VERSION` (None = unknown, assumed fine)
+    pub fn incompatible(&self) -> bool {
+        self.protocol.is_some_and(|p| p != PROTOCOL_VERSION)
+    }
+}
+
+/// What the server allows the current user to do (`GET /me`)
+#[derive(Deserialize, Default, Debug)]
+pub struct Permissions {
+    #[serde(default)]
+    pub admin: bool,
+}
+
+pub enum PollEvent {
+    Messages(Vec<Message>),
+    Error(String),
+}
+
+/// Transport to a Hank server. `App` only talks to the server through this
+/// trait, so it can be swapped out for tests or other protocols.
+pub trait ChatBackend: Send + Sync {
+    /// Base URL used for display and to match the saved history
+    fn server_url(&self) -> &str;
+
+    /// Fetch all messages of `room` newer than `since` (milliseconds, 0 = everything)
+    fn fetch_messages<'a>(&'a self, since: u64, room: Option<&'a str>, timeout: Duration) -> BoxFuture<'a, Result<Vec<ServerMessage>, String>>;
+
+    /// Send a chat message and wait for Hank's reply
+    fn send_chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>>;
+
+    /// Send a chat message and pass Hank's reply on in chunks while it is
+    /// generated (optional capability; without it the reply comes in one piece)
+    fn send_chat_stream(
+        &self,
+        request: ChatRequest,
+        _chunks: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+        self.send_chat(request)
+    }
+
+    /// Clear the conversation of `room` on the server
+    fn clear_messages<'a>(&'a self, room: Option<&'a str>) -> BoxFuture<'a, Result<(), String>>;
+
+    /// Online/away status of the participants of `room` (optional capability)
+    fn fetch_presence<'a>(&'a self, _room: Option<&'a str>) -> BoxFuture<'a, Result<Vec<Presence>, String>> {
+        Box::pin(async { Err(tr("Server unterstützt keine Presence").to_string()) })
+    }
+
+    /// Names of participants currently typing in `room` (optional capability)
+    fn fetch_typing<'a>(&'a self, _room: Option<&'a str>) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        Box::pin(async { Err(tr("Server unterstützt keine Tipp-Anzeige").to_string()) })
+    }
+
+    /// Tell the server that we are composing a message in `room`
+    fn send_typing<'a>(&'a self, _room: Option<&'a str>, _display_name: Option<&'a str>) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Participants of `room` that accept direct messages (optional capability)
+    fn fetch_targets<'a>(&'a self, _room: Option<&'a str>) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        Box::pin(async { Err(tr("Server unterstützt keine Direktnachrichten").to_string()) })
+    }
+
+    /// Permissions of the current user (optional capability)
+    fn fetch_permissions(&self) -> BoxFuture<'_, Result<Permissions, String>> {
+        Box::pin(async { Err(tr("Server liefert keine Berechtigungen").to_string()) })
+    }
+
+    /// Run a moderation action in `room` (admins only)
+    fn moderate<'a>(&'a self, _room: Option<&'a str>, _action: &'a ModAction) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Err(tr("Server unterstützt keine Moderation").to_string()) })
+    }
+
+    /// Profile details of a participant (optional capability)
+    fn fetch_profile<'a>(&'a self, _name: &'a str) -> BoxFuture<'a, Result<UserProfile, String>> {
+        Box::pin(async { Err(tr("Server liefert keine Profile").to_string()) })
+    }
+
+    /// Models the server can answer with (optional capability)
+    fn fetch_models(&self) -> BoxFuture<'_, Result<Vec<String>, String>> {
+        Box::pin(async { Err(tr("Server bietet keine Modellauswahl").to_string()) })
+    }
+
+    /// Store `content` as a file named `name`; returns its URL or id (optional capability)
+    fn upload_file<'a>(&'a self, _name: &'a str, _content: String) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async { Err(tr("Server unterstützt keine Dateianhänge").to_string()) })
+    }
+
+    /// Queue depth, active model and load of the server (optional capability)
+    fn fetch_stats(&self) -> BoxFuture<'_, Result<ServerStats, String>> {
+        Box::pin(async { Err(tr("Server liefert keine Statistiken").to_string()) })
+    }
+
+    /// Version, model and protocol of the server (optional capability)
+    fn fetch_server_info(&self) -> BoxFuture<'_, Result<ServerInfo, String>> {
+        Box::pin(async { Err(tr("Server liefert keine Versionsinfo").to_string()) })
+    }
+
+    /// Replace the content of an own message (optional capability)
+    fn edit_message<'a>(&'a self, _id: &'a str, _content: String) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Err(tr("Server unterstützt kein Bearbeiten").to_string()) })
+    }
+
+    /// Report the newest message read in `room` (optional capability)
+    fn mark_read<'a>(
+        &'a self,
+        _room: Option<&'a str>,
+        _timestamp: u64,
+        _message_id: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// List the rooms offered by the server (optional capability)
+    fn list_rooms(&self) -> BoxFuture<'_, Result<Vec<RoomInfo>, String>> {
+        Box::pin(async { Err(tr("Server unterstützt keine Räume").to_string()) })
+    }
+}
+
+/// Most characters `hank-tui ask` takes from a pipe unless the server's
+/// `max_message_chars` or `--max-stdin` says otherwise
+pub const PIPE_LIMIT: usize = 100_000;
+
+/// The prompt for `git diff | hank-tui ask "review this"`: the question,
+/// then the piped text, in a code fence when `fence` is given (`Some("")`
+/// = without language). Fails when the result is over `limit` characters.
+pub fn piped_prompt(question: &str, piped: &str, fence: Option<&str>, limit: usize) -> Result<String, String> {
+    let piped = piped.trim_end();
+    let body = match fence {
+        _ if piped.is_empty() => String::new(),
+        Some(lang) => {
+            // A fence longer than any backtick run inside keeps it closed
+            let longest = piped.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+            let ticks = "`".repeat(longest.max(2) + 1);
+            format!("{}{}\n{}\n{}", ticks, lang, piped, ticks)
+        }
+        None => piped.to_string(),
+    };
+    let prompt = match (question.trim(), body.is_empty()) {
+        ("", _) => body,
+        (question, true) => question.to_string(),
+        (question, false) => format!("{}\n\n{}", question, body),
+    };
+    let chars = prompt.chars().count();
+    if chars > limit {
+        return Err(trf("Eingabe zu groß ({} Zeichen, max. {}); --max-stdin erhöht die Grenze", &[&chars.to_string(), &limit.to_string()]));
+    }
+    Ok(prompt)
+}
+
+/// Send one message outside the TUI (`hank-tui ask`) and wait for the
+/// whole answer; `on_chunk` gets its text while it is generated
+pub async fn ask(backend: &dyn ChatBackend, request: ChatRequest, mut on_chunk: impl FnMut(&str)) -> Result<ChatResponse, ChatError> {
+    let (chunk_tx, mut chunk_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let forward = async {
+        while let Some(chunk) = chunk_rx.recv().await {
+            on_chunk(&chunk);
+        }
+    };
+    let (result, ()) = tokio::join!(backend.send_chat_stream(request, chunk_tx), forward);
+    result
+}
+
+/// How the HTTP client reaches and verifies the servers
+#[derive(Debug, Default, Clone)]
+pub struct ClientOptions {
+    /// PEM file with extra trusted CA certificates
+    pub ca_bundle: Option<PathBuf>,
+    /// Accept any certificate (self-signed servers in the LAN)
+    pub insecure: bool,
+    /// Proxy URL for all requests, "none" for a direct connection; unset
+    /// uses HTTP_PROXY/HTTPS_PROXY/ALL_PROXY (and NO_PROXY)
+    pub proxy: Option<String>,
+    /// Give up connecting after this long (default 10 s)
+    pub connect_timeout: Option<Duration>,
+    /// Keep idle connections open this long for the next poll (default 90 s)
+    pub keep_alive: Option<Duration>,
+    /// Longest wait for a chat answer to start (default 120 s); a streamed
+    /// answer may take longer to finish. The other requests bring their own
+    /// shorter limits.
+    pub request_timeout: Option<Duration>,
+}
+
+/// The shared connection pool together with the time a chat answer may
+/// take to start
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    answer_timeout: Duration,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self { client: reqwest::Client::default(), answer_timeout: Duration::from_secs(120) }
+    }
+}
+
+impl ClientOptions {
+    /// HTTP client with these settings. Build it once and share it between
+    /// all backends: its connection pool saves a handshake per request.
+    /// The request timeout is not set on the client: it would also cut off
+    /// answers that are still streaming.
+    pub fn client(&self) -> Result<HttpClient, String> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout.unwrap_or(Duration::from_secs(10)))
+            .pool_idle_timeout(self.keep_alive.unwrap_or(Duration::from_secs(90)))
+            .tcp_keepalive(Duration::from_secs(60));
+        match self.proxy.as_deref().map(str::trim) {
+            None | Some("") => {}
+            Some("none") => builder = builder.no_proxy(),
+            Some(url) => {
+                let invalid = |e: &dyn fmt::Display| format!("Proxy {}: {}", url, e);
+                // http(s):// and socks5(h):// alike; socks5h resolves names at the proxy
+                let proxy = reqwest::Proxy::all(url).map_err(|e| invalid(&e))?;
+                builder = builder.proxy(proxy.no_proxy(reqwest::NoProxy::from_env()));
+            }
+        }
+        if let Some(path) = &self.ca_bundle {
+            let invalid = |e: &dyn fmt::Display| format!("CA-Bundle {}: {}", path.display(), e);
+            let pem = fs::read(path).map_err(|e| invalid(&e))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| invalid(&e))?;
+            if certificates.is_empty() {
+                return Err(invalid(&"keine Zertifikate gefunden"));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        let client = builder.danger_accept_invalid_certs(self.insecure).build().map_err(|e| e.to_string())?;
+        Ok(HttpClient { client, answer_timeout: self.request_timeout.unwrap_or(Duration::from_secs(120)) })
+    }
+}
+
+/// Default backend talking to `hank-rest` over HTTP
+pub struct HttpBackend {
+    client: HttpClient,
+    server_url: String,
+    trace: Option<Arc<HttpTrace>>,
+    /// Header name and value sent with every request
+    auth: Option<(String, String)>,
+    /// ETag and Last-Modified of the last message poll per room, sent back
+    /// so an unchanged conversation costs a 304 without body
+    validators: Mutex<BTreeMap<String, Validators>>,
+}
+
+#[derive(Clone, Default)]
+struct Validators {
+    /// Valid for this `since` only
+    since: u64,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl HttpBackend {
+    pub fn new(server_url: String) -> Self {
+        // Building a client loads the system certificates: do it once
+        static DEFAULT_CLIENT: OnceLock<HttpClient> = OnceLock::new();
+        let client = DEFAULT_CLIENT.get_or_init(|| ClientOptions::default().client().unwrap_or_default()).clone();
+        Self { client, server_url, trace: None, auth: None, validators: Mutex::default() }
+    }
+
+    /// Send requests through `client`, e.g. one from [`ClientOptions::client`]
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Log every request and response to `trace` (`--debug-http`)
+    pub fn with_trace(mut self, trace: Option<Arc<HttpTrace>>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Send `token` with every request: as `Authorization: Bearer <token>`
+    /// by default, as is in `header` otherwise. A token that already names
+    /// its scheme ("Basic …") is sent unchanged.
+    pub fn with_auth(mut self, token: Option<String>, header: Option<String>) -> Self {
+        self.auth = token.filter(|t| !t.trim().is_empty()).map(|token| {
+            let header = header.filter(|h| !h.trim().is_empty()).unwrap_or_else(|| "Authorization".to_string());
+            let value = if header.eq_ignore_ascii_case("authorization") && !token.trim().contains(' ') {
+                format!("Bearer {}", token.trim())
+            } else {
+                token.trim().to_string()
+            };
+            (header, value)
+        });
+        self
+    }
+
+    /// Request to `path` on the server, with the credential if one is set
+    pub(crate) fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.request_url(method, format!("{}{}", self.server_url, path))
+    }
+
+    /// URL of `segments` below the server URL, each escaped on its own, so
+    /// a user name or message id with `/`, `?`, `#` or spaces stays one path
+    /// segment
+    pub(crate) fn url_with_segments(&self, segments: &[&str]) -> Result<reqwest::Url, String> {
+        let mut url = reqwest::Url::parse(&self.server_url).map_err(|e| trf("Ungültige Server-URL: {}", &[&e.to_string()]))?;
+        url.path_segments_mut()
+            .map_err(|_| trf("Ungültige Server-URL: {}", &[&self.server_url]))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn request_url(&self, method: reqwest::Method, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        let request = self.client.client.request(method, url);
+        match &self.auth {
+            Some((header, value)) => request.header(header.as_str(), value.as_str()),
+            None => request,
+        }
+    }
+}
+
+/// Request/response log of `--debug-http`: full entries go to
+/// `hank-tui/http.log`, one-line summaries stay for the F12 debug pane
+pub struct HttpTrace {
+    file: Option<Mutex<fs::File>>,
+    recent: Mutex<VecDeque<String>>,
+}
+
+/// Summaries kept for the debug pane
+const HTTP_TRACE_RECENT: usize = 50;
+/// Bodies are cut after this many characters in the log
+const HTTP_TRACE_BODY_CHARS: usize = 2000;
+
+impl HttpTrace {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|mut path| {
+            path.push("hank-tui");
+            path.push("http.log");
+            path
+        })
+    }
+
+    /// Trace appending to `path`; without a file only the pane is fed
+    pub fn open(path: Option<&Path>) -> Self {
+        let file = path.and_then(|path| {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            fs::OpenOptions::new().create(true).append(true).open(path).ok()
+        });
+        Self { file: file.map(Mutex::new), recent: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Add one exchange: `summary` for the pane, `details` (indented) for the file
+    pub fn record(&self, summary: String, details: &[String]) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = writeln!(file, "{} {}", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"), summary);
+            for line in details {
+                let _ = writeln!(file, "    {}", line);
+            }
+        }
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == HTTP_TRACE_RECENT {
+                recent.pop_front();
+            }
+            recent.push_back(summary);
+        }
+    }
+
+    /// Newest `count` summaries, oldest first
+    pub fn recent(&self, count: usize) -> Vec<String> {
+        let recent = self.recent.lock().map(|r| r.iter().cloned().collect::<Vec<_>>()).unwrap_or_default();
+        recent[recent.len().saturating_sub(count)..].to_vec()
+    }
+}
+
+/// Whether a header, query parameter or JSON key holds a secret
+pub(crate) fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["authorization", "cookie", "token", "password", "secret", "api_key", "api-key", "apikey"]
+        .iter()
+        .any(|secret| name.contains(secret))
+}
+
+/// Body for the HTTP log: secrets in JSON replaced by `***`, cut after
+/// `HTTP_TRACE_BODY_CHARS`
+pub fn trace_body(bytes: &[u8]) -> String {
+    fn redact(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if is_secret(key) {
+                        *value = serde_json::Value::String("***".to_string());
+                    } else {
+                        redact(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+            _ => {}
+        }
+    }
+    let text = match serde_json::from_slice::<serde_json::Value>(bytes) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    match text.char_indices().nth(HTTP_TRACE_BODY_CHARS) {
+        Some((cut, _)) => format!("{}… ({} Bytes)", &text[..cut], bytes.len()),
+        None => text,
+    }
+}
+
+/// URL for the HTTP log with secret query parameters replaced by `***`
+pub(crate) fn trace_url(url: &reqwest::Url) -> String {
+    if !url.query_pairs().any(|(key, _)| is_secret(&key)) {
+        return url.to_string();
+    }
+    let mut url = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            let value = if is_secret(&key) { "***".to_string() } else { value.into_owned() };
+            (key.into_owned(), value)
+        })
+        .collect();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url.to_string()
+}
+
+/// `send()` that records the exchange when a trace is set. The response
+/// body is read for the log and handed on unchanged; streamed answers are
+/// logged without their body so they keep streaming.
+trait SendTraced {
+    fn send_traced(self, trace: &Option<Arc<HttpTrace>>) -> BoxFuture<'static, reqwest::Result<reqwest::Response>>;
+}
+
+impl SendTraced for reqwest::RequestBuilder {
+    fn send_traced(self, trace: &Option<Arc<HttpTrace>>) -> BoxFuture<'static, reqwest::Result<reqwest::Response>> {
+        let Some(trace) = trace.clone() else {
+            return Box::pin(self.send());
+        };
+        Box::pin(async move {
+            let (client, request) = self.build_split();
+            let request = request?;
+            let mut details: Vec<String> = request
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    let value = if is_secret(name.as_str()) { "***" } else { value.to_str().unwrap_or("?") };
+                    format!("> {}: {}", name, value)
+                })
+                .collect();
+            if let Some(body) = request.body().and_then(|b| b.as_bytes()) {
+                details.push(format!("> {}", trace_body(body)));
+            }
+            let target = format!("{} {}", request.method(), trace_url(request.url()));
+            let started = Instant::now();
+            let response = match client.execute(request).await {
+                Ok(response) => response,
+                Err(e) => {
+                    trace.record(format!("{} → Fehler nach {}ms: {}", target, started.elapsed().as_millis(), e), &details);
+                    return Err(e);
+                }
+            };
+            let status = response.status();
+            let content_type = response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+            details.push(format!("< content-type: {}", content_type.unwrap_or("-")));
+            if content_type.is_some_and(|t| t.starts_with("application/x-ndjson")) {
+                let summary = format!("{} → {} nach {}ms (Stream)", target, status, started.elapsed().as_millis());
+                trace.record(summary, &details);
+                return Ok(response);
+            }
+            let mut copy = http::Response::builder().status(status).version(response.version());
+            if let Some(headers) = copy.headers_mut() {
+                *headers = response.headers().clone();
+            }
+            let body = response.bytes().await;
+            let elapsed = started.elapsed().as_millis();
+            let body = match body {
+                Ok(body) => body,
+                Err(e) => {
+                    trace.record(format!("{} → {}, Body abgebrochen nach {}ms: {}", target, status, elapsed, e), &details);
+                    return Err(e);
+                }
+            };
+            details.push(format!("< {}", trace_body(&body)));
+            trace.record(format!("{} → {} in {}ms, {} Bytes", target, status, elapsed, body.len()), &details);
+            Ok(reqwest::Response::from(copy.body(body).expect("status and headers come from a response")))
+        })
+    }
+}
+
+impl ChatBackend for HttpBackend {
+    fn server_url(&self) -> &str {
+        &self.server_url
+    }
+
+    fn fetch_messages<'a>(&'a self, since: u64, room: Option<&'a str>, timeout: Duration) -> BoxFuture<'a, Result<Vec<ServerMessage>, String>> {
+        Box::pin(async move {
+            let mut request = self.request(reqwest::Method::GET, "/messages")
+                .query(&[("since", since.to_string())]);
+            if let Some(room) = room {
+                request = request.query(&[("room", room)]);
+            }
+            let key = room.unwrap_or_default().to_string();
+            let known = self.validators.lock().ok().and_then(|v| v.get(&key).cloned()).filter(|v| v.since == since).unwrap_or_default();
+            if let Some(etag) = &known.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(modified) = &known.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, modified);
+            }
+            let response = request
+                .timeout(timeout)
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+                return Ok(Vec::new());
+            }
+            if !response.status().is_success() {
+                return Err(status_error(response.status()));
+            }
+            let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+            let fresh = Validators { since, etag: header(reqwest::header::ETAG), last_modified: header(reqwest::header::LAST_MODIFIED) };
+            if let Ok(mut validators) = self.validators.lock() {
+                validators.insert(key, fresh);
+            }
+            response
+                .json::<Vec<ServerMessage>>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn send_chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+        let (chunks, _) = tokio::sync::mpsc::unbounded_channel();
+        self.send_chat_stream(request, chunks)
+    }
+
+    /// Servers that stream answer with `application/x-ndjson` (see
+    /// `parse_stream_line`), all others with a plain `ChatResponse`
+    fn send_chat_stream(
+        &self,
+        request: ChatRequest,
+        chunks: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+        Box::pin(async move {
+            // Only until the answer starts: a stream may run longer
+            let timeout = self.client.answer_timeout;
+            let deadline = tokio::time::Instant::now() + timeout;
+            let timed_out = || trf("Keine Antwort nach {}", &[&format!("{:?}", timeout)]);
+            let sent = self.request(reqwest::Method::POST, "/chat")
+                .header(reqwest::header::ACCEPT, "application/x-ndjson, application/json")
+                .json(&request)
+                .send_traced(&self.trace);
+            let mut response = tokio::time::timeout_at(deadline, sent)
+                .await
+                .map_err(|_| timed_out())?
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                let header = response.headers().get(reqwest::header::RETRY_AFTER);
+                let retry_after = parse_retry_after(header.and_then(|v| v.to_str().ok()));
+                return Err(ChatError::RateLimited { retry_after });
+            }
+            if response.status() == reqwest::StatusCode::PAYLOAD_TOO_LARGE {
+                let body = response.json::<serde_json::Value>().await.unwrap_or_default();
+                let limit = ["max_chars", "max_length", "limit"]
+                    .iter()
+                    .find_map(|key| body.get(key).and_then(serde_json::Value::as_u64))
+                    .map(|limit| limit as usize);
+                return Err(ChatError::TooLarge { limit });
+            }
+            match response.status() {
+                s if s.is_success() => {}
+                s @ (reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+                    return Err(ChatError::Unauthorized { status: s.as_u16() });
+                }
+                s => return Err(status_error(s).into()),
+            }
+            let streamed = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|t| t.starts_with("application/x-ndjson"));
+            if !streamed {
+                return Ok(tokio::time::timeout_at(deadline, response.json::<ChatResponse>())
+                    .await
+                    .map_err(|_| timed_out())?
+                    .map_err(|e| format!("Failed to parse response: {}", e))?);
+            }
+
+            let mut buffer = Vec::new();
+            loop {
+                let chunk = response.chunk().await.map_err(|e| format!("Connection error: {}", e))?;
+                let done = chunk.is_none();
+                buffer.extend_from_slice(chunk.as_deref().unwrap_or_default());
+                // A final line without newline counts once the body is over
+                while let Some(end) = buffer.iter().position(|&b| b == b'\n').or((done && !buffer.is_empty()).then(|| buffer.len() - 1)) {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    match parse_stream_line(&String::from_utf8_lossy(&line))? {
+                        Some(StreamEvent::Delta(text)) => {
+                            let _ = chunks.send(text);
+                        }
+                        Some(StreamEvent::Done(response)) => return Ok(response),
+                        None => {}
+                    }
+                }
+                if done {
+                    return Err(tr("Antwort-Stream vorzeitig beendet").to_string().into());
+                }
+            }
+        })
+    }
+
+    fn clear_messages<'a>(&'a self, room: Option<&'a str>) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut request = self.request(reqwest::Method::POST, "/messages/clear");
+            if let Some(room) = room {
+                request = request.query(&[("room", room)]);
+            }
+            match request.timeout(Duration::from_secs(10)).send_traced(&self.trace).await {
+                Ok(resp) if resp.status().is_success() => Ok(()),
+                Ok(resp) => Err(resp.status().to_string()),
+                Err(e) => Err(e.to_string()),
+            }
+        })
+    }
+
+    fn fetch_presence<'a>(&'a self, room: Option<&'a str>) -> BoxFuture<'a, Result<Vec<Presence>, String>> {
+        Box::pin(async move {
+            let mut request = self.request(reqwest::Method::GET, "/presence");
+            if let Some(room) = room {
+                request = request.query(&[("room", room)]);
+            }
+            let response = request
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server unterstützt keine Presence ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<Vec<Presence>>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn fetch_typing<'a>(&'a self, room: Option<&'a str>) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        Box::pin(async move {
+            let mut request = self.request(reqwest::Method::GET, "/typing");
+            if let Some(room) = room {
+                request = request.query(&[("room", room)]);
+            }
+            let response = request
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server unterstützt keine Tipp-Anzeige ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<Vec<String>>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn send_typing<'a>(&'a self, room: Option<&'a str>, display_name: Option<&'a str>) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.request(reqwest::Method::POST, "/typing")
+                .json(&serde_json::json!({ "room": room, "display_name": display_name }))
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Connection error: {}", e))
+        })
+    }
+
+    fn fetch_targets<'a>(&'a self, room: Option<&'a str>) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        Box::pin(async move {
+            let mut request = self.request(reqwest::Method::GET, "/targets");
+            if let Some(room) = room {
+                request = request.query(&[("room", room)]);
+            }
+            let response = request
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server unterstützt keine Direktnachrichten ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<Vec<String>>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn fetch_permissions(&self) -> BoxFuture<'_, Result<Permissions, String>> {
+        Box::pin(async move {
+            let response = self.request(reqwest::Method::GET, "/me")
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server liefert keine Berechtigungen ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<Permissions>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn moderate<'a>(&'a self, room: Option<&'a str>, action: &'a ModAction) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut body = serde_json::to_value(action).map_err(|e| e.to_string())?;
+            body["room"] = serde_json::json!(room);
+            let response = self.request(reqwest::Method::POST, &format!("/moderation/{}", action.name()))
+                .json(&body)
+                .timeout(Duration::from_secs(5))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            match response.status() {
+                s if s.is_success() => Ok(()),
+                reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::UNAUTHORIZED => {
+                    Err(tr("Keine Berechtigung für Moderation").to_string())
+                }
+                s => Err(trf("Moderation fehlgeschlagen: {}", &[&s.to_string()])),
+            }
+        })
+    }
+
+    fn fetch_profile<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<UserProfile, String>> {
+        Box::pin(async move {
+            let url = self.url_with_segments(&["users", name])?;
+            let response = self.request_url(reqwest::Method::GET, url)
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server liefert keine Profile ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<UserProfile>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn upload_file<'a>(&'a self, name: &'a str, content: String) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let response = self.request(reqwest::Method::POST, "/files")
+                .json(&serde_json::json!({ "name": name, "content": content }))
+                .timeout(Duration::from_secs(30))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server unterstützt keine Dateianhänge ({})", &[&response.status().to_string()]));
+            }
+            let body = response
+                .json::<serde_json::Value>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))?;
+            ["url", "id"]
+                .iter()
+                .find_map(|key| body.get(key).and_then(serde_json::Value::as_str))
+                .map(str::to_string)
+                .ok_or_else(|| tr("Server nannte keine Adresse für den Anhang").to_string())
+        })
+    }
+
+    fn fetch_stats(&self) -> BoxFuture<'_, Result<ServerStats, String>> {
+        Box::pin(async move {
+            let response = self.request(reqwest::Method::GET, "/stats")
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server liefert keine Statistiken ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<ServerStats>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn fetch_server_info(&self) -> BoxFuture<'_, Result<ServerInfo, String>> {
+        Box::pin(async move {
+            let mut last_status = None;
+            let mut info: Option<ServerInfo> = None;
+            for path in ["/health", "/version"] {
+                let sent = self.request(reqwest::Method::GET, path)
+                    .timeout(Duration::from_secs(2))
+                    .send_traced(&self.trace)
+                    .await;
+                let response = match sent {
+                    Ok(response) => response,
+                    // /health answered already: that is enough
+                    Err(_) if info.is_some() => break,
+                    Err(e) => return Err(format!("Connection error: {}", e)),
+                };
+                if !response.status().is_success() {
+                    last_status = Some(response.status());
+                    continue;
+                }
+                let body = response.text().await.map_err(|e| format!("Failed to read response: {}", e))?;
+                // A bare "ok" or "1.4.2" is an answer as well
+                let found: ServerInfo = serde_json::from_str(&body).unwrap_or_else(|_| ServerInfo {
+                    version: Some(body.trim().to_string()).filter(|v| path == "/version" && !v.is_empty()),
+                    ..Default::default()
+                });
+                let merged = info.get_or_insert_with(ServerInfo::default);
+                merged.version = merged.version.take().or(found.version);
+                merged.model = merged.model.take().or(found.model);
+                merged.protocol = merged.protocol.or(found.protocol);
+                // A health check without a version ("ok") goes on to /version
+                if merged.version.is_some() {
+                    break;
+                }
+            }
+            info.ok_or_else(|| trf("Server liefert keine Versionsinfo ({})", &[&last_status.map(|s| s.to_string()).unwrap_or_default()]))
+        })
+    }
+
+    fn fetch_models(&self) -> BoxFuture<'_, Result<Vec<String>, String>> {
+        Box::pin(async move {
+            let response = self.request(reqwest::Method::GET, "/models")
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server bietet keine Modellauswahl ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<Vec<String>>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+
+    fn edit_message<'a>(&'a self, id: &'a str, content: String) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let url = self.url_with_segments(&["messages", id])?;
+            let response = self.request_url(reqwest::Method::PUT, url)
+                .json(&serde_json::json!({ "content": content }))
+                .timeout(Duration::from_secs(5))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            match response.status() {
+                s if s.is_success() => Ok(()),
+                reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::METHOD_NOT_ALLOWED => {
+                    Err(tr("Server unterstützt kein Bearbeiten").to_string())
+                }
+                s => Err(trf("Bearbeiten fehlgeschlagen: {}", &[&s.to_string()])),
+            }
+        })
+    }
+
+    fn mark_read<'a>(
+        &'a self,
+        room: Option<&'a str>,
+        timestamp: u64,
+        message_id: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.request(reqwest::Method::POST, "/read")
+                .json(&serde_json::json!({ "room": room, "timestamp": timestamp, "message_id": message_id }))
+                .timeout(Duration::from_secs(2))
+                .send_traced(&self.trace)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("Connection error: {}", e))
+        })
+    }
+
+    fn list_rooms(&self) -> BoxFuture<'_, Result<Vec<RoomInfo>, String>> {
+        Box::pin(async move {
+            let response = self.request(reqwest::Method::GET, "/rooms")
+                .timeout(Duration::from_secs(5))
+                .send_traced(&self.trace)
+                .await
+                .map_err(|e| format!("Connection error: {}", e))?;
+            if !response.status().is_success() {
+                return Err(trf("Server unterstützt keine Räume ({})", &[&response.status().to_string()]));
+            }
+            response
+                .json::<Vec<RoomInfo>>()
+                .await
+                .map_err(|e| format!("Failed to parse response: {}", e))
+        })
+    }
+}
@@ -0,0 +1,466 @@
+//! Conversations as documents: Markdown, JSON and HTML exports for tickets
+//! and wikis.
+
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use std::{fs, path::{Path, PathBuf}, str::FromStr};
+
+use crate::{history::*, lang::*, ui::*};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    /// Format matching the extension of `path` (`.md`, `.json`, `.html`)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "json" => Ok(ExportFormat::Json),
+            "html" | "htm" => Ok(ExportFormat::Html),
+            other => Err(trf("Unbekanntes Format '{}' (md, json, html)", &[other])),
+        }
+    }
+}
+
+/// Names written for own messages and the assistant's answers
+#[derive(Debug, Clone, Copy)]
+pub struct Speakers<'a> {
+    pub own: &'a str,
+    pub assistant: &'a str,
+}
+
+impl Speakers<'_> {
+    fn of<'m>(&'m self, msg: &'m Message) -> &'m str {
+        match msg.role.as_str() {
+            "user" => msg.sender.as_deref().unwrap_or(self.own),
+            "assistant" => self.assistant,
+            "error" => tr("Fehler"),
+            "announcement" => tr("Ankündigung"),
+            other => other,
+        }
+    }
+}
+
+/// `messages` as a document; status notes are left out
+pub fn export_transcript(messages: &[Message], title: Option<&str>, format: ExportFormat, speakers: Speakers) -> String {
+    let title = title.map_or_else(|| tr("Unterhaltung").to_string(), str::to_string);
+    let shown: Vec<&Message> = messages.iter().filter(|m| m.role != "system").collect();
+    match format {
+        ExportFormat::Markdown => to_markdown(&shown, &title, speakers),
+        ExportFormat::Json => to_json(&shown, &title, speakers),
+        ExportFormat::Html => to_html(&shown, &title, speakers),
+    }
+}
+
+/// "2024-05-01 12:03:04" from the message's time, else its clock time
+fn when(msg: &Message) -> String {
+    msg.timestamp_ms
+        .and_then(|ms| Local.timestamp_millis_opt(ms as i64).single())
+        .map_or_else(|| msg.timestamp.clone(), |t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn heading(msg: &Message, speakers: Speakers) -> String {
+    let mut who = speakers.of(msg).to_string();
+    if let Some(model) = &msg.model {
+        who.push_str(&format!(" ({})", model));
+    }
+    if let Some(to) = &msg.to {
+        who.push_str(&format!(" → {}", to));
+    }
+    who
+}
+
+fn to_markdown(messages: &[&Message], title: &str, speakers: Speakers) -> String {
+    let mut out = format!("# {}\n\n_{}_\n", title, trf("Exportiert aus hank-tui am {}", &[&Local::now().format("%Y-%m-%d %H:%M").to_string()]));
+    for msg in messages {
+        out.push_str(&format!("\n### {} · {}\n\n", heading(msg, speakers), when(msg)));
+        // Verbatim, so fenced code blocks stay intact
+        out.push_str(msg.content.trim_end());
+        out.push('\n');
+        for call in &msg.tool_calls {
+            out.push_str(&format!("\n- 🔧 `{}`", call.summary()));
+        }
+        if !msg.tool_calls.is_empty() {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[derive(Serialize)]
+struct ExportedConversation<'a> {
+    title: &'a str,
+    exported_at: String,
+    messages: Vec<ExportedMessage<'a>>,
+}
+
+/// Close to the OpenAI chat format: `role`, `name`, `content`
+#[derive(Serialize)]
+struct ExportedMessage<'a> {
+    role: &'a str,
+    name: &'a str,
+    content: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[ToolCall]>::is_empty")]
+    tool_calls: &'a [ToolCall],
+}
+
+fn to_json(messages: &[&Message], title: &str, speakers: Speakers) -> String {
+    let conversation = ExportedConversation {
+        title,
+        exported_at: Local::now().to_rfc3339(),
+        messages: messages
+            .iter()
+            .map(|msg| ExportedMessage {
+                role: &msg.role,
+                name: speakers.of(msg),
+                content: &msg.content,
+                timestamp: msg.timestamp_ms.and_then(|ms| Local.timestamp_millis_opt(ms as i64).single()).map(|t| t.to_rfc3339()),
+                model: msg.model.as_deref(),
+                to: msg.to.as_deref(),
+                tool_calls: &msg.tool_calls,
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&conversation).unwrap_or_default() + "\n"
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Message text as HTML: fenced code blocks become `<pre><code>`, the rest
+/// paragraphs with line breaks
+fn content_html(content: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut code: Option<(String, Vec<&str>)> = None;
+    let flush = |paragraph: &mut Vec<&str>, out: &mut String| {
+        if !paragraph.is_empty() {
+            let lines: Vec<String> = paragraph.iter().map(|l| escape_html(l)).collect();
+            out.push_str(&format!("<p>{}</p>\n", lines.join("<br>\n")));
+            paragraph.clear();
+        }
+    };
+    for line in content.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut code, fence) {
+            (Some((lang, lines)), Some(_)) => {
+                let class = if lang.is_empty() { String::new() } else { format!(" class=\"language-{}\"", escape_html(lang)) };
+                out.push_str(&format!("<pre><code{}>{}</code></pre>\n", class, escape_html(&lines.join("\n"))));
+                code = None;
+            }
+            (Some((_, lines)), None) => lines.push(line),
+            (None, Some(lang)) => {
+                flush(&mut paragraph, &mut out);
+                code = Some((lang.trim().to_string(), Vec::new()));
+            }
+            (None, None) if line.trim().is_empty() => flush(&mut paragraph, &mut out),
+            (None, None) => paragraph.push(line),
+        }
+    }
+    // An unclosed fence still shows its code
+    if let Some((_, lines)) = code {
+        out.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&lines.join("\n"))));
+    }
+    flush(&mut paragraph, &mut out);
+    out
+}
+
+fn to_html(messages: &[&Message], title: &str, speakers: Speakers) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; line-height: 1.4; }}\n\
+         article {{ border-top: 1px solid #ccc; padding: .5em 0; }}\n\
+         article.assistant header strong {{ color: #6a3d9a; }}\n\
+         time {{ color: #777; font-size: .9em; margin-left: .5em; }}\n\
+         pre {{ background: #f4f4f4; padding: .5em; overflow-x: auto; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        title = escape_html(title)
+    );
+    for msg in messages {
+        out.push_str(&format!(
+            "<article class=\"{}\">\n<header><strong>{}</strong><time>{}</time></header>\n",
+            escape_html(&msg.role),
+            escape_html(&heading(msg, speakers)),
+            escape_html(&when(msg))
+        ));
+        out.push_str(&content_html(&msg.content));
+        for call in &msg.tool_calls {
+            out.push_str(&format!("<p>🔧 <code>{}</code></p>\n", escape_html(&call.summary())));
+        }
+        out.push_str("</article>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// A conversation read from another tool's export
+#[derive(Clone)]
+pub struct ImportedConversation {
+    pub title: Option<String>,
+    pub messages: Vec<Message>,
+}
+
+/// Conversations in `text`: an OpenAI-style JSON export (a message list,
+/// `{"messages": [...]}`, ChatGPT's `conversations.json`, our own JSON
+/// export) or a Markdown transcript with one heading or `**Name:**` line per
+/// message. Speaker names matching `speakers.own` become own messages.
+pub fn import_transcript(text: &str, speakers: Speakers) -> Result<Vec<ImportedConversation>, String> {
+    let trimmed = text.trim_start();
+    let conversations = if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        let value: serde_json::Value = serde_json::from_str(text).map_err(|e| trf("Ungültiges JSON: {}", &[&e.to_string()]))?;
+        from_json(&value, speakers)
+    } else {
+        vec![from_markdown(text, speakers)]
+    };
+    let conversations: Vec<ImportedConversation> = conversations.into_iter().filter(|c| !c.messages.is_empty()).collect();
+    if conversations.is_empty() {
+        return Err(tr("Keine Nachrichten erkannt").to_string());
+    }
+    Ok(conversations)
+}
+
+/// A message as imported: `ms` is its original time (None = now)
+fn imported(role: &str, content: String, ms: Option<u64>) -> Message {
+    let msg = Message::new(role, content);
+    match ms {
+        Some(ms) => Message { timestamp: format_timestamp(ms), timestamp_ms: Some(ms), ..msg },
+        None => msg,
+    }
+}
+
+/// Milliseconds from an RFC 3339 string or epoch seconds/milliseconds
+fn json_time(value: &serde_json::Value) -> Option<u64> {
+    match value {
+        serde_json::Value::String(text) => chrono::DateTime::parse_from_rfc3339(text).ok().map(|t| t.timestamp_millis() as u64),
+        serde_json::Value::Number(n) => n.as_f64().filter(|n| *n > 0.0).map(|n| if n > 1e12 { n as u64 } else { (n * 1000.0) as u64 }),
+        _ => None,
+    }
+}
+
+/// Text of an OpenAI `content`: a string, a list of parts
+/// (`{"type": "text", "text": …}`) or ChatGPT's `{"parts": [...]}`
+fn json_text(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.as_str().or_else(|| part.get("text").and_then(|t| t.as_str())))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        serde_json::Value::Object(object) => object.get("parts").map(json_text).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn json_message(value: &serde_json::Value, speakers: Speakers) -> Option<Message> {
+    // ChatGPT nests the role under `author`
+    let role = value.get("role").or_else(|| value.pointer("/author/role"))?.as_str()?;
+    if role != "user" && role != "assistant" {
+        return None;
+    }
+    let content = json_text(value.get("content")?);
+    if content.trim().is_empty() {
+        return None;
+    }
+    let time = ["timestamp", "create_time", "created_at"].iter().find_map(|key| value.get(key).and_then(json_time));
+    let text = |key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let mut msg = imported(role, content, time);
+    msg.sender = text("name").filter(|name| role == "user" && name != speakers.own);
+    msg.model = text("model").or_else(|| value.pointer("/metadata/model_slug").and_then(|v| v.as_str()).map(str::to_string));
+    msg.to = text("to");
+    msg.tool_calls = value.get("tool_calls").and_then(|calls| serde_json::from_value(calls.clone()).ok()).unwrap_or_default();
+    Some(msg)
+}
+
+fn from_json(value: &serde_json::Value, speakers: Speakers) -> Vec<ImportedConversation> {
+    let title = value.get("title").and_then(|t| t.as_str()).map(str::to_string);
+    if let Some(mapping) = value.get("mapping").and_then(|m| m.as_object()) {
+        // ChatGPT: a tree of nodes; the shown branch leads from the root to
+        // `current_node`
+        let mut branch = Vec::new();
+        let mut node = value.get("current_node").and_then(|n| n.as_str());
+        while let Some(entry) = node.and_then(|id| mapping.get(id)) {
+            branch.push(entry);
+            node = entry.get("parent").and_then(|p| p.as_str());
+        }
+        if branch.is_empty() {
+            branch = mapping.values().collect();
+            branch.sort_by(|a, b| {
+                let time = |n: &serde_json::Value| n.pointer("/message/create_time").and_then(|t| t.as_f64()).unwrap_or(0.0);
+                time(a).total_cmp(&time(b))
+            });
+        } else {
+            branch.reverse();
+        }
+        let messages = branch.iter().filter_map(|n| n.get("message")).filter_map(|m| json_message(m, speakers)).collect();
+        return vec![ImportedConversation { title, messages }];
+    }
+    match value {
+        serde_json::Value::Object(object) => {
+            let messages = object.get("messages").and_then(|m| m.as_array()).map(Vec::as_slice).unwrap_or_default();
+            vec![ImportedConversation { title, messages: messages.iter().filter_map(|m| json_message(m, speakers)).collect() }]
+        }
+        // A list of conversations (ChatGPT) or a list of messages
+        serde_json::Value::Array(items) if items.iter().any(|item| item.get("mapping").is_some() || item.get("messages").is_some()) => {
+            items.iter().flat_map(|item| from_json(item, speakers)).collect()
+        }
+        serde_json::Value::Array(items) => {
+            vec![ImportedConversation { title: None, messages: items.iter().filter_map(|m| json_message(m, speakers)).collect() }]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Role of a speaker name in a Markdown transcript, if it is one
+fn speaker_role(name: &str, speakers: Speakers) -> Option<&'static str> {
+    let name = name.trim().trim_matches('*').trim_end_matches(':').trim().to_lowercase();
+    if name == speakers.own.to_lowercase() || ["user", "you", "du", "ich", "human", "me"].contains(&name.as_str()) {
+        Some("user")
+    } else if name == speakers.assistant.to_lowercase() || ["assistant", "hank", "chatgpt", "gpt", "ai", "bot", "model"].contains(&name.as_str()) {
+        Some("assistant")
+    } else {
+        None
+    }
+}
+
+/// The start of a message: `## Name`, `### Name (model) → to · time` (our
+/// export) or `**Name:** text` / `Name: text` for a known speaker
+fn speaker_line(line: &str, speakers: Speakers) -> Option<Message> {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    if (2..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+        let header = line[hashes..].trim();
+        let (who, time) = header.rsplit_once(" · ").map_or((header, None), |(who, time)| (who, Some(time)));
+        let (who, to) = who.split_once(" → ").map_or((who, None), |(who, to)| (who, Some(to.trim().to_string())));
+        let (name, model) = match who.strip_suffix(')').and_then(|w| w.rsplit_once(" (")) {
+            Some((name, model)) => (name, Some(model.to_string())),
+            None => (who, None),
+        };
+        // Other participants only count in our own format, with a time
+        let role = speaker_role(name, speakers).or(time.map(|_| "user"))?;
+        let ms = time
+            .and_then(|t| chrono::NaiveDateTime::parse_from_str(t.trim(), "%Y-%m-%d %H:%M:%S").ok())
+            .and_then(|t| Local.from_local_datetime(&t).single())
+            .map(|t| t.timestamp_millis() as u64);
+        let mut msg = imported(role, String::new(), ms);
+        msg.sender = Some(name.trim().to_string()).filter(|_| role == "user" && speaker_role(name, speakers).is_none());
+        msg.model = model;
+        msg.to = to;
+        return Some(msg);
+    }
+    let line = line.trim_start();
+    let (name, rest) = match line.strip_prefix("**") {
+        Some(bold) => bold.split_once("**").map(|(name, rest)| (name, rest.trim_start_matches(':')))?,
+        None => line.split_once(':')?,
+    };
+    let role = speaker_role(name, speakers)?;
+    Some(imported(role, rest.trim().to_string(), None))
+}
+
+fn from_markdown(text: &str, speakers: Speakers) -> ImportedConversation {
+    let mut title = None;
+    let mut messages: Vec<Message> = Vec::new();
+    let mut current: Option<Message> = None;
+    let mut in_fence = false;
+    for line in text.lines() {
+        if !in_fence {
+            if current.is_none() && title.is_none()
+                && let Some(heading) = line.strip_prefix("# ")
+            {
+                title = Some(heading.trim().to_string());
+                continue;
+            }
+            if let Some(msg) = speaker_line(line, speakers) {
+                messages.extend(current.replace(msg));
+                continue;
+            }
+        }
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        // Text before the first speaker (e.g. our "exported on" line) is dropped
+        if let Some(msg) = current.as_mut() {
+            if !msg.content.is_empty() {
+                msg.content.push('\n');
+            }
+            msg.content.push_str(line);
+        }
+    }
+    messages.extend(current);
+    for msg in &mut messages {
+        msg.content = msg.content.trim().to_string();
+    }
+    messages.retain(|m| !m.content.is_empty());
+    ImportedConversation { title, messages }
+}
+
+/// Save `conversation` as a new conversation in `dir` (the sessions
+/// directory) for `server_url`, so Ctrl+O lists it and the global search
+/// finds it. Returns the path of the history file.
+pub fn save_imported(dir: &Path, server_url: &str, conversation: &ImportedConversation) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let id = new_session_id();
+    let path = (1..)
+        .map(|n| dir.join(if n == 1 { format!("{}.json", id) } else { format!("{}-{}.json", id, n) }))
+        .find(|path| !path.exists())
+        .unwrap_or_default();
+    ChatHistory::save_to(&path, server_url, &conversation.messages, None)?;
+    // save_to names the conversation after its first own message
+    if let Some(title) = &conversation.title
+        && let Some(mut history) = ChatHistory::load_from(&path)
+    {
+        history.title = Some(title.clone());
+        fs::write(&path, serde_json::to_string_pretty(&history)?)?;
+    }
+    Ok(path)
+}
+
+/// The conversation as plain lines for `hank-tui history show`:
+/// `[time] Name: text`, further lines of a message indented. `last` keeps
+/// only the newest messages; `color` adds ANSI colors for a terminal.
+pub fn print_transcript(messages: &[Message], speakers: Speakers, last: Option<usize>, color: bool) -> String {
+    let shown: Vec<&Message> = messages.iter().filter(|m| m.role != "system").collect();
+    let skip = last.map_or(0, |last| shown.len().saturating_sub(last));
+    let paint = |code: &str, text: &str| if color { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text.to_string() };
+    let mut out = String::new();
+    for msg in &shown[skip..] {
+        let name_color = match msg.role.as_str() {
+            "user" => "1;36",
+            "assistant" => "1;35",
+            "error" => "1;31",
+            _ => "1",
+        };
+        out.push_str(&format!("{} {}:", paint("2", &format!("[{}]", when(msg))), paint(name_color, &heading(msg, speakers))));
+        let mut lines = msg.content.lines();
+        if let Some(first) = lines.next() {
+            out.push(' ');
+            out.push_str(first);
+        }
+        out.push('\n');
+        for line in lines {
+            out.push_str(&format!("  {}\n", line));
+        }
+        for call in &msg.tool_calls {
+            out.push_str(&format!("  🔧 {}\n", call.summary()));
+        }
+    }
+    out
+}
@@ -0,0 +1,3049 @@
+//! Rendering: colors, help, line layout helpers and the frame drawing.
+
+use chrono::{Local, TimeZone};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Terminal,
+};
+use std::{env, fs, io::{self, Write}, path::PathBuf, time::{Duration, Instant}};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{app::*, config::*, history::*, keys::*, lang::*, net::*};
+
+/// Colors for other participants. Leaves out the colors with a fixed meaning:
+/// cyan (own messages), green (Hank), red (errors), yellow (mentions).
+const SENDER_PALETTE: [Color; 10] = [
+    Color::Magenta,
+    Color::Blue,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightBlue,
+    Color::LightCyan,
+    Color::LightYellow,
+    Color::Indexed(208),
+    Color::Indexed(141),
+    Color::Indexed(37),
+];
+
+/// Stable color for a participant name (FNV-1a hash into the palette), so the
+/// same person keeps their color across sessions and machines
+pub fn sender_color(name: &str) -> Color {
+    let hash = name
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+    SENDER_PALETTE[(hash % SENDER_PALETTE.len() as u64) as usize]
+}
+
+const OKABE_ORANGE: Color = Color::Rgb(0xE6, 0x9F, 0x00);
+const OKABE_SKY_BLUE: Color = Color::Rgb(0x56, 0xB4, 0xE9);
+const OKABE_GREEN: Color = Color::Rgb(0x00, 0x9E, 0x73);
+const OKABE_YELLOW: Color = Color::Rgb(0xF0, 0xE4, 0x42);
+const OKABE_BLUE: Color = Color::Rgb(0x00, 0x72, 0xB2);
+const OKABE_VERMILLION: Color = Color::Rgb(0xD5, 0x5E, 0x00);
+const OKABE_PURPLE: Color = Color::Rgb(0xCC, 0x79, 0xA7);
+
+/// Colors of the UI by meaning. `Theme::preset` knows the built-in presets;
+/// the default is the dark-terminal look.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// Own messages, focused borders, section titles
+    pub accent: Color,
+    /// Timestamps, hints, system messages
+    pub muted: Color,
+    pub assistant: Color,
+    pub error: Color,
+    /// Mentions, warnings, announcements
+    pub highlight: Color,
+    /// Tool calls, threads
+    pub info: Color,
+    /// Direct messages
+    pub private: Color,
+    /// Text on a highlight/accent background
+    pub on_highlight: Color,
+    pub status_fg: Color,
+    pub status_bg: Color,
+    /// Background of popups
+    pub overlay_bg: Color,
+    /// Color other participants by name (off for NO_COLOR)
+    pub sender_colors: bool,
+    /// Mark roles with a glyph in front of the prefix, so they do not
+    /// depend on telling colors apart
+    pub role_glyphs: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            accent: Color::Cyan,
+            muted: Color::DarkGray,
+            assistant: Color::Green,
+            error: Color::Red,
+            highlight: Color::Yellow,
+            info: Color::Blue,
+            private: Color::LightMagenta,
+            on_highlight: Color::Black,
+            status_fg: Color::White,
+            status_bg: Color::DarkGray,
+            overlay_bg: Color::Black,
+            sender_colors: true,
+            role_glyphs: false,
+        }
+    }
+}
+
+impl Theme {
+    pub const PRESETS: [&'static str; 8] = [
+        "dark",
+        "high-contrast",
+        "light-terminal",
+        "solarized",
+        "no-color",
+        "deuteranopia",
+        "protanopia",
+        "tritanopia",
+    ];
+
+    /// Built-in preset by name (see `PRESETS`; "light" is short for
+    /// "light-terminal")
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::default()),
+            "high-contrast" => Some(Self {
+                accent: Color::LightCyan,
+                muted: Color::White,
+                assistant: Color::LightGreen,
+                error: Color::LightRed,
+                highlight: Color::LightYellow,
+                info: Color::LightBlue,
+                private: Color::LightMagenta,
+                on_highlight: Color::Black,
+                status_fg: Color::Black,
+                status_bg: Color::White,
+                overlay_bg: Color::Black,
+                sender_colors: true,
+                role_glyphs: false,
+            }),
+            // Dark shades that stay readable on white backgrounds
+            "light-terminal" | "light" => Some(Self {
+                accent: Color::Blue,
+                muted: Color::Indexed(240),
+                assistant: Color::Indexed(28),
+                error: Color::Indexed(160),
+                highlight: Color::Indexed(130),
+                info: Color::Indexed(25),
+                private: Color::Indexed(90),
+                on_highlight: Color::White,
+                status_fg: Color::White,
+                status_bg: Color::Indexed(240),
+                overlay_bg: Color::Reset,
+                sender_colors: true,
+                role_glyphs: false,
+            }),
+            // Solarized dark (https://ethanschoonover.com/solarized/)
+            "solarized" => Some(Self {
+                accent: Color::Rgb(0x26, 0x8B, 0xD2),
+                muted: Color::Rgb(0x58, 0x6E, 0x75),
+                assistant: Color::Rgb(0x85, 0x99, 0x00),
+                error: Color::Rgb(0xDC, 0x32, 0x2F),
+                highlight: Color::Rgb(0xB5, 0x89, 0x00),
+                info: Color::Rgb(0x6C, 0x71, 0xC4),
+                private: Color::Rgb(0xD3, 0x36, 0x82),
+                on_highlight: Color::Rgb(0x00, 0x2B, 0x36),
+                status_fg: Color::Rgb(0x93, 0xA1, 0xA1),
+                status_bg: Color::Rgb(0x07, 0x36, 0x42),
+                overlay_bg: Color::Rgb(0x00, 0x2B, 0x36),
+                sender_colors: true,
+                role_glyphs: false,
+            }),
+            "no-color" => Some(Self::no_color()),
+            // Color-blind presets from the Okabe-Ito palette, plus role glyphs
+            "deuteranopia" => Some(Self::color_blind(OKABE_SKY_BLUE, OKABE_ORANGE, OKABE_VERMILLION, OKABE_YELLOW)),
+            // Red looks dark to protanopes, errors use yellow instead
+            "protanopia" => Some(Self::color_blind(OKABE_SKY_BLUE, OKABE_ORANGE, OKABE_YELLOW, Color::White)),
+            // No blue/yellow contrasts for tritanopes
+            "tritanopia" => Some(Self::color_blind(OKABE_PURPLE, OKABE_GREEN, OKABE_VERMILLION, Color::White)),
+            _ => None,
+        }
+    }
+
+    /// Terminal default colors everywhere; bold/italic still carry structure
+    pub fn no_color() -> Self {
+        Self {
+            accent: Color::Reset,
+            muted: Color::Reset,
+            assistant: Color::Reset,
+            error: Color::Reset,
+            highlight: Color::Reset,
+            info: Color::Reset,
+            private: Color::Reset,
+            on_highlight: Color::Reset,
+            status_fg: Color::Reset,
+            status_bg: Color::Reset,
+            overlay_bg: Color::Reset,
+            sender_colors: false,
+            role_glyphs: true,
+        }
+    }
+
+    fn color_blind(accent: Color, assistant: Color, error: Color, highlight: Color) -> Self {
+        Self {
+            accent,
+            muted: Color::Indexed(245),
+            assistant,
+            error,
+            highlight,
+            info: OKABE_BLUE,
+            private: OKABE_PURPLE,
+            on_highlight: Color::Black,
+            status_fg: Color::Black,
+            status_bg: Color::Indexed(250),
+            overlay_bg: Color::Black,
+            sender_colors: true,
+            role_glyphs: true,
+        }
+    }
+
+    /// Glyph in front of the role prefix when `role_glyphs` is set
+    pub fn role_glyph(&self, role: &str, own: bool, mention: bool) -> &'static str {
+        if !self.role_glyphs {
+            return "";
+        }
+        match role {
+            _ if mention => "★ ",
+            "user" if own => "› ",
+            "user" => "• ",
+            "assistant" => "◆ ",
+            "error" => "✗ ",
+            "announcement" => "‼ ",
+            _ => "",
+        }
+    }
+
+    /// Theme for this run: `--theme` wins over `NO_COLOR`
+    /// (https://no-color.org), which wins over `theme` in the config and
+    /// the default
+    pub fn select(requested: Option<&str>, configured: Option<&str>) -> Result<Self, String> {
+        let dir = Self::dir();
+        match (requested, configured) {
+            (Some(name), _) => Self::load(name, dir.as_deref()),
+            _ if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) => Ok(Self::no_color()),
+            (None, Some(name)) => Self::load(name, dir.as_deref()),
+            (None, None) => Ok(Self::default()),
+        }
+    }
+
+    /// Directory of the theme files (`~/.config/hank-tui/themes/`)
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|mut path| {
+            path.push("hank-tui");
+            path.push("themes");
+            path
+        })
+    }
+
+    /// Preset or theme file `<dir>/<name>.toml`; a file may override a preset
+    pub fn load(name: &str, dir: Option<&std::path::Path>) -> Result<Self, String> {
+        if let Some(path) = dir.map(|dir| dir.join(format!("{}.toml", name)))
+            && path.is_file()
+        {
+            let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            return Self::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e));
+        }
+        Self::preset(name).ok_or_else(|| {
+            trf("Unbekanntes Theme '{}' (verfügbar: {})", &[name, &Self::available(dir).join(", ")])
+        })
+    }
+
+    /// Names of the presets and theme files in `dir`
+    pub fn available(dir: Option<&std::path::Path>) -> Vec<String> {
+        let mut names: Vec<String> = Self::PRESETS.iter().map(|name| name.to_string()).collect();
+        let mut files: Vec<String> = dir
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .filter(|name| !names.contains(name))
+            .collect();
+        files.sort();
+        names.extend(files);
+        names
+    }
+
+    /// Theme file: `base` names the preset to start from (default "dark"),
+    /// every other key replaces one color, e.g. `accent = "#ff8800"`
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let file: ThemeFile = toml::from_str(text).map_err(|e| e.message().to_string())?;
+        let base = file.base.as_deref().unwrap_or("dark");
+        let mut theme = Self::preset(base).ok_or_else(|| trf("base: unbekanntes Theme '{}'", &[base]))?;
+        let colors = [
+            (&file.accent, &mut theme.accent, "accent"),
+            (&file.muted, &mut theme.muted, "muted"),
+            (&file.assistant, &mut theme.assistant, "assistant"),
+            (&file.error, &mut theme.error, "error"),
+            (&file.highlight, &mut theme.highlight, "highlight"),
+            (&file.info, &mut theme.info, "info"),
+            (&file.private, &mut theme.private, "private"),
+            (&file.on_highlight, &mut theme.on_highlight, "on_highlight"),
+            (&file.status_fg, &mut theme.status_fg, "status_fg"),
+            (&file.status_bg, &mut theme.status_bg, "status_bg"),
+            (&file.overlay_bg, &mut theme.overlay_bg, "overlay_bg"),
+        ];
+        for (value, slot, key) in colors {
+            if let Some(value) = value {
+                *slot = value.parse().map_err(|_| trf("{}: '{}' ist keine Farbe", &[key, value]))?;
+            }
+        }
+        theme.sender_colors = file.sender_colors.unwrap_or(theme.sender_colors);
+        theme.role_glyphs = file.role_glyphs.unwrap_or(theme.role_glyphs);
+        Ok(theme)
+    }
+}
+
+/// A theme file as written in `themes/<name>.toml`
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    accent: Option<String>,
+    muted: Option<String>,
+    assistant: Option<String>,
+    error: Option<String>,
+    highlight: Option<String>,
+    info: Option<String>,
+    private: Option<String>,
+    on_highlight: Option<String>,
+    status_fg: Option<String>,
+    status_bg: Option<String>,
+    overlay_bg: Option<String>,
+    sender_colors: Option<bool>,
+    role_glyphs: Option<bool>,
+}
+
+/// tmux integration, active when running inside tmux (`$TMUX`): copies go
+/// into the paste buffer, the unread count is published as the window option
+/// `@hank_unread` and the window can carry the conversation name
+pub struct Tmux {
+    /// Our pane (`$TMUX_PANE`), so commands target the right window
+    pub pane: String,
+    pub rename_window: bool,
+    unread: Option<usize>,
+    title: Option<String>,
+}
+
+impl Tmux {
+    pub fn detect(rename_window: bool) -> Option<Self> {
+        env::var_os("TMUX")?;
+        Some(Self {
+            pane: env::var("TMUX_PANE").unwrap_or_default(),
+            rename_window,
+            unread: None,
+            title: None,
+        })
+    }
+
+    fn run(&self, subcommand: &str, args: &[&str]) -> bool {
+        let mut command = std::process::Command::new("tmux");
+        command.arg(subcommand);
+        if !self.pane.is_empty() {
+            command.args(["-t", &self.pane]);
+        }
+        command
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    /// Update window option and name; tmux is only called on changes
+    pub fn sync(&mut self, unread: usize, title: &str) {
+        if self.unread != Some(unread) {
+            self.unread = Some(unread);
+            if unread > 0 {
+                self.run("set-option", &["-w", "@hank_unread", &unread.to_string()]);
+            } else {
+                self.run("set-option", &["-wu", "@hank_unread"]);
+            }
+        }
+        if self.rename_window && self.title.as_deref() != Some(title) {
+            self.title = Some(title.to_string());
+            self.run("rename-window", &[&format!("hank: {}", title)]);
+        }
+    }
+
+    /// Give the window back to tmux on exit
+    pub fn restore(&self) {
+        self.run("set-option", &["-wu", "@hank_unread"]);
+        if self.rename_window {
+            self.run("set-option", &["-w", "automatic-rename", "on"]);
+        }
+    }
+}
+
+/// Put text into the tmux paste buffer (`-w` also forwards it to the outer
+/// terminal's clipboard where tmux is set up for that)
+fn tmux_load_buffer(text: &str) -> bool {
+    let child = std::process::Command::new("tmux")
+        .args(["load-buffer", "-w", "-"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else { return false };
+    let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+    child.wait().is_ok_and(|status| status.success()) && written
+}
+
+/// Section of the F1 help
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HelpSection {
+    General,
+    Input,
+    Scroll,
+    Chat,
+    Other,
+    Commands,
+}
+
+impl HelpSection {
+    pub const ALL: [HelpSection; 6] = [
+        HelpSection::General,
+        HelpSection::Input,
+        HelpSection::Scroll,
+        HelpSection::Chat,
+        HelpSection::Other,
+        HelpSection::Commands,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            HelpSection::General => "Allgemein",
+            HelpSection::Input => "Eingabe (Input fokussiert)",
+            HelpSection::Scroll => "Chat Scroll",
+            HelpSection::Chat => "Chat (Chat fokussiert)",
+            HelpSection::Other => "Sonstiges",
+            HelpSection::Commands => "Befehle",
+        }
+    }
+}
+
+/// A documented key binding or command
+pub struct Binding {
+    pub section: HelpSection,
+    pub keys: &'static str,
+    pub description: &'static str,
+    /// Configurable actions behind the keys; the help shows their
+    /// configured chords once they differ from the built-in ones
+    pub actions: &'static [Action],
+}
+
+const fn bind(section: HelpSection, keys: &'static str, description: &'static str) -> Binding {
+    Binding { section, keys, description, actions: &[] }
+}
+
+const fn act(section: HelpSection, actions: &'static [Action], keys: &'static str, description: &'static str) -> Binding {
+    Binding { section, keys, description, actions }
+}
+
+/// All key bindings and commands; the F1 help is generated from this list
+pub const KEYMAP: &[Binding] = {
+    use HelpSection::*;
+    &[
+        act(General, &[Action::Help], "F1, ?", "Hilfe anzeigen/schließen"),
+        act(General, &[Action::FocusToggle], "Tab", "Fokus wechseln (Input ↔ Chat)"),
+        act(General, &[Action::Quit], "Esc, Ctrl+C", "Beenden"),
+        act(General, &[Action::Conversations], "F2", "Unterhaltungen (↑↓, Enter=öffnen)"),
+        bind(General, "F12", "Debug-Anzeige"),
+        bind(General, "F9", "Server-Statistik (Last, Warteschlange)"),
+        act(General, &[Action::CopyReply], "Ctrl+Y", "Letzte Antwort von Hank kopieren"),
+        act(General, &[Action::CopyCode], "Ctrl+Shift+Y", "Nur Codeblöcke der letzten Antwort kopieren"),
+        bind(General, "Esc (wartend)", "Anfrage abbrechen / Antwort stoppen"),
+        act(Input, &[Action::Send], "Ctrl+S", "Nachricht senden"),
+        act(Input, &[Action::Newline], "Enter", "Neue Zeile"),
+        bind(Input, "Ctrl+Enter", "Senden (Shift+Enter = neue Zeile)"),
+        bind(Input, "Ctrl+V", "Einfügen aus Zwischenablage"),
+        bind(Input, "Ctrl+X Ctrl+E", "Eingabe im $EDITOR schreiben"),
+        bind(Input, "↑/↓", "Cursor zwischen Zeilen bewegen"),
+        bind(Input, "←/→", "Cursor links/rechts"),
+        bind(Input, "Home/End", "Zeilenanfang/-ende (auch Ctrl+A/E)"),
+        bind(Input, "Ctrl+←/→", "Wortweise springen (auch Alt+B/F)"),
+        bind(Input, "Ctrl+W", "Wort vor dem Cursor löschen"),
+        bind(Input, "Ctrl+U/K", "Bis Zeilenanfang/-ende löschen"),
+        bind(Input, "Ctrl+↑/↓", "Command History (vorherige Nachrichten)"),
+        bind(Scroll, "Tab", "Chat fokussieren"),
+        act(Scroll, &[Action::ScrollUp, Action::ScrollDown], "Alt+↑/↓", "Chat scrollen (immer)"),
+        bind(Scroll, "Mausrad", "Chat scrollen"),
+        bind(Chat, "↑/↓", "Scrollen (1 Zeile)"),
+        act(Chat, &[Action::PageUp, Action::PageDown], "PgUp/PgDown", "Scrollen (10 Zeilen)"),
+        bind(Chat, "Home", "Zum Anfang"),
+        bind(Chat, "End", "Zum Ende (Auto-Scroll), erst zu neuen Nachrichten"),
+        bind(Chat, "r", "Nicht zugestellte Nachricht erneut senden"),
+        bind(Chat, "t", "Tool-Aufrufe auf-/zuklappen"),
+        bind(Chat, "d", "Kompakte Ansicht ein/aus"),
+        bind(Chat, "z", "Zeitstempel: aus/Uhrzeit/Datum"),
+        bind(Chat, "p", "Verlauf im $PAGER öffnen (/pager)"),
+        bind(Chat, "/", "Im Chat suchen (auch Ctrl+F), n/N = nächster/voriger Treffer"),
+        bind(Chat, "j/k", "Nachricht auswählen (dann auch ↑/↓, Esc=aufheben)"),
+        bind(Chat, "y", "Ausgewählte/letzte Nachricht kopieren (auch via SSH)"),
+        bind(Chat, "Y", "Ganzen Verlauf kopieren"),
+        bind(Chat, ">", "Ausgewählte Nachricht zitieren"),
+        bind(Chat, "R", "Ausgewählte/letzte Nachricht roh / formatiert"),
+        bind(Chat, "J", "JSON in Hanks Antwort auf-/zuklappen"),
+        bind(Chat, "v", "Kopiermodus (hjkl/w/b, v/V, y, > zitieren)"),
+        bind(Chat, "g", "Letzte Antwort mit anderem Modell"),
+        bind(Chat, "x", "Letzte wartende Anfrage abbrechen"),
+        bind(Other, "F3", "Thread öffnen / schließen"),
+        bind(Other, "F4", "Nur Erwähnungen anzeigen"),
+        bind(Other, "F5", "Teilnehmerliste (↑↓, Enter=DM, i)"),
+        bind(Other, "F6", "Zwischen Servern wechseln"),
+        bind(Other, "F7", "Empfänger durchschalten"),
+        bind(Other, "F8", "Maus freigeben/fangen (Textauswahl)"),
+        bind(Other, "Ctrl+Tab", "Nächster Tab (Ctrl+Shift+Tab zurück)"),
+        bind(Other, "Ctrl+PgUp/Dn", "Zwischen Räumen wechseln"),
+        act(Other, &[Action::NewConversation], "Ctrl+N", "Neue Unterhaltung"),
+        act(Other, &[Action::OpenConversation], "Ctrl+O", "Gespeicherte Unterhaltung öffnen"),
+        act(Other, &[Action::GlobalSearch], "Ctrl+Shift+F", "Alle gespeicherten Unterhaltungen durchsuchen"),
+        act(Other, &[Action::Clear], "Ctrl+L", "Chat löschen (Server + lokal)"),
+        act(Other, &[Action::ClearHistory], "Ctrl+Shift+D", "History-Datei löschen"),
+        bind(Commands, "/join <raum>", "Raum betreten / wechseln"),
+        bind(Commands, "/leave", "Aktiven Raum verlassen"),
+        bind(Commands, "/edit", "Letzte eigene Nachricht bearbeiten"),
+        bind(Commands, "/connect h:p|name", "Weitere Server-Verbindung oder Profil öffnen"),
+        bind(Commands, "/disconnect", "Aktive Zusatzverbindung trennen"),
+        bind(Commands, "/msg <n> text", "Direktnachricht an n"),
+        bind(Commands, "/msg <n>", "Empfänger wählen (/all = alle)"),
+        bind(Commands, "/kick /mute /purge <n>", "Moderation (Admins)"),
+        bind(Commands, "/pager", "Verlauf im $PAGER öffnen"),
+        bind(Commands, "/diff", "Änderung der letzten Bearbeitung"),
+        bind(Commands, "/template", "Prompt-Vorlage einfügen"),
+        bind(Commands, "/filter [@hank] re", "Nur passende Nachrichten (Esc)"),
+        bind(Commands, "/date <tag>", "Zum ersten Beitrag des Tages (D)"),
+        bind(Commands, "/theme [name]", "Farbschema wechseln / auflisten"),
+        bind(Commands, "/status", "Server prüfen: Version, Modell, Antwortzeit"),
+        bind(Commands, "/save-config", "Aktiven Server als Standard in die Config schreiben"),
+        bind(Commands, "/export <datei>", "Unterhaltung als .md, .json oder .html speichern"),
+        bind(Commands, "//text", "Nachricht mit / am Anfang"),
+    ]
+};
+
+/// Alternative chords, enabled at startup on terminals that cannot deliver
+/// some of the default keys
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fallback {
+    /// Alt+1 … Alt+8 for F1 … F8 (Linux console, macOS Terminal, unknown TERM)
+    FunctionKeys,
+    /// Alt+→ / Alt+← for Ctrl+Tab / Ctrl+Shift+Tab (legacy key encoding)
+    CtrlTab,
+    /// Ctrl+J for Ctrl+Enter (legacy key encoding)
+    CtrlEnter,
+}
+
+impl Fallback {
+    /// Help line: which chord replaces which
+    pub fn describe(self) -> &'static str {
+        match self {
+            Fallback::FunctionKeys => "  Alt+1…8       statt F1…F8",
+            Fallback::CtrlTab => "  Alt+→/←       statt Ctrl+Tab / Ctrl+Shift+Tab",
+            Fallback::CtrlEnter => "  Ctrl+J        statt Ctrl+Enter",
+        }
+    }
+
+    /// Decide from the terminal's identity which fallbacks are needed.
+    /// The Windows console reports all chords natively.
+    pub fn detect(term: &str, term_program: &str, windows: bool, enhanced_keys: bool, has_terminfo: bool) -> Vec<Self> {
+        if windows {
+            return Vec::new();
+        }
+        let mut fallbacks = Vec::new();
+        let limited = matches!(term, "" | "dumb" | "linux" | "vt100" | "vt220" | "ansi")
+            || term_program == "Apple_Terminal"
+            || !has_terminfo;
+        if limited {
+            fallbacks.push(Fallback::FunctionKeys);
+        }
+        if !enhanced_keys {
+            fallbacks.push(Fallback::CtrlTab);
+            fallbacks.push(Fallback::CtrlEnter);
+        }
+        fallbacks
+    }
+
+    /// Probe `TERM`, `TERM_PROGRAM` and the terminfo database
+    pub fn probe(enhanced_keys: bool) -> Vec<Self> {
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+        let has_terminfo = cfg!(windows) || terminfo_exists(&term);
+        Self::detect(&term, &term_program, cfg!(windows), enhanced_keys, has_terminfo)
+    }
+}
+
+/// Whether the terminfo database has an entry for `term`
+fn terminfo_exists(term: &str) -> bool {
+    let Some(first) = term.chars().next() else { return false };
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    dirs.extend(env::var_os("TERMINFO").map(PathBuf::from));
+    dirs.extend(dirs::home_dir().map(|home| home.join(".terminfo")));
+    if let Some(list) = env::var_os("TERMINFO_DIRS") {
+        dirs.extend(env::split_paths(&list));
+    }
+    dirs.extend(["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo", "/usr/lib/terminfo"].map(PathBuf::from));
+    // Linux uses the first letter as directory, macOS its hex code
+    dirs.iter().any(|dir| {
+        dir.join(first.to_string()).join(term).exists() || dir.join(format!("{:x}", first as u32)).join(term).exists()
+    })
+}
+
+/// Map an active fallback chord to the key it stands for
+pub fn translate_fallback(fallbacks: &[Fallback], key: KeyEvent) -> KeyEvent {
+    if key.modifiers != KeyModifiers::ALT {
+        return key;
+    }
+    let code = match key.code {
+        KeyCode::Char(c @ '1'..='8') if fallbacks.contains(&Fallback::FunctionKeys) => {
+            return KeyEvent::new(KeyCode::F(c as u8 - b'0'), KeyModifiers::NONE);
+        }
+        KeyCode::Right if fallbacks.contains(&Fallback::CtrlTab) => KeyCode::Tab,
+        KeyCode::Left if fallbacks.contains(&Fallback::CtrlTab) => KeyCode::BackTab,
+        _ => return key,
+    };
+    KeyEvent::new(code, KeyModifiers::CONTROL)
+}
+
+/// Read text from the system clipboard
+#[cfg(feature = "clipboard")]
+pub(crate) fn clipboard_get_text() -> Result<String, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| trf("Clipboard-Fehler: {}", &[&e.to_string()]))?;
+    clipboard
+        .get_text()
+        .map_err(|_| tr("Clipboard ist leer oder nicht verfügbar").to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub(crate) fn clipboard_get_text() -> Result<String, String> {
+    Err(tr("Clipboard-Unterstützung nicht einkompiliert (Feature \"clipboard\")").to_string())
+}
+
+/// Copy text to the system clipboard, falling back to the terminal (OSC 52)
+/// when there is no display server, e.g. over SSH
+pub fn clipboard_set_text(text: &str) -> Result<(), String> {
+    let tmux = env::var_os("TMUX").is_some();
+    let in_tmux_buffer = tmux && tmux_load_buffer(text);
+    #[cfg(feature = "clipboard")]
+    if let Ok(mut clipboard) = arboard::Clipboard::new()
+        && clipboard.set_text(text).is_ok()
+    {
+        return Ok(());
+    }
+    if in_tmux_buffer {
+        return Ok(());
+    }
+    let sequence = osc52_sequence(text, tmux);
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(|e| trf("Clipboard-Fehler: {}", &[&e.to_string()]))
+}
+
+/// OSC 52 "set clipboard" escape sequence; inside tmux it is wrapped for DCS
+/// passthrough (needs `set -g allow-passthrough on` or `set-clipboard on`)
+pub fn osc52_sequence(text: &str, tmux: bool) -> String {
+    let osc = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b"))
+    } else {
+        osc
+    }
+}
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Bidi class of a character, reduced to what chat text needs
+#[derive(Clone, Copy, PartialEq)]
+enum BidiClass {
+    Ltr,
+    Rtl,
+    Number,
+    Neutral,
+}
+
+fn bidi_class(ch: char) -> BidiClass {
+    match ch as u32 {
+        // Hebrew, Arabic, Syriac, Thaana, NKo, … and their presentation forms
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF => BidiClass::Rtl,
+        _ if ch.is_ascii_digit() => BidiClass::Number,
+        _ if ch.is_alphabetic() => BidiClass::Ltr,
+        _ => BidiClass::Neutral,
+    }
+}
+
+/// Embedding levels of a line after a simplified Unicode bidi algorithm
+/// (UAX #9): base direction from the first strong character, neutrals
+/// between equal strong runs follow them, numbers stay left to right.
+/// None for lines without right-to-left text.
+pub(crate) fn bidi_levels(line: &str) -> Option<Vec<u8>> {
+    let classes: Vec<BidiClass> = line.chars().map(bidi_class).collect();
+    if !classes.contains(&BidiClass::Rtl) {
+        return None;
+    }
+    let rtl_base = classes.iter().find(|c| matches!(c, BidiClass::Ltr | BidiClass::Rtl)) == Some(&BidiClass::Rtl);
+
+    // Strong direction per character; numbers count as left to right (W7)
+    let mut rtl: Vec<Option<bool>> = classes
+        .iter()
+        .map(|c| match c {
+            BidiClass::Rtl => Some(true),
+            BidiClass::Ltr | BidiClass::Number => Some(false),
+            BidiClass::Neutral => None,
+        })
+        .collect();
+    // Neutrals take the direction of equal neighbours, else the base (N1/N2);
+    // trailing neutrals go to the base (L1)
+    let mut start = 0;
+    while start < rtl.len() {
+        if rtl[start].is_some() {
+            start += 1;
+            continue;
+        }
+        let end = (start..rtl.len()).find(|&j| rtl[j].is_some()).unwrap_or(rtl.len());
+        let before = if start == 0 { Some(rtl_base) } else { rtl[start - 1] };
+        let after = rtl.get(end).copied().flatten();
+        let dir = if before == after { before.unwrap_or(rtl_base) } else { rtl_base };
+        rtl[start..end].iter_mut().for_each(|d| *d = Some(dir));
+        start = end;
+    }
+
+    // Levels (I1/I2); numbers right after right-to-left text form their own
+    // left-to-right run inside it
+    let mut last_strong_rtl = rtl_base;
+    let levels = classes
+        .iter()
+        .zip(&rtl)
+        .map(|(class, dir)| {
+            let dir = dir.unwrap_or(rtl_base);
+            match class {
+                BidiClass::Rtl => last_strong_rtl = true,
+                BidiClass::Ltr => last_strong_rtl = false,
+                _ => {}
+            }
+            match (rtl_base, dir, class) {
+                (_, true, _) => 1,
+                (false, false, BidiClass::Number) if last_strong_rtl => 2,
+                (false, false, _) => 0,
+                (true, false, _) => 2,
+            }
+        })
+        .collect();
+    Some(levels)
+}
+
+/// Visual order of one display line: entry `i` is the logical index of the
+/// character shown at position `i`. None for purely left-to-right lines.
+pub fn bidi_visual_order(line: &str) -> Option<Vec<usize>> {
+    let levels = bidi_levels(line)?;
+    // Reverse every run at or above each level, from the highest down (L2)
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let max = levels.iter().copied().max().unwrap_or(0);
+    for level in (1..=max).rev() {
+        let mut start = 0;
+        while start < order.len() {
+            if levels[order[start]] < level {
+                start += 1;
+                continue;
+            }
+            let end = (start..order.len()).find(|&j| levels[order[j]] < level).unwrap_or(order.len());
+            order[start..end].reverse();
+            start = end;
+        }
+    }
+    Some(order)
+}
+
+/// Part of an answer for the JSON view: a line of text or a JSON document
+#[derive(Debug, PartialEq)]
+pub enum JsonSegment {
+    Text(String),
+    Json(serde_json::Value),
+}
+
+/// Contents of the ``` fenced blocks in `content`, without the fences
+pub fn code_blocks(content: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        if line.trim_start().starts_with("```") {
+            let block: Vec<&str> = lines.by_ref().take_while(|l| l.trim_start() != "```").collect();
+            blocks.push(block.join("\n"));
+        }
+    }
+    blocks
+}
+
+/// Split an answer into text and JSON: the whole answer if it is a JSON
+/// object or array, otherwise ```` ```json ```` (or unlabeled) fenced
+/// blocks that parse. None if there is no JSON in it.
+pub fn json_segments(content: &str) -> Option<Vec<JsonSegment>> {
+    let parse = |text: &str| {
+        let text = text.trim();
+        (text.starts_with('{') || text.starts_with('['))
+            .then(|| serde_json::from_str::<serde_json::Value>(text).ok())
+            .flatten()
+    };
+    if let Some(value) = parse(content) {
+        return Some(vec![JsonSegment::Json(value)]);
+    }
+    let mut segments = Vec::new();
+    let mut found = false;
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let Some(lang) = line.trim_start().strip_prefix("```") else {
+            segments.push(JsonSegment::Text(line.to_string()));
+            continue;
+        };
+        let block: Vec<&str> = lines.by_ref().take_while(|l| l.trim_start() != "```").collect();
+        match parse(&block.join("\n")).filter(|_| matches!(lang.trim(), "" | "json")) {
+            Some(value) => {
+                segments.push(JsonSegment::Json(value));
+                found = true;
+            }
+            None => {
+                segments.push(JsonSegment::Text(line.to_string()));
+                segments.extend(block.iter().map(|l| JsonSegment::Text(l.to_string())));
+                segments.push(JsonSegment::Text("```".to_string()));
+            }
+        }
+    }
+    found.then_some(segments)
+}
+
+/// Objects and arrays spanning more lines than this are folded below the
+/// top level until `J` unfolds them
+const JSON_FOLD_LINES: usize = 12;
+
+/// Pretty-prints JSON into colored lines (keys, strings, numbers, literals)
+pub(crate) struct JsonPrinter<'a> {
+    theme: &'a Theme,
+    fold: bool,
+    lines: Vec<Vec<Span<'static>>>,
+}
+
+impl JsonPrinter<'_> {
+    /// Lines `value` takes unfolded
+    fn height(value: &serde_json::Value) -> usize {
+        match value {
+            serde_json::Value::Object(map) if !map.is_empty() => 2 + map.values().map(Self::height).sum::<usize>(),
+            serde_json::Value::Array(items) if !items.is_empty() => 2 + items.iter().map(Self::height).sum::<usize>(),
+            _ => 1,
+        }
+    }
+
+    fn punct(&self, text: impl Into<String>) -> Span<'static> {
+        Span::styled(text.into(), Style::default().fg(self.theme.muted))
+    }
+
+    /// Print `value` continuing `line` (indent and key already in it)
+    fn value(&mut self, value: &serde_json::Value, depth: usize, mut line: Vec<Span<'static>>, comma: bool) {
+        use serde_json::Value;
+        let comma_span = |printer: &Self| printer.punct(if comma { "," } else { "" });
+        let (open, close, len) = match value {
+            Value::Object(map) if !map.is_empty() => ("{", "}", map.len()),
+            Value::Array(items) if !items.is_empty() => ("[", "]", items.len()),
+            scalar => {
+                let style = match scalar {
+                    Value::String(_) => Style::default().fg(self.theme.assistant),
+                    Value::Number(_) => Style::default().fg(self.theme.highlight),
+                    _ => Style::default().fg(self.theme.info),
+                };
+                line.push(Span::styled(scalar.to_string(), style));
+                line.push(comma_span(self));
+                self.lines.push(line);
+                return;
+            }
+        };
+        if self.fold && depth > 0 && Self::height(value) > JSON_FOLD_LINES {
+            line.push(self.punct(trf("{} … {} Einträge [J] {}", &[open, &len.to_string(), close])));
+            line.push(comma_span(self));
+            self.lines.push(line);
+            return;
+        }
+        line.push(self.punct(open));
+        self.lines.push(line);
+        let indent = "  ".repeat(depth + 1);
+        match value {
+            Value::Object(map) => {
+                for (i, (key, item)) in map.iter().enumerate() {
+                    let key = Span::styled(Value::String(key.clone()).to_string(), Style::default().fg(self.theme.accent));
+                    let line = vec![Span::raw(indent.clone()), key, self.punct(": ")];
+                    self.value(item, depth + 1, line, i + 1 < len);
+                }
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.value(item, depth + 1, vec![Span::raw(indent.clone())], i + 1 < len);
+                }
+            }
+            _ => {}
+        }
+        let close_line = vec![Span::raw("  ".repeat(depth)), self.punct(close), comma_span(self)];
+        self.lines.push(close_line);
+    }
+}
+
+/// Content exactly as received, line by line: no bidi reordering, the
+/// final newline kept as an empty line, and whatever the terminal would
+/// swallow made visible (`\t`, `\r`, other control or format characters
+/// as `\u{…}`, trailing spaces as `·`)
+pub fn raw_text_lines(content: &str) -> Vec<String> {
+    content
+        .split('\n')
+        .map(|line| {
+            let kept = line.trim_end_matches(' ');
+            let mut out = String::new();
+            for c in kept.chars() {
+                match c {
+                    '\t' => out.push_str("\\t"),
+                    '\r' => out.push_str("\\r"),
+                    c if c.is_control() || matches!(c, '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' | '\u{feff}') => {
+                        out.push_str(&format!("\\u{{{:04x}}}", c as u32))
+                    }
+                    c => out.push(c),
+                }
+            }
+            out.push_str(&"·".repeat(line.len() - kept.len()));
+            out
+        })
+        .collect()
+}
+
+/// Line in display order; brackets in right-to-left runs are mirrored (L4)
+pub fn bidi_display(line: &str) -> String {
+    let (Some(levels), Some(order)) = (bidi_levels(line), bidi_visual_order(line)) else {
+        return line.to_string();
+    };
+    let chars: Vec<char> = line.chars().collect();
+    order
+        .into_iter()
+        .map(|logical| match chars[logical] {
+            ch if levels[logical] % 2 == 0 => ch,
+            '(' => ')',
+            ')' => '(',
+            '[' => ']',
+            ']' => '[',
+            '{' => '}',
+            '}' => '{',
+            '<' => '>',
+            '>' => '<',
+            ch => ch,
+        })
+        .collect()
+}
+
+/// Indicator text for the participants currently typing, e.g. "Hank tippt…"
+pub fn typing_text(names: &[String]) -> Option<String> {
+    match names {
+        [] => None,
+        [one] => Some(trf("{} tippt…", &[one])),
+        [first, second] => Some(trf("{} und {} tippen…", &[first, second])),
+        [first, second, rest @ ..] => Some(trf("{}, {} und {} weitere tippen…", &[first, second, &rest.len().to_string()])),
+    }
+}
+
+/// Duration as "M:SS", or "H:MM:SS" from one hour on
+pub(crate) fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs / 3600 {
+        0 => format!("{}:{:02}", secs / 60, secs % 60),
+        hours => format!("{}:{:02}:{:02}", hours, secs / 60 % 60, secs % 60),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffOp {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Word-level diff of two texts (longest common subsequence over words and
+/// the whitespace between them); adjacent pieces of the same kind are merged
+pub fn word_diff(old: &str, new: &str) -> Vec<(DiffOp, String)> {
+    fn tokens(text: &str) -> Vec<&str> {
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        let mut chars = text.char_indices().peekable();
+        while let Some((idx, ch)) = chars.next() {
+            let boundary = chars.peek().is_none_or(|&(_, next)| next.is_whitespace() != ch.is_whitespace());
+            if boundary {
+                let end = idx + ch.len_utf8();
+                tokens.push(&text[start..end]);
+                start = end;
+            }
+        }
+        tokens
+    }
+    let (a, b) = (tokens(old), tokens(new));
+    let mut ops: Vec<(DiffOp, &str)> = Vec::new();
+    // Very long texts: old out, new in, instead of a huge table
+    if a.len() * b.len() > 4_000_000 {
+        ops.extend(a.iter().map(|t| (DiffOp::Removed, *t)));
+        ops.extend(b.iter().map(|t| (DiffOp::Added, *t)));
+    } else {
+        // lcs[i][j]: common tokens of a[i..] and b[j..]
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push((DiffOp::Same, a[i]));
+                (i, j) = (i + 1, j + 1);
+            } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push((DiffOp::Removed, a[i]));
+                i += 1;
+            } else {
+                ops.push((DiffOp::Added, b[j]));
+                j += 1;
+            }
+        }
+    }
+    let mut merged: Vec<(DiffOp, String)> = Vec::new();
+    for (op, text) in ops {
+        match merged.last_mut() {
+            Some((last, acc)) if *last == op => acc.push_str(text),
+            _ => merged.push((op, text.to_string())),
+        }
+    }
+    merged
+}
+
+/// Response time next to an answer: "4.2s", from a minute on "1m05s"
+pub(crate) fn format_response_time(ms: u64) -> String {
+    if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m{:02}s", ms / 60_000, ms / 1000 % 60)
+    }
+}
+
+pub(crate) fn format_timestamp(ms: u64) -> String {
+    let ts = chrono::Local.timestamp_millis_opt(ms as i64).single();
+    match ts {
+        Some(t) => t.format("%H:%M:%S").to_string(),
+        None => Local::now().format("%H:%M:%S").to_string(),
+    }
+}
+
+pub(crate) fn wrapped_line_count(lines: &[Line], width: usize, east_asian_width: EastAsianWidth) -> u32 {
+    lines
+        .iter()
+        .fold(0u32, |total, line| total.saturating_add(wrapped_rows(line, width, east_asian_width)))
+}
+
+/// Screen rows one line takes when wrapped to `width`
+pub(crate) fn wrapped_rows(line: &Line, width: usize, east_asian_width: EastAsianWidth) -> u32 {
+    if width == 0 || line.spans.is_empty() {
+        return 1;
+    }
+
+    let mut col = 0usize;
+    let mut line_count: u32 = 1;
+    for span in &line.spans {
+        for ch in span.content.chars() {
+            let char_width = east_asian_width.char_width(ch);
+            if char_width == 0 {
+                continue;
+            }
+            if col + char_width > width {
+                line_count = line_count.saturating_add(1);
+                col = char_width;
+            } else {
+                col += char_width;
+            }
+        }
+    }
+    line_count
+}
+
+/// Scroll offset (rows from the bottom) that keeps the line at the top of
+/// the chat pane in place when the pane changes from `old` to `new`
+/// (text width, visible rows)
+pub(crate) fn reanchor_scroll(
+    lines: &[Line],
+    east_asian_width: EastAsianWidth,
+    old: (usize, u16),
+    new: (usize, u16),
+    scroll: u16,
+) -> u16 {
+    let old_rows: Vec<u32> = lines.iter().map(|l| wrapped_rows(l, old.0, east_asian_width)).collect();
+    let old_total = old_rows.iter().sum::<u32>() + CHAT_PADDING_LINES;
+    let top = old_total.saturating_sub(old.1 as u32).saturating_sub(scroll as u32);
+
+    // Line at the top and how many of its rows are scrolled past
+    let mut start = 0u32;
+    let Some((index, skipped)) = old_rows.iter().enumerate().find_map(|(i, &rows)| {
+        let found = (top < start + rows).then_some((i, top - start));
+        start += rows;
+        found
+    }) else {
+        return scroll;
+    };
+
+    let new_rows = |l: &Line| wrapped_rows(l, new.0, east_asian_width);
+    let new_start: u32 = lines[..index].iter().map(new_rows).sum();
+    let new_top = new_start + skipped.min(new_rows(&lines[index]) - 1);
+    let new_total = lines.iter().map(new_rows).sum::<u32>() + CHAT_PADDING_LINES;
+    let new_max = new_total.saturating_sub(new.1 as u32);
+    new_max.saturating_sub(new_top).min(u32::from(u16::MAX)) as u16
+}
+
+/// Scroll value (rows from the bottom) that puts `line` at the top of a
+/// chat pane of `size` (width, visible rows)
+pub(crate) fn scroll_to_line(lines: &[Line], east_asian_width: EastAsianWidth, size: (usize, u16), line: usize) -> u16 {
+    let top: u32 = lines[..line.min(lines.len())].iter().map(|l| wrapped_rows(l, size.0, east_asian_width)).sum();
+    let total = wrapped_line_count(lines, size.0, east_asian_width) + CHAT_PADDING_LINES;
+    let max = total.saturating_sub(size.1 as u32);
+    max.saturating_sub(top).min(u32::from(u16::MAX)) as u16
+}
+
+pub(crate) const CHAT_PADDING_LINES: u32 = 20;
+
+/// The lines a pane of `height` rows shows when scrolled `offset` rows
+/// down, and the rows still to scroll within the first of them. Only
+/// these go into the Paragraph, however long the transcript is.
+pub(crate) fn visible_window(
+    lines: Vec<Line<'static>>,
+    width: usize,
+    east_asian_width: EastAsianWidth,
+    offset: u16,
+    height: u16,
+) -> (Vec<Line<'static>>, u16) {
+    let rows: Vec<u32> = lines.iter().map(|l| wrapped_rows(l, width, east_asian_width)).collect();
+    let mut skipped = 0u32;
+    let start = rows
+        .iter()
+        .position(|&r| {
+            let inside = skipped + r > offset as u32;
+            if !inside {
+                skipped += r;
+            }
+            inside
+        })
+        .unwrap_or(lines.len());
+    let within = offset as u32 - skipped;
+    let mut filled = 0u32;
+    let count = rows[start..]
+        .iter()
+        .take_while(|&&r| {
+            let more = filled < within + height as u32;
+            filled += r;
+            more
+        })
+        .count();
+    let shown = lines.into_iter().skip(start).take(count).collect();
+    (shown, within.min(u32::from(u16::MAX)) as u16)
+}
+
+/// Row within a wrapped line (as counted by `wrapped_rows`) that holds the
+/// character at `index`
+pub(crate) fn wrapped_row_of(line: &Line, index: usize, width: usize, east_asian_width: EastAsianWidth) -> u32 {
+    if width == 0 {
+        return 0;
+    }
+    let mut col = 0usize;
+    let mut row = 0u32;
+    for ch in line.spans.iter().flat_map(|span| span.content.chars()).take(index + 1) {
+        let char_width = east_asian_width.char_width(ch);
+        if char_width == 0 {
+            continue;
+        }
+        if col + char_width > width {
+            row += 1;
+            col = char_width;
+        } else {
+            col += char_width;
+        }
+    }
+    row
+}
+
+/// A search hit in the chat lines: line, first character and length
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchHit {
+    pub line: usize,
+    pub start: usize,
+    pub len: usize,
+}
+
+/// Occurrences of `query` in `lines` as they are shown (names, timestamps,
+/// formatted JSON included); case-insensitive unless the query has capitals
+pub fn find_in_lines(lines: &[Line], query: &str) -> Vec<SearchHit> {
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let fold = |c: char| if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c };
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    let mut hits = Vec::new();
+    if needle.is_empty() {
+        return hits;
+    }
+    for (idx, line) in lines.iter().enumerate() {
+        let text: Vec<char> = line.spans.iter().flat_map(|span| span.content.chars()).map(fold).collect();
+        let mut start = 0;
+        while start + needle.len() <= text.len() {
+            if text[start..start + needle.len()] == needle[..] {
+                hits.push(SearchHit { line: idx, start, len: needle.len() });
+                start += needle.len();
+            } else {
+                start += 1;
+            }
+        }
+    }
+    hits
+}
+
+/// Restyle the characters `start..start + len` of `line`, splitting spans
+pub(crate) fn highlight_chars(line: &mut Line<'static>, start: usize, len: usize, style: Style) {
+    let mut pos = 0;
+    let mut spans = Vec::new();
+    for span in line.spans.drain(..) {
+        let count = span.content.chars().count();
+        let from = start.clamp(pos, pos + count) - pos;
+        let to = (start + len).clamp(pos, pos + count) - pos;
+        pos += count;
+        if from == to {
+            spans.push(span);
+            continue;
+        }
+        let chars: Vec<char> = span.content.chars().collect();
+        let part = |range: std::ops::Range<usize>| chars[range].iter().collect::<String>();
+        if from > 0 {
+            spans.push(Span::styled(part(0..from), span.style));
+        }
+        spans.push(Span::styled(part(from..to), span.style.patch(style)));
+        if to < count {
+            spans.push(Span::styled(part(to..count), span.style));
+        }
+    }
+    line.spans = spans;
+}
+
+/// Scroll so the rows `start..end` of the chat lines are visible, their
+/// start first
+fn reveal_rows(app: &mut App, start: u32, end: u32, max_scroll: u16, visible: u32) {
+    let max_top = max_scroll as u32;
+    let top = if app.auto_scroll { max_top } else { max_top.saturating_sub(app.scroll as u32) };
+    let top = if start < top {
+        start
+    } else if end > top + visible {
+        end.saturating_sub(visible).min(start)
+    } else {
+        top
+    };
+    app.scroll = max_top.saturating_sub(top.min(max_top)) as u16;
+    app.auto_scroll = app.scroll == 0;
+}
+
+/// Write the transcript to a temp file and show it in `$PAGER` (default
+/// `less`). The TUI is suspended meanwhile; the caller redraws afterwards.
+pub(crate) fn open_in_pager(app: &mut App) -> Result<(), String> {
+    let path = env::temp_dir().join(format!("hank-tui-{}.txt", std::process::id()));
+    fs::write(&path, app.transcript_text()).map_err(|e| trf("Transkript nicht geschrieben: {}", &[&e.to_string()]))?;
+    let pager = env::var("PAGER").ok().filter(|p| !p.trim().is_empty()).unwrap_or_else(|| "less".to_string());
+    let status = run_suspended(app, &pager, &path);
+    let _ = fs::remove_file(&path);
+    status.map(|_| ())
+}
+
+/// Compose the input in `$VISUAL`/`$EDITOR` (default `vi`) on a temp file
+/// seeded with the current input; the saved text replaces the input. The
+/// TUI is suspended meanwhile; the caller redraws afterwards.
+pub(crate) fn edit_in_editor(app: &mut App) -> Result<(), String> {
+    let path = env::temp_dir().join(format!("hank-tui-{}-eingabe.md", std::process::id()));
+    fs::write(&path, &app.input).map_err(|e| trf("Eingabe nicht geschrieben: {}", &[&e.to_string()]))?;
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|e| !e.trim().is_empty()))
+        .unwrap_or_else(|| "vi".to_string());
+    let status = run_suspended(app, &editor, &path);
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    match (status?, text) {
+        (status, _) if !status.success() => Err(trf("{} mit Fehler beendet, Eingabe unverändert", &[&editor])),
+        (_, Ok(text)) => {
+            app.take_edited_input(&text);
+            Ok(())
+        }
+        (_, Err(e)) => Err(trf("Eingabe nicht gelesen: {}", &[&e.to_string()])),
+    }
+}
+
+/// Run `command` (program and arguments, split at whitespace) on `path`
+/// with the terminal handed over, then take it back
+fn run_suspended(app: &mut App, command: &str, path: &std::path::Path) -> Result<std::process::ExitStatus, String> {
+    use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode};
+    let mut words = command.split_whitespace();
+    let program = words.next().unwrap_or_default();
+
+    let (mouse, enhanced) = (app.mouse_capture, app.enhanced_keys);
+    let _ = app.set_mouse_capture(false);
+    let _ = app.disable_enhanced_keys();
+    let _ = disable_raw_mode();
+    let _ = crossterm::execute!(io::stdout(), LeaveAlternateScreen);
+
+    let status = std::process::Command::new(program).args(words).arg(path).status();
+
+    if !app.accessible {
+        let _ = crossterm::execute!(io::stdout(), EnterAlternateScreen);
+    }
+    let _ = enable_raw_mode();
+    if enhanced {
+        let _ = app.enable_enhanced_keys();
+    }
+    if mouse {
+        let _ = app.set_mouse_capture(true);
+    }
+    status.map_err(|e| trf("{} konnte nicht gestartet werden: {}", &[program, &e.to_string()]))
+}
+
+/// Print what `App::narrate` has to say into the scrollback above the inline
+/// viewport (accessible mode)
+pub(crate) fn speak<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let width = terminal.size()?.width.max(1) as usize;
+    let ascii = app.ascii;
+    for text in app.narrate() {
+        // Wrap by hand so the reserved height always fits
+        let mut rows = vec![String::new()];
+        let mut col = 0;
+        for ch in text.chars() {
+            let w = app.east_asian_width.char_width(ch);
+            if col + w > width {
+                rows.push(String::new());
+                col = 0;
+            }
+            rows.last_mut().unwrap().push(ch);
+            col += w;
+        }
+        let height = rows.len() as u16;
+        terminal.insert_before(height, |buf| {
+            let lines: Vec<Line> = rows.into_iter().map(Line::from).collect();
+            ratatui::widgets::Widget::render(Paragraph::new(lines), buf.area, buf);
+            if ascii {
+                ascii_buffer(buf);
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// ASCII stand-in for a character (`--ascii`): box drawing becomes `+-|=`,
+/// arrows and glyphs their nearest look-alike, umlauts lose their dots and
+/// anything else unknown turns into `?`
+fn ascii_fallback(ch: char) -> char {
+    match ch {
+        _ if ch.is_ascii() => ch,
+        '─' | '━' | '┄' | '┈' | '╌' => '-',
+        '│' | '┃' | '┆' | '┊' | '╎' | '║' => '|',
+        '═' => '=',
+        '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼' | '╭' | '╮' | '╯' | '╰' | '╔' | '╗' | '╚' | '╝' => '+',
+        '↑' | '▲' => '^',
+        '↓' | '▼' => 'v',
+        '←' | '◀' | '‹' => '<',
+        '→' | '▶' | '›' | '»' => '>',
+        '↔' => '-',
+        '⚠' | '‼' => '!',
+        '✓' | '✔' => 'v',
+        '✗' | '✘' | '×' => 'x',
+        '★' | '•' | '◆' | '●' | '·' => '*',
+        '○' | '◯' => 'o',
+        '…' => '.',
+        '▌' | '█' => '_',
+        '–' | '—' => '-',
+        '„' | '“' | '”' | '«' => '"',
+        '‚' | '‘' | '’' => '\'',
+        'ä' | 'à' | 'á' | 'â' => 'a',
+        'Ä' => 'A',
+        'ö' | 'ó' | 'ô' => 'o',
+        'Ö' => 'O',
+        'ü' | 'ú' | 'û' => 'u',
+        'Ü' => 'U',
+        'é' | 'è' | 'ê' => 'e',
+        'ß' => 's',
+        _ => '?',
+    }
+}
+
+/// Fold a rendered frame to ASCII without color (`--ascii`); every cell
+/// keeps its width, so layout and cursor stay where they were
+pub(crate) fn ascii_buffer(buf: &mut ratatui::buffer::Buffer) {
+    for cell in buf.content.iter_mut() {
+        if !cell.symbol().is_ascii() {
+            let folded = cell.symbol().chars().next().map_or(' ', ascii_fallback);
+            cell.set_char(folded);
+        }
+        cell.fg = Color::Reset;
+        cell.bg = Color::Reset;
+    }
+}
+
+/// Small box in the lower right corner of `area` listing outstanding requests
+pub(crate) fn draw_pending_panel(f: &mut ratatui::Frame, app: &App, area: ratatui::layout::Rect) {
+    let lines = app.pending_lines();
+    let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 2).max(24).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let panel = ratatui::layout::Rect::new(
+        area.x + area.width - width,
+        area.y + area.height - height,
+        width,
+        height,
+    );
+    f.render_widget(ratatui::widgets::Clear, panel);
+    let title = tr(if app.send_queue.is_empty() { " Ausstehend " } else { " Ausstehend [x=letzte abbrechen] " });
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(app.theme.info)),
+        ),
+        panel,
+    );
+}
+
+/// Accessible mode: a plain prompt line and a textual status, no borders
+pub(crate) fn draw_accessible(f: &mut ratatui::Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.area());
+    let width = chunks[0].width.saturating_sub(2) as usize;
+    let input: Vec<Line> = app
+        .input_display_text(width)
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| Line::from(format!("{}{}", if idx == 0 { "> " } else { "  " }, line)))
+        .collect();
+    let (cursor_line, cursor_col) = app.cursor_display_col(width);
+    let scroll = (cursor_line as u16 + 1).saturating_sub(chunks[0].height);
+    f.render_widget(Paragraph::new(input).scroll((scroll, 0)), chunks[0]);
+
+    let mut status = format!("{} | {}", app.rooms[app.active_room].label(), app.connection_status.label());
+    if app.loading {
+        status.push_str(" | ");
+        status.push_str(&trf("{} denkt nach", &[&app.assistant_name]));
+    }
+    if let Some(note) = app.rate_limit_status() {
+        status.push_str(&format!(" | {}", note));
+    }
+    f.render_widget(Paragraph::new(status), chunks[1]);
+    if app.focus == Focus::Input {
+        f.set_cursor_position((
+            chunks[0].x + cursor_col as u16 + 2,
+            chunks[0].y + cursor_line as u16 - scroll,
+        ));
+    }
+    if app.ascii {
+        ascii_buffer(f.buffer_mut());
+    }
+}
+
+impl App {
+    /// Sidebar content: joined rooms with unread counts, then rooms not joined yet
+    pub fn room_list_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        for (idx, room) in self.rooms.iter().enumerate() {
+            let label = match &room.name {
+                Some(name) => format!("#{}", name),
+                None => room.label().to_string(),
+            };
+            if idx == self.active_room {
+                lines.push(Line::from(Span::styled(
+                    format!("▶ {}", label),
+                    Style::default().fg(self.theme.highlight).add_modifier(Modifier::BOLD),
+                )));
+            } else if room.unread > 0 {
+                lines.push(Line::from(Span::styled(
+                    format!("  {} ({})", label, room.unread),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )));
+            } else {
+                lines.push(Line::from(format!("  {}", label)));
+            }
+        }
+        let others: Vec<&String> = self
+            .available_rooms
+            .iter()
+            .filter(|name| !self.rooms.iter().any(|r| r.name.as_ref() == Some(*name)))
+            .collect();
+        if !others.is_empty() {
+            lines.push(Line::from(""));
+            for name in others {
+                lines.push(Line::from(Span::styled(
+                    format!("  #{}", name),
+                    Style::default().fg(self.theme.muted),
+                )));
+            }
+        }
+        lines
+    }
+
+    /// Sidebar section listing participants with their presence
+    pub fn presence_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        for p in &self.presence {
+            let label = if p.status == PresenceStatus::Online {
+                p.name.clone()
+            } else {
+                format!("{} ({})", p.name, p.status.label())
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", p.status.glyph()), Style::default().fg(p.status.color(&self.theme))),
+                Span::raw(label),
+            ]));
+        }
+        lines
+    }
+
+    /// Participant pane content: presence, name and role, plus a loaded profile
+    pub fn participant_lines(&self) -> Vec<Line<'static>> {
+        let participants = self.participants();
+        if participants.is_empty() {
+            return vec![Line::from(Span::styled(tr("(niemand)"), Style::default().fg(self.theme.muted)))];
+        }
+        let selected = self.participant_index.min(participants.len() - 1);
+        let mut lines = Vec::new();
+        for (idx, p) in participants.iter().enumerate() {
+            let marker = if idx == selected && self.focus == Focus::Participants { "▶" } else { " " };
+            let mut spans = vec![
+                Span::raw(format!("{} ", marker)),
+                Span::styled(format!("{} ", p.status.glyph()), Style::default().fg(p.status.color(&self.theme))),
+                Span::styled(p.name.clone(), if idx == selected { Style::default().add_modifier(Modifier::BOLD) } else { Style::default() }),
+            ];
+            if let Some(role) = &p.role {
+                spans.push(Span::styled(format!(" [{}]", role), Style::default().fg(self.theme.muted)));
+            }
+            lines.push(Line::from(spans));
+        }
+        if let Some((name, profile)) = &self.profile {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(format!("── {} ──", name), Style::default().fg(self.theme.accent))));
+            for (key, value) in profile {
+                let value = match value {
+                    serde_json::Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                lines.push(Line::from(format!("{}: {}", key, value)));
+            }
+        }
+        lines
+    }
+
+    /// Content of the server metrics overlay (F9)
+    pub fn stats_lines(&self) -> Vec<Line<'static>> {
+        let Some((result, fetched_at)) = &self.stats else {
+            return Vec::new();
+        };
+        let label = |text: &str| Span::styled(format!("{:<14}", text), Style::default().fg(self.theme.muted));
+        let mut lines = Vec::new();
+        match result {
+            Err(e) => lines.push(Line::from(Span::styled(e.clone(), Style::default().fg(self.theme.error)))),
+            Ok(stats) => {
+                if let Some(depth) = stats.queue_depth {
+                    let style = if depth > 0 { Style::default().fg(self.theme.highlight) } else { Style::default() };
+                    let ahead = if depth > 0 { trf(" ({} vor dir)", &[&depth.to_string()]) } else { String::new() };
+                    lines.push(Line::from(vec![label(tr("Warteschlange")), Span::styled(format!("{}{}", depth, ahead), style)]));
+                }
+                if let Some(model) = &stats.model {
+                    lines.push(Line::from(vec![label(tr("Modell")), Span::raw(model.clone())]));
+                }
+                if let Some(load) = stats.load {
+                    let load = load.clamp(0.0, 1.0);
+                    let filled = (load * 20.0).round() as usize;
+                    let color = if load >= 0.9 { self.theme.error } else if load >= 0.7 { self.theme.highlight } else { self.theme.assistant };
+                    lines.push(Line::from(vec![
+                        label(tr("Last")),
+                        Span::styled("█".repeat(filled), Style::default().fg(color)),
+                        Span::styled("░".repeat(20 - filled), Style::default().fg(self.theme.muted)),
+                        Span::raw(format!(" {:.0}%", load * 100.0)),
+                    ]));
+                }
+                for (key, value) in &stats.other {
+                    let value = match value {
+                        serde_json::Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    lines.push(Line::from(vec![label(key), Span::raw(value)]));
+                }
+                if lines.is_empty() {
+                    lines.push(Line::from(tr("Keine Angaben")));
+                }
+            }
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            trf("Stand {}  [r=neu laden, sonst schließen]", &[&fetched_at.format("%H:%M:%S").to_string()]),
+            Style::default().fg(self.theme.muted),
+        )));
+        lines
+    }
+
+    /// Content of the size offer overlay
+    pub fn oversize_lines(&self) -> Vec<Line<'static>> {
+        let Some(offer) = &self.oversize else {
+            return Vec::new();
+        };
+        let parts = split_message(&offer.text, offer.limit).len();
+        let key = |k: &str| Span::styled(format!("[{}] ", k), Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD));
+        vec![
+            Line::from(trf(
+                "Die Nachricht hat {} Zeichen, der Server nimmt höchstens {}.",
+                &[&offer.text.chars().count().to_string(), &offer.limit.to_string()],
+            )),
+            Line::from(""),
+            Line::from(vec![key("t"), Span::raw(trf("In {} Teile aufteilen (\"[1/{}] …\"), direkt nacheinander", &[&parts.to_string(), &parts.to_string()]))]),
+            Line::from(vec![key("a"), Span::raw(tr("Als Datei anhängen"))]),
+            Line::from(vec![key("Esc"), Span::raw(tr("Zurück zum Bearbeiten"))]),
+        ]
+    }
+
+    /// Content of the pending panel: the request in flight, then the queue
+    pub fn pending_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        if let Some(pending) = &self.in_flight {
+            lines.push(Line::from(vec![
+                Span::styled("⟳ ", Style::default().fg(self.theme.highlight)),
+                Span::raw(pending.label()),
+                Span::styled(
+                    format!(" {} [Esc]", format_elapsed(self.age(pending.queued_at))),
+                    Style::default().fg(self.theme.muted),
+                ),
+            ]));
+        }
+        for pending in &self.send_queue {
+            lines.push(Line::from(vec![
+                Span::styled("⏸ ", Style::default().fg(self.theme.muted)),
+                Span::raw(pending.label()),
+                Span::styled(format!(" {}", format_elapsed(self.age(pending.queued_at))), Style::default().fg(self.theme.muted)),
+            ]));
+        }
+        lines
+    }
+
+    /// Content of the template picker overlay
+    /// Entries of the conversation picker: title, when it was saved, size
+    pub fn session_picker_lines(&self) -> Vec<Line<'static>> {
+        let Some(picker) = &self.session_picker else {
+            return Vec::new();
+        };
+        let selected = Style::default().fg(self.theme.on_highlight).bg(self.theme.highlight);
+        picker
+            .sessions
+            .iter()
+            .enumerate()
+            .map(|(idx, s)| {
+                let saved = chrono::DateTime::parse_from_rfc3339(&s.saved_at)
+                    .map(|t| t.with_timezone(&Local).format("%d.%m.%Y %H:%M").to_string())
+                    .unwrap_or_default();
+                let entry = trf("{}  ·  {}  ·  {} Nachr.", &[&s.title, &saved, &s.messages.to_string()]);
+                if idx == picker.index {
+                    Line::from(Span::styled(format!("▶ {}", entry), selected))
+                } else {
+                    Line::from(format!("  {}", entry))
+                }
+            })
+            .collect()
+    }
+
+    /// Global search screen: the query, then one line per hit (where, when,
+    /// who, the matching text), scrolled to keep the chosen hit in `height`
+    pub fn global_search_lines(&self, height: usize) -> Vec<Line<'static>> {
+        let Some(search) = &self.global_search else {
+            return Vec::new();
+        };
+        let selected = Style::default().fg(self.theme.on_highlight).bg(self.theme.highlight);
+        let mut lines = vec![
+            Line::from(vec![
+                Span::styled(tr("Suche: "), Style::default().fg(self.theme.accent)),
+                Span::raw(format!("{}▏", search.query)),
+            ]),
+            Line::from(""),
+        ];
+        if search.hits.is_empty() {
+            let note = tr(if search.query.is_empty() { "Suchbegriff eingeben" } else { "Keine Treffer" });
+            lines.push(Line::from(Span::styled(note, Style::default().fg(self.theme.muted))));
+            return lines;
+        }
+        let room = height.saturating_sub(lines.len()).max(1);
+        let first = (search.index + 1).saturating_sub(room);
+        for (idx, hit) in search.hits.iter().enumerate().skip(first).take(room) {
+            let when = hit
+                .timestamp_ms
+                .and_then(|ms| Local.timestamp_millis_opt(ms as i64).single())
+                .map(|t| t.format("%d.%m. %H:%M").to_string())
+                .unwrap_or_default();
+            let who = match (&hit.sender, hit.role.as_str()) {
+                (Some(sender), _) => sender.clone(),
+                (None, "assistant") => self.assistant_name.clone(),
+                (None, _) => "Du".to_string(),
+            };
+            let entry = format!("{} · {} · {}: {}", hit.title, when, who, hit.snippet);
+            lines.push(if idx == search.index {
+                Line::from(Span::styled(format!("▶ {}", entry), selected))
+            } else {
+                Line::from(format!("  {}", entry))
+            });
+        }
+        lines
+    }
+
+    /// Conversation sidebar (F2): title with unread count, then a preview
+    /// of the newest message; the open conversation is bold and previews live
+    pub fn session_sidebar_lines(&self, width: usize) -> Vec<Line<'static>> {
+        let Some(sidebar) = &self.session_sidebar else {
+            return Vec::new();
+        };
+        let clip = |text: &str, room: usize| -> String {
+            if text.width() <= room {
+                return text.to_string();
+            }
+            let mut out = String::new();
+            for c in text.chars() {
+                if out.width() + c.to_string().width() + 1 > room {
+                    break;
+                }
+                out.push(c);
+            }
+            out.push('…');
+            out
+        };
+        let mut lines = Vec::new();
+        for (idx, s) in sidebar.sessions.iter().enumerate() {
+            let open = s.id == self.session && self.active_room == 0;
+            let marker = if idx == sidebar.index && self.focus == Focus::Sessions { "▶ " } else { "  " };
+            let unread = if s.unread > 0 && !open { format!(" ({})", s.unread) } else { String::new() };
+            let mut spans = vec![Span::raw(marker)];
+            let room = width.saturating_sub(2 + unread.width());
+            spans.push(Span::styled(
+                clip(&s.title, room),
+                if open { Style::default().add_modifier(Modifier::BOLD) } else { Style::default() },
+            ));
+            if !unread.is_empty() {
+                spans.push(Span::styled(unread, Style::default().fg(self.theme.highlight).add_modifier(Modifier::BOLD)));
+            }
+            lines.push(Line::from(spans));
+            let preview = if open { session_preview(&self.messages) } else { s.preview.clone() };
+            lines.push(Line::from(Span::styled(
+                format!("  {}", clip(&preview, width.saturating_sub(2))),
+                Style::default().fg(self.theme.muted),
+            )));
+        }
+        lines
+    }
+
+    pub fn template_picker_lines(&self) -> Vec<Line<'static>> {
+        let Some(picker) = &self.template_picker else {
+            return Vec::new();
+        };
+        let selected = Style::default().fg(self.theme.on_highlight).bg(self.theme.highlight);
+        let Some(field) = picker.field else {
+            return picker
+                .templates
+                .iter()
+                .enumerate()
+                .map(|(idx, template)| {
+                    if idx == picker.index {
+                        Line::from(Span::styled(format!("▶ {}", template.name), selected))
+                    } else {
+                        Line::from(format!("  {}", template.name))
+                    }
+                })
+                .collect();
+        };
+        let mut lines: Vec<Line<'static>> = picker
+            .values
+            .iter()
+            .enumerate()
+            .map(|(idx, (name, value))| {
+                if idx == field {
+                    Line::from(vec![Span::styled(format!("{}: ", name), selected), Span::raw(format!("{}▏", value))])
+                } else {
+                    Line::from(format!("{}: {}", name, value))
+                }
+            })
+            .collect();
+        lines.push(Line::from(""));
+        let preview = picker.templates[picker.index].render(&picker.values);
+        lines.extend(preview.lines().map(|l| Line::from(Span::styled(l.to_string(), Style::default().fg(self.theme.muted)))));
+        lines
+    }
+
+    /// Inline word diff of the open `edit_diff`: removed words as `[-…-]`,
+    /// added ones as `{+…+}`, colored as well
+    pub fn edit_diff_lines(&self) -> Vec<Line<'static>> {
+        let Some((old, new)) = &self.edit_diff else {
+            return Vec::new();
+        };
+        let mut lines = vec![Line::default()];
+        for (op, text) in word_diff(old, new) {
+            let (open, close, style) = match op {
+                DiffOp::Same => ("", "", Style::default()),
+                DiffOp::Removed => (
+                    "[-",
+                    "-]",
+                    Style::default().fg(self.theme.error).add_modifier(Modifier::CROSSED_OUT),
+                ),
+                DiffOp::Added => ("{+", "+}", Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD)),
+            };
+            // Markers per line, so every line reads on its own
+            for (idx, part) in text.split('\n').enumerate() {
+                if idx > 0 {
+                    lines.push(Line::default());
+                }
+                if !part.is_empty() {
+                    let line = lines.last_mut().unwrap();
+                    line.spans.push(Span::styled(format!("{}{}{}", open, part, close), style));
+                }
+            }
+        }
+        lines
+    }
+
+    /// Render all messages as chat lines (timestamp, prefix, indented continuation lines)
+    pub fn chat_lines(&self) -> Vec<Line<'static>> {
+        self.chat_layout().0
+    }
+
+    /// Chat lines plus `(message index, first line)` of every message shown
+    pub fn chat_layout(&self) -> (Vec<Line<'static>>, Vec<(usize, usize)>) {
+        let mut lines: Vec<Line<'static>> = Vec::new();
+        let mut starts = Vec::new();
+        let first_unread = self.read_marker.and_then(|marker| {
+            self.messages.iter().position(|m| {
+                m.role != "system" && !self.is_thread_reply(m) && m.timestamp_ms.is_some_and(|t| t > marker)
+            })
+        });
+        if self.filter.is_some() {
+            let total = self.messages.iter().filter(|m| m.role != "system").count();
+            let shown = self.messages.iter().filter(|m| self.filter_matches(m)).count();
+            lines.push(Line::from(Span::styled(
+                trf("── gefiltert: {} von {} Nachrichten [Esc=aufheben] ──", &[&shown.to_string(), &total.to_string()]),
+                Style::default().fg(self.theme.info).add_modifier(Modifier::BOLD),
+            )));
+        }
+        for (idx, msg) in self.messages.iter().enumerate() {
+            // Like the mentions view, the filter lists matches from threads as well
+            if self.filter.is_some() {
+                if self.filter_matches(msg) {
+                    starts.push((idx, lines.len()));
+                    self.push_message_lines(msg, 0, &mut lines);
+                }
+                continue;
+            }
+            // The mentions view lists matches from threads as well
+            if self.mentions_only {
+                if self.is_mention(msg) {
+                    starts.push((idx, lines.len()));
+                    self.push_message_lines(msg, 0, &mut lines);
+                }
+                continue;
+            }
+            if self.is_thread_reply(msg) {
+                continue;
+            }
+            if Some(idx) == first_unread {
+                lines.push(Line::from(Span::styled(
+                    tr("── neue Nachrichten ──"),
+                    Style::default().fg(self.theme.error).add_modifier(Modifier::BOLD),
+                )));
+            }
+            starts.push((idx, lines.len()));
+            self.push_message_lines(msg, self.reply_count(msg), &mut lines);
+        }
+        (lines, starts)
+    }
+
+    /// The answer being generated, with a caret at its end, or the
+    /// "thinking" line while nothing has arrived yet
+    pub fn streaming_lines(&self) -> Vec<Line<'static>> {
+        let Some(text) = &self.streaming else {
+            return vec![Line::from(Span::styled(
+                trf("{} denkt nach...", &[&self.assistant_name]),
+                Style::default().fg(self.theme.highlight),
+            ))];
+        };
+        let mut lines = Vec::new();
+        self.push_message_lines(&Message::new("assistant", format!("{}▌", text)), 0, &mut lines);
+        lines
+    }
+
+    /// Root message of the open thread followed by all of its replies
+    pub fn thread_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        let Some(root) = self.thread.as_deref() else {
+            return lines;
+        };
+        for msg in &self.messages {
+            if msg.id.as_deref() == Some(root) || msg.thread_id.as_deref() == Some(root) {
+                self.push_message_lines(msg, 0, &mut lines);
+            }
+        }
+        lines
+    }
+
+    /// Tool calls below an assistant answer: one line each when collapsed,
+    /// pretty-printed arguments and result when expanded
+    fn push_tool_call_lines(&self, msg: &Message, indent: usize, lines: &mut Vec<Line<'static>>) {
+        let pad = " ".repeat(indent);
+        let dim = Style::default().fg(self.theme.muted);
+        for call in &msg.tool_calls {
+            if msg.raw {
+                let json = serde_json::to_string(call).unwrap_or_default();
+                lines.push(Line::from(Span::styled(format!("{}{}", pad, json), dim)));
+                continue;
+            }
+            let status = if call.result.is_some() { " ✓" } else { " …" };
+            if !self.tools_expanded {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{}▸ ⚙ ", pad), dim),
+                    Span::styled(call.summary(), Style::default().fg(self.theme.info)),
+                    Span::styled(status, dim),
+                ]));
+                continue;
+            }
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}▾ ⚙ ", pad), dim),
+                Span::styled(call.name.clone(), Style::default().fg(self.theme.info).add_modifier(Modifier::BOLD)),
+                Span::styled(status, dim),
+            ]));
+            let sections = [(tr("Argumente"), Some(&call.arguments)), (tr("Ergebnis"), call.result.as_ref())];
+            for (title, value) in sections {
+                let Some(value) = value else { continue };
+                lines.push(Line::from(Span::styled(format!("{}  {}:", pad, title), dim)));
+                let pretty = serde_json::to_string_pretty(value).unwrap_or_default();
+                for line in pretty.lines() {
+                    lines.push(Line::from(Span::styled(format!("{}    {}", pad, line), dim)));
+                }
+            }
+        }
+    }
+
+    fn push_message_lines(&self, msg: &Message, replies: usize, lines: &mut Vec<Line<'static>>) {
+        let (prefix, style) = match msg.role.as_str() {
+            "user" => {
+                // Every other participant gets their own stable color
+                let color = match msg.sender.as_deref() {
+                    Some(sender) if msg.sender != self.display_name => self.color_for(sender),
+                    _ => self.own_color.unwrap_or(self.theme.accent),
+                };
+                (format!("{}: ", self.user_label(msg)), Style::default().fg(color))
+            }
+            "assistant" => (
+                format!("{}: ", self.assistant_name),
+                Style::default().fg(self.assistant_color.unwrap_or(self.theme.assistant)),
+            ),
+            "system" => (String::new(), Style::default().fg(self.theme.muted).add_modifier(Modifier::ITALIC)),
+            "error" => ("Error: ".to_string(), Style::default().fg(self.theme.error)),
+            "announcement" => (
+                tr("Ankündigung: ").to_string(),
+                Style::default().fg(self.theme.on_highlight).bg(self.theme.highlight).add_modifier(Modifier::BOLD),
+            ),
+            _ => (String::new(), Style::default()),
+        };
+        let style = if self.is_mention(msg) {
+            Style::default().fg(self.theme.highlight).add_modifier(Modifier::BOLD)
+        } else {
+            style
+        };
+        // Direct messages: "Glan → Mara: " in their own style
+        let (prefix, style) = match &msg.to {
+            Some(to) => (
+                format!("{} → {}", prefix.trim_end_matches(": "), to) + ": ",
+                Style::default().fg(self.theme.private).add_modifier(Modifier::ITALIC),
+            ),
+            None => (prefix, style),
+        };
+        let prefix = if self.compact {
+            self.compact_prefix(msg)
+        } else {
+            let own = msg.role == "user" && !self.is_from_others(msg);
+            format!("{}{}", self.theme.role_glyph(&msg.role, own, self.is_mention(msg)), prefix)
+        };
+        let mut timestamp = self.display_timestamp(msg);
+        if let Some(ms) = msg.response_ms
+            && !timestamp.is_empty()
+            && !self.deterministic
+        {
+            timestamp.push_str(&format!(" {}", format_response_time(ms)));
+        }
+
+        // Timestamp für non-system messages
+        if !msg.role.is_empty() && msg.role != "system" {
+            let indent = match self.east_asian_width.str_width(&timestamp) {
+                0 => 0,
+                width => width + 1,
+            } + self.east_asian_width.str_width(&prefix);
+            let gap = if timestamp.is_empty() { "" } else { " " };
+            let body = self.message_body(msg, style);
+            let mut first = vec![
+                Span::styled(timestamp, Style::default().fg(self.theme.muted)),
+                Span::raw(gap),
+                Span::styled(prefix, style.add_modifier(Modifier::BOLD)),
+            ];
+            first.extend(body.first().cloned().unwrap_or_else(|| vec![Span::styled("", style)]));
+            if msg.raw {
+                first.push(Span::styled(tr(" [roh, R]"), Style::default().fg(self.theme.info)));
+            }
+            if msg.edited {
+                first.push(Span::styled(tr(" (bearbeitet)"), Style::default().fg(self.theme.muted)));
+            }
+            if let Some(model) = &msg.model {
+                first.push(Span::styled(format!(" [{}]", model), Style::default().fg(self.theme.info)));
+            }
+            match msg.delivery {
+                Some(Delivery::Failed) => first.push(Span::styled(
+                    trf(" {} nicht zugestellt [r=erneut]", &[Delivery::Failed.glyph()]),
+                    Style::default().fg(self.theme.error),
+                )),
+                Some(state) => first.push(Span::styled(format!(" {}", state.glyph()), Style::default().fg(self.theme.muted))),
+                None => {}
+            }
+            lines.push(Line::from(first));
+
+            // Weitere Zeilen
+            for line in body.into_iter().skip(1) {
+                let mut spans = vec![Span::styled(" ".repeat(indent), style)];
+                spans.extend(line);
+                lines.push(Line::from(spans));
+            }
+            if msg.interrupted {
+                lines.push(Line::from(Span::styled(
+                    format!("{:width$}{}", "", tr("(unterbrochen)"), width = indent),
+                    Style::default().fg(self.theme.muted).add_modifier(Modifier::ITALIC),
+                )));
+            }
+            self.push_tool_call_lines(msg, indent, lines);
+            if replies > 0 {
+                let label = tr(if replies == 1 { "Antwort" } else { "Antworten" });
+                lines.push(Line::from(Span::styled(
+                    format!("{:width$}↳ {} {} [F3]", "", replies, label, width = indent),
+                    Style::default().fg(self.theme.info),
+                )));
+            }
+        } else {
+            lines.push(Line::from(Span::styled(bidi_display(&msg.content), style)));
+        }
+        if !self.compact {
+            lines.push(Line::from(""));
+        }
+    }
+
+    /// Content lines of a message: raw (`R`), with JSON in Hank's answers
+    /// pretty-printed and colored, or as plain text
+    fn message_body(&self, msg: &Message, style: Style) -> Vec<Vec<Span<'static>>> {
+        let plain = |line: String| vec![Span::styled(line, style)];
+        if msg.raw {
+            return raw_text_lines(&msg.content).into_iter().map(plain).collect();
+        }
+        let segments = (msg.role == "assistant").then(|| json_segments(&msg.content)).flatten();
+        let Some(segments) = segments else {
+            return msg.content.lines().map(bidi_display).map(plain).collect();
+        };
+        let mut body = Vec::new();
+        for segment in segments {
+            match segment {
+                JsonSegment::Text(line) => body.push(plain(bidi_display(&line))),
+                JsonSegment::Json(value) => {
+                    let mut printer = JsonPrinter { theme: &self.theme, fold: !msg.json_expanded, lines: Vec::new() };
+                    printer.value(&value, 0, Vec::new(), false);
+                    body.extend(printer.lines);
+                }
+            }
+        }
+        body
+    }
+
+    /// Timestamp as selected with `z`; the compact view drops the seconds
+    fn display_timestamp(&self, msg: &Message) -> String {
+        let time = msg.timestamp_ms.and_then(|ms| Local.timestamp_millis_opt(ms as i64).single());
+        match (self.timestamps, time) {
+            (TimestampMode::Hidden, _) => String::new(),
+            (mode, _) if self.deterministic => {
+                let format = match (mode, self.compact) {
+                    (TimestampMode::Full, true) => "%d.%m. %H:%M",
+                    (TimestampMode::Full, false) => "%d.%m.%Y %H:%M:%S",
+                    (_, true) => "%H:%M",
+                    (_, false) => "%H:%M:%S",
+                };
+                DETERMINISTIC_TIME.format(format).to_string()
+            }
+            (TimestampMode::Full, Some(time)) if self.compact => time.format("%d.%m. %H:%M").to_string(),
+            (TimestampMode::Full, Some(time)) => time.format("%d.%m.%Y %H:%M:%S").to_string(),
+            _ if self.compact => msg.timestamp.chars().take(5).collect(),
+            _ => msg.timestamp.clone(),
+        }
+    }
+
+    /// One-character sender glyph for the compact view: initial of other
+    /// participants, `›` for own messages, `◆` for Hank
+    fn compact_prefix(&self, msg: &Message) -> String {
+        let glyph = match msg.role.as_str() {
+            "user" if self.is_from_others(msg) => {
+                self.user_label(msg).chars().next().map_or('?', |c| c.to_uppercase().next().unwrap_or(c))
+            }
+            "user" => '›',
+            "assistant" => '◆',
+            "error" => '!',
+            "announcement" => '‼',
+            _ => ' ',
+        };
+        match &msg.to {
+            Some(to) => format!("{}→{} ", glyph, to),
+            None => format!("{} ", glyph),
+        }
+    }
+
+    /// Content of the F1 help: `KEYMAP` by section, then the fallback
+    /// chords active on this terminal
+    pub fn help_lines(&self) -> Vec<Line<'static>> {
+        let heading = |title: &str| Line::from(Span::styled(format!("── {} ──", title), Style::default().fg(self.theme.accent)));
+        let mut lines = vec![
+            Line::from(Span::styled(
+                tr("═══ Hank TUI Hilfe ═══"),
+                Style::default().fg(self.theme.highlight).add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+        ];
+        for section in HelpSection::ALL {
+            lines.push(heading(tr(section.title())));
+            for binding in KEYMAP.iter().filter(|b| b.section == section) {
+                let keys = if binding.actions.iter().all(|&action| self.keys.is_default(action)) {
+                    binding.keys.to_string()
+                } else {
+                    binding.actions.iter().map(|&action| self.keys.describe(action)).collect::<Vec<_>>().join(" / ")
+                };
+                lines.push(Line::from(format!("  {:<13} {}", keys, tr(binding.description))));
+            }
+            lines.push(Line::from(""));
+        }
+        if !self.fallbacks.is_empty() {
+            lines.push(heading(tr("Ersatztasten (dieses Terminal)")));
+            lines.extend(self.fallbacks.iter().map(|fallback| Line::from(tr(fallback.describe()))));
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(Span::styled(
+            tr("Drücke eine beliebige Taste zum Schließen"),
+            Style::default().fg(self.theme.muted).add_modifier(Modifier::ITALIC),
+        )));
+        lines
+    }
+
+    /// Calculate total lines for input (accounting for wrapping and newlines)
+    /// Fill a status bar template. Placeholders: `{server}`, `{latency}`,
+    /// `{session}`, `{unread}`, `{model}`, `{version}`, `{time}`, `{status}`, `{elapsed}`
+    /// (session time), `{idle}` (since Hank's last answer); unknown values
+    /// render as "-".
+    pub fn status_line(&self, template: &str) -> String {
+        let host = self.server_url.split("://").nth(1).unwrap_or(&self.server_url);
+        let values = [
+            ("{server}", host.to_string()),
+            ("{latency}", self.latency.filter(|_| !self.deterministic).map_or("-".to_string(), |l| format!("{}ms", l.as_millis()))),
+            ("{session}", self.title()),
+            ("{unread}", self.unread_total().to_string()),
+            ("{model}", self.model.clone().unwrap_or_else(|| "-".to_string())),
+            ("{version}", self.server_info.as_ref().and_then(|i| i.version.clone()).unwrap_or_else(|| "-".to_string())),
+            ("{time}", self.clock_text().unwrap_or_else(|| self.now().format("%H:%M").to_string())),
+            ("{status}", self.connection_status.label().to_string()),
+            ("{elapsed}", format_elapsed(self.age(self.started))),
+            ("{idle}", self.since_last_reply().map_or("-".to_string(), format_elapsed)),
+            ("{sync}", self.heartbeat().0),
+        ];
+        values
+            .iter()
+            .fold(template.to_string(), |line, (key, value)| line.replace(key, value))
+    }
+
+    /// Current time in the configured clock format; an invalid format
+    /// falls back to HH:MM instead of breaking the status bar
+    pub fn clock_text(&self) -> Option<String> {
+        use std::fmt::Write as _;
+        let format = self.clock.as_deref().filter(|_| !self.deterministic)?;
+        let now = Local::now();
+        let mut text = String::new();
+        if write!(text, "{}", now.format(format)).is_err() {
+            return Some(now.format("%H:%M").to_string());
+        }
+        Some(text)
+    }
+
+    /// Wait before the next poll: the poll interval, doubled per failed
+    /// poll up to a minute, and per quiet minute up to `POLL_IDLE_MAX`
+    pub fn poll_delay(&self) -> Duration {
+        if self.poll_failures > 0 {
+            return self.poll_interval.saturating_mul(2u32.saturating_pow(self.poll_failures)).min(POLL_BACKOFF_MAX.max(self.poll_interval));
+        }
+        let quiet_minutes = (self.last_activity.elapsed().as_secs() / 60).min(4) as u32;
+        self.poll_interval.saturating_mul(2u32.pow(quiet_minutes)).min(POLL_IDLE_MAX.max(self.poll_interval))
+    }
+
+    /// Banner over the chat while the server is unreachable
+    pub fn reconnect_banner(&self) -> Option<String> {
+        let since = self.offline_since?;
+        let down = format_elapsed(self.age(since));
+        if self.polling {
+            return Some(trf(" ⚠ Keine Verbindung zu {} seit {} – verbinde neu…", &[&self.server_url, &down]));
+        }
+        let wait = self.poll_delay().saturating_sub(self.last_poll.elapsed()).as_millis().div_ceil(1000).max(1);
+        Some(trf(" ⚠ Keine Verbindung zu {} seit {} – neuer Versuch in {}s", &[&self.server_url, &down, &wait.to_string()]))
+    }
+
+    /// Heartbeat for the status bar: time since the last successful poll,
+    /// and whether that is longer than `SYNC_STALE_AFTER` or three regular
+    /// pauses between polls (then with the reason the polls fail)
+    pub fn heartbeat(&self) -> (String, bool) {
+        let age = self.age(self.last_sync.unwrap_or(self.started));
+        let pause = if self.poll_failures > 0 { self.poll_interval } else { self.poll_delay() };
+        let stale = age >= SYNC_STALE_AFTER.max(pause.saturating_mul(3));
+        let mut text = match self.last_sync {
+            Some(_) => format!("♥ {}", format_elapsed(age)),
+            None => "♥ -".to_string(),
+        };
+        if stale && let Some(reason) = &self.sync_error {
+            let mut short: String = reason.chars().take(40).collect();
+            if short.len() < reason.len() {
+                short = format!("{}…", short.trim_end());
+            }
+            text.push_str(&format!(" ({})", short));
+        }
+        (text, stale)
+    }
+}
+
+/// One frame of the main screen: tabs, transcript, input, status bar and
+/// whichever overlay is open
+pub fn draw(f: &mut ratatui::Frame, app: &mut App) {
+    if app.accessible {
+        draw_accessible(f, app);
+        return;
+    }
+    let area = f.area();
+    let text_lines = app.input_total_lines(area.width.saturating_sub(2) as usize);
+    let input_height = app.input_height.pane_height(text_lines, area.height);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(3),
+            Constraint::Length(input_height),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+
+    // Tab bar above the chat once there is more than one conversation
+    let tabs = app.tabs();
+    let chat_pane = if tabs.len() > 1 {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(3)])
+            .split(chunks[0]);
+        let active = app.active_tab(&tabs);
+        let mut spans = Vec::new();
+        for (idx, tab) in tabs.iter().enumerate() {
+            let mut text = format!(" {} ", tab.label);
+            if tab.unread > 0 {
+                text.push_str(&format!("({}) ", tab.unread));
+            }
+            let style = if idx == active {
+                Style::default().fg(app.theme.on_highlight).bg(app.theme.accent).add_modifier(Modifier::BOLD)
+            } else if tab.unread > 0 {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(app.theme.muted)
+            };
+            spans.push(Span::styled(text, style));
+            spans.push(Span::raw("│"));
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), rows[0]);
+        rows[1]
+    } else {
+        chunks[0]
+    };
+
+    // Server announcements stay pinned across the full width until acknowledged
+    let chat_pane = match app.pending_announcement() {
+        Some(msg) => {
+            let banner = vec![Line::from(trf(" Ankündigung ({}): {}  [Esc=OK]", &[&msg.timestamp, &msg.content]))];
+            let height = wrapped_line_count(&banner, chat_pane.width as usize, app.east_asian_width).clamp(1, 4) as u16;
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(height), Constraint::Min(3)])
+                .split(chat_pane);
+            let banner_widget = Paragraph::new(banner)
+                .style(Style::default().fg(app.theme.on_highlight).bg(app.theme.highlight).add_modifier(Modifier::BOLD))
+                .wrap(Wrap { trim: false });
+            f.render_widget(banner_widget, rows[0]);
+            rows[1]
+        }
+        None => chat_pane,
+    };
+
+    let chat_pane = match app.reconnect_banner() {
+        Some(text) => {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(3)])
+                .split(chat_pane);
+            let banner_widget = Paragraph::new(text)
+                .style(Style::default().fg(app.theme.error).add_modifier(Modifier::BOLD | Modifier::REVERSED));
+            f.render_widget(banner_widget, rows[0]);
+            rows[1]
+        }
+        None => chat_pane,
+    };
+
+    // "alice tippt…" row right above the input box
+    let chat_row = match typing_text(&app.typing) {
+        Some(text) => {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(1)])
+                .split(chat_pane);
+            let typing_widget = Paragraph::new(Span::styled(
+                format!(" {}", text),
+                Style::default().fg(app.theme.muted).add_modifier(Modifier::ITALIC),
+            ));
+            f.render_widget(typing_widget, rows[1]);
+            rows[0]
+        }
+        None => chat_pane,
+    };
+
+    // Search bar below the chat (/ or Ctrl+F), filled in once the hits are known
+    let (chat_row, search_row) = if app.search.is_some() {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(1)])
+            .split(chat_row);
+        (rows[0], Some(rows[1]))
+    } else {
+        (chat_row, None)
+    };
+
+    // Conversation sidebar on the far left (toggle with F2)
+    let chat_row = if app.session_sidebar.is_some() {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(28), Constraint::Min(10)])
+            .split(chat_row);
+        let pane = Paragraph::new(app.session_sidebar_lines(columns[0].width.saturating_sub(2) as usize)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr(" Unterhaltungen [Enter] "))
+                .border_style(if app.focus == Focus::Sessions {
+                    Style::default().fg(app.theme.highlight)
+                } else {
+                    Style::default()
+                }),
+        );
+        f.render_widget(pane, columns[0]);
+        columns[1]
+    } else {
+        chat_row
+    };
+
+    // Sidebar left of the chat (only if the server offers rooms or presence)
+    let chat_area = if app.rooms_supported || app.presence_supported {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(22), Constraint::Min(10)])
+            .split(chat_row);
+        let mut sidebar = Vec::new();
+        if app.rooms_supported {
+            sidebar.extend(app.room_list_lines());
+        }
+        if !app.presence.is_empty() && !app.show_participants {
+            if !sidebar.is_empty() {
+                sidebar.push(Line::from(""));
+            }
+            sidebar.push(Line::from(Span::styled(tr("Teilnehmer"), Style::default().fg(app.theme.accent))));
+            sidebar.extend(app.presence_lines());
+        }
+        let title = tr(if app.rooms_supported { " Räume " } else { " Teilnehmer " });
+        let sidebar_widget = Paragraph::new(sidebar)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(sidebar_widget, columns[0]);
+        columns[1]
+    } else {
+        chat_row
+    };
+
+    // Participant list on the right (toggle with F5)
+    let chat_area = if app.show_participants {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(10), Constraint::Length(26)])
+            .split(chat_area);
+        let pane = Paragraph::new(app.participant_lines())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(tr(" Teilnehmer [Enter=DM, i=Profil] "))
+                    .border_style(if app.focus == Focus::Participants {
+                        Style::default().fg(app.theme.highlight)
+                    } else {
+                        Style::default()
+                    }),
+            )
+            .wrap(Wrap { trim: false });
+        f.render_widget(pane, columns[1]);
+        columns[0]
+    } else {
+        chat_area
+    };
+
+    // Chat-Verlauf mit Timestamps
+    let (mut lines, starts) = app.chat_layout();
+
+    // The selected message (j/k) is shown reversed, without its spacer lines
+    let selection = app.selected.and_then(|sel| {
+        let pos = starts.iter().position(|&(idx, _)| idx == sel)?;
+        let first = starts[pos].1;
+        let mut end = starts.get(pos + 1).map_or(lines.len(), |&(_, line)| line);
+        while end > first + 1 && lines[end - 1].width() == 0 {
+            end -= 1;
+        }
+        Some((first, end))
+    });
+    if let Some((first, end)) = selection {
+        for line in &mut lines[first..end] {
+            line.style = line.style.add_modifier(Modifier::REVERSED);
+        }
+    }
+
+    // Search hits (/ or Ctrl+F), the current one set apart
+    let hits = match &app.search {
+        Some(search) => find_in_lines(&lines, &search.query),
+        None => Vec::new(),
+    };
+    if let Some(search) = app.search.as_mut() {
+        search.current = search.current.min(hits.len().saturating_sub(1));
+        for (idx, hit) in hits.iter().enumerate() {
+            let style = if idx == search.current {
+                Style::default().fg(app.theme.on_highlight).bg(app.theme.accent).add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            } else {
+                Style::default().fg(app.theme.on_highlight).bg(app.theme.highlight)
+            };
+            highlight_chars(&mut lines[hit.line], hit.start, hit.len, style);
+        }
+    }
+
+    // The answer streaming in, or "thinking" until the first piece arrives
+    if app.loading {
+        lines.extend(app.streaming_lines());
+    }
+
+    // Show last error if any
+    if let Some(ref err) = app.last_error {
+        lines.push(Line::from(Span::styled(
+            format!("⚠ {}", err),
+            Style::default().fg(app.theme.error),
+        )));
+    }
+
+    // Calculate scroll offset for chat using the same wrapping logic as rendering
+    let chat_width = chat_area.width.saturating_sub(2) as usize;
+    let visible_lines = chat_area.height.saturating_sub(2);
+    // After a resize the same message stays at the top
+    if !app.auto_scroll
+        && let Some(old) = app.chat_geometry
+        && old != (chat_width, visible_lines)
+    {
+        app.scroll = reanchor_scroll(&lines, app.east_asian_width, old, (chat_width, visible_lines), app.scroll);
+    }
+    app.chat_geometry = Some((chat_width, visible_lines));
+    if let Some(anchor) = app.scroll_anchor.take()
+        && let Some(&(_, line)) = starts.iter().find(|&&(idx, _)| idx >= anchor)
+    {
+        app.scroll = scroll_to_line(&lines, app.east_asian_width, (chat_width, visible_lines), line);
+        app.auto_scroll = false;
+    }
+    let total_lines: u32 = wrapped_line_count(&lines, chat_width, app.east_asian_width)
+        .saturating_add(CHAT_PADDING_LINES);
+    // Messages appended while scrolled up push the bottom further away
+    if std::mem::take(&mut app.pin_view)
+        && !app.auto_scroll
+        && let Some(before) = app.chat_total_lines
+    {
+        let added = total_lines.saturating_sub(before).min(u32::from(u16::MAX)) as u16;
+        app.scroll = app.scroll.saturating_add(added);
+    }
+    app.chat_total_lines = Some(total_lines);
+    if app.auto_scroll {
+        app.unseen_after = None;
+    }
+    let visible_lines_u32 = visible_lines as u32;
+    let max_scroll_u32 = total_lines.saturating_sub(visible_lines_u32);
+    let max_scroll: u16 = max_scroll_u32.min(u32::from(u16::MAX)) as u16;
+
+    // Clamp stored scroll to max
+    if app.scroll > max_scroll {
+        app.scroll = max_scroll;
+    }
+
+    // Scrolled to the top: the next older chunk comes in before the next draw
+    if app.older_chunks > 0 && !app.auto_scroll && app.scroll >= max_scroll {
+        app.wants_older = true;
+    }
+
+    // Bring a newly selected message into view, its start first
+    if std::mem::take(&mut app.reveal_selection)
+        && let Some((first, end)) = selection
+    {
+        let rows = |range: std::ops::Range<usize>| -> u32 {
+            lines[range].iter().map(|l| wrapped_rows(l, chat_width, app.east_asian_width)).sum()
+        };
+        let (start_row, end_row) = (rows(0..first), rows(0..end));
+        reveal_rows(app, start_row, end_row, max_scroll, visible_lines_u32);
+    }
+
+    // The same for the current search hit, down to its wrapped row
+    if let Some(search) = app.search.as_mut()
+        && std::mem::take(&mut search.reveal)
+        && let Some(hit) = hits.get(search.current)
+    {
+        let row = lines[..hit.line].iter().map(|l| wrapped_rows(l, chat_width, app.east_asian_width)).sum::<u32>()
+            + wrapped_row_of(&lines[hit.line], hit.start, chat_width, app.east_asian_width);
+        reveal_rows(app, row, row + 1, max_scroll, visible_lines_u32);
+    }
+
+    let scroll_offset = if total_lines <= visible_lines_u32 {
+        0
+    } else if app.auto_scroll {
+        max_scroll
+    } else {
+        max_scroll.saturating_sub(app.scroll)
+    };
+
+    // Chat widget with focus indicator
+    let mut room_label = app.room_name().map(|r| format!(" #{}", r)).unwrap_or_default();
+    if app.session.is_some() && app.active_room == 0 {
+        let title = session_title(&app.messages).unwrap_or_else(|| tr("Neue Unterhaltung").to_string());
+        room_label.push_str(&format!(" · {}", title));
+    }
+    if app.mentions_only {
+        room_label.push_str(tr(" [Erwähnungen, F4=Alle]"));
+    }
+    let chat_title = if app.copy_mode.is_some() {
+        trf(" Chat{} [KOPIERMODUS - hjkl/w/b/e, v/V=Auswahl, y=Kopieren, >=Zitieren, Esc] ", &[&room_label])
+    } else if app.focus == Focus::Chat {
+        if app.selected.is_some() {
+            trf(" Chat{} [AUSWAHL - j/k=Nachricht, y=Kopieren, >=Zitieren, Esc] ", &[&room_label])
+        } else {
+            trf(" Chat{} [FOKUSSIERT - ↑↓=Scroll, j/k=Auswahl, Tab=Wechsel] ", &[&room_label])
+        }
+    } else {
+        trf(" Chat{} [Tab=Fokussieren] ", &[&room_label])
+    };
+    
+    let unseen = app.unseen_count();
+    let pill = (unseen > 0).then(|| trf(" {} neue Nachrichten ↓ [End] ", &[&unseen.to_string()]));
+    app.unread_pill = pill.as_ref().map(|text| {
+        let width = (text.width() as u16).min(chat_area.width.saturating_sub(2));
+        Rect::new(chat_area.right().saturating_sub(width + 1), chat_area.bottom().saturating_sub(1), width, 1)
+    });
+    let mut chat_block = Block::default()
+        .borders(Borders::ALL)
+        .title(chat_title)
+        .border_style(if app.focus == Focus::Chat {
+            Style::default().fg(app.theme.highlight)
+        } else {
+            Style::default()
+        });
+    if let Some(text) = pill {
+        chat_block = chat_block.title_bottom(
+            Line::styled(text, Style::default().fg(app.theme.on_highlight).bg(app.theme.highlight).add_modifier(Modifier::BOLD))
+                .right_aligned(),
+        );
+    }
+
+    // Copy mode shows the plain transcript unwrapped, following the cursor
+    let messages_widget = if let Some(copy) = app.copy_mode.as_mut() {
+        copy.follow(visible_lines as usize);
+        let left = (copy.cursor_col() + 1).saturating_sub(chat_width) as u16;
+        Paragraph::new(copy.lines(&app.theme))
+            .block(chat_block)
+            .scroll((copy.top.min(u16::MAX as usize) as u16, left))
+    } else {
+        let (shown, offset) = visible_window(lines, chat_width, app.east_asian_width, scroll_offset, visible_lines);
+        Paragraph::new(shown)
+            .block(chat_block)
+            .wrap(Wrap { trim: false })
+            .scroll((offset, 0))
+    };
+    f.render_widget(messages_widget, chat_area);
+
+    if let (Some(search), Some(area)) = (&app.search, search_row) {
+        let caret = if search.editing { "▏" } else { "" };
+        let (count, count_style) = match hits.len() {
+            _ if search.query.is_empty() => (String::new(), Style::default()),
+            0 => (tr("keine Treffer").to_string(), Style::default().fg(app.theme.error)),
+            total => (format!("{}/{}", search.current + 1, total), Style::default().fg(app.theme.muted)),
+        };
+        let hint = tr(if search.editing { "Enter=fertig, Esc" } else { "n/N=weiter/zurück, /=neu, Esc" });
+        let bar = Line::from(vec![
+            Span::styled(format!(" /{}{}  ", search.query, caret), Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(count, count_style),
+            Span::styled(format!("  [{}]", hint), Style::default().fg(app.theme.muted)),
+        ]);
+        f.render_widget(Paragraph::new(bar), area);
+    }
+    if app.pending_panel_visible() {
+        draw_pending_panel(f, app, chat_area);
+    }
+
+    // Input with wrapping and focus indicator
+    // Typing goes on while Hank answers; sending queues behind the answer
+    let send = app.keys.label(Action::Send);
+    let input_title = if app.loading {
+        trf(" {} antwortet [Esc=Stopp, {}=danach senden] ", &[&app.assistant_name, &send])
+    } else if app.editing.is_some() {
+        trf(" Bearbeiten [{}=Speichern, leer senden=Abbrechen] ", &[&send])
+    } else if let Some(target) = &app.target {
+        trf(" An {} [{}=Senden, F7=Empfänger, /all=Alle] ", &[target, &send])
+    } else if app.focus == Focus::Input {
+        trf(" Nachricht [{}=Senden, {}=Hilfe] ", &[&send, &app.keys.label(Action::Help)])
+    } else {
+        trf(" Nachricht [{}=Fokussieren] ", &[&app.keys.label(Action::FocusToggle)])
+    };
+    
+    let input_block = Block::default()
+        .borders(Borders::ALL)
+        .title(input_title)
+        .border_style(if app.focus == Focus::Input {
+            Style::default().fg(app.theme.accent)
+        } else {
+            Style::default()
+        });
+    
+    // Calculate input dimensions
+    let input_area_width = chunks[1].width.saturating_sub(2) as usize;
+    let visible_input_lines = input_height.saturating_sub(2);
+    
+    // Update scroll to keep cursor visible
+    app.update_input_scroll(input_area_width, visible_input_lines);
+    
+    // Use manually wrapped text to ensure cursor matches display
+    let wrapped_input = app.input_display_text(input_area_width);
+    let input_widget = Paragraph::new(wrapped_input)
+        .block(input_block)
+        .scroll((app.input_scroll, 0));
+    f.render_widget(input_widget, chunks[1]);
+
+    // Status bar: configured template or the built-in overview
+    let status_text = if let Some(template) = &app.status_format {
+        app.status_line(template)
+    } else {
+        let version = app.server_info.as_ref().and_then(|i| i.version.as_deref()).map(|v| format!(" v{}", v.trim_start_matches('v')));
+        let mut status_text = format!(
+            " {}{} | Msgs: {} | Lines: {}/{} | Scroll: {} | {}",
+            app.server_url,
+            version.unwrap_or_default(),
+            app.messages.len(),
+            total_lines,
+            visible_lines,
+            if app.auto_scroll { "bottom".to_string() } else { app.scroll.to_string() },
+            app.connection_status.label()
+        );
+        if !app.mouse_capture {
+            status_text.push_str(tr(" | Maus frei [F8]"));
+        }
+        if let Some(status) = app.assistant_presence() {
+            status_text.push_str(&format!(" | {} {} {}", app.assistant_name, status.glyph(), status.label()));
+        }
+        status_text.push_str(&format!(" | ⏱ {}", format_elapsed(app.age(app.started))));
+        if let Some(idle) = app.since_last_reply() {
+            status_text.push_str(&trf(" | {} vor {}", &[&app.assistant_name, &format_elapsed(idle)]));
+        }
+        if let Some(note) = app.rate_limit_status() {
+            status_text.push_str(&format!(" | {}", note));
+        }
+        if let Some(clock) = app.clock_text() {
+            status_text.push_str(&format!(" | {}", clock));
+        }
+        if app.connections.len() > 1 {
+            let unread: usize = app.connections.iter().map(|c| c.unread).sum();
+            status_text.insert_str(0, &format!(" [{}/{}]", app.active_connection + 1, app.connections.len()));
+            if unread > 0 {
+                status_text.push_str(&trf(" | F6: {} neu", &[&unread.to_string()]));
+            }
+        }
+        status_text
+    };
+    let status_style = if app.flash_until.is_some_and(|t| Instant::now() < t) {
+        Style::default().bg(app.theme.highlight).fg(app.theme.on_highlight)
+    } else {
+        Style::default().bg(app.theme.status_bg).fg(app.theme.status_fg)
+    };
+    // Heartbeat last, red once polls have been failing for a while
+    let mut status_spans = Vec::new();
+    if let Some((notice, until)) = &app.notice
+        && Instant::now() < *until
+    {
+        status_spans.push(Span::styled(format!(" ✓ {} ", notice), Style::default().add_modifier(Modifier::BOLD)));
+    }
+    status_spans.push(Span::raw(status_text));
+    if app.status_format.is_none() {
+        let (heartbeat, stale) = app.heartbeat();
+        let style = if stale {
+            Style::default().fg(app.theme.error).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled(heartbeat, style));
+    }
+    let status_widget = Paragraph::new(Line::from(status_spans)).style(status_style);
+    f.render_widget(status_widget, chunks[2]);
+
+    // Cursor positioning (only when input is focused)
+    if app.focus == Focus::Input {
+        let input_width = chunks[1].width.saturating_sub(2) as usize;
+        if input_width > 0 {
+            let (cursor_line, cursor_col) = app.cursor_display_col(input_width);
+            let visible_line = (cursor_line as u16).saturating_sub(app.input_scroll);
+            
+            if visible_line < visible_input_lines {
+                f.set_cursor_position((
+                    chunks[1].x + cursor_col as u16 + 1,
+                    chunks[1].y + visible_line + 1,
+                ));
+            }
+        }
+    }
+    
+    // Thread overlay on top of the transcript (toggle with F3)
+    if app.thread.is_some() && chat_area.width > 8 && chat_area.height > 4 {
+        let thread_area = ratatui::layout::Rect::new(
+            chat_area.x + 2,
+            chat_area.y + 1,
+            chat_area.width - 4,
+            chat_area.height - 2,
+        );
+        f.render_widget(ratatui::widgets::Clear, thread_area);
+        let thread_lines = app.thread_lines();
+        let thread_visible = thread_area.height.saturating_sub(2) as u32;
+        let thread_total = wrapped_line_count(&thread_lines, thread_area.width.saturating_sub(2) as usize, app.east_asian_width);
+        let thread_scroll = thread_total.saturating_sub(thread_visible).min(u32::from(u16::MAX)) as u16;
+        let thread_widget = Paragraph::new(thread_lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(tr(" Thread [F3/Esc=Schließen] "))
+                    .border_style(Style::default().fg(app.theme.info)),
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((thread_scroll, 0));
+        f.render_widget(thread_widget, thread_area);
+    }
+
+    // Confirmation dialog for moderation commands
+    if let Some(action) = &app.confirm {
+        let question = format!(" {} [j/n] ", action.question());
+        let width = (question.width() as u16 + 4).min(f.area().width);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(3) / 2,
+            width,
+            3.min(f.area().height),
+        );
+        f.render_widget(ratatui::widgets::Clear, area);
+        let dialog = Paragraph::new(question).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Moderation ")
+                .border_style(Style::default().fg(app.theme.error)),
+        );
+        f.render_widget(dialog, area);
+    }
+
+    // Prompt template picker
+    if let Some(picker) = &app.template_picker {
+        let lines = app.template_picker_lines();
+        let title = match picker.field {
+            None => tr(" Vorlage wählen [Enter/Esc] ").to_string(),
+            Some(_) => trf(" {} [Enter=weiter, Esc] ", &[&picker.templates[picker.index].name]),
+        };
+        let width = (f.area().width * 2 / 3).max(30).min(f.area().width);
+        let inner = width.saturating_sub(2) as usize;
+        let height = (wrapped_line_count(&lines, inner, app.east_asian_width) as u16 + 2).min(f.area().height);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(ratatui::widgets::Clear, area);
+        let picker_widget = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(Style::default().fg(app.theme.accent))
+                    .style(Style::default().bg(app.theme.overlay_bg)),
+            )
+            .wrap(Wrap { trim: false });
+        f.render_widget(picker_widget, area);
+    }
+
+    // Search across all saved histories
+    if let Some(search) = &app.global_search {
+        let width = (f.area().width * 4 / 5).max(30).min(f.area().width);
+        let height = (f.area().height * 4 / 5).max(6).min(f.area().height);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        let title = trf(" Alle Unterhaltungen durchsuchen ({} Treffer) [↑↓, Enter=öffnen, Esc] ", &[&search.hits.len().to_string()]);
+        f.render_widget(ratatui::widgets::Clear, area);
+        let search_widget = Paragraph::new(app.global_search_lines(height.saturating_sub(2) as usize)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(app.theme.accent))
+                .style(Style::default().bg(app.theme.overlay_bg)),
+        );
+        f.render_widget(search_widget, area);
+    }
+
+    // Conversation picker
+    if app.session_picker.is_some() {
+        let lines = app.session_picker_lines();
+        let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 4).max(30).min(f.area().width);
+        let height = (lines.len() as u16 + 2).min(f.area().height);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(ratatui::widgets::Clear, area);
+        let picker_widget = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr(" Unterhaltung öffnen [↑↓, Enter/Esc] "))
+                .border_style(Style::default().fg(app.theme.accent))
+                .style(Style::default().bg(app.theme.overlay_bg)),
+        );
+        f.render_widget(picker_widget, area);
+    }
+
+    // Diff of an edited message
+    if app.edit_diff.is_some() {
+        let lines = app.edit_diff_lines();
+        let width = (f.area().width * 3 / 4).max(30).min(f.area().width);
+        let inner = width.saturating_sub(2) as usize;
+        let height = (wrapped_line_count(&lines, inner, app.east_asian_width) as u16 + 2).min(f.area().height);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(ratatui::widgets::Clear, area);
+        let diff_widget = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(tr(" Bearbeitet: [-alt-] {+neu+} "))
+                    .border_style(Style::default().fg(app.theme.info))
+                    .style(Style::default().bg(app.theme.overlay_bg)),
+            )
+            .wrap(Wrap { trim: false });
+        f.render_widget(diff_widget, area);
+    }
+
+    // Message over the size limit
+    if app.oversize.is_some() {
+        let lines = app.oversize_lines();
+        let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 4).min(f.area().width);
+        let height = (lines.len() as u16 + 2).min(f.area().height);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(ratatui::widgets::Clear, area);
+        let offer_widget = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr(" Nachricht zu lang "))
+                .border_style(Style::default().fg(app.theme.highlight))
+                .style(Style::default().bg(app.theme.overlay_bg)),
+        );
+        f.render_widget(offer_widget, area);
+    }
+
+    // Server metrics (F9)
+    if app.stats.is_some() {
+        let lines = app.stats_lines();
+        let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 4)
+            .max(40)
+            .min(f.area().width);
+        let height = (lines.len() as u16 + 2).min(f.area().height);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(ratatui::widgets::Clear, area);
+        let stats_widget = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr(" Server-Statistik "))
+                .border_style(Style::default().fg(app.theme.info))
+                .style(Style::default().bg(app.theme.overlay_bg)),
+        );
+        f.render_widget(stats_widget, area);
+    }
+
+    // Model picker for regenerating an answer
+    if let Some(picker) = &app.model_picker {
+        let lines: Vec<Line> = picker
+            .models
+            .iter()
+            .enumerate()
+            .map(|(idx, model)| {
+                if idx == picker.index {
+                    Line::from(Span::styled(
+                        format!("▶ {}", model),
+                        Style::default().fg(app.theme.on_highlight).bg(app.theme.highlight),
+                    ))
+                } else {
+                    Line::from(format!("  {}", model))
+                }
+            })
+            .collect();
+        let width = (picker.models.iter().map(|m| m.width()).max().unwrap_or(0) as u16 + 6)
+            .max(30)
+            .min(f.area().width);
+        let height = (lines.len() as u16 + 2).min(f.area().height);
+        let area = ratatui::layout::Rect::new(
+            f.area().width.saturating_sub(width) / 2,
+            f.area().height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(ratatui::widgets::Clear, area);
+        let picker_widget = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(tr(" Neu generieren mit [Enter/Esc] "))
+                .border_style(Style::default().fg(app.theme.accent)),
+        );
+        f.render_widget(picker_widget, area);
+    }
+
+    // Help overlay
+    if app.focus == Focus::Help {
+        let help_text = app.help_lines();
+        
+        // Clamp help dimensions to terminal size
+        let term_width = f.area().width;
+        let term_height = f.area().height;
+        let help_height = (help_text.len() as u16 + 2).min(term_height.saturating_sub(2));
+        let help_width = 55u16.min(term_width.saturating_sub(2));
+        let help_x = term_width.saturating_sub(help_width) / 2;
+        let help_y = term_height.saturating_sub(help_height) / 2;
+        
+        // Ensure we don't overflow
+        let help_width = help_width.min(term_width.saturating_sub(help_x));
+        let help_height = help_height.min(term_height.saturating_sub(help_y));
+        
+        if help_width > 2 && help_height > 2 {
+            let help_area = ratatui::layout::Rect::new(help_x, help_y, help_width, help_height);
+            
+            // Clear area behind help
+            f.render_widget(ratatui::widgets::Clear, help_area);
+            
+            let help_block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme.highlight))
+                .style(Style::default().bg(app.theme.overlay_bg));
+            
+            let help_widget = Paragraph::new(help_text)
+                .block(help_block)
+                .wrap(Wrap { trim: false });
+            f.render_widget(help_widget, help_area);
+        }
+    }
+
+    // Debug overlay (toggle with F12)
+    if app.debug_overlay {
+        let mut dbg_lines = vec![
+            Line::from(format!(
+                "tl={} vis={} max={} off={}",
+                total_lines, visible_lines, max_scroll, scroll_offset
+            )),
+            Line::from(format!(
+                "auto={} scroll={} pad={}",
+                app.auto_scroll, app.scroll, CHAT_PADDING_LINES
+            )),
+            Line::from(format!("msgs={} loading={}", app.messages.len(), app.loading)),
+        ];
+        // Newest HTTP exchanges with --debug-http
+        if let Some(trace) = &app.http_trace {
+            dbg_lines.push(Line::from(Span::styled("http:", Style::default().fg(app.theme.muted))));
+            dbg_lines.extend(trace.recent(8).into_iter().map(Line::from));
+        }
+
+        let term_width = f.area().width;
+        let term_height = f.area().height;
+        let dbg_width = if app.http_trace.is_some() { 100u16 } else { 48 }.min(term_width.saturating_sub(2));
+        let dbg_height = (dbg_lines.len() as u16 + 2).min(term_height.saturating_sub(2));
+        let dbg_x = term_width.saturating_sub(dbg_width + 1);
+        let dbg_y = term_height.saturating_sub(dbg_height + 1);
+
+        if dbg_width > 2 && dbg_height > 2 {
+            let dbg_area = ratatui::layout::Rect::new(dbg_x, dbg_y, dbg_width, dbg_height);
+            f.render_widget(ratatui::widgets::Clear, dbg_area);
+
+            let dbg_block = Block::default()
+                .borders(Borders::ALL)
+                .title(" debug ")
+                .border_style(Style::default().fg(app.theme.private))
+                .style(Style::default().bg(app.theme.overlay_bg));
+
+            let dbg_widget = Paragraph::new(dbg_lines)
+                .block(dbg_block)
+                .wrap(Wrap { trim: false });
+            f.render_widget(dbg_widget, dbg_area);
+        }
+    }
+    if app.ascii {
+        ascii_buffer(f.buffer_mut());
+    }
+}